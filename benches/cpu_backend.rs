@@ -0,0 +1,66 @@
+//! Steps per second of the multithreaded cpu backend, and of the reference for the smaller
+//! systems. Run with `cargo bench --bench cpu_backend -- [particles...]`, the default sizes
+//! go from 10k to 1M particles. RAYON_NUM_THREADS sets the threads.
+
+use std::time::Instant;
+
+use ParticleLife3D::system::backend::Backend;
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::cpu::parallel::ParallelBackend;
+use ParticleLife3D::system::cpu::reference::ReferenceBackend;
+
+/// the reference takes seconds per step beyond this
+const REFERENCE_LIMIT: u32 = 100_000;
+
+/// runs substeps for at least `seconds` after one warm up frame, returns steps/s
+fn measure(backend: &mut dyn Backend, substeps: u32, seconds: f32) -> f32 {
+    backend.step();
+    let start = Instant::now();
+    let mut frames = 0;
+    while frames == 0 || start.elapsed().as_secs_f32() < seconds {
+        backend.step();
+        frames += 1;
+    }
+    (frames * substeps) as f32 / start.elapsed().as_secs_f32()
+}
+
+/// the default config is a dilute gas, the liquid has a reduced density of 0.8
+fn config(particles: u32, liquid: bool) -> Config {
+    let mut config = Config::default();
+    config.system.particles = particles;
+    config.integrator.substeps = 1;
+    if liquid {
+        let sigma = config.force_field.preset.build().min_sigma();
+        config.system.box_size = Some((particles as f32 / 0.8).cbrt() * sigma);
+    }
+    config
+}
+
+fn main() {
+    let sizes: Vec<u32> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    let sizes = if sizes.is_empty() {
+        vec![10_000, 100_000, 1_000_000]
+    } else {
+        sizes
+    };
+
+    println!("{} threads", rayon::current_num_threads());
+    println!("{:>10} {:>8} {:>10} {:>10} {:>18}", "particles", "phase", "backend", "steps/s", "particle steps/s");
+    for particles in sizes {
+        for (phase, liquid) in [("gas", false), ("liquid", true)] {
+            let config = config(particles, liquid);
+            let mut parallel = ParallelBackend::new(&config).expect("the benchmark config runs on the cpu");
+            let rate = measure(&mut parallel, 1, 2.0);
+            println!("{:>10} {:>8} {:>10} {:>10.2} {:>18.3e}", particles, phase, "parallel", rate, rate * particles as f32);
+
+            if particles <= REFERENCE_LIMIT {
+                let mut reference = ReferenceBackend::new(&config).expect("the benchmark config runs on the cpu");
+                let rate = measure(&mut reference, 1, 2.0);
+                println!("{:>10} {:>8} {:>10} {:>10.2} {:>18.3e}", particles, phase, "reference", rate, rate * particles as f32);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Steps per second of the gpu pipeline with the particle buffers in a random order, like a
+//! liquid that mixed for a long time, and sorted along a Morton curve every few frames.
+//! Run with `cargo bench --bench gpu_reorder -- [--fallback] [particles...]`, the default
+//! sizes go from 10k to 1M particles.
+
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::simulation::Simulation;
+
+const REORDER_INTERVAL: u32 = 10;
+
+/// runs frames for at least `seconds` after the first sort, returns steps/s
+fn measure(simulation: &mut Simulation, seconds: f32) -> f32 {
+    simulation.step(REORDER_INTERVAL as usize + 1);
+    let start = Instant::now();
+    let mut frames = 0;
+    while frames == 0 || start.elapsed().as_secs_f32() < seconds {
+        simulation.step(1);
+        frames += 1;
+    }
+    let substeps = simulation.config().integrator.substeps;
+    (frames * substeps) as f32 / start.elapsed().as_secs_f32()
+}
+
+/// a liquid with a reduced density of 0.8
+fn config(particles: u32, reorder_interval: u32, fallback: bool) -> Config {
+    let mut config = Config::default();
+    config.system.particles = particles;
+    config.integrator.substeps = 5;
+    config.gpu.fallback = fallback;
+    config.gpu.reorder_interval = reorder_interval;
+    let sigma = config.force_field.preset.build().min_sigma();
+    config.system.box_size = Some((particles as f32 / 0.8).cbrt() * sigma);
+    config
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let fallback = args.iter().any(|arg| arg == "--fallback");
+    let sizes: Vec<u32> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+    let sizes = if sizes.is_empty() {
+        vec![10_000, 100_000, 1_000_000]
+    } else {
+        sizes
+    };
+
+    println!("{:>10} {:>10} {:>10} {:>18}", "particles", "order", "steps/s", "particle steps/s");
+    for particles in sizes {
+        for (order, reorder_interval) in [("random", 0), ("morton", REORDER_INTERVAL)] {
+            let config = config(particles, reorder_interval, fallback);
+            let mut simulation = Simulation::builder().config(config).build().expect("the benchmark config is valid");
+            let mut shuffled = simulation.particles();
+            shuffled.shuffle(&mut StdRng::seed_from_u64(particles as u64));
+            simulation.set_particles(&shuffled);
+            let rate = measure(&mut simulation, 2.0);
+            println!("{:>10} {:>10} {:>10.2} {:>18.3e}", particles, order, rate, rate * particles as f32);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use ParticleLife3D::headless::{run_headless, HeadlessError, HeadlessOptions};
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::device;
+
+const USAGE: &str = "usage: headless [--steps <n>] [--backend gpu|cpu|parallel] [--compare] [--config <file>] [--set <section.key>=<value>]...
+                [--gpu-api all|vulkan|gl|dx12|metal] [--adapter <name>] [--fallback] [--list-adapters]
+
+Runs the simulation without a window and writes the stats (output.stats_file) and an
+extended XYZ trajectory (output.trajectory_file).
+
+  --steps <n>   substeps to run, rounded up to whole frames (default 1000)
+  --gpu-api     the graphics api to look for adapters on (gpu.api, default all)
+  --adapter     only use an adapter with this in its name (gpu.adapter)
+  --fallback    use the software adapter, for machines without a gpu (gpu.fallback)
+  --list-adapters
+                print the adapters of the graphics api with their limits and exit
+  --backend     gpu (default), cpu (the single threaded reference implementation) or
+                parallel (multithreaded, RAYON_NUM_THREADS sets the threads)
+  --compare     run the gpu and a cpu backend from the same start and compare them, the
+                cpu reference unless --backend names another one
+
+exit codes: 0 done, 1 diverged, 2 bad arguments or configuration, 3 no usable device,
+4 output could not be written, 5 the backends disagree";
+
+/// splits off the headless options, the rest goes to the config
+fn parse_args(args: Vec<String>) -> Result<(Config, HeadlessOptions), HeadlessError> {
+    let mut options = HeadlessOptions::default();
+    let mut list_adapters = false;
+    let mut config_args = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--steps" | "-n" => {
+                options.steps = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| HeadlessError::Config("--steps expects a number".to_string()))?;
+            }
+            "--backend" => {
+                options.backend = args
+                    .next()
+                    .ok_or_else(|| "--backend expects gpu, cpu or parallel".to_string())
+                    .and_then(|kind| kind.parse())
+                    .map_err(HeadlessError::Config)?;
+            }
+            "--compare" => options.compare = true,
+            "--list-adapters" => list_adapters = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => config_args.push(arg),
+        }
+    }
+    let config = Config::from_args(config_args).map_err(|e| HeadlessError::Config(e.to_string()))?;
+    if list_adapters {
+        print!("{}", device::list_adapters(&config.gpu));
+        std::process::exit(0);
+    }
+    Ok((config, options))
+}
+
+fn main() {
+    env_logger::init();
+    let result = parse_args(std::env::args().skip(1).collect()).and_then(|(config, options)| {
+        println!("{}", config);
+        run_headless(&config, &options)
+    });
+    match result {
+        Ok(stats) => println!("done after {} ps: {:?}", stats.time, stats),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time;
+
+use crate::system::backend::{compare, Backend, BackendKind};
+use crate::system::config::Config;
+use crate::system::cpu::parallel::ParallelBackend;
+use crate::system::cpu::reference::ReferenceBackend;
+use crate::system::device;
+use crate::system::simulation::Simulation;
+use crate::system::stats::Stats;
+use crate::system::trajectory::Trajectory;
+
+/// Settings of a headless run that are not part of the system description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadlessOptions {
+    pub steps: u64, // substeps to run, rounded up to whole frames
+    pub backend: BackendKind,
+    pub compare: bool, // run a cpu backend (the reference unless `backend` is one) next to the gpu
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            steps: 1000,
+            backend: BackendKind::Gpu,
+            compare: false,
+        }
+    }
+}
+
+/// Why a headless run failed, every kind has its own exit code.
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// bad command line or configuration
+    Config(String),
+    /// no adapter or device with the features the pipelines need
+    Device(String),
+    /// the energy became NaN or infinite, or the particles got too fast for the bins
+    Diverged { frame: u64, time: f64 },
+    /// the stats or the trajectory could not be written
+    Output(String),
+    /// the gpu and the cpu reference disagree on the energy
+    Mismatch(f32),
+}
+
+impl HeadlessError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HeadlessError::Diverged { .. } => 1,
+            HeadlessError::Config(_) => 2,
+            HeadlessError::Device(_) => 3,
+            HeadlessError::Output(_) => 4,
+            HeadlessError::Mismatch(_) => 5,
+        }
+    }
+}
+
+impl Display for HeadlessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeadlessError::Config(message) => write!(f, "configuration: {}", message),
+            HeadlessError::Device(message) => write!(f, "device: {}", message),
+            HeadlessError::Diverged { frame, time } => {
+                write!(f, "the simulation diverged in frame {} (t = {} ps)", frame, time)
+            }
+            HeadlessError::Output(message) => write!(f, "output: {}", message),
+            HeadlessError::Mismatch(difference) => {
+                write!(f, "the backends disagree, relative energy difference {:e}", difference)
+            }
+        }
+    }
+}
+
+impl Error for HeadlessError {}
+
+/// The largest relative energy difference `--compare` accepts between the gpu and a cpu
+/// backend, they all integrate in f32 but sum in a different order.
+pub const COMPARE_TOLERANCE: f32 = 1e-3;
+
+fn gpu_backend(config: &Config) -> Result<Simulation, HeadlessError> {
+    let (_adapter, device, queue) =
+        pollster::block_on(device::create_device(&config.gpu)).map_err(|e| HeadlessError::Device(e.to_string()))?;
+    // pipeline validation errors would otherwise only be logged by the default handler
+    device.on_uncaptured_error(Box::new(|e: wgpu::Error| {
+        let error = HeadlessError::Device(e.to_string());
+        println!("error: {}", error);
+        std::process::exit(error.exit_code());
+    }));
+    Simulation::builder()
+        .config(config.clone())
+        .build_on(Arc::new(device), Arc::new(queue))
+        .map_err(|e| HeadlessError::Config(e.to_string()))
+}
+
+fn cpu_backend(config: &Config, kind: BackendKind) -> Result<Box<dyn Backend>, HeadlessError> {
+    let backend: Result<Box<dyn Backend>, _> = match kind {
+        BackendKind::Parallel => ParallelBackend::new(config).map(|b| Box::new(b) as Box<dyn Backend>),
+        _ => ReferenceBackend::new(config).map(|b| Box::new(b) as Box<dyn Backend>),
+    };
+    backend.map_err(|e| HeadlessError::Config(e.to_string()))
+}
+
+/// Runs the configured system for `options.steps` substeps without a window, writes the
+/// stats and, if configured, a trajectory. Returns the stats of the last frame.
+pub fn run_headless(config: &Config, options: &HeadlessOptions) -> Result<Stats, HeadlessError> {
+    let substeps = config.integrator.substeps as u64;
+    let frames = options.steps.div_ceil(substeps);
+    if options.compare {
+        let mut gpu = gpu_backend(config)?;
+        let mut cpu = cpu_backend(config, options.backend)?;
+        println!("comparing {} and {} over {} frames of {} substeps", gpu.name(), cpu.name(), frames, substeps);
+        let comparison = compare(&mut gpu, cpu.as_mut(), frames as usize);
+        println!("{}", comparison);
+        let difference = comparison.relative_energy_difference();
+        if difference.is_nan() || difference > COMPARE_TOLERANCE {
+            return Err(HeadlessError::Mismatch(difference));
+        }
+        return Ok(cpu.stats());
+    }
+    let mut backend: Box<dyn Backend> = match options.backend {
+        BackendKind::Gpu => Box::new(gpu_backend(config)?),
+        kind => cpu_backend(config, kind)?,
+    };
+
+    let output = &config.output;
+    let mut trajectory = match &output.trajectory_file {
+        Some(path) => Some(Trajectory::create(path).map_err(|e| HeadlessError::Output(e.to_string()))?),
+        None => None,
+    };
+
+    println!("running {} frames of {} substeps on the {} backend", frames, substeps, backend.name());
+    let start = time::Instant::now();
+    let mut result = Ok(());
+    for frame in 0..frames {
+        backend.step();
+
+        let stats = backend.stats();
+        let energy = stats.KE + stats.PE + stats.PE_real + stats.PE_recip + stats.PE_bonded;
+        // a particle that crosses a whole bin in one substep misses its neighbours
+        if !energy.is_finite() || stats.v_max * stats.dt > backend.bin_size() {
+            result = Err(HeadlessError::Diverged { frame, time: stats.time });
+            break;
+        }
+        if frame != 0 && frame % output.report_interval as u64 == 0 {
+            // how well the skin of the Verlet list fits the motion of the particles
+            let rebuilds = match stats.rebuilds {
+                0 => String::new(),
+                n => format!(" - neighbour list rebuilt every {:.1} substeps", stats.iteration as f32 / n as f32),
+            };
+            println!(
+                "frame {} - time: {:.3} ps - temperature: {:.2} K - energy: {} eV - pressure: {:.1} bar - {:.1} frames/s{}",
+                frame,
+                stats.time,
+                backend.temperature(),
+                energy,
+                stats.pressure,
+                (frame + 1) as f32 / start.elapsed().as_secs_f32(),
+                rebuilds
+            );
+        }
+        if let Some(trajectory) = trajectory.as_mut() {
+            if frame % output.trajectory_interval as u64 == 0 {
+                let particles = backend.particles();
+                trajectory
+                    .write_frame(&particles, &backend.simulation_box(), stats.time)
+                    .map_err(|e| HeadlessError::Output(e.to_string()))?;
+            }
+        }
+    }
+
+    // the stats are written even if the run diverged, they show how it went wrong
+    let file_name = backend.save_stats().map_err(|e| HeadlessError::Output(e.to_string()))?;
+    println!("stats saved to file: {}", file_name);
+    if let Some(mut trajectory) = trajectory {
+        trajectory.flush().map_err(|e| HeadlessError::Output(e.to_string()))?;
+        println!("{} trajectory frames saved", trajectory.frames());
+    }
+    result.map(|_| backend.stats())
+}
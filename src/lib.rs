@@ -16,6 +16,7 @@ use winit::{
 // mod particle;
 // mod camera;
 
+pub mod headless;
 #[macro_use]
 pub mod render;
 pub mod state;
@@ -23,8 +24,9 @@ pub mod system;
 pub mod utils;
 
 use crate::state::State;
+use crate::system::config::Config;
 
-pub async fn run(width: i32, height: i32) {
+pub async fn run(width: i32, height: i32, config: Config) {
     env_logger::init();
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
@@ -34,7 +36,13 @@ pub async fn run(width: i32, height: i32) {
         .build(&event_loop)
         .unwrap();
 
-    let mut state: State = State::new(window).await;
+    let mut state: State = match State::new(window, &config).await {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(3);
+        }
+    };
 
     event_loop.run(move |event, _, control_flow| {
         state.handle_event(&event);
@@ -1,9 +1,25 @@
 use ParticleLife3D::run;
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::device;
 
 const WIDTH: i32 = 1700;
 const HEIGHT: i32 = 1000;
 
 fn main() {
     // env_logger::init();
-    pollster::block_on(run(WIDTH, HEIGHT));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let list_adapters = args.iter().any(|arg| arg == "--list-adapters");
+    let config = match Config::from_args(args.into_iter().filter(|arg| arg != "--list-adapters")) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if list_adapters {
+        print!("{}", device::list_adapters(&config.gpu));
+        return;
+    }
+    println!("{}", config);
+    pollster::block_on(run(WIDTH, HEIGHT, config));
 }
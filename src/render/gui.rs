@@ -9,7 +9,14 @@ use egui::{
         PlotPoints,
     },
 };
+use crate::system::barostat::{Barostat, BarostatKind, PressureCoupling};
+use crate::system::constraints::Constraints;
+use crate::system::integrator::{Integrator, IntegratorKind};
+use crate::system::life::{ForceModel, ParticleLife};
+use crate::system::minimizer::{Minimizer, MinimizerKind, MinimizerState};
 use crate::system::stats::StatHistory;
+use crate::system::thermostat::{Thermostat, ThermostatKind, MAX_CHAIN_LENGTH};
+use crate::system::timestep::Timestep;
 
 // ----------------------------------------------------------------------------
 
@@ -19,6 +26,22 @@ use crate::system::stats::StatHistory;
 pub struct GUI {
     plot_is_open: bool,
     plot: EnergyGraph,
+    life_is_open: bool,
+    life: LifeEditor,
+    thermostat_is_open: bool,
+    thermostat: ThermostatEditor,
+    barostat_is_open: bool,
+    barostat: BarostatEditor,
+    integrator_is_open: bool,
+    integrator: IntegratorEditor,
+    timestep_is_open: bool,
+    timestep: TimestepEditor,
+    force_cap_is_open: bool,
+    force_cap: ForceCapEditor,
+    constraints_is_open: bool,
+    constraints: ConstraintsEditor,
+    minimizer_is_open: bool,
+    minimizer: MinimizerEditor,
 }
 
 impl Default for GUI {
@@ -26,6 +49,22 @@ impl Default for GUI {
         Self {
             plot_is_open: true,
             plot: Default::default(),
+            life_is_open: true,
+            life: Default::default(),
+            thermostat_is_open: true,
+            thermostat: Default::default(),
+            barostat_is_open: true,
+            barostat: Default::default(),
+            integrator_is_open: true,
+            integrator: Default::default(),
+            timestep_is_open: true,
+            timestep: Default::default(),
+            force_cap_is_open: true,
+            force_cap: Default::default(),
+            constraints_is_open: true,
+            constraints: Default::default(),
+            minimizer_is_open: true,
+            minimizer: Default::default(),
         }
     }
 }
@@ -39,6 +78,586 @@ impl GUI {
     /// Show the open windows.
     fn show_windows(&mut self, ctx: &Context, data: StatHistory) {
         self.plot.show(ctx, &mut self.plot_is_open, data);
+        self.life.show(ctx, &mut self.life_is_open);
+        self.thermostat.show(ctx, &mut self.thermostat_is_open);
+        self.barostat.show(ctx, &mut self.barostat_is_open);
+        self.integrator.show(ctx, &mut self.integrator_is_open);
+        self.timestep.show(ctx, &mut self.timestep_is_open);
+        self.force_cap.show(ctx, &mut self.force_cap_is_open);
+        self.constraints.show(ctx, &mut self.constraints_is_open);
+        self.minimizer.show(ctx, &mut self.minimizer_is_open);
+    }
+
+    /// Set the force model and particle life settings the editor starts from, the radius
+    /// goes up to `max_radius` (the largest the box has room for bins of).
+    pub fn set_particle_life(&mut self, force_model: ForceModel, life: ParticleLife, max_radius: f32) {
+        self.life.force_model = force_model;
+        self.life.life = Some(life);
+        self.life.max_radius = max_radius;
+        self.life.changed = false;
+    }
+
+    /// Returns the edited settings once after they have been changed in the ui.
+    pub fn take_particle_life(&mut self) -> Option<(ForceModel, ParticleLife)> {
+        if !self.life.changed {
+            return None;
+        }
+        self.life.changed = false;
+        self.life.life.clone().map(|life| (self.life.force_model, life))
+    }
+
+    /// Set the thermostat the editor starts from.
+    pub fn set_thermostat(&mut self, thermostat: Thermostat) {
+        self.thermostat.thermostat = thermostat;
+        self.thermostat.changed = false;
+    }
+
+    /// Returns the edited thermostat once after it has been changed in the ui.
+    pub fn take_thermostat(&mut self) -> Option<Thermostat> {
+        if !self.thermostat.changed {
+            return None;
+        }
+        self.thermostat.changed = false;
+        Some(self.thermostat.thermostat)
+    }
+
+    /// Set the barostat the editor starts from.
+    pub fn set_barostat(&mut self, barostat: Barostat) {
+        self.barostat.barostat = barostat;
+        self.barostat.changed = false;
+    }
+
+    /// Returns the edited barostat once after it has been changed in the ui.
+    pub fn take_barostat(&mut self) -> Option<Barostat> {
+        if !self.barostat.changed {
+            return None;
+        }
+        self.barostat.changed = false;
+        Some(self.barostat.barostat)
+    }
+
+    /// Set the integrator the editor starts from and the substeps per frame the inner steps
+    /// of r-RESPA have to divide.
+    pub fn set_integrator(&mut self, integrator: Integrator, substeps: u32) {
+        self.integrator.integrator = integrator;
+        self.integrator.substeps = substeps;
+        self.integrator.changed = false;
+    }
+
+    /// Returns the edited integrator once after it has been changed in the ui.
+    pub fn take_integrator(&mut self) -> Option<Integrator> {
+        if !self.integrator.changed {
+            return None;
+        }
+        self.integrator.changed = false;
+        Some(self.integrator.integrator)
+    }
+
+    /// Set the timestep the editor starts from.
+    pub fn set_timestep(&mut self, timestep: Timestep) {
+        self.timestep.timestep = timestep;
+        self.timestep.changed = false;
+    }
+
+    /// Returns the edited timestep once after it has been changed in the ui.
+    pub fn take_timestep(&mut self) -> Option<Timestep> {
+        if !self.timestep.changed {
+            return None;
+        }
+        self.timestep.changed = false;
+        Some(self.timestep.timestep)
+    }
+
+    /// Set the force cap the editor starts from, zero is off.
+    pub fn set_max_force(&mut self, max_force: f32) {
+        self.force_cap.enabled = max_force > 0.0;
+        if max_force > 0.0 {
+            self.force_cap.max_force = max_force;
+        }
+        self.force_cap.changed = false;
+    }
+
+    /// Returns the edited force cap once after it has been changed in the ui, zero is off.
+    pub fn take_max_force(&mut self) -> Option<f32> {
+        if !self.force_cap.changed {
+            return None;
+        }
+        self.force_cap.changed = false;
+        Some(if self.force_cap.enabled { self.force_cap.max_force } else { 0.0 })
+    }
+
+    /// Set the constraint settings the editor starts from.
+    pub fn set_constraints(&mut self, constraints: Constraints) {
+        self.constraints.constraints = constraints;
+        self.constraints.changed = false;
+    }
+
+    /// Returns the edited constraint settings once after they have been changed in the ui.
+    pub fn take_constraints(&mut self) -> Option<Constraints> {
+        if !self.constraints.changed {
+            return None;
+        }
+        self.constraints.changed = false;
+        Some(self.constraints.constraints)
+    }
+
+    /// Set the progress of the running or last minimization.
+    pub fn set_minimizer_progress(&mut self, running: bool, state: Option<MinimizerState>) {
+        self.minimizer.running = running;
+        self.minimizer.state = state;
+    }
+
+    /// Returns the minimizer once after it has been started in the ui.
+    pub fn take_minimizer(&mut self) -> Option<Minimizer> {
+        if !self.minimizer.start {
+            return None;
+        }
+        self.minimizer.start = false;
+        Some(self.minimizer.minimizer)
+    }
+}
+
+/// Selects the thermostat and its target temperature and coupling time.
+#[derive(Default)]
+pub struct ThermostatEditor {
+    thermostat: Thermostat,
+    changed: bool,
+}
+
+impl ThermostatEditor {
+    fn name(&self) -> &'static str {
+        "Thermostat"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let thermostat = &mut self.thermostat;
+        let mut changed = false;
+
+        ui.horizontal_wrapped(|ui| {
+            for (kind, name) in [
+                (ThermostatKind::Off, "Off (NVE)"),
+                (ThermostatKind::Berendsen, "Berendsen"),
+                (ThermostatKind::Csvr, "CSVR"),
+                (ThermostatKind::Langevin, "Langevin"),
+                (ThermostatKind::NoseHoover, "Nosé–Hoover"),
+                (ThermostatKind::Andersen, "Andersen"),
+            ] {
+                changed |= ui.radio_value(&mut thermostat.kind, kind, name).changed();
+            }
+        });
+
+        ui.add_space(12.0);
+        changed |= ui.add(egui::Slider::new(&mut thermostat.temperature, 0.0..=1000.0).text("temperature (K)")).changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut thermostat.tau, 0.01..=10.0).logarithmic(true).text("coupling time (ps)"))
+            .changed();
+        if thermostat.kind == ThermostatKind::NoseHoover {
+            changed |= ui.add(egui::Slider::new(&mut thermostat.chain_length, 1..=MAX_CHAIN_LENGTH).text("chain length")).changed();
+        }
+
+        self.changed |= changed;
+    }
+}
+
+/// Selects the barostat, its coupling and the target pressure.
+#[derive(Default)]
+pub struct BarostatEditor {
+    barostat: Barostat,
+    changed: bool,
+}
+
+impl BarostatEditor {
+    fn name(&self) -> &'static str {
+        "Barostat"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let barostat = &mut self.barostat;
+        let mut changed = false;
+
+        ui.horizontal_wrapped(|ui| {
+            for (kind, name) in [
+                (BarostatKind::Off, "Off"),
+                (BarostatKind::Berendsen, "Berendsen"),
+                (BarostatKind::MonteCarlo, "Monte-Carlo"),
+            ] {
+                changed |= ui.radio_value(&mut barostat.kind, kind, name).changed();
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            changed |= ui.radio_value(&mut barostat.coupling, PressureCoupling::Isotropic, "isotropic").changed();
+            changed |= ui.radio_value(&mut barostat.coupling, PressureCoupling::PerAxis, "per axis").changed();
+        });
+
+        ui.add_space(12.0);
+        changed |= ui.add(egui::Slider::new(&mut barostat.pressure, -1000.0..=10000.0).text("pressure (bar)")).changed();
+        match barostat.kind {
+            BarostatKind::Berendsen => {
+                changed |= ui
+                    .add(egui::Slider::new(&mut barostat.tau, 0.1..=100.0).logarithmic(true).text("coupling time (ps)"))
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut barostat.compressibility, 1e-6..=1e-3).logarithmic(true).text("compressibility (1/bar)"))
+                    .changed();
+            }
+            BarostatKind::MonteCarlo => {
+                changed |= ui.add(egui::Slider::new(&mut barostat.interval, 1..=100).text("frames per move")).changed();
+            }
+            BarostatKind::Off => {}
+        }
+
+        self.changed |= changed;
+    }
+}
+
+/// Selects the integrator and the inner steps of r-RESPA.
+#[derive(Default)]
+pub struct IntegratorEditor {
+    integrator: Integrator,
+    substeps: u32, // per frame
+    changed: bool,
+}
+
+impl IntegratorEditor {
+    fn name(&self) -> &'static str {
+        "Integrator"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let integrator = &mut self.integrator;
+        let mut changed = false;
+
+        ui.horizontal_wrapped(|ui| {
+            for (kind, name) in [
+                (IntegratorKind::VelocityVerlet, "Velocity Verlet"),
+                (IntegratorKind::Leapfrog, "Leapfrog"),
+                (IntegratorKind::Beeman, "Beeman"),
+                (IntegratorKind::Respa, "r-RESPA"),
+            ] {
+                changed |= ui.radio_value(&mut integrator.kind, kind, name).changed();
+            }
+        });
+        if integrator.kind == IntegratorKind::Respa {
+            if !self.substeps.is_multiple_of(integrator.respa_steps) {
+                integrator.respa_steps = (1..integrator.respa_steps).rev().find(|steps| self.substeps.is_multiple_of(*steps)).unwrap_or(1);
+            }
+            ui.add_space(12.0);
+            ui.label("inner steps");
+            // every frame ends with an outer step
+            ui.horizontal_wrapped(|ui| {
+                for steps in (1..=self.substeps.min(16)).filter(|steps| self.substeps.is_multiple_of(*steps)) {
+                    changed |= ui.radio_value(&mut integrator.respa_steps, steps, steps.to_string()).changed();
+                }
+            });
+        }
+
+        self.changed |= changed;
+    }
+}
+
+/// Switches between a fixed and an adaptive timestep.
+#[derive(Default)]
+pub struct TimestepEditor {
+    timestep: Timestep,
+    changed: bool,
+}
+
+impl TimestepEditor {
+    fn name(&self) -> &'static str {
+        "Timestep"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let timestep = &mut self.timestep;
+        let mut changed = false;
+
+        changed |= ui.checkbox(&mut timestep.adaptive, "adaptive").changed();
+        ui.add_space(12.0);
+        if timestep.adaptive {
+            changed |= ui
+                .add(egui::Slider::new(&mut timestep.dt_min, 1e-6..=1e-2).logarithmic(true).text("smallest dt (ps)"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut timestep.dt_max, 1e-6..=1e-2).logarithmic(true).text("largest dt (ps)"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut timestep.displacement, 0.001..=0.2).logarithmic(true).text("largest move (sigma)"))
+                .changed();
+            timestep.dt_max = timestep.dt_max.max(timestep.dt_min);
+            timestep.dt = timestep.dt_min;
+        } else {
+            changed |= ui.add(egui::Slider::new(&mut timestep.dt, 1e-6..=1e-2).logarithmic(true).text("dt (ps)")).changed();
+        }
+
+        self.changed |= changed;
+    }
+}
+
+/// Caps the pair forces, for relaxing starting structures with overlaps.
+pub struct ForceCapEditor {
+    enabled: bool,
+    max_force: f32,
+    changed: bool,
+}
+
+impl Default for ForceCapEditor {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_force: 1000.0,
+            changed: false,
+        }
+    }
+}
+
+impl ForceCapEditor {
+    fn name(&self) -> &'static str {
+        "Force cap"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+
+        changed |= ui.checkbox(&mut self.enabled, "cap pair forces").changed();
+        if self.enabled {
+            changed |= ui
+                .add(egui::Slider::new(&mut self.max_force, 1.0..=1e6).logarithmic(true).text("max force (mU/nm)"))
+                .changed();
+        }
+
+        self.changed |= changed;
+    }
+}
+
+/// Tolerance and iteration limit of SHAKE and RATTLE.
+#[derive(Default)]
+pub struct ConstraintsEditor {
+    constraints: Constraints,
+    changed: bool,
+}
+
+impl ConstraintsEditor {
+    fn name(&self) -> &'static str {
+        "Constraints"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let constraints = &mut self.constraints;
+        let mut changed = false;
+
+        changed |= ui
+            .add(egui::Slider::new(&mut constraints.tolerance, 1e-8..=1e-2).logarithmic(true).text("relative tolerance"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut constraints.max_iterations, 1..=1000).logarithmic(true).text("max iterations"))
+            .changed();
+
+        self.changed |= changed;
+    }
+}
+
+/// Starts an energy minimization and shows how far it got.
+#[derive(Default)]
+pub struct MinimizerEditor {
+    minimizer: Minimizer,
+    start: bool,
+    running: bool,
+    state: Option<MinimizerState>,
+}
+
+impl MinimizerEditor {
+    fn name(&self) -> &'static str {
+        "Minimizer"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let minimizer = &mut self.minimizer;
+
+        ui.horizontal_wrapped(|ui| {
+            for (kind, name) in [(MinimizerKind::SteepestDescent, "Steepest descent"), (MinimizerKind::Fire, "FIRE")] {
+                if ui.radio_value(&mut minimizer.kind, kind, name).changed() {
+                    *minimizer = match kind {
+                        MinimizerKind::SteepestDescent => Minimizer::steepest_descent(minimizer.force_tolerance),
+                        MinimizerKind::Fire => Minimizer::fire(minimizer.force_tolerance),
+                    };
+                }
+            }
+        });
+        ui.add_space(12.0);
+        ui.add(egui::Slider::new(&mut minimizer.force_tolerance, 0.01..=1e4).logarithmic(true).text("force tolerance (mU/nm)"));
+        ui.add(egui::Slider::new(&mut minimizer.energy_tolerance, 1e-10..=1e-2).logarithmic(true).text("energy tolerance"));
+        ui.add(egui::Slider::new(&mut minimizer.max_steps, 10..=100000).logarithmic(true).text("max steps"));
+
+        ui.add_space(12.0);
+        if ui.add_enabled(!self.running, egui::Button::new("Minimize")).clicked() {
+            self.start = true;
+        }
+        if let Some(state) = self.state {
+            let status = if self.running { "running".to_string() } else { format!("{:?}", state.status()) };
+            ui.label(format!("{} after {} steps", status, state.steps));
+            ui.label(format!("energy: {} mU", state.energy));
+            ui.label(format!("largest force: {} mU/nm", state.max_force));
+        }
+    }
+}
+
+/// Editor for the force model and the particle life attraction matrix.
+pub struct LifeEditor {
+    force_model: ForceModel,
+    life: Option<ParticleLife>,
+    max_radius: f32, // in nm
+    changed: bool,
+}
+
+impl Default for LifeEditor {
+    fn default() -> Self {
+        Self {
+            force_model: ForceModel::LennardJones,
+            life: None,
+            max_radius: 1.0,
+            changed: false,
+        }
+    }
+}
+
+impl LifeEditor {
+    fn name(&self) -> &'static str {
+        "Particle Life"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .default_open(false)
+            .open(open)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let Some(life) = self.life.as_mut() else {
+            ui.label("No particle life settings loaded.");
+            return;
+        };
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            changed |= ui.radio_value(&mut self.force_model, ForceModel::LennardJones, "Lennard-Jones").changed();
+            changed |= ui.radio_value(&mut self.force_model, ForceModel::ParticleLife, "Particle life").changed();
+            changed |= ui.radio_value(&mut self.force_model, ForceModel::Tabulated, "Tabulated").changed();
+        });
+
+        ui.add_space(12.0);
+        ui.heading("Interaction");
+        // the neighbour search only looks one bin far
+        changed |= ui.add(egui::Slider::new(&mut life.radius, 0.05..=self.max_radius).text("radius (nm)")).changed();
+        changed |= ui.add(egui::Slider::new(&mut life.beta, 0.05..=0.95).text("beta")).changed();
+        changed |= ui.add(egui::Slider::new(&mut life.force, 0.0..=500.0).text("force (nm u / ps^2)")).changed();
+        changed |= ui.add(egui::Slider::new(&mut life.friction, 0.0..=50.0).text("friction (1 / ps)")).changed();
+
+        ui.add_space(12.0);
+        ui.heading("Attraction matrix");
+        ui.horizontal(|ui| {
+            let mut seed = life.seed;
+            ui.add(egui::DragValue::new(&mut seed).prefix("seed: "));
+            if ui.button("Randomize").clicked() {
+                life.randomize(seed);
+                changed = true;
+            } else {
+                life.seed = seed;
+            }
+        });
+        ui.label("row: type that feels the force, column: type it is pulled to");
+        egui::Grid::new("life_matrix").show(ui, |ui| {
+            ui.label("");
+            for j in 0..life.size() {
+                ui.label(format!("{}", j));
+            }
+            ui.end_row();
+            for i in 0..life.size() {
+                ui.label(format!("{}", i));
+                for j in 0..life.size() {
+                    let value = life.get_mut(i, j);
+                    changed |= ui
+                        .add(egui::DragValue::new(value).speed(0.01).clamp_range(-1.0..=1.0))
+                        .changed();
+                }
+                ui.end_row();
+            }
+        });
+
+        self.changed |= changed;
     }
 }
 
@@ -70,6 +689,12 @@ impl EnergyGraph {
         
         ui.label(format!("Temperature: {}", data.temperature()));
         ui.label(format!("velocity rms: {}", data.velocity_rms()));
+        ui.label(format!("thermostat energy: {}", data.thermostat_energy()));
+        ui.label(format!("pressure: {} bar", data.pressure()));
+        ui.label(format!("volume: {} nm^3", data.volume()));
+        ui.label(format!("time: {:.3} ps, dt: {} ps", data.time(), data.dt()));
+        ui.label(format!("capped pair forces: {}", data.capped()));
+        ui.label(format!("constraint error (rms): {:e}", data.constraint_error()));
 
         ui.add_space(12.0); // ui.separator();
         ui.heading("Graph");
@@ -88,17 +713,54 @@ impl EnergyGraph {
         pe_line = pe_line.color(egui::Color32::from_rgb(0, 255, 0));
         pe_line = pe_line.name("PE");
 
+        let mut coulomb_line = Line::new(PlotPoints::new(
+            data.graph_coulomb(sample_rate),
+        ));
+        coulomb_line = coulomb_line.color(egui::Color32::from_rgb(255, 255, 0));
+        coulomb_line = coulomb_line.name("Coulomb");
+
+        let mut bonded_line = Line::new(PlotPoints::new(
+            data.graph_bonded(sample_rate),
+        ));
+        bonded_line = bonded_line.color(egui::Color32::from_rgb(255, 0, 255));
+        bonded_line = bonded_line.name("Bonded");
+
         let mut te_line = Line::new(
             PlotPoints::new(data.graph_TE(sample_rate)),
         );
         te_line = te_line.color(egui::Color32::from_rgb(0, 0, 255));
         te_line = te_line.name("TE");
 
+        let mut conserved_line = Line::new(
+            PlotPoints::new(data.graph_conserved(sample_rate)),
+        );
+        conserved_line = conserved_line.color(egui::Color32::from_rgb(0, 255, 255));
+        conserved_line = conserved_line.name("Conserved");
+
 
         plot.show(ui, |ui| {
             ui.line(ke_line);
             ui.line(pe_line);
+            ui.line(coulomb_line);
+            ui.line(bonded_line);
             ui.line(te_line);
+            ui.line(conserved_line);
+        });
+
+        ui.heading("Pressure");
+        let mut pressure_line = Line::new(PlotPoints::new(data.graph_pressure(sample_rate)));
+        pressure_line = pressure_line.color(egui::Color32::from_rgb(255, 128, 0));
+        pressure_line = pressure_line.name("pressure (bar)");
+        Plot::new("Pressure").height(120.0).show(ui, |ui| {
+            ui.line(pressure_line);
+        });
+
+        ui.heading("Timestep");
+        let mut dt_line = Line::new(PlotPoints::new(data.graph_dt(sample_rate)));
+        dt_line = dt_line.color(egui::Color32::from_rgb(128, 128, 255));
+        dt_line = dt_line.name("dt (ps)");
+        Plot::new("Timestep").height(120.0).show(ui, |ui| {
+            ui.line(dt_line);
         });
 
     }
@@ -8,11 +8,11 @@ use crate::render::{
     vertex::{Circle, Vertex},
 };
 
-use crate::system::{consts::*, particle::Particle};
+use crate::system::{consts::*, particle::Particle, simulation_box::SimulationBox};
 
 use crate::utils::buffers::Buffer;
 
-use super::{camera::Projection, vertex::UVSphere};
+use super::{camera::Projection, vertex::{BoxOutline, UVSphere}};
 
 pub struct RenderSet {
     size: winit::dpi::PhysicalSize<u32>,
@@ -25,7 +25,10 @@ pub struct RenderSet {
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     num_indices: u32,
+    num_particles: u32,
     render_pipeline: wgpu::RenderPipeline,
+    box_vertex_buffer: Buffer,
+    box_pipeline: wgpu::RenderPipeline,
 }
 
 impl RenderSet {
@@ -33,6 +36,8 @@ impl RenderSet {
         window: &Window,
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        simulation_box: &SimulationBox,
+        num_particles: u32,
     ) -> Self {
         let size = window.inner_size();
 
@@ -51,6 +56,12 @@ impl RenderSet {
 
         let num_indices = circle.num_indices;
 
+        let outline = BoxOutline::new(simulation_box);
+        let box_vertex_buffer = Buffer::new()
+            .with_data(bytemuck::cast_slice(&outline.get_vertices()))
+            .with_usage(wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST)
+            .build(&device, Some("Box Vertex Buffer"));
+
         let camera_uniform = CameraUniform::new();
         let camera_buffer = Buffer::new()
             .with_data(bytemuck::cast_slice(&[camera_uniform]))
@@ -138,6 +149,38 @@ impl RenderSet {
             multiview: None,
         });
 
+        let box_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\box.wgsl"));
+
+        let box_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Box Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &box_shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &box_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
         Self {
             size,
             camera,
@@ -149,7 +192,10 @@ impl RenderSet {
             vertex_buffer,
             index_buffer,
             num_indices,
+            num_particles,
             render_pipeline,
+            box_vertex_buffer,
+            box_pipeline,
         }
     }
 
@@ -177,6 +223,16 @@ impl RenderSet {
         );
     }
 
+    /// updates the outline after the box changed shape
+    pub fn set_box(&self, queue: &wgpu::Queue, simulation_box: &SimulationBox) {
+        let outline = BoxOutline::new(simulation_box);
+        queue.write_buffer(
+            self.box_vertex_buffer.get_buffer(),
+            0,
+            bytemuck::cast_slice(&outline.get_vertices()),
+        );
+    }
+
     pub fn render(
         &mut self,
         encoder: &mut CommandEncoder,
@@ -218,7 +274,11 @@ impl RenderSet {
                 wgpu::IndexFormat::Uint16,
             );
 
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..NUMBER_PARTICLES as _);
+            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_particles);
+
+            render_pass.set_pipeline(&self.box_pipeline);
+            render_pass.set_vertex_buffer(0, self.box_vertex_buffer.get_buffer().slice(..));
+            render_pass.draw(0..BoxOutline::NUM_VERTICES, 0..1);
         }
         encoder.pop_debug_group();
 
@@ -1,3 +1,5 @@
+use crate::system::simulation_box::SimulationBox;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Vertex {
@@ -150,3 +152,37 @@ impl UVSphere {
         self.indices.clone()
     }
 }
+
+/// The 12 edges of the simulation box as a line list.
+pub struct BoxOutline {
+    vertices: Vec<Vertex>,
+}
+
+impl BoxOutline {
+    pub const NUM_VERTICES: u32 = 24;
+
+    pub fn new(simulation_box: &SimulationBox) -> Self {
+        let corner = |s: [f32; 3]| Vertex {
+            position: simulation_box.position(s),
+        };
+        let mut vertices = Vec::with_capacity(Self::NUM_VERTICES as usize);
+        // every edge runs along one axis from one of the 4 corners with s[axis] = 0
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for (a, b) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+                let mut s = [0.0; 3];
+                s[u] = a;
+                s[v] = b;
+                vertices.push(corner(s));
+                s[axis] = 1.0;
+                vertices.push(corner(s));
+            }
+        }
+
+        Self { vertices }
+    }
+
+    pub fn get_vertices(&self) -> Vec<Vertex> {
+        self.vertices.clone()
+    }
+}
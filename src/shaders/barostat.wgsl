@@ -0,0 +1,33 @@
+// scales all positions together with the box, the box is centred on the origin so the
+// fractional coordinates stay the same
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+// scale factors of x, y and z, w is unused
+@binding(0) @group(0) var<uniform> scale : vec4<f32>;
+@binding(1) @group(0) var<storage, read_write> particles : array<Particle>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+    particles[index].x = particles[index].x * scale.x;
+    particles[index].y = particles[index].y * scale.y;
+    particles[index].z = particles[index].z * scale.z;
+}
@@ -0,0 +1,285 @@
+// bonded forces: every particle evaluates the bonds, angles and dihedrals it takes part in
+// and keeps its own share of the force, energy and virial
+
+// a compute shader in wgsl that simulates gravity for all particles
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Bonded {
+    force: vec3<f32>, // in mU / nm
+    energy: f32, // in mU
+    virial: vec3<f32>, // diagonal of the virial tensor in mU
+}
+
+struct BondType {
+    style: u32, // 0: harmonic, 1: FENE
+    k: f32, // in mU / nm^2
+    r0: f32, // in nm
+    epsilon: f32, // in mU
+    sigma: f32, // in nm
+}
+
+struct AngleType {
+    k: f32, // in mU / rad^2
+    theta0: f32, // in rad
+}
+
+struct DihedralType {
+    k: f32, // in mU
+    phi0: f32, // in rad
+    n: u32,
+}
+
+struct Bond {
+    i: u32,
+    j: u32,
+    type_: u32,
+}
+
+struct Angle {
+    i: u32,
+    j: u32,
+    k: u32,
+    type_: u32,
+}
+
+struct Dihedral {
+    i: u32,
+    j: u32,
+    k: u32,
+    l: u32,
+    type_: u32,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read> bond_types : array<BondType>;
+@binding(3) @group(0) var<storage, read> angle_types : array<AngleType>;
+@binding(4) @group(0) var<storage, read> dihedral_types : array<DihedralType>;
+@binding(5) @group(0) var<storage, read> bonds : array<Bond>;
+@binding(6) @group(0) var<storage, read> angles : array<Angle>;
+@binding(7) @group(0) var<storage, read> dihedrals : array<Dihedral>;
+// N + 1 offsets followed by (kind << 30 | index) entries
+@binding(8) @group(0) var<storage, read> terms : array<u32>;
+@binding(9) @group(0) var<storage, read_write> bonded : array<Bonded>;
+
+fn position(i: u32) -> vec3<f32> {
+    return vec3<f32>(particles[i].x, particles[i].y, particles[i].z);
+}
+
+fn boundary_mode(axis: u32) -> u32 {
+    switch axis {
+        case 0u: {
+            return params.boundary_x;
+        }
+        case 1u: {
+            return params.boundary_y;
+        }
+        default: {
+            return params.boundary_z;
+        }
+    }
+}
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+// the minimum image convention, only along the periodic axes
+fn minimum_image(d: vec3<f32>) -> vec3<f32> {
+    var s = to_fractional(d);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if boundary_mode(axis) == 0u {
+            s[axis] = s[axis] - round(s[axis]);
+        }
+    }
+    return from_fractional(s);
+}
+
+// x_a - x_b with the minimum image convention
+fn delta(a: u32, b: u32) -> vec3<f32> {
+    return minimum_image(position(a) - position(b));
+}
+
+// force along the bond (positive pushes apart) in x, energy in y
+fn bond_force(bond_type: BondType, dist: f32) -> vec2<f32> {
+    if bond_type.style == 0u {
+        let stretch = dist - bond_type.r0;
+        return vec2<f32>(-bond_type.k * stretch, 0.5 * bond_type.k * stretch * stretch);
+    }
+    // beyond r_c the force stays at its value at r_c and the energy grows linearly, clamping
+    // the logarithm alone would let an overstretched bond pull without an energy
+    let r_c = sqrt(0.99) * bond_type.r0;
+    let r = min(dist, r_c);
+    let x = r * r / (bond_type.r0 * bond_type.r0);
+    var force = -bond_type.k * r / (1.0 - x);
+    var energy = -0.5 * bond_type.k * bond_type.r0 * bond_type.r0 * log(1.0 - x) - force * max(dist - r_c, 0.0);
+    if dist < 1.122462 * bond_type.sigma {
+        let r6 = pow(bond_type.sigma / dist, 6.0);
+        force += 24.0 * bond_type.epsilon * (2.0 * r6 * r6 - r6) / dist;
+        energy += 4.0 * bond_type.epsilon * (r6 * r6 - r6) + bond_type.epsilon;
+    }
+    return vec2<f32>(force, energy);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+
+    var force = vec3<f32>(0.0);
+    var energy = 0.0;
+    var virial = vec3<f32>(0.0);
+    for (var t = terms[index]; t < terms[index + 1u]; t += 1u) {
+        let kind = terms[t] >> 30u;
+        let term = terms[t] & 0x3fffffffu;
+        if kind == 0u {
+            let bond = bonds[term];
+            let r = delta(bond.i, bond.j);
+            let dist = length(r);
+            let f = bond_force(bond_types[bond.type_], dist);
+            energy += f.y * 0.5;
+            virial += 0.5 * f.x * r * r / dist;
+            if index == bond.i {
+                force += f.x * r / dist;
+            } else {
+                force -= f.x * r / dist;
+            }
+        } else if kind == 1u {
+            let angle = angles[term];
+            let angle_type = angle_types[angle.type_];
+            let r_ij = delta(angle.i, angle.j);
+            let r_kj = delta(angle.k, angle.j);
+            let a = length(r_ij);
+            let b = length(r_kj);
+            // atan2 keeps the angle accurate where acos of an f32 cosine is not
+            let cross_length = length(cross(r_ij, r_kj));
+            let cos_theta = dot(r_ij, r_kj) / (a * b);
+            let sin_theta = max(cross_length / (a * b), 1e-6);
+            let diff = atan2(cross_length, dot(r_ij, r_kj)) - angle_type.theta0;
+            let dv = angle_type.k * diff;
+            let f_i = dv / sin_theta * (r_kj / b - cos_theta * r_ij / a) / a;
+            let f_k = dv / sin_theta * (r_ij / a - cos_theta * r_kj / b) / b;
+            energy += 0.5 * angle_type.k * diff * diff / 3.0;
+            virial += (r_ij * f_i + r_kj * f_k) / 3.0;
+            if index == angle.i {
+                force += f_i;
+            } else if index == angle.k {
+                force += f_k;
+            } else {
+                force -= f_i + f_k;
+            }
+        } else {
+            let dihedral = dihedrals[term];
+            let dihedral_type = dihedral_types[dihedral.type_];
+            let r_ij = delta(dihedral.i, dihedral.j);
+            let r_kj = delta(dihedral.k, dihedral.j);
+            let r_kl = delta(dihedral.k, dihedral.l);
+            let m = cross(r_ij, r_kj);
+            let n = cross(r_kj, r_kl);
+            let m2 = max(dot(m, m), 1e-12);
+            let n2 = max(dot(n, n), 1e-12);
+            let kj2 = dot(r_kj, r_kj);
+            let kj = sqrt(kj2);
+            var phi = atan2(length(cross(m, n)), dot(m, n));
+            if dot(r_ij, n) < 0.0 {
+                phi = -phi;
+            }
+            let multiplicity = f32(dihedral_type.n);
+            let phase = multiplicity * phi - dihedral_type.phi0;
+            let dv = -dihedral_type.k * multiplicity * sin(phase);
+            energy += dihedral_type.k * (1.0 + cos(phase)) * 0.25;
+            let f_i = -dv * kj / m2 * m;
+            let f_l = dv * kj / n2 * n;
+            let s = dot(r_ij, r_kj) / kj2 * f_i - dot(r_kl, r_kj) / kj2 * f_l;
+            virial += (r_ij * f_i - r_kj * s - r_kl * f_l) * 0.25;
+            if index == dihedral.i {
+                force += f_i;
+            } else if index == dihedral.j {
+                force -= f_i - s;
+            } else if index == dihedral.k {
+                force -= f_l + s;
+            } else {
+                force += f_l;
+            }
+        }
+    }
+    bonded[index] = Bonded(force, energy, virial);
+}
@@ -0,0 +1,23 @@
+// Outline of the simulation box
+const COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4<f32>(model.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(COLOR, 1.0);
+}
@@ -1,27 +1,46 @@
 
 
-struct Atom{
-    size: f32, // in nm
-    mass: f32, // in Dalton (1.66053906660e-27 kg)
-    charge: i32, // in elementary charge (1.602176634e-19 C)
-    sigma: f32, // in nm
-    epsilon: f32, // eV (1.602176634e-19 J)
-}
-
 struct Params {
     N: u32, // number of particles
     dt: f32,  // in ps
     neghborhood_size: f32, // in nm
     max_force: f32, // in nm * amu / ps^2
-    friction: f32,  // in amu / ps
-    box_size: f32, // in nm
-    bin_size: f32, // in nm
-    bin_count: u32,
-    bin_capacity: u32,
-    align1: u32,
-    align2: u32,
-    align3: u32,
-    helium: Atom,
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
 }
 
 
@@ -41,39 +60,78 @@ struct Particle {
     type_: f32,
 }
 
+// the cell list is built in three steps: `main` counts the particles of every cell,
+// scan.wgsl turns the counts into the index of the first particle of every cell and
+// `scatter` writes the particle indices sorted by cell
+
 @binding(0) @group(0) var<uniform> params : Params;
 @binding(1) @group(0) var<storage, read> particles : array<Particle>;
-@binding(2) @group(0) var<storage, read_write> bin_load : array<atomic<u32>>;
-@binding(3) @group(0) var<storage, read_write> depth : array<i32>;
+// the particles per cell, after the scan the index of the first one in cell_particles
+@binding(2) @group(0) var<storage, read_write> cell_start : array<atomic<u32>>;
+@binding(3) @group(0) var<storage, read_write> cell_particles : array<u32>;
+@binding(4) @group(0) var<storage, read_write> slot : array<u32>; // of a particle within its cell
 
+const NO_CELL: u32 = 0xffffffffu;
 
-@compute @workgroup_size(64)
-fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
-    let index = GlobalInvocationID.x;
-    let array_length = arrayLength(&particles);
-    if index >= array_length {
-        return;
-    }
 
-    // get bin index
-    let x = (particles[index].x + params.box_size) / 2f;
-    let y = (particles[index].y + params.box_size) / 2f;
-    let z = (particles[index].z + params.box_size) / 2f;
-    let bin_x = u32(floor(x / params.bin_size));
-    let bin_y = u32(floor(y / params.bin_size));
-    let bin_z = u32(floor(z / params.bin_size));
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
 
-    let bin_index = bin_x + bin_y * params.bin_count + bin_z * params.bin_count * params.bin_count;
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
 
-    let depth_index = atomicAdd(&bin_load[bin_index], 1u);
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
 
-    if (depth_index >= params.bin_capacity) {
-        return;
+// the cell of a particle, NO_CELL if an open boundary absorbed it
+fn cell_of(index: u32) -> u32 {
+    let pos = vec3<f32>(particles[index].x, particles[index].y, particles[index].z);
+    let s = to_fractional(pos - box_origin());
+    if any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0)) {
+        return NO_CELL;
     }
 
-    let final_index = bin_index * params.bin_capacity + depth_index;
+    let bin_x = min(u32(floor(s.x * f32(params.bin_count_x))), params.bin_count_x - 1u);
+    let bin_y = min(u32(floor(s.y * f32(params.bin_count_y))), params.bin_count_y - 1u);
+    let bin_z = min(u32(floor(s.z * f32(params.bin_count_z))), params.bin_count_z - 1u);
+
+    return bin_x + bin_y * params.bin_count_x + bin_z * params.bin_count_x * params.bin_count_y;
+}
 
-    depth[final_index] = i32(index);
-    // atomicAdd(&depth[final_index], i32(index));
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+    let cell = cell_of(index);
+    if cell == NO_CELL {
+        return;
+    }
+    slot[index] = atomicAdd(&cell_start[cell], 1u);
 }
 
+@compute @workgroup_size(64)
+fn scatter(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+    let cell = cell_of(index);
+    if cell == NO_CELL {
+        return;
+    }
+    cell_particles[atomicLoad(&cell_start[cell]) + slot[index]] = index;
+}
@@ -15,15 +15,42 @@ struct Params {
     dt: f32,  // in ps
     neghborhood_size: f32, // in nm
     max_force: f32, // in nm * amu / ps^2
-    friction: f32,  // in amu / ps
-    box_size: f32, // in nm
-    bin_size: f32, // in nm
-    bin_count: u32,
-    bin_capacity: u32,
-    align1: u32,
-    align2: u32,
-    align3: u32,
-    helium: Atom,
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
 }
 
 struct Particle {
@@ -45,43 +72,166 @@ struct Particle {
 struct Stats {
     KE: f32,
     PE: f32,
+    PE_real: f32, // real space coulomb energy
+    PE_recip: f32, // reciprocal coulomb energy (including the self energy)
+    PE_bonded: f32, // bonds, angles and dihedrals
+    E_thermostat: f32, // filled in by thermostat.wgsl
+    W_x: f32, // diagonal of the virial tensor in mU
+    W_y: f32,
+    W_z: f32,
+    capped: f32, // pair forces cut to max_force, every pair counts half on both sides
+    constraint_error: f32, // filled in by constraints.wgsl
+    unconverged: f32,
+}
+
+struct Bonded {
+    force: vec3<f32>, // in mU / nm
+    energy: f32, // in mU
+    virial: vec3<f32>, // in mU
+}
+
+// per particle state of the integrators: the acceleration of the last step for Beeman,
+// the slow acceleration with its energies and virial for r-RESPA
+struct IntegratorParticle {
+    acc: vec3<f32>, // in nm / ps^2
+    pe_real: f32, // in mU
+    virial: vec3<f32>, // in mU
+    pe_recip: f32, // in mU
+}
+
+// reciprocal ewald sum and the correction for the excluded pairs
+struct LongRange {
+    force: vec3<f32>, // in mU / nm
+    pe_real: f32, // in mU
+    virial: vec3<f32>, // in mU
+    pe_recip: f32, // in mU
+}
+
+struct KVector {
+    kx: f32, // in 1 / nm
+    ky: f32,
+    kz: f32,
+    prefactor: f32, // in mU * nm
+    s_re: f32, // in e
+    s_im: f32,
+    align1: f32,
+    align2: f32,
+}
+
+const COULOMB_CONSTANT: f32 = 138.935458; // in mU * nm / e^2
+const SQRT_PI: f32 = 1.7724538509;
+
+struct Pair {
+    sigma: f32, // in nm
+    epsilon: f32, // nm^2 * u * ps^-2
+    e_shift: f32, // V(r_cut)
+    f_shift: f32, // -V'(r_cut)
+    table_offset: u32, // first entry in tables
+    table_len: u32, // 0 if the pair has no table
+    table_r_min: f32, // in nm
+    table_inv_dr: f32, // in 1 / nm
+}
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
 }
 
 @binding(0) @group(0) var<uniform> params : Params;
 @binding(1) @group(0) var<storage, read> particlesA : array<Particle>;
 @binding(2) @group(0) var<storage, read_write> particlesB : array<Particle>;
-@binding(3) @group(0) var<storage, read> bin_load : array<u32>;
-@binding(4) @group(0) var<storage, read> depth : array<i32>;
+// the particles of a cell are cell_particles[cell_start[cell]..cell_start[cell + 1]],
+// with a Verlet list the first N entries are followed by the list of neighbours.wgsl
+@binding(3) @group(0) var<storage, read> cell_start : array<u32>;
+@binding(4) @group(0) var<storage, read> cell_particles : array<u32>;
 @binding(5) @group(0) var<storage, read_write> stats : array<Stats>;
+@binding(6) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(7) @group(0) var<storage, read> pairs : array<Pair>;
+@binding(8) @group(0) var<storage, read> life_matrix : array<f32>;
+@binding(9) @group(0) var<storage, read> kvectors : array<KVector>;
+@binding(10) @group(0) var<storage, read> tables : array<vec2<f32>>; // (V, F) on equidistant points
+// N + 1 offsets followed by the 1-2 and 1-3 partners of every particle
+@binding(11) @group(0) var<storage, read> exclusions : array<u32>;
+// bonded forces, energies and virials from bonded.wgsl
+@binding(12) @group(0) var<storage, read> bonded : array<Bonded>;
+@binding(13) @group(0) var<storage, read_write> integrator_particles : array<IntegratorParticle>;
+// the length of the substep comes from timestep.wgsl
+@binding(14) @group(0) var<storage, read> timestep : Timestep;
 
-// let PARTICLES_TO_CHECK_SIZE: u32 = params.bin_capacity * 9u;
 
 
 
-fn wrap_bin(x: i32, y: i32, z: i32) -> u32 {
-    var bin_x = x;
-    var bin_y = y;
-    var bin_z = z;
-    let bin_count = i32(params.bin_count);
-    if bin_x < 0 {
-        bin_x = bin_count + bin_x;
-    }
-    if bin_x >= bin_count {
-        bin_x = bin_x - bin_count;
-    }
-    if bin_y < 0 {
-        bin_y = bin_count + bin_y;
-    }
-    if bin_y >= bin_count {
-        bin_y = bin_y - bin_count;
+const NO_BIN: u32 = 0xffffffffu;
+
+fn boundary_mode(axis: u32) -> u32 {
+    switch axis {
+        case 0u: {
+            return params.boundary_x;
+        }
+        case 1u: {
+            return params.boundary_y;
+        }
+        default: {
+            return params.boundary_z;
+        }
     }
-    if bin_z < 0 {
-        bin_z = bin_count + bin_z;
+}
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+// the minimum image convention, only along the periodic axes
+fn minimum_image(d: vec3<f32>) -> vec3<f32> {
+    var s = to_fractional(d);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if boundary_mode(axis) == 0u {
+            s[axis] = s[axis] - round(s[axis]);
+        }
     }
-    if bin_z >= bin_count {
-        bin_z = bin_z - bin_count;
+    return from_fractional(s);
+}
+
+// wraps the bin around periodic axes, bins beyond a wall or open face do not exist (NO_BIN)
+fn wrap_bin(x: i32, y: i32, z: i32) -> u32 {
+    let counts = vec3<i32>(i32(params.bin_count_x), i32(params.bin_count_y), i32(params.bin_count_z));
+    var bin = vec3<i32>(x, y, z);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if bin[axis] < 0 || bin[axis] >= counts[axis] {
+            if boundary_mode(axis) != 0u {
+                return NO_BIN;
+            }
+            bin[axis] = (bin[axis] + counts[axis]) % counts[axis];
+        }
     }
-    return u32(bin_x + bin_y * bin_count + bin_z * bin_count * bin_count);    
+    return u32(bin.x + bin.y * counts.x + bin.z * counts.x * counts.y);
 }
 
 // fn cap_bin(x: i32, y: i32, z: i32) -> i32 {
@@ -108,11 +258,151 @@ fn lennard_jones_force(dist: f32, sigma: f32, epsilon: f32) -> f32 {
     return force;
 }
 
-fn calc_forces(dist: f32, normal: vec3<f32>) -> vec3<f32> {
-    let force = lennard_jones_force(dist, params.helium.sigma, params.helium.epsilon);
-    return force * normal;
+// lennard jones with the selected cutoff scheme, returns the force along the normal in x and the energy in y
+fn lennard_jones_cut(dist: f32, pair: Pair) -> vec2<f32> {
+    var energy = lennard_jones(dist, pair.sigma, pair.epsilon);
+    var force = lennard_jones_force(dist, pair.sigma, pair.epsilon);
+    let r_cut = params.neghborhood_size;
+    switch params.cutoff_scheme {
+        case 1u: {
+            energy = energy - pair.e_shift;
+        }
+        case 2u: {
+            energy = energy - pair.e_shift + (dist - r_cut) * pair.f_shift;
+            force = force - pair.f_shift;
+        }
+        case 3u: {
+            if dist > params.r_switch {
+                // CHARMM switching function, goes from 1 at r_switch to 0 at r_cut
+                let rc2 = r_cut * r_cut;
+                let rs2 = params.r_switch * params.r_switch;
+                let r2 = dist * dist;
+                let denom = pow(rc2 - rs2, 3.0);
+                let s = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denom;
+                let ds = 12.0 * dist * (rc2 - r2) * (rs2 - r2) / denom;
+                force = force * s - energy * ds;
+                energy = energy * s;
+            }
+        }
+        default: {}
+    }
+    return vec2<f32>(force, energy);
+}
+
+// linear interpolation in the table of the pair, returns the force along the normal in x and the energy in y
+fn tabulated(dist: f32, pair: Pair) -> vec2<f32> {
+    let x = max(dist - pair.table_r_min, 0.0) * pair.table_inv_dr;
+    let i = min(u32(x), pair.table_len - 2u);
+    if x > f32(pair.table_len - 1u) {
+        return vec2<f32>(0.0);
+    }
+    let t = min(x - f32(i), 1.0);
+    let a = tables[pair.table_offset + i];
+    let b = tables[pair.table_offset + i + 1u];
+    let value = mix(a, b, t);
+    return vec2<f32>(value.y, value.x);
+}
+
+// force capping, a pair force above params.max_force is cut to it and counted, the energy is
+// left as it is. A max_force of zero turns the capping off.
+fn cap(force: f32, capped: ptr<function, f32>) -> f32 {
+    if params.max_force <= 0.0 || abs(force) <= params.max_force {
+        return force;
+    }
+    *capped = *capped + 0.5;
+    return clamp(force, -params.max_force, params.max_force);
+}
+
+fn is_excluded(i: u32, j: u32) -> bool {
+    for (var e = exclusions[i]; e < exclusions[i + 1u]; e += 1u) {
+        if exclusions[e] == j {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Abramowitz & Stegun 7.1.26, max error 1.5e-7
+fn erfc_approx(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    return poly * exp(-x * x);
+}
+
+// real space part of the ewald sum, returns the force along the normal in x and the energy in y
+fn coulomb_real(dist: f32, qq: f32) -> vec2<f32> {
+    let alpha = params.ewald_alpha;
+    let erfc_ = erfc_approx(alpha * dist);
+    let energy = COULOMB_CONSTANT * qq * erfc_ / dist;
+    let force = COULOMB_CONSTANT * qq * (erfc_ / dist + 2.0 * alpha / SQRT_PI * exp(-alpha * alpha * dist * dist)) / dist;
+    return vec2<f32>(force, energy);
+}
+
+fn particle_life_force(dist: f32, attraction: f32) -> f32 {
+    // piecewise linear: repulsion below beta, a triangle with height attraction above it
+    // (positive values pull the particles together)
+    let r = dist / params.life_radius;
+    if r < params.life_beta {
+        return r / params.life_beta - 1.0;
+    }
+    if r < 1.0 {
+        return attraction * (1.0 - abs(2.0 * r - 1.0 - params.life_beta) / (1.0 - params.life_beta));
+    }
+    return 0.0;
+}
+
+
+fn position_of(index: u32, from_b: bool) -> vec3<f32> {
+    if from_b {
+        return vec3<f32>(particlesB[index].x, particlesB[index].y, particlesB[index].z);
+    }
+    return vec3<f32>(particlesA[index].x, particlesA[index].y, particlesA[index].z);
 }
 
+// the reciprocal part of the ewald sum (the structure factors come from ewald.wgsl) and the
+// full coulomb interaction of the excluded pairs, which the reciprocal sum contains as well
+fn long_range(index: u32, pos: vec3<f32>, q_i: f32, from_b: bool) -> LongRange {
+    var result: LongRange;
+    for (var k = 0u; k < params.kvector_count; k += 1u) {
+        let kv = kvectors[k];
+        let kvec = vec3<f32>(kv.kx, kv.ky, kv.kz);
+        let kr = dot(kvec, pos);
+        let c = cos(kr);
+        let s = sin(kr);
+        result.force = result.force + 2.0 * kv.prefactor * q_i * (kv.s_re * s - kv.s_im * c) * kvec;
+        let e_k = kv.prefactor * q_i * (kv.s_re * c + kv.s_im * s);
+        result.pe_recip = result.pe_recip + e_k;
+        // diagonal of the reciprocal virial tensor
+        let k2 = dot(kvec, kvec);
+        result.virial = result.virial + e_k * (1.0 - 2.0 * kvec * kvec / k2 * (1.0 + k2 / (4.0 * params.ewald_alpha * params.ewald_alpha)));
+    }
+    result.pe_recip = result.pe_recip - COULOMB_CONSTANT * params.ewald_alpha / SQRT_PI * q_i * q_i;
+
+    for (var e = exclusions[index]; e < exclusions[index + 1u]; e += 1u) {
+        let p_index = exclusions[e];
+        var type_j = u32(particlesA[p_index].type_);
+        if from_b {
+            type_j = u32(particlesB[p_index].type_);
+        }
+        let qq = q_i * f32(atoms[type_j].charge);
+        if qq == 0.0 {
+            continue;
+        }
+        let d = minimum_image(pos - position_of(p_index, from_b));
+        let dist = length(d);
+        let coulomb = coulomb_real(dist, qq);
+        let full = COULOMB_CONSTANT * qq / dist;
+        result.pe_real = result.pe_real + (coulomb.y - full) * 0.5;
+        result.force = result.force + (coulomb.x - full / dist) * d / dist;
+        result.virial = result.virial + 0.5 * (coulomb.x - full / dist) * d * d / dist;
+    }
+    return result;
+}
+
+fn is_absorbed(pos: vec3<f32>) -> bool {
+    let s = to_fractional(pos - box_origin());
+    return any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0));
+}
 
 @compute @workgroup_size(64)
 fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
@@ -125,158 +415,197 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     var vPos = vec3<f32>(particlesA[index].x, particlesA[index].y, particlesA[index].z);
     var vVel = vec3<f32>(particlesA[index].vel_x, particlesA[index].vel_y, particlesA[index].vel_z);
     var vAcc = vec3<f32>(particlesA[index].acc_x, particlesA[index].acc_y, particlesA[index].acc_z);
+    let type_i = u32(particlesA[index].type_);
+    let atom = atoms[type_i];
+
+    // absorbed by an open boundary, the particle stays where it left the box
+    let s = to_fractional(vPos - box_origin());
+    if any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0)) {
+        particlesB[index] = particlesA[index];
+        stats[index].KE = 0.0;
+        stats[index].PE = 0.0;
+        stats[index].PE_real = 0.0;
+        stats[index].PE_recip = 0.0;
+        stats[index].PE_bonded = 0.0;
+        stats[index].E_thermostat = 0.0;
+        stats[index].W_x = 0.0;
+        stats[index].W_y = 0.0;
+        stats[index].W_z = 0.0;
+        stats[index].capped = 0.0;
+        stats[index].constraint_error = 0.0;
+        stats[index].unconverged = 0.0;
+        return;
+    }
+    let q_i = f32(atom.charge);
 
     var pe = 0.0;
+    var pe_real = 0.0;
+    var pe_recip = 0.0;
     var pos: vec3<f32>;
     var vel: vec3<f32>;
     var d: vec3<f32>;
     var dist: f32;
     var normal: vec3<f32>;
-    var acc = vec3<f32>(0.0);
-    var f: f32;
-
-    let x_temp = (vPos.x + params.box_size) / 2f;
-    let y_temp = (vPos.y + params.box_size) / 2f;
-    let z_temp = (vPos.z + params.box_size) / 2f;
-    let bin_x = i32(floor(x_temp / params.bin_size));
-    let bin_y = i32(floor(y_temp / params.bin_size));
-    let bin_z = i32(floor(z_temp / params.bin_size));
-
-    for (var x = -1; x <= 1; x += 1) {
-        for (var y = -1; y <= 1; y += 1) {
-            for (var z = -1; z <= 1; z += 1) {
-                let new_x = bin_x + x;
-                let new_y = bin_y + y;
-                let new_z = bin_z + z;
-
-                // if (new_x < 0 || new_x >= i32(params.bin_count) || new_y < 0 || new_y >= i32(params.bin_count)) {
-                    // continue;
-                // }
-                let bin_index = wrap_bin(new_x, new_y, new_z);
-                // let bin_index = u32(new_x) + u32(new_y) * params.bin_count;
-                let bin_size = bin_load[bin_index];
-                for (var j = 0u; j < bin_size; j += 1u) {
-                    let p_index = u32(depth[bin_index*params.bin_capacity + j]);
-                    if p_index == index {
+    var force = vec3<f32>(0.0);
+    // sum of d * force over the pairs, every pair counts half like the energy
+    var virial = vec3<f32>(0.0);
+    var capped = 0.0;
+    var pair: Pair;
+
+    let bin_x = min(i32(floor(s.x * f32(params.bin_count_x))), i32(params.bin_count_x) - 1);
+    let bin_y = min(i32(floor(s.y * f32(params.bin_count_y))), i32(params.bin_count_y) - 1);
+    let bin_z = min(i32(floor(s.z * f32(params.bin_count_z))), i32(params.bin_count_z) - 1);
+
+    // the Verlet list of the particle, or the 27 bins around it without a list or if the
+    // list had no room for all of its neighbours
+    let neighbours = select(0u, cell_particles[params.N + index], params.verlet_skin > 0.0);
+    let from_list = params.verlet_skin > 0.0 && neighbours <= arrayLength(&cell_particles) / params.N - 2u;
+    // a single pass over the list
+    let first = select(-1, 1, from_list);
+
+    for (var x = first; x <= 1; x += 1) {
+        for (var y = first; y <= 1; y += 1) {
+            for (var z = first; z <= 1; z += 1) {
+                // the partners are cell_particles[start], cell_particles[start + stride], .. before end
+                var start = 2u * params.N + index;
+                var end = start + neighbours * params.N;
+                var stride = params.N;
+                if !from_list {
+                    let bin_index = wrap_bin(bin_x + x, bin_y + y, bin_z + z);
+                    if bin_index == NO_BIN {
+                        continue;
+                    }
+                    start = cell_start[bin_index];
+                    end = cell_start[bin_index + 1u];
+                    stride = 1u;
+                }
+                for (var j = start; j < end; j += stride) {
+                    let p_index = cell_particles[j];
+                    if p_index == index || is_excluded(index, p_index) {
                         continue;
                     }
 
                     pos = vec3<f32>(particlesA[p_index].x, particlesA[p_index].y, particlesA[p_index].z);
                     vel = vec3<f32>(particlesA[p_index].vel_x, particlesA[p_index].vel_y, particlesA[p_index].vel_z);
-                    d = vPos - pos;
-                    // wrap around
-                    if d.x > params.box_size {
-                        d.x -= params.box_size*2.0;
-                    }
-                    if d.x < -params.box_size {
-                        d.x += params.box_size*2.0;
-                    }
-                    if d.y > params.box_size {
-                        d.y -= params.box_size*2.0;
+                    d = minimum_image(vPos - pos);
+
+                    dist = length(d);
+                    let type_j = u32(particlesA[p_index].type_);
+                    if params.force_model == 1u {
+                        if dist <= 0.0 || dist >= params.life_radius {
+                            continue;
+                        }
+                        let attraction = life_matrix[type_i * params.type_count + type_j];
+                        let life = -particle_life_force(dist, attraction) * params.life_force * d / dist;
+                        force = force + life;
+                        virial = virial + 0.5 * d * life;
+                        continue;
                     }
-                    if d.y < -params.box_size {
-                        d.y += params.box_size*2.0;
+                    if params.electrostatics == 1u && q_i != 0.0 && dist > 0.0 && dist < params.neghborhood_size {
+                        let qq = q_i * f32(atoms[type_j].charge);
+                        let coulomb = coulomb_real(dist, qq);
+                        let f = cap(coulomb.x, &capped);
+                        pe_real = pe_real + coulomb.y * 0.5;
+                        force = force + f * d / dist;
+                        virial = virial + 0.5 * f * d * d / dist;
                     }
-                    if d.z > params.box_size {
-                        d.z -= params.box_size*2.0;
+                    if dist >= params.neghborhood_size {
+                        continue;
                     }
-                    if d.z < -params.box_size {
-                        d.z += params.box_size*2.0;
+                    pair = pairs[type_i * params.type_count + type_j];
+                    if params.force_model == 2u && pair.table_len > 0u && dist > 0.0 {
+                        let table = tabulated(dist, pair);
+                        let f = cap(table.x, &capped);
+                        pe = pe + table.y * 0.5;
+                        force = force + f * d / dist;
+                        virial = virial + 0.5 * f * d * d / dist;
+                        continue;
                     }
-
-                    dist = length(d);
-                    // if dist > params.neghborhood_size {
-                    //     continue;
-                    // }
-                    if dist < params.helium.sigma * 0.35 {
-                        dist = params.helium.sigma * 0.35;
+                    // on top of each other, there is no direction to push them apart, the
+                    // diverging force only counts as capped while the capping is on
+                    if dist <= 0.0 {
+                        if params.max_force > 0.0 {
+                            capped = capped + 0.5;
+                        }
+                        continue;
                     }
                     normal = d / dist;
-                    pe = pe + lennard_jones(dist, params.helium.sigma, params.helium.epsilon) * 0.5;
-                    acc = acc + calc_forces(dist, normal) / params.helium.mass;
+                    let lj = lennard_jones_cut(dist, pair);
+                    let f = cap(lj.x, &capped);
+                    pe = pe + lj.y * 0.5;
+                    force = force + f * normal;
+                    virial = virial + 0.5 * f * normal * d;
                 }
             }
         }
     }
 
 
-    // leap frog integration
-    // vPos = vPos + vVel * params.dt + vAcc * params.dt * params.dt * 0.5;
-    // vVel = vVel + acc * params.dt;
-    
+    // the long range part, r-RESPA evaluates it only once per outer step in `slow`
+    if params.electrostatics == 1u && q_i != 0.0 {
+        if params.integrator == 3u {
+            let slow = integrator_particles[index];
+            pe_real = pe_real + slow.pe_real;
+            pe_recip = slow.pe_recip;
+            virial = virial + slow.virial;
+        } else {
+            let slow = long_range(index, vPos, q_i, false);
+            force = force + slow.force;
+            pe_real = pe_real + slow.pe_real;
+            pe_recip = slow.pe_recip;
+            virial = virial + slow.virial;
+        }
+    }
 
-    vVel = vVel + (acc + vAcc) * params.dt * 0.5;
-    // vPos = vPos + vVel * params.dt + vAcc * params.dt * params.dt * 0.5;
-
-    // vPos = vPos + vVel * params.dt + vAcc * params.dt * params.dt * 0.5;
-    // vVel = vVel + acc * params.dt;
-
-    // let vVelHalf = vVel + acc * params.dt * 0.5 + acc * params.dt * params.dt * 0.25 + vAcc * params.dt * params.dt * 0.125;
-    // vPos = vPos + vVelHalf * params.dt + vAcc * params.dt * params.dt * 0.5;
-    // vVel = vVelHalf + acc * params.dt * 0.5 + acc * params.dt * params.dt * 0.25 + vAcc * params.dt * params.dt * 0.125;
-
-    // // Runge-Kutta
-    // let dxdt = vVel + acc * params.dt * 0.5;
-    // let dvdt = acc;
-    // let dt = params.dt;
-
-    // // Compute intermediate values
-    // let k1x = dxdt*dt;
-    // let k1v = dvdt*dt;
-    // let k2x = (dxdt + 0.5*k1v)*dt;
-    // let k2v = (dvdt + 0.5*k1x)*dt;
-    // let k3x = (dxdt + 0.5*k2v)*dt;
-    // let k3v = (dvdt + 0.5*k2x)*dt;
-    // let k4x = (dxdt + k3v)*dt;
-    // let k4v = (dvdt + k3x)*dt;
-    
-    // // Compute final values
-    // vPos = vPos + (k1x + 2.0*k2x + 2.0*k3x + k4x)/6.0;
-    // vVel = vVel + (k1v + 2.0*k2v + 2.0*k3v + k4v)/6.0;
-    
+    force = force + bonded[index].force;
+    virial = virial + bonded[index].virial;
 
-    let ke = 0.5 * dot(vVel, vVel) * params.helium.mass;
-    
-    if vPos.x < 0.0 {
-        vPos.x += params.box_size*2.0;
-    }
-    if vPos.x > params.box_size {
-        vPos.x -= params.box_size*2.0;
-    }
-    if vPos.y < 0.0 {
-        // vPos.y =-params.box_size;
-        vPos.y += params.box_size*2.0;
-    }
-    if vPos.y > params.box_size {
-        vPos.y -= params.box_size*2.0;
-    }
-    if vPos.z < 0.0 {
-        vPos.z += params.box_size*2.0;
+    let acc = force / atom.mass;
+
+    var ke_vel = vVel;
+    switch params.integrator {
+        // leapfrog, varlets.wgsl already moved the velocities to the next half step,
+        // the kinetic energy belongs to the full step
+        case 1u: {
+            ke_vel = vVel + acc * timestep.dt * 0.5;
+        }
+        // beeman, keeps the old acceleration for the next step
+        case 2u: {
+            vVel = vVel + (2.0 * acc + 5.0 * vAcc - integrator_particles[index].acc) * timestep.dt / 6.0;
+            integrator_particles[index].acc = vAcc;
+            ke_vel = vVel;
+        }
+        // minimization, minimize.wgsl sets the velocities from the new forces
+        case 4u: {
+            ke_vel = vVel;
+        }
+        // velocity verlet, r-RESPA with the short range forces
+        default: {
+            vVel = vVel + (acc + vAcc) * timestep.dt * 0.5;
+            ke_vel = vVel;
+        }
     }
-    if vPos.z > params.box_size {
-        vPos.z -= params.box_size*2.0;
+    if params.force_model == 1u {
+        vVel = vVel * exp(-params.life_friction * timestep.dt);
+        ke_vel = ke_vel * exp(-params.life_friction * timestep.dt);
     }
 
-    // if vPos.x < -params.box_size {
-    //     vPos.x = -params.box_size;
-    //     vVel.x = vVel.x * -1.0;
-    // }
-    // if vPos.x > params.box_size {
-    //     vPos.x = params.box_size;
-    //     vVel.x = vVel.x * -1.0;
-    // }
-    // if vPos.y < -params.box_size {
-    //     vPos.y = -params.box_size;
-    //     vVel.y = vVel.y * -1.0;
-    // }
-    // if vPos.y > params.box_size {
-    //     vPos.y = params.box_size;
-    //     vVel.y = vVel.y * -1.0;
-    // }
-
+    let ke = 0.5 * dot(ke_vel, ke_vel) * atom.mass;
     
+    // the boundaries were applied to the position in varlets.wgsl
+
     stats[index].KE = ke;
     stats[index].PE = pe;
+    stats[index].PE_real = pe_real;
+    stats[index].PE_recip = pe_recip;
+    stats[index].PE_bonded = bonded[index].energy;
+    stats[index].E_thermostat = 0.0;
+    stats[index].W_x = virial.x;
+    stats[index].W_y = virial.y;
+    stats[index].W_z = virial.z;
+    stats[index].capped = capped;
+    stats[index].constraint_error = 0.0;
+    stats[index].unconverged = 0.0;
     particlesB[index].x = vPos.x;
     particlesB[index].y = vPos.y;
     particlesB[index].z = vPos.z;
@@ -286,4 +615,53 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     particlesB[index].acc_x = acc.x;
     particlesB[index].acc_y = acc.y;
     particlesB[index].acc_z = acc.z;
-}
\ No newline at end of file
+}
+// r-RESPA: the slow forces at the end of an outer step, on the particles written by `main`,
+// followed by the second half of the slow kick
+@compute @workgroup_size(64)
+fn slow(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particlesB) {
+        return;
+    }
+    let pos = position_of(index, true);
+    let atom = atoms[u32(particlesB[index].type_)];
+    let q_i = f32(atom.charge);
+    if q_i == 0.0 || is_absorbed(pos) {
+        return;
+    }
+
+    let old = integrator_particles[index];
+    let slow = long_range(index, pos, q_i, true);
+    let acc = slow.force / atom.mass;
+    integrator_particles[index] = IntegratorParticle(acc, slow.pe_real, slow.virial, slow.pe_recip);
+
+    var vel = vec3<f32>(particlesB[index].vel_x, particlesB[index].vel_y, particlesB[index].vel_z);
+    vel = vel + acc * timestep.dt * f32(params.respa_steps) * 0.5;
+    particlesB[index].vel_x = vel.x;
+    particlesB[index].vel_y = vel.y;
+    particlesB[index].vel_z = vel.z;
+
+    // `main` used the slow terms of the last outer step
+    stats[index].KE = 0.5 * dot(vel, vel) * atom.mass;
+    stats[index].PE_real = stats[index].PE_real - old.pe_real + slow.pe_real;
+    stats[index].PE_recip = slow.pe_recip;
+    stats[index].W_x = stats[index].W_x - old.virial.x + slow.virial.x;
+    stats[index].W_y = stats[index].W_y - old.virial.y + slow.virial.y;
+    stats[index].W_z = stats[index].W_z - old.virial.z + slow.virial.z;
+}
+
+// r-RESPA: the first half of the slow kick at the start of an outer step, dispatched with
+// the bind group of the other parity so particlesB holds the particles before the drift
+@compute @workgroup_size(64)
+fn slow_kick(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particlesB) || is_absorbed(position_of(index, true)) {
+        return;
+    }
+    let vel = vec3<f32>(particlesB[index].vel_x, particlesB[index].vel_y, particlesB[index].vel_z)
+        + integrator_particles[index].acc * timestep.dt * f32(params.respa_steps) * 0.5;
+    particlesB[index].vel_x = vel.x;
+    particlesB[index].vel_y = vel.y;
+    particlesB[index].vel_z = vel.z;
+}
@@ -0,0 +1,354 @@
+// SHAKE and RATTLE for the constraints of the topology. One thread takes a cluster of
+// constraints that share particles and sweeps over it until every constraint is kept.
+// `shake` runs after the drift in varlets.wgsl on particlesA, the positions before the drift
+// are still in particlesB (the force pass of the last substep copied them there). `rattle`
+// runs after the velocity update on particlesB, which compute.wgsl has just written.
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Atom{
+    size: f32, // in nm
+    mass: f32, // in Dalton (1.66053906660e-27 kg)
+    charge: i32, // in elementary charge (1.602176634e-19 C)
+    sigma: f32, // in nm
+    epsilon: f32, // eV (1.602176634e-19 J)
+}
+
+struct Stats {
+    KE: f32,
+    PE: f32,
+    PE_real: f32,
+    PE_recip: f32,
+    PE_bonded: f32,
+    E_thermostat: f32,
+    W_x: f32,
+    W_y: f32,
+    W_z: f32,
+    capped: f32,
+    constraint_error: f32, // sum of the squared relative deviations of the cluster
+    unconverged: f32, // 1 if SHAKE or RATTLE hit the iteration limit on the cluster
+}
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+struct Constraint {
+    i: u32,
+    j: u32,
+    length: f32, // in nm
+}
+
+// what `shake` leaves for `rattle` of the same substep
+struct Cluster {
+    virial: vec3<f32>, // in mU, of the SHAKE forces
+    unconverged: f32,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read_write> particlesA : array<Particle>;
+@binding(2) @group(0) var<storage, read_write> particlesB : array<Particle>;
+@binding(3) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(4) @group(0) var<storage, read> constraints : array<Constraint>;
+// cluster_count + 1 offsets followed by the constraint indices of every cluster
+@binding(5) @group(0) var<storage, read> clusters : array<u32>;
+@binding(6) @group(0) var<storage, read_write> cluster_states : array<Cluster>;
+@binding(7) @group(0) var<storage, read_write> stats : array<Stats>;
+@binding(8) @group(0) var<storage, read> timestep : Timestep;
+
+fn boundary_mode(axis: u32) -> u32 {
+    switch axis {
+        case 0u: {
+            return params.boundary_x;
+        }
+        case 1u: {
+            return params.boundary_y;
+        }
+        default: {
+            return params.boundary_z;
+        }
+    }
+}
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+fn minimum_image(d: vec3<f32>) -> vec3<f32> {
+    var s = to_fractional(d);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if boundary_mode(axis) == 0u {
+            s[axis] = s[axis] - round(s[axis]);
+        }
+    }
+    return from_fractional(s);
+}
+
+fn is_absorbed(pos: vec3<f32>) -> bool {
+    let s = to_fractional(pos - box_origin());
+    return any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0));
+}
+
+// back into the box after a correction: around periodic axes and onto walls, open faces keep
+// the particles that were pushed out
+fn wrap(pos: vec3<f32>) -> vec3<f32> {
+    var s = to_fractional(pos - box_origin());
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        switch boundary_mode(axis) {
+            case 0u: {
+                s[axis] = s[axis] - floor(s[axis]);
+            }
+            case 3u: {}
+            default: {
+                s[axis] = clamp(s[axis], 0.0, 1.0);
+            }
+        }
+    }
+    return box_origin() + from_fractional(s);
+}
+
+fn cluster_count() -> u32 {
+    return clusters[0] - 1u;
+}
+
+fn inverse_mass(index: u32) -> f32 {
+    return 1.0 / atoms[u32(particlesA[index].type_)].mass;
+}
+
+fn position_a(index: u32) -> vec3<f32> {
+    return vec3<f32>(particlesA[index].x, particlesA[index].y, particlesA[index].z);
+}
+
+fn position_b(index: u32) -> vec3<f32> {
+    return vec3<f32>(particlesB[index].x, particlesB[index].y, particlesB[index].z);
+}
+
+fn velocity_b(index: u32) -> vec3<f32> {
+    return vec3<f32>(particlesB[index].vel_x, particlesB[index].vel_y, particlesB[index].vel_z);
+}
+
+// moves a particle of particlesA, the velocity of the last half step moves along
+fn shift_a(index: u32, delta: vec3<f32>, dt: f32) {
+    particlesA[index].x = particlesA[index].x + delta.x;
+    particlesA[index].y = particlesA[index].y + delta.y;
+    particlesA[index].z = particlesA[index].z + delta.z;
+    particlesA[index].vel_x = particlesA[index].vel_x + delta.x / dt;
+    particlesA[index].vel_y = particlesA[index].vel_y + delta.y / dt;
+    particlesA[index].vel_z = particlesA[index].vel_z + delta.z / dt;
+}
+
+fn kick_b(index: u32, delta: vec3<f32>) {
+    particlesB[index].vel_x = particlesB[index].vel_x + delta.x;
+    particlesB[index].vel_y = particlesB[index].vel_y + delta.y;
+    particlesB[index].vel_z = particlesB[index].vel_z + delta.z;
+}
+
+fn wrap_a(index: u32) {
+    let pos = wrap(position_a(index));
+    particlesA[index].x = pos.x;
+    particlesA[index].y = pos.y;
+    particlesA[index].z = pos.z;
+}
+
+fn skipped(c: Constraint) -> bool {
+    return is_absorbed(position_a(c.i)) || is_absorbed(position_a(c.j));
+}
+
+@compute @workgroup_size(64)
+fn shake(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let cluster = GlobalInvocationID.x;
+    if cluster >= cluster_count() {
+        return;
+    }
+    let first = clusters[cluster];
+    let last = clusters[cluster + 1u];
+    let dt = timestep.dt;
+    var virial = vec3<f32>(0.0);
+    var converged = dt <= 0.0;
+
+    for (var iteration = 0u; iteration < params.shake_iterations && !converged; iteration += 1u) {
+        converged = true;
+        for (var n = first; n < last; n += 1u) {
+            let c = constraints[clusters[n]];
+            if skipped(c) {
+                continue;
+            }
+            let r = minimum_image(position_a(c.i) - position_a(c.j));
+            let d2 = c.length * c.length;
+            let diff = d2 - dot(r, r);
+            if abs(diff) <= 2.0 * params.shake_tolerance * d2 {
+                continue;
+            }
+            converged = false;
+            // the correction is along the constraint before the drift, r_old . r only gets
+            // small if the pair turned by about 90 degrees in one substep
+            let r_old = minimum_image(position_b(c.i) - position_b(c.j));
+            let w_i = inverse_mass(c.i);
+            let w_j = inverse_mass(c.j);
+            let g = diff / (2.0 * (w_i + w_j) * max(dot(r_old, r), 1e-3 * d2));
+            shift_a(c.i, g * w_i * r_old, dt);
+            shift_a(c.j, -g * w_j * r_old, dt);
+            // the constraint force on i is 2 g r_old / dt^2
+            virial = virial + 2.0 * g * r_old * r_old / (dt * dt);
+        }
+    }
+    for (var n = first; n < last; n += 1u) {
+        let c = constraints[clusters[n]];
+        wrap_a(c.i);
+        wrap_a(c.j);
+    }
+    cluster_states[cluster] = Cluster(virial, select(1.0, 0.0, converged));
+}
+
+@compute @workgroup_size(64)
+fn rattle(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let cluster = GlobalInvocationID.x;
+    if cluster >= cluster_count() {
+        return;
+    }
+    let first = clusters[cluster];
+    let last = clusters[cluster + 1u];
+    let dt = timestep.dt;
+    var virial = vec3<f32>(0.0);
+    var converged = dt <= 0.0;
+
+    for (var iteration = 0u; iteration < params.shake_iterations && !converged; iteration += 1u) {
+        converged = true;
+        for (var n = first; n < last; n += 1u) {
+            let c = constraints[clusters[n]];
+            if skipped(c) {
+                continue;
+            }
+            let r = minimum_image(position_b(c.i) - position_b(c.j));
+            let rv = dot(r, velocity_b(c.i) - velocity_b(c.j));
+            if abs(rv) * dt <= params.shake_tolerance * c.length * c.length {
+                continue;
+            }
+            converged = false;
+            let w_i = inverse_mass(c.i);
+            let w_j = inverse_mass(c.j);
+            let k = -rv / ((w_i + w_j) * dot(r, r));
+            kick_b(c.i, k * w_i * r);
+            kick_b(c.j, -k * w_j * r);
+            // the constraint force on i is 2 k r / dt
+            virial = virial + 2.0 * k * r * r / dt;
+        }
+    }
+
+    // the kinetic energy of compute.wgsl is out of date, the error is taken on the final positions
+    var error = 0.0;
+    for (var n = first; n < last; n += 1u) {
+        let c = constraints[clusters[n]];
+        let r = minimum_image(position_b(c.i) - position_b(c.j));
+        let deviation = (length(r) - c.length) / c.length;
+        error = error + deviation * deviation;
+        for (var end = 0u; end < 2u; end += 1u) {
+            let index = select(c.j, c.i, end == 0u);
+            var vel = velocity_b(index);
+            // leapfrog, the kinetic energy belongs to the full step
+            if params.integrator == 1u {
+                vel = vel + vec3<f32>(particlesB[index].acc_x, particlesB[index].acc_y, particlesB[index].acc_z) * dt * 0.5;
+            }
+            stats[index].KE = 0.5 * dot(vel, vel) / inverse_mass(index);
+        }
+    }
+
+    // SHAKE and RATTLE both estimate the whole constraint force, once at the start and
+    // once at the end of the substep
+    let owner = constraints[clusters[first]].i;
+    stats[owner].constraint_error = error;
+    // without a step (dt = 0) SHAKE did not run in this substep
+    if dt > 0.0 {
+        let state = cluster_states[cluster];
+        let w = 0.5 * (state.virial + virial);
+        stats[owner].W_x = stats[owner].W_x + w.x;
+        stats[owner].W_y = stats[owner].W_y + w.y;
+        stats[owner].W_z = stats[owner].W_z + w.z;
+        stats[owner].unconverged = max(state.unconverged, select(1.0, 0.0, converged));
+    }
+}
@@ -1,44 +1,59 @@
 
 
-// This is a simple compute shader that clears the bin_load buffer to 0 and 
-// clears the depth buffer to 1.0.
-struct Atom{
-    size: f32, // in nm
-    mass: f32, // in Dalton (1.66053906660e-27 kg)
-    charge: i32, // in elementary charge (1.602176634e-19 C)
-    sigma: f32, // in nm
-    epsilon: f32, // eV (1.602176634e-19 J)
-}
-
+// Clears the particle counts of the cells before calc_grid.wgsl counts them again.
 struct Params {
     N: u32, // number of particles
     dt: f32,  // in ps
     neghborhood_size: f32, // in nm
     max_force: f32, // in nm * amu / ps^2
-    friction: f32,  // in amu / ps
-    box_size: f32, // in nm
-    bin_size: f32, // in nm
-    bin_count: u32,
-    bin_capacity: u32,
-    align1: u32,
-    align2: u32,
-    align3: u32,
-    helium: Atom,
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
 }
 
 @binding(0) @group(0) var<uniform> params : Params;
-@binding(1) @group(0) var<storage, read_write> bin_load : array<u32>;
-@binding(2) @group(0) var<storage, read_write> depth : array<i32>;
+// the particles per cell, one more entry than cells that becomes the total after the scan
+@binding(1) @group(0) var<storage, read_write> cell_start : array<u32>;
 
 
 @compute @workgroup_size(256)
 fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     let index = GlobalInvocationID.x;
-    if (index >= arrayLength(&bin_load)) {
+    if (index > params.bin_count_x * params.bin_count_y * params.bin_count_z) {
         return;
     }
-    bin_load[index] = 0u;
-    for (var i = 0u; i < params.bin_capacity; i = i + 1u) {
-        depth[index * params.bin_capacity + i] = -1;
-    }
-}
\ No newline at end of file
+    cell_start[index] = 0u;
+}
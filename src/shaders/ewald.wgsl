@@ -0,0 +1,109 @@
+// structure factors S(k) = sum_i q_i exp(i k.r_i) for the reciprocal part of the Ewald sum,
+// one invocation per k vector
+
+struct Atom{
+    size: f32, // in nm
+    mass: f32, // in Dalton (1.66053906660e-27 kg)
+    charge: i32, // in elementary charge (1.602176634e-19 C)
+    sigma: f32, // in nm
+    epsilon: f32, // eV (1.602176634e-19 J)
+}
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct KVector {
+    kx: f32, // in 1 / nm
+    ky: f32,
+    kz: f32,
+    prefactor: f32, // in mU * nm
+    s_re: f32, // in e
+    s_im: f32,
+    align1: f32,
+    align2: f32,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(3) @group(0) var<storage, read_write> kvectors : array<KVector>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.kvector_count {
+        return;
+    }
+    let k = vec3<f32>(kvectors[index].kx, kvectors[index].ky, kvectors[index].kz);
+
+    var s_re = 0.0;
+    var s_im = 0.0;
+    let array_length = arrayLength(&particles);
+    for (var i = 0u; i < array_length; i += 1u) {
+        let q = f32(atoms[u32(particles[i].type_)].charge);
+        if q == 0.0 {
+            continue;
+        }
+        let kr = dot(k, vec3<f32>(particles[i].x, particles[i].y, particles[i].z));
+        s_re += q * cos(kr);
+        s_im += q * sin(kr);
+    }
+    kvectors[index].s_re = s_re;
+    kvectors[index].s_im = s_im;
+}
@@ -0,0 +1,208 @@
+// energy minimization with steepest descent or FIRE, runs after the force pass. `reduce` sums
+// up forces and energies and takes the minimizer step, `step` sets the velocities the drift in
+// varlets.wgsl follows in the next substep
+
+struct Atom{
+    size: f32, // in nm
+    mass: f32, // in Dalton (1.66053906660e-27 kg)
+    charge: i32, // in elementary charge (1.602176634e-19 C)
+    sigma: f32, // in nm
+    epsilon: f32, // eV (1.602176634e-19 J)
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Stats {
+    KE: f32,
+    PE: f32,
+    PE_real: f32,
+    PE_recip: f32,
+    PE_bonded: f32,
+    E_thermostat: f32,
+    W_x: f32,
+    W_y: f32,
+    W_z: f32,
+    capped: f32,
+    constraint_error: f32,
+    unconverged: f32,
+}
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+struct Minimizer {
+    energy: f32, // in mU
+    max_force: f32, // in mU / nm
+    power: f32, // sum of F . v
+    v_norm: f32,
+    f_norm: f32,
+    alpha: f32,
+    step: f32, // steepest descent: largest move in nm, FIRE: dt in ps
+    positive_steps: u32,
+    steps: u32,
+    status: u32, // 0 running, 1 force converged, 2 energy converged, 3 out of steps
+    mixing: u32, // FIRE: 1 mixes the velocities, 0 stops them
+    kind: u32, // 1 steepest descent, 2 FIRE
+    force_tolerance: f32,
+    energy_tolerance: f32,
+    max_steps: u32,
+    step_max: f32,
+}
+
+@binding(0) @group(0) var<storage, read_write> particles : array<Particle>;
+@binding(1) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(2) @group(0) var<storage, read> stats : array<Stats>;
+@binding(3) @group(0) var<storage, read_write> minimizer : Minimizer;
+@binding(4) @group(0) var<storage, read_write> timestep : Timestep;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+// FIRE parameters of Bitzek et al. 2006
+const FIRE_N_MIN: u32 = 5u;
+const FIRE_F_INC: f32 = 1.1;
+const FIRE_F_DEC: f32 = 0.5;
+const FIRE_ALPHA_START: f32 = 0.1;
+const FIRE_F_ALPHA: f32 = 0.99;
+
+// F . v, v^2, F^2 and the energy
+var<workgroup> sums : array<vec4<f32>, WORKGROUP_SIZE>;
+var<workgroup> f2_max : array<f32, WORKGROUP_SIZE>;
+
+fn force_of(i: u32) -> vec3<f32> {
+    let mass = atoms[u32(particles[i].type_)].mass;
+    return mass * vec3<f32>(particles[i].acc_x, particles[i].acc_y, particles[i].acc_z);
+}
+
+@compute @workgroup_size(256)
+fn reduce(@builtin(local_invocation_index) index: u32) {
+    var sum = vec4<f32>(0.0);
+    var f2 = 0.0;
+    for (var i = index; i < arrayLength(&particles); i += WORKGROUP_SIZE) {
+        let vel = vec3<f32>(particles[i].vel_x, particles[i].vel_y, particles[i].vel_z);
+        let force = force_of(i);
+        let energy = stats[i].PE + stats[i].PE_real + stats[i].PE_recip + stats[i].PE_bonded;
+        sum = sum + vec4<f32>(dot(force, vel), dot(vel, vel), dot(force, force), energy);
+        f2 = max(f2, dot(force, force));
+    }
+    sums[index] = sum;
+    f2_max[index] = f2;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if index < stride {
+            sums[index] = sums[index] + sums[index + stride];
+            f2_max[index] = max(f2_max[index], f2_max[index + stride]);
+        }
+        workgroupBarrier();
+    }
+    if index != 0u || minimizer.status != 0u {
+        return;
+    }
+
+    let energy = sums[0].w;
+    let first = minimizer.steps == 0u;
+    let energy_old = minimizer.energy;
+    minimizer.energy = energy;
+    minimizer.max_force = sqrt(f2_max[0]);
+    minimizer.power = sums[0].x;
+    minimizer.v_norm = sqrt(sums[0].y);
+    minimizer.f_norm = sqrt(sums[0].z);
+    minimizer.steps = minimizer.steps + 1u;
+
+    // FIRE starts from rest after every stop, the energy barely changes in the first steps
+    let moving = minimizer.kind == 1u || minimizer.positive_steps > FIRE_N_MIN;
+    if minimizer.max_force <= minimizer.force_tolerance {
+        minimizer.status = 1u;
+    } else if !first && moving && abs(energy - energy_old) <= minimizer.energy_tolerance * abs(energy) {
+        minimizer.status = 2u;
+    } else if minimizer.steps >= minimizer.max_steps {
+        minimizer.status = 3u;
+    }
+    if minimizer.status != 0u {
+        return;
+    }
+
+    switch minimizer.kind {
+        // steepest descent, the largest move grows while the energy goes down
+        case 1u: {
+            if !first {
+                minimizer.step = select(minimizer.step * 0.5, min(minimizer.step * 1.2, minimizer.step_max), energy < energy_old);
+            }
+            // the velocities are in nm per step
+            timestep.dt = 1.0;
+        }
+        // FIRE, mixes the velocities towards the forces while going downhill and stops on the way up
+        default: {
+            if first || minimizer.power <= 0.0 {
+                if !first {
+                    minimizer.step = minimizer.step * FIRE_F_DEC;
+                }
+                minimizer.alpha = FIRE_ALPHA_START;
+                minimizer.positive_steps = 0u;
+                minimizer.mixing = 0u;
+            } else {
+                minimizer.positive_steps = minimizer.positive_steps + 1u;
+                if minimizer.positive_steps > FIRE_N_MIN {
+                    minimizer.step = min(minimizer.step * FIRE_F_INC, minimizer.step_max);
+                    minimizer.alpha = minimizer.alpha * FIRE_F_ALPHA;
+                }
+                minimizer.mixing = 1u;
+            }
+            timestep.dt = minimizer.step;
+        }
+    }
+}
+
+@compute @workgroup_size(64)
+fn step(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+    var vel = vec3<f32>(particles[index].vel_x, particles[index].vel_y, particles[index].vel_z);
+    let force = force_of(index);
+
+    if minimizer.status != 0u {
+        // converged, the particles are handed over to the dynamics at rest
+        vel = vec3<f32>(0.0);
+    } else if minimizer.kind == 1u {
+        vel = force / max(minimizer.max_force, 1e-20) * minimizer.step;
+    } else {
+        if minimizer.mixing == 1u {
+            vel = (1.0 - minimizer.alpha) * vel + minimizer.alpha * minimizer.v_norm / max(minimizer.f_norm, 1e-20) * force;
+        } else {
+            vel = vec3<f32>(0.0);
+        }
+        let mass = atoms[u32(particles[index].type_)].mass;
+        vel = vel + force / mass * minimizer.step;
+    }
+
+    particles[index].vel_x = vel.x;
+    particles[index].vel_y = vel.y;
+    particles[index].vel_z = vel.z;
+}
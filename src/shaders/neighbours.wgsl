@@ -0,0 +1,256 @@
+// The Verlet list. Once per substep `check` finds the particle that moved furthest since
+// the last build and, if it moved more than half the skin or any list overflowed, fills in
+// the workgroup counts of empty_bins.wgsl, calc_grid.wgsl, scan.wgsl and `build`, which are
+// dispatched indirectly from the list state. Until then they stay zero and the force pass
+// keeps the old list. An overflowed particle searches the bins, so they follow the particles
+// every substep until `ComputeSet` grows the list at the next frame.
+// `build` does not bind the list state, a buffer can not be read indirectly and written by
+// the same dispatch, the next check finds the longest list instead.
+//
+// The list follows the particles sorted by bin in cell_particles as a column major matrix
+// of N columns: row 0 holds the number of neighbours of every particle, row k + 1 its k-th
+// neighbour, so the threads of a workgroup read neighbouring entries.
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Dispatch {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+struct NeighbourList {
+    cells: Dispatch, // a thread per bin, also the blocks of scan.wgsl
+    particles: Dispatch, // a thread per particle
+    single: Dispatch, // one workgroup
+    rebuild: u32, // set by the cpu, the next check rebuilds the list
+    rebuilds: u32,
+    max_neighbours: u32, // of the current list
+    max_displacement: f32, // in nm
+    _padding: u32,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read> cell_start : array<u32>;
+@binding(3) @group(0) var<storage, read_write> cell_particles : array<u32>;
+// where the particles were at the last build, w is unused
+@binding(4) @group(0) var<storage, read_write> list_positions : array<vec4<f32>>;
+// only bound by `check`
+@binding(5) @group(0) var<storage, read_write> list : NeighbourList;
+
+const NO_BIN: u32 = 0xffffffffu;
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> d2_max : array<f32, WORKGROUP_SIZE>;
+var<workgroup> count_max : array<u32, WORKGROUP_SIZE>;
+
+fn boundary_mode(axis: u32) -> u32 {
+    switch axis {
+        case 0u: {
+            return params.boundary_x;
+        }
+        case 1u: {
+            return params.boundary_y;
+        }
+        default: {
+            return params.boundary_z;
+        }
+    }
+}
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+// the minimum image convention, only along the periodic axes
+fn minimum_image(d: vec3<f32>) -> vec3<f32> {
+    var s = to_fractional(d);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if boundary_mode(axis) == 0u {
+            s[axis] = s[axis] - round(s[axis]);
+        }
+    }
+    return from_fractional(s);
+}
+
+// wraps the bin around periodic axes, bins beyond a wall or open face do not exist (NO_BIN)
+fn wrap_bin(x: i32, y: i32, z: i32) -> u32 {
+    let counts = vec3<i32>(i32(params.bin_count_x), i32(params.bin_count_y), i32(params.bin_count_z));
+    var bin = vec3<i32>(x, y, z);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        if bin[axis] < 0 || bin[axis] >= counts[axis] {
+            if boundary_mode(axis) != 0u {
+                return NO_BIN;
+            }
+            bin[axis] = (bin[axis] + counts[axis]) % counts[axis];
+        }
+    }
+    return u32(bin.x + bin.y * counts.x + bin.z * counts.x * counts.y);
+}
+
+fn position_of(index: u32) -> vec3<f32> {
+    return vec3<f32>(particles[index].x, particles[index].y, particles[index].z);
+}
+
+// the entry of row `row` and particle `index` in cell_particles
+fn list_entry(row: u32, index: u32) -> u32 {
+    return (row + 1u) * params.N + index;
+}
+
+@compute @workgroup_size(256)
+fn check(@builtin(local_invocation_index) index: u32) {
+    var d2 = 0.0;
+    var count = 0u;
+    for (var i = index; i < params.N; i += WORKGROUP_SIZE) {
+        let d = minimum_image(position_of(i) - list_positions[i].xyz);
+        d2 = max(d2, dot(d, d));
+        count = max(count, cell_particles[list_entry(0u, i)]);
+    }
+    d2_max[index] = d2;
+    count_max[index] = count;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if index < stride {
+            d2_max[index] = max(d2_max[index], d2_max[index + stride]);
+            count_max[index] = max(count_max[index], count_max[index + stride]);
+        }
+        workgroupBarrier();
+    }
+    if index != 0u {
+        return;
+    }
+
+    list.max_displacement = sqrt(d2_max[0]);
+    list.max_neighbours = count_max[0];
+    let half_skin = 0.5 * params.verlet_skin;
+    let capacity = arrayLength(&cell_particles) / params.N - 2u;
+    let rebuild = list.rebuild != 0u || d2_max[0] > half_skin * half_skin || count_max[0] > capacity;
+    let cells = params.bin_count_x * params.bin_count_y * params.bin_count_z + 1u;
+    list.cells = Dispatch(select(0u, (cells + 255u) / 256u, rebuild), 1u, 1u);
+    list.particles = Dispatch(select(0u, (params.N + 63u) / 64u, rebuild), 1u, 1u);
+    list.single = Dispatch(select(0u, 1u, rebuild), 1u, 1u);
+    if rebuild {
+        list.rebuild = 0u;
+        list.rebuilds = list.rebuilds + 1u;
+    }
+}
+
+// the particles within the cutoff plus the skin, a particle with more neighbours than the
+// list has room for keeps the count and the force pass searches the bins for it instead
+@compute @workgroup_size(64)
+fn build(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.N {
+        return;
+    }
+    let pos = position_of(index);
+    list_positions[index] = vec4<f32>(pos, 0.0);
+
+    // absorbed by an open boundary, the force pass skips the particle
+    let s = to_fractional(pos - box_origin());
+    if any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0)) {
+        cell_particles[list_entry(0u, index)] = 0u;
+        return;
+    }
+
+    let capacity = arrayLength(&cell_particles) / params.N - 2u;
+    let radius = max(params.neghborhood_size, params.life_radius) + params.verlet_skin;
+    let bin_x = min(i32(floor(s.x * f32(params.bin_count_x))), i32(params.bin_count_x) - 1);
+    let bin_y = min(i32(floor(s.y * f32(params.bin_count_y))), i32(params.bin_count_y) - 1);
+    let bin_z = min(i32(floor(s.z * f32(params.bin_count_z))), i32(params.bin_count_z) - 1);
+    var count = 0u;
+    for (var cell = 0; cell < 27; cell += 1) {
+        let bin_index = wrap_bin(bin_x + cell % 3 - 1, bin_y + cell / 3 % 3 - 1, bin_z + cell / 9 - 1);
+        if bin_index == NO_BIN {
+            continue;
+        }
+        let end = cell_start[bin_index + 1u];
+        for (var j = cell_start[bin_index]; j < end; j += 1u) {
+            let p_index = cell_particles[j];
+            let d = minimum_image(pos - position_of(p_index));
+            if p_index == index || dot(d, d) >= radius * radius {
+                continue;
+            }
+            if count < capacity {
+                cell_particles[list_entry(count + 1u, index)] = p_index;
+            }
+            count += 1u;
+        }
+    }
+    cell_particles[list_entry(0u, index)] = count;
+}
@@ -2,6 +2,16 @@
 struct Stats {
     KE: f32,
     PE: f32,
+    PE_real: f32,
+    PE_recip: f32,
+    PE_bonded: f32,
+    E_thermostat: f32,
+    W_x: f32,
+    W_y: f32,
+    W_z: f32,
+    capped: f32,
+    constraint_error: f32,
+    unconverged: f32,
 };
 
 @binding(0) @group(0) var<storage, read> stats_in : array<Stats>;
@@ -16,10 +26,40 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     }
     let KE = stats_in[index * 2u].KE + stats_in[index * 2u + 1u].KE;
     let PE = stats_in[index * 2u].PE + stats_in[index * 2u + 1u].PE;
+    let PE_real = stats_in[index * 2u].PE_real + stats_in[index * 2u + 1u].PE_real;
+    let PE_recip = stats_in[index * 2u].PE_recip + stats_in[index * 2u + 1u].PE_recip;
+    let PE_bonded = stats_in[index * 2u].PE_bonded + stats_in[index * 2u + 1u].PE_bonded;
+    let E_thermostat = stats_in[index * 2u].E_thermostat + stats_in[index * 2u + 1u].E_thermostat;
+    let W_x = stats_in[index * 2u].W_x + stats_in[index * 2u + 1u].W_x;
+    let W_y = stats_in[index * 2u].W_y + stats_in[index * 2u + 1u].W_y;
+    let W_z = stats_in[index * 2u].W_z + stats_in[index * 2u + 1u].W_z;
+    let capped = stats_in[index * 2u].capped + stats_in[index * 2u + 1u].capped;
+    let constraint_error = stats_in[index * 2u].constraint_error + stats_in[index * 2u + 1u].constraint_error;
+    let unconverged = stats_in[index * 2u].unconverged + stats_in[index * 2u + 1u].unconverged;
     stats_out[index].KE = KE;
     stats_out[index].PE = PE;
+    stats_out[index].PE_real = PE_real;
+    stats_out[index].PE_recip = PE_recip;
+    stats_out[index].PE_bonded = PE_bonded;
+    stats_out[index].E_thermostat = E_thermostat;
+    stats_out[index].W_x = W_x;
+    stats_out[index].W_y = W_y;
+    stats_out[index].W_z = W_z;
+    stats_out[index].capped = capped;
+    stats_out[index].constraint_error = constraint_error;
+    stats_out[index].unconverged = unconverged;
     if (index == 0u) {
         final_.KE = KE;
         final_.PE = PE;
+        final_.PE_real = PE_real;
+        final_.PE_recip = PE_recip;
+        final_.PE_bonded = PE_bonded;
+        final_.E_thermostat = E_thermostat;
+        final_.W_x = W_x;
+        final_.W_y = W_y;
+        final_.W_z = W_z;
+        final_.capped = capped;
+        final_.constraint_error = constraint_error;
+        final_.unconverged = unconverged;
     }
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+// Sorts the particles along a Morton curve through a grid of 2^MORTON_BITS cells per axis,
+// so particles that are close in space are close in memory as well. Like the cell list it
+// is a counting sort: `count` counts the particles of every cell, `scan` turns the counts
+// into the first index of every cell and `scatter` moves every particle with its per
+// particle state to its new index. order maps the index of a particle to its identity, the
+// index it had at the start, and is sorted along.
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+// the state the integrators keep between substeps, acc, pe_real, virial and pe_recip
+struct IntegratorParticle {
+    a: vec4<f32>,
+    b: vec4<f32>,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read_write> sorted : array<Particle>;
+// the particles per cell, after the scan the index of the first one
+@binding(3) @group(0) var<storage, read_write> cell_start : array<atomic<u32>>;
+@binding(4) @group(0) var<storage, read_write> slot : array<u32>; // of a particle within its cell
+@binding(5) @group(0) var<storage, read> order : array<u32>;
+@binding(6) @group(0) var<storage, read_write> sorted_order : array<u32>;
+@binding(7) @group(0) var<storage, read> integrator_particles : array<IntegratorParticle>;
+@binding(8) @group(0) var<storage, read_write> sorted_integrator_particles : array<IntegratorParticle>;
+// seed and energy of the local thermostats
+@binding(9) @group(0) var<storage, read> thermostat_particles : array<vec2<u32>>;
+@binding(10) @group(0) var<storage, read_write> sorted_thermostat_particles : array<vec2<u32>>;
+
+// MORTON_BITS comes from consts.rs, `ComputeSet` puts it in front of the source
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> scratch : array<u32, WORKGROUP_SIZE>;
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+// spreads the bits of v so two zeros follow every bit
+fn spread_bits(v: u32) -> u32 {
+    var x = v & 0x3ffu;
+    x = (x | (x << 16u)) & 0x030000ffu;
+    x = (x | (x << 8u)) & 0x0300f00fu;
+    x = (x | (x << 4u)) & 0x030c30c3u;
+    x = (x | (x << 2u)) & 0x09249249u;
+    return x;
+}
+
+// the position of the cell of a particle along the curve, the particles absorbed by an
+// open boundary go into an extra cell at the end
+fn cell_of(index: u32) -> u32 {
+    let cells = 1u << MORTON_BITS;
+    let pos = vec3<f32>(particles[index].x, particles[index].y, particles[index].z);
+    let s = to_fractional(pos - box_origin());
+    if any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0)) {
+        return cells * cells * cells;
+    }
+    let cell = min(vec3<u32>(floor(s * f32(cells))), vec3<u32>(cells - 1u));
+    return spread_bits(cell.x) | (spread_bits(cell.y) << 1u) | (spread_bits(cell.z) << 2u);
+}
+
+@compute @workgroup_size(64)
+fn count(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.N {
+        return;
+    }
+    slot[index] = atomicAdd(&cell_start[cell_of(index)], 1u);
+}
+
+// a single workgroup, every invocation takes a run of consecutive cells
+@compute @workgroup_size(256)
+fn scan(@builtin(local_invocation_index) local: u32) {
+    let cells = arrayLength(&cell_start);
+    let run = (cells + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let first = local * run;
+    let last = min(first + run, cells);
+    var sum = 0u;
+    for (var i = first; i < last; i += 1u) {
+        sum += atomicLoad(&cell_start[i]);
+    }
+    scratch[local] = sum;
+    workgroupBarrier();
+    // Hillis-Steele scan of the run totals
+    for (var offset = 1u; offset < WORKGROUP_SIZE; offset = offset * 2u) {
+        var value = 0u;
+        if local >= offset {
+            value = scratch[local - offset];
+        }
+        workgroupBarrier();
+        scratch[local] += value;
+        workgroupBarrier();
+    }
+    var start = scratch[local] - sum;
+    for (var i = first; i < last; i += 1u) {
+        let count = atomicLoad(&cell_start[i]);
+        atomicStore(&cell_start[i], start);
+        start += count;
+    }
+}
+
+@compute @workgroup_size(64)
+fn scatter(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.N {
+        return;
+    }
+    let target_index = atomicLoad(&cell_start[cell_of(index)]) + slot[index];
+    sorted[target_index] = particles[index];
+    sorted_order[target_index] = order[index];
+    sorted_integrator_particles[target_index] = integrator_particles[index];
+    sorted_thermostat_particles[target_index] = thermostat_particles[index];
+}
@@ -0,0 +1,122 @@
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+// Exclusive prefix sum over the particle counts of the cells, in place: afterwards
+// cell_start[cell] is the index of the first particle of the cell in cell_particles and
+// the extra entry after the last cell holds the number of binned particles.
+// scan_blocks scans blocks of BLOCK cells, scan_sums scans the block totals in a single
+// workgroup and add_offsets adds them to the cells of every block.
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read_write> cell_start : array<u32>;
+@binding(2) @group(0) var<storage, read_write> block_sums : array<u32>;
+
+const BLOCK: u32 = 256u;
+
+var<workgroup> scratch : array<u32, BLOCK>;
+
+// the cells and the entry for the total
+fn scan_length() -> u32 {
+    return params.bin_count_x * params.bin_count_y * params.bin_count_z + 1u;
+}
+
+// Hillis-Steele scan of scratch, returns the inclusive sum up to index
+fn inclusive_scan(index: u32) -> u32 {
+    for (var offset = 1u; offset < BLOCK; offset = offset * 2u) {
+        var value = 0u;
+        if index >= offset {
+            value = scratch[index - offset];
+        }
+        workgroupBarrier();
+        scratch[index] += value;
+        workgroupBarrier();
+    }
+    return scratch[index];
+}
+
+@compute @workgroup_size(256)
+fn scan_blocks(@builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    let index = group.x * BLOCK + local;
+    var count = 0u;
+    if index < scan_length() {
+        count = cell_start[index];
+    }
+    scratch[local] = count;
+    workgroupBarrier();
+    let inclusive = inclusive_scan(local);
+    if index < scan_length() {
+        cell_start[index] = inclusive - count;
+    }
+    if local == BLOCK - 1u {
+        block_sums[group.x] = inclusive;
+    }
+}
+
+// every invocation of the single workgroup takes a run of consecutive block totals
+@compute @workgroup_size(256)
+fn scan_sums(@builtin(local_invocation_index) local: u32) {
+    let blocks = (scan_length() + BLOCK - 1u) / BLOCK;
+    let run = (blocks + BLOCK - 1u) / BLOCK;
+    let first = local * run;
+    let last = min(first + run, blocks);
+    var sum = 0u;
+    for (var i = first; i < last; i += 1u) {
+        sum += block_sums[i];
+    }
+    scratch[local] = sum;
+    workgroupBarrier();
+    var offset = inclusive_scan(local) - sum;
+    for (var i = first; i < last; i += 1u) {
+        let total = block_sums[i];
+        block_sums[i] = offset;
+        offset += total;
+    }
+}
+
+@compute @workgroup_size(256)
+fn add_offsets(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>, @builtin(workgroup_id) group: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index < scan_length() {
+        cell_start[index] += block_sums[group.x];
+    }
+}
@@ -0,0 +1,398 @@
+// thermostats, see thermostat.rs
+//  collide: Langevin and Andersen, runs on every particle after every substep
+//  update:  Berendsen, CSVR and Nose-Hoover, one thread computes the scale factor from the reduced kinetic energy
+//  rescale: applies the scale factor of update to every particle
+
+struct Atom{
+    size: f32, // in nm
+    mass: f32, // in Dalton (1.66053906660e-27 kg)
+    charge: i32, // in elementary charge (1.602176634e-19 C)
+    sigma: f32, // in nm
+    epsilon: f32, // eV (1.602176634e-19 J)
+}
+
+struct Params {
+    N: u32, // number of particles
+    dt: f32,  // in ps
+    neghborhood_size: f32, // in nm
+    max_force: f32, // in nm * amu / ps^2
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Stats {
+    KE: f32,
+    PE: f32,
+    PE_real: f32, // real space coulomb energy
+    PE_recip: f32, // reciprocal coulomb energy (including the self energy)
+    PE_bonded: f32, // bonds, angles and dihedrals
+    E_thermostat: f32, // filled in by thermostat.wgsl
+    W_x: f32, // diagonal of the virial tensor in mU
+    W_y: f32,
+    W_z: f32,
+    capped: f32, // pair forces cut to max_force, every pair counts half on both sides
+    constraint_error: f32, // filled in by constraints.wgsl
+    unconverged: f32,
+}
+
+const MAX_CHAIN_LENGTH: u32 = 5u;
+
+struct ThermostatState {
+    lambda: f32,
+    energy: f32, // in mU, taken out by the global thermostats
+    particle_energy: f32, // in mU, taken out by Langevin and Andersen
+    seed: u32,
+    xi: array<f32, 5>,
+    v_xi: array<f32, 5>, // in 1 / ps
+}
+
+struct ThermostatParticle {
+    seed: u32,
+    energy: f32, // in mU
+}
+
+const BOLTZMANN_CONSTANT: f32 = 0.0083144626; // in mU / K
+const TWO_PI: f32 = 6.2831853;
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+@binding(0) @group(0) var<uniform> params : Params;
+@binding(1) @group(0) var<storage, read_write> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(3) @group(0) var<storage, read_write> thermostat_particles : array<ThermostatParticle>;
+@binding(4) @group(0) var<storage, read_write> stats : array<Stats>;
+@binding(5) @group(0) var<storage, read_write> state : ThermostatState;
+@binding(6) @group(0) var<storage, read_write> final_ : Stats;
+@binding(7) @group(0) var<storage, read> timestep : Timestep;
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// particles absorbed by an open boundary are outside the box and keep their velocity
+fn is_absorbed(position: vec3<f32>) -> bool {
+    let s = to_fractional(position + 0.5 * from_fractional(vec3<f32>(1.0)));
+    return any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0));
+}
+
+// pcg hash
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// uniform in (0, 1]
+fn uniform(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return (f32(*seed >> 8u) + 1.0) / 16777216.0;
+}
+
+fn gaussian(seed: ptr<function, u32>) -> f32 {
+    let u1 = uniform(seed);
+    let u2 = uniform(seed);
+    return sqrt(-2.0 * log(u1)) * cos(TWO_PI * u2);
+}
+
+// Gamma(shape, 1) distributed for shape >= 1 (Marsaglia and Tsang)
+fn gamma(shape: f32, seed: ptr<function, u32>) -> f32 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / sqrt(9.0 * d);
+    for (var i = 0u; i < 64u; i += 1u) {
+        let x = gaussian(seed);
+        let v = pow(1.0 + c * x, 3.0);
+        if v <= 0.0 {
+            continue;
+        }
+        if log(uniform(seed)) < 0.5 * x * x + d - d * v + d * log(v) {
+            return d * v;
+        }
+    }
+    return d;
+}
+
+// the sum of n squared standard gaussians
+fn chi_squared(n: f32, seed: ptr<function, u32>) -> f32 {
+    if n < 2.0 {
+        let g = gaussian(seed);
+        return select(0.0, g * g, n >= 1.0);
+    }
+    return 2.0 * gamma(0.5 * n, seed);
+}
+
+fn degrees_of_freedom() -> f32 {
+    return 3.0 * f32(params.N) - f32(params.constraint_count);
+}
+
+@compute @workgroup_size(64)
+fn collide(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.N {
+        return;
+    }
+    var thermo = thermostat_particles[index];
+    stats[index].E_thermostat = thermo.energy;
+    var vel = vec3<f32>(particles[index].vel_x, particles[index].vel_y, particles[index].vel_z);
+    if is_absorbed(vec3<f32>(particles[index].x, particles[index].y, particles[index].z)) {
+        return;
+    }
+
+    let mass = atoms[u32(particles[index].type_)].mass;
+    let sigma = sqrt(BOLTZMANN_CONSTANT * params.target_temperature / mass);
+    let ke_old = 0.5 * mass * dot(vel, vel);
+    switch params.thermostat {
+        // Langevin, exact solution of the friction and noise part over one step
+        case 3u: {
+            let c = exp(-params.friction * timestep.dt);
+            let noise = vec3<f32>(gaussian(&thermo.seed), gaussian(&thermo.seed), gaussian(&thermo.seed));
+            vel = c * vel + sqrt(1.0 - c * c) * sigma * noise;
+        }
+        // Andersen, a collision replaces the whole velocity
+        case 5u: {
+            if uniform(&thermo.seed) < timestep.dt / params.thermostat_tau {
+                vel = sigma * vec3<f32>(gaussian(&thermo.seed), gaussian(&thermo.seed), gaussian(&thermo.seed));
+            }
+        }
+        default: {
+            return;
+        }
+    }
+    let ke = 0.5 * mass * dot(vel, vel);
+    thermo.energy = thermo.energy + ke_old - ke;
+
+    thermostat_particles[index] = thermo;
+    stats[index].KE = ke;
+    stats[index].E_thermostat = thermo.energy;
+    particles[index].vel_x = vel.x;
+    particles[index].vel_y = vel.y;
+    particles[index].vel_z = vel.z;
+}
+
+// propagates the Nose-Hoover chain by h for the kinetic energy ke, returns the velocity scale factor
+// (Martyna, Tuckerman, Klein 1996). A frame is much longer than a time step and the end of the
+// chain gets fast far from equilibrium, so h is split into substeps short against the chain
+// velocities, each with the third order Suzuki-Yoshida weights
+fn nose_hoover_chain(ke: f32, h: f32) -> f32 {
+    let m = min(max(params.thermostat_chain, 1u), MAX_CHAIN_LENGTH);
+    let n_f = degrees_of_freedom();
+    let kt = BOLTZMANN_CONSTANT * params.target_temperature;
+    let tau2 = params.thermostat_tau * params.thermostat_tau;
+    let w1 = 1.3512071919596578;
+    let w2 = 1.0 - 2.0 * w1;
+    var scale = 1.0;
+    var t = 0.0;
+    for (var i = 0u; i < 4096u && t < h; i += 1u) {
+        // fastest rate of the chain, from its velocities and accelerations
+        var rate = sqrt(abs(2.0 * ke * scale * scale - n_f * kt) / (n_f * kt * tau2)) + abs(state.v_xi[0]);
+        for (var j = 1u; j < m; j += 1u) {
+            let g = (select(kt, n_f * kt, j == 1u) * tau2 * state.v_xi[j - 1u] * state.v_xi[j - 1u] - kt) / (kt * tau2);
+            rate = max(rate, sqrt(abs(g)) + abs(state.v_xi[j]));
+        }
+        var h_c = min(min(params.thermostat_tau / 50.0, 0.05 / rate), h - t);
+        if i == 4095u {
+            h_c = h - t;
+        }
+        scale = scale * chain_step(ke * scale * scale, w1 * h_c);
+        scale = scale * chain_step(ke * scale * scale, w2 * h_c);
+        scale = scale * chain_step(ke * scale * scale, w1 * h_c);
+        t = t + h_c;
+    }
+    return scale;
+}
+
+// one Trotter step of the chain
+fn chain_step(ke: f32, h: f32) -> f32 {
+    let n_f = degrees_of_freedom();
+    let kt = BOLTZMANN_CONSTANT * params.target_temperature;
+    let m = min(max(params.thermostat_chain, 1u), MAX_CHAIN_LENGTH);
+    let tau2 = params.thermostat_tau * params.thermostat_tau;
+    var q: array<f32, 5>;
+    for (var j = 0u; j < m; j += 1u) {
+        q[j] = select(kt * tau2, n_f * kt * tau2, j == 0u);
+    }
+    var ke_ = ke;
+
+    // half step of the chain velocities, from the end of the chain
+    for (var jj = 0u; jj < m; jj += 1u) {
+        let j = m - 1u - jj;
+        var g = (2.0 * ke_ - n_f * kt) / q[0];
+        if j > 0u {
+            g = (q[j - 1u] * state.v_xi[j - 1u] * state.v_xi[j - 1u] - kt) / q[j];
+        }
+        if j + 1u < m {
+            let damping = exp(-0.25 * h * state.v_xi[j + 1u]);
+            state.v_xi[j] = (state.v_xi[j] * damping + 0.5 * h * g) * damping;
+        } else {
+            state.v_xi[j] = state.v_xi[j] + 0.5 * h * g;
+        }
+    }
+
+    let scale = exp(-h * state.v_xi[0]);
+    ke_ = ke_ * scale * scale;
+    for (var j = 0u; j < m; j += 1u) {
+        state.xi[j] = state.xi[j] + h * state.v_xi[j];
+    }
+
+    // second half step, from the start of the chain
+    for (var j = 0u; j < m; j += 1u) {
+        var g = (2.0 * ke_ - n_f * kt) / q[0];
+        if j > 0u {
+            g = (q[j - 1u] * state.v_xi[j - 1u] * state.v_xi[j - 1u] - kt) / q[j];
+        }
+        if j + 1u < m {
+            let damping = exp(-0.25 * h * state.v_xi[j + 1u]);
+            state.v_xi[j] = (state.v_xi[j] * damping + 0.5 * h * g) * damping;
+        } else {
+            state.v_xi[j] = state.v_xi[j] + 0.5 * h * g;
+        }
+    }
+    return scale;
+}
+
+// energy stored in the Nose-Hoover chain, the extended system conserves KE + PE + this
+fn chain_energy() -> f32 {
+    let n_f = degrees_of_freedom();
+    let kt = BOLTZMANN_CONSTANT * params.target_temperature;
+    let m = min(max(params.thermostat_chain, 1u), MAX_CHAIN_LENGTH);
+    let tau2 = params.thermostat_tau * params.thermostat_tau;
+    var energy = 0.0;
+    for (var j = 0u; j < m; j += 1u) {
+        let q = select(kt * tau2, n_f * kt * tau2, j == 0u);
+        energy = energy + 0.5 * q * state.v_xi[j] * state.v_xi[j] + select(kt, n_f * kt, j == 0u) * state.xi[j];
+    }
+    return energy;
+}
+
+@compute @workgroup_size(1)
+fn update() {
+    let ke = final_.KE;
+    let h = params.thermostat_interval;
+    let n_f = degrees_of_freedom();
+    let ke_target = 0.5 * n_f * BOLTZMANN_CONSTANT * params.target_temperature;
+    var lambda = 1.0;
+    var seed = state.seed;
+
+    if ke > 0.0 {
+        switch params.thermostat {
+            // Berendsen, limited like in gromacs so a cold start does not explode
+            case 1u: {
+                let lambda2 = 1.0 + h / params.thermostat_tau * (ke_target / ke - 1.0);
+                lambda = clamp(sqrt(max(lambda2, 0.0)), 0.8, 1.25);
+            }
+            // CSVR, the new kinetic energy is drawn from the canonical distribution
+            case 2u: {
+                let c = exp(-h / params.thermostat_tau);
+                let r = gaussian(&seed);
+                let s = chi_squared(n_f - 1.0, &seed);
+                let ke_new = c * ke + (1.0 - c) * ke_target * (s + r * r) / n_f
+                    + 2.0 * r * sqrt(c * (1.0 - c) * ke * ke_target / n_f);
+                lambda = sqrt(max(ke_new, 0.0) / ke);
+            }
+            case 4u: {
+                // the energy of the chain is what the extended system conserves, not the
+                // kinetic energy taken out by this single step
+                let energy_old = chain_energy();
+                lambda = nose_hoover_chain(ke, h);
+                state.energy = state.energy + chain_energy() - energy_old - ke * (1.0 - lambda * lambda);
+            }
+            default: {}
+        }
+    }
+
+    state.energy = state.energy + ke * (1.0 - lambda * lambda);
+    state.lambda = lambda;
+    state.seed = seed;
+    final_.KE = ke * lambda * lambda;
+
+    // Langevin and Andersen report through the reduction, keep the last sum for when they are switched off
+    if params.thermostat == 3u || params.thermostat == 5u {
+        state.particle_energy = final_.E_thermostat;
+    }
+    final_.E_thermostat = state.energy + state.particle_energy;
+}
+
+@compute @workgroup_size(64)
+fn rescale(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
+    let index = GlobalInvocationID.x;
+    if index >= params.N {
+        return;
+    }
+    particles[index].vel_x = particles[index].vel_x * state.lambda;
+    particles[index].vel_y = particles[index].vel_y * state.lambda;
+    particles[index].vel_z = particles[index].vel_z * state.lambda;
+}
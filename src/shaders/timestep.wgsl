@@ -0,0 +1,84 @@
+// one workgroup finds the largest speed and acceleration of the particles written by the last
+// substep, adds the substep to the simulated time and picks the dt of the next substep
+
+struct Particle {
+    x: f32,
+    y: f32,
+    z: f32,
+    vel_x: f32,
+    vel_y: f32,
+    vel_z: f32,
+    acc_x: f32,
+    acc_y: f32,
+    acc_z: f32,
+    color_x: f32,
+    color_y: f32,
+    color_z: f32,
+    type_: f32,
+}
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+@binding(0) @group(0) var<storage, read> particles : array<Particle>;
+@binding(1) @group(0) var<storage, read_write> timestep : Timestep;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+// squared speeds and accelerations
+var<workgroup> v2_max : array<f32, WORKGROUP_SIZE>;
+var<workgroup> a2_max : array<f32, WORKGROUP_SIZE>;
+
+@compute @workgroup_size(256)
+fn main(@builtin(local_invocation_index) index: u32) {
+    var v2 = 0.0;
+    var a2 = 0.0;
+    for (var i = index; i < arrayLength(&particles); i += WORKGROUP_SIZE) {
+        let vel = vec3<f32>(particles[i].vel_x, particles[i].vel_y, particles[i].vel_z);
+        let acc = vec3<f32>(particles[i].acc_x, particles[i].acc_y, particles[i].acc_z);
+        v2 = max(v2, dot(vel, vel));
+        a2 = max(a2, dot(acc, acc));
+    }
+    v2_max[index] = v2;
+    a2_max[index] = a2;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if index < stride {
+            v2_max[index] = max(v2_max[index], v2_max[index + stride]);
+            a2_max[index] = max(a2_max[index], a2_max[index + stride]);
+        }
+        workgroupBarrier();
+    }
+    if index != 0u {
+        return;
+    }
+
+    // the substep that just ended
+    let fraction = timestep.time_fraction + timestep.dt;
+    timestep.time = timestep.time + floor(fraction);
+    timestep.time_fraction = fract(fraction);
+
+    let v = sqrt(v2_max[0]);
+    let a = sqrt(a2_max[0]);
+    timestep.v_max = v;
+    timestep.a_max = a;
+    if timestep.adaptive == 0u {
+        return;
+    }
+    // the largest dt with v dt + a dt^2 / 2 <= max_displacement
+    let d = timestep.max_displacement;
+    let dt = 2.0 * d / max(v + sqrt(v * v + 2.0 * a * d), 1e-20);
+    timestep.dt = clamp(min(dt, timestep.dt * timestep.growth), timestep.dt_min, timestep.dt_max);
+}
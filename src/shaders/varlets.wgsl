@@ -2,30 +2,56 @@
 
 // a compute shader in wgsl that simulates gravity for all particles
 
-struct Atom{
-    size: f32, // in nm
-    mass: f32, // in Dalton (1.66053906660e-27 kg)
-    charge: i32, // in elementary charge (1.602176634e-19 C)
-    sigma: f32, // in nm
-    epsilon: f32, // eV (1.602176634e-19 J)
-}
-
 struct Params {
     N: u32, // number of particles
     dt: f32,  // in ps
     neghborhood_size: f32, // in nm
     max_force: f32, // in nm * amu / ps^2
-    friction: f32,  // in amu / ps
-    box_size: f32, // in nm
-    bin_size: f32, // in nm
-    bin_count: u32,
-    bin_capacity: u32,
-    align1: u32,
-    align2: u32,
-    align3: u32,
-    helium: Atom,
+    friction: f32,  // in 1 / ps
+    box_lx: f32, // in nm, cell vectors (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    box_ly: f32,
+    box_lz: f32,
+    tilt_xy: f32, // in nm
+    tilt_xz: f32,
+    tilt_yz: f32,
+    bin_count_x: u32,
+    bin_count_y: u32,
+    bin_count_z: u32,
+    type_count: u32,
+    force_model: u32, // 0: lennard jones, 1: particle life, 2: tabulated
+    life_radius: f32, // in nm
+    life_beta: f32,
+    life_force: f32, // in nm * amu / ps^2
+    life_friction: f32, // in 1 / ps
+    electrostatics: u32, // 0: off, 1: ewald
+    ewald_alpha: f32, // in 1 / nm
+    kvector_count: u32,
+    cutoff_scheme: u32, // 0: truncated, 1: shifted, 2: force shifted, 3: switched
+    r_switch: f32, // in nm
+    boundary_x: u32, // 0: periodic, 1: reflective, 2: thermal wall, 3: open
+    boundary_y: u32,
+    boundary_z: u32,
+    wall_temperature: f32, // in K
+    thermostat: u32, // 0: off, 1: berendsen, 2: csvr, 3: langevin, 4: nose-hoover, 5: andersen
+    target_temperature: f32, // in K
+    thermostat_tau: f32, // in ps
+    thermostat_chain: u32,
+    thermostat_interval: f32, // in ps
+    integrator: u32, // 0: velocity verlet, 1: leapfrog, 2: beeman, 3: r-respa
+    respa_steps: u32, // inner steps per outer r-respa step
+    constraint_count: u32,
+    shake_tolerance: f32, // relative
+    shake_iterations: u32,
+    verlet_skin: f32, // in nm, 0: the force pass searches the bins
 }
 
+// see compute.wgsl, only the acceleration of the last step is used here (Beeman)
+struct IntegratorParticle {
+    acc: vec3<f32>,
+    pe_real: f32,
+    virial: vec3<f32>,
+    pe_recip: f32,
+}
 
 struct Particle {
     x: f32,
@@ -43,8 +69,109 @@ struct Particle {
     type_: f32,
 }
 
+struct Atom{
+    size: f32, // in nm
+    mass: f32, // in Dalton (1.66053906660e-27 kg)
+    charge: i32, // in elementary charge (1.602176634e-19 C)
+    sigma: f32, // in nm
+    epsilon: f32, // eV (1.602176634e-19 J)
+}
+
+const BOLTZMANN_CONSTANT: f32 = 0.0083144626; // in mU / K
+const TWO_PI: f32 = 6.2831853;
+
+struct Timestep {
+    time: f32,
+    time_fraction: f32,
+    v_max: f32,
+    a_max: f32,
+    dt: f32,
+    adaptive: u32,
+    dt_min: f32,
+    dt_max: f32,
+    max_displacement: f32,
+    growth: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
 @binding(0) @group(0) var<uniform> params : Params;
 @binding(1) @group(0) var<storage, read_write> particles : array<Particle>;
+@binding(2) @group(0) var<storage, read> atoms : array<Atom>;
+@binding(3) @group(0) var<storage, read> integrator_particles : array<IntegratorParticle>;
+@binding(4) @group(0) var<storage, read> timestep : Timestep;
+
+fn boundary_mode(axis: u32) -> u32 {
+    switch axis {
+        case 0u: {
+            return params.boundary_x;
+        }
+        case 1u: {
+            return params.boundary_y;
+        }
+        default: {
+            return params.boundary_z;
+        }
+    }
+}
+
+// fractional coordinates of a displacement
+fn to_fractional(d: vec3<f32>) -> vec3<f32> {
+    let s_z = d.z / params.box_lz;
+    let s_y = (d.y - params.tilt_yz * s_z) / params.box_ly;
+    let s_x = (d.x - params.tilt_xy * s_y - params.tilt_xz * s_z) / params.box_lx;
+    return vec3<f32>(s_x, s_y, s_z);
+}
+
+fn from_fractional(s: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        params.box_lx * s.x + params.tilt_xy * s.y + params.tilt_xz * s.z,
+        params.box_ly * s.y + params.tilt_yz * s.z,
+        params.box_lz * s.z,
+    );
+}
+
+// the corner of the box at s = (0, 0, 0), the box is centred on the origin
+fn box_origin() -> vec3<f32> {
+    return -0.5 * from_fractional(vec3<f32>(1.0));
+}
+
+// unit normal of the faces of an axis, pointing towards increasing s (the gradient of s[axis])
+fn face_normal(axis: u32) -> vec3<f32> {
+    let lx = params.box_lx;
+    let ly = params.box_ly;
+    let lz = params.box_lz;
+    switch axis {
+        case 0u: {
+            return normalize(vec3<f32>(1.0 / lx, -params.tilt_xy / (lx * ly), (params.tilt_xy * params.tilt_yz - params.tilt_xz * ly) / (lx * ly * lz)));
+        }
+        case 1u: {
+            return normalize(vec3<f32>(0.0, 1.0 / ly, -params.tilt_yz / (ly * lz)));
+        }
+        default: {
+            return vec3<f32>(0.0, 0.0, 1.0);
+        }
+    }
+}
+
+// pcg hash, good enough to decorrelate the wall collisions
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// uniform in (0, 1]
+fn uniform(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return (f32(*seed >> 8u) + 1.0) / 16777216.0;
+}
+
+fn gaussian(seed: ptr<function, u32>) -> f32 {
+    let u1 = uniform(seed);
+    let u2 = uniform(seed);
+    return sqrt(-2.0 * log(u1)) * cos(TWO_PI * u2);
+}
 
 @compute @workgroup_size(64)
 fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
@@ -58,10 +185,82 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     var vVel = vec3<f32>(particles[index].vel_x, particles[index].vel_y, particles[index].vel_z);
     var vAcc = vec3<f32>(particles[index].acc_x, particles[index].acc_y, particles[index].acc_z);
 
-    let dt: f32 = params.dt;
-    vPos = vPos + vVel * dt + vAcc * dt * dt * 0.5;
+    // already absorbed by an open boundary
+    let origin = box_origin();
+    var s = to_fractional(vPos - origin);
+    if any(s < vec3<f32>(0.0)) || any(s > vec3<f32>(1.0)) {
+        return;
+    }
+
+    let dt: f32 = timestep.dt;
+    switch params.integrator {
+        // leapfrog, the velocities jump from one half step to the next
+        case 1u: {
+            vVel = vVel + vAcc * dt;
+            vPos = vPos + vVel * dt;
+        }
+        // beeman
+        case 2u: {
+            vPos = vPos + vVel * dt + (4.0 * vAcc - integrator_particles[index].acc) * dt * dt / 6.0;
+        }
+        // minimization, minimize.wgsl already turned the forces into velocities
+        case 4u: {
+            vPos = vPos + vVel * dt;
+        }
+        // velocity verlet, r-RESPA
+        default: {
+            vPos = vPos + vVel * dt + vAcc * dt * dt * 0.5;
+        }
+    }
+
+    s = to_fractional(vPos - origin);
+    for (var axis = 0u; axis < 3u; axis += 1u) {
+        let x = s[axis];
+        if x >= 0.0 && x <= 1.0 {
+            continue;
+        }
+        // +1 when the particle left through the upper face
+        let side = sign(x - 0.5);
+        let normal = face_normal(axis);
+        switch boundary_mode(axis) {
+            // periodic
+            case 0u: {
+                s[axis] = x - floor(x);
+            }
+            // specular reflection, the old acceleration is mirrored as well so the
+            // second half kick in compute.wgsl does not undo the reflection
+            case 1u: {
+                s[axis] = select(2.0 - x, -x, x < 0.0);
+                vVel = vVel - 2.0 * dot(vVel, normal) * normal;
+                vAcc = vAcc - 2.0 * dot(vAcc, normal) * normal;
+            }
+            // thermal wall, the particle leaves with a velocity from the wall temperature:
+            // Rayleigh distributed normal to the wall, Gaussian along it
+            case 2u: {
+                var seed = hash(index ^ hash(bitcast<u32>(x) ^ hash(bitcast<u32>(vVel[axis]))));
+                let atom = atoms[u32(particles[index].type_)];
+                let sigma = sqrt(BOLTZMANN_CONSTANT * params.wall_temperature / atom.mass);
+                s[axis] = select(2.0 - x, -x, x < 0.0);
+                vVel = sigma * vec3<f32>(gaussian(&seed), gaussian(&seed), gaussian(&seed));
+                vVel = vVel - dot(vVel, normal) * normal - side * sigma * sqrt(-2.0 * log(uniform(&seed))) * normal;
+                vAcc = vAcc - dot(vAcc, normal) * normal;
+            }
+            // open, the particle stops outside the box
+            default: {
+                vVel = vec3<f32>(0.0);
+                vAcc = vec3<f32>(0.0);
+            }
+        }
+    }
+    vPos = origin + from_fractional(s);
 
     particles[index].x = vPos.x;
     particles[index].y = vPos.y;
     particles[index].z = vPos.z;
+    particles[index].vel_x = vVel.x;
+    particles[index].vel_y = vVel.y;
+    particles[index].vel_z = vVel.z;
+    particles[index].acc_x = vAcc.x;
+    particles[index].acc_y = vAcc.y;
+    particles[index].acc_z = vAcc.z;
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use std::error::Error;
+use std::sync::Arc;
 use std::time;
 use std::time::Duration;
 
@@ -11,7 +13,7 @@ use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEve
 use winit::window::Window;
 
 use crate::render::gui::GUI;
-use crate::system::{compute_set::ComputeSet, consts::*, params::Params, particle::Particle};
+use crate::system::{barostat::BarostatKind, config::Config, consts::*, device, simulation::{Param, Simulation}, params::Params, particle::Particle};
 
 use crate::render::{
     camera::{Camera, CameraController, CameraUniform},
@@ -23,15 +25,15 @@ use crate::utils::buffers::Buffer;
 
 // using version 0.15.0 of wgpu
 pub struct State {
-    pub device: Device,
-    pub queue: wgpu::Queue,
+    pub device: Arc<Device>,
+    pub queue: Arc<wgpu::Queue>,
     pub surface: wgpu::Surface,
     pub adapter: wgpu::Adapter,
     pub config: wgpu::SurfaceConfiguration,
     pub encoder: Option<wgpu::CommandEncoder>,
     pub window: Window,
     pub render: RenderSet,
-    pub compute: ComputeSet,
+    pub simulation: Simulation,
     pub platform: Platform,
     pub egui_rpass: RenderPass,
     pub demo_app: GUI,
@@ -45,51 +47,30 @@ pub struct State {
 
 impl State {
     // Creating some of the wgpu types requires async code
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, simulation: &Config) -> Result<Self, Box<dyn Error>> {
         let size = window.inner_size();
 
-        // The instance is a handle to our GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        // instance is a handle to our GPU
-
+        // The instance is a handle to our GPU, gpu.api picks the backends it looks at
         // bentchmarking
         // Vulkan: 900
         // DX12: 860
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::DX12,
-            dx12_shader_compiler: Default::default(),
-        });
+        let instance = device::create_instance(&simulation.gpu);
 
         // Surface is the abstraction to present things to the screen
         // it works by creating a "swapchain" of images that are presented to the screen
-        let surface = unsafe { instance.create_surface(&window).unwrap() };
+        let surface = unsafe { instance.create_surface(&window) }?;
 
         // The adapter is a handle to our actual graphics card.
         // You can use this to get information about the graphics card such as its name and what backend the adapter uses.
         // We use this to create our Device and Queue later.
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance, // LowPower or HighPerformance
-                compatible_surface: Some(&surface), //  tells wgpu to find an adapter that can present to the supplied surface.
-                force_fallback_adapter: false, //  tells wgpu to use the software adapter if no hardware adapters are available.
-            })
-            .await
-            .unwrap();
+        // Only adapters that can present to the surface are considered.
+        let adapter = device::select_adapter(&instance, &simulation.gpu, Some(&surface))?;
 
         // The device and queue are the main handles to the GPU.
         // The device is used to create most of the objects we will use in wgpu.
         // The queue is used to submit commands to the GPU.
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue) = device::request_device(&adapter).await?;
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
 
         // The surface capabilities tell us the surface's current size and other details.
         let surface_caps = surface.get_capabilities(&adapter);
@@ -126,15 +107,24 @@ impl State {
             style: Default::default(),
         });
         let egui_rpass = RenderPass::new(&device, surface_format, 1);
-        let demo_app = GUI::default();
-
-        let render = RenderSet::new(&window, &device, &config);
-        let compute = ComputeSet::new(&device);
+        let mut demo_app = GUI::default();
+
+        // the viewer draws the particles straight from the buffers of the simulation
+        let simulation = Simulation::builder().config(simulation.clone()).build_on(device.clone(), queue.clone())?;
+        let compute = simulation.compute();
+        let render = RenderSet::new(&window, &device, &config, &compute.simulation_box(), simulation.config().system.particles);
+        demo_app.set_particle_life(compute.force_model(), compute.particle_life().clone(), compute.max_life_radius());
+        demo_app.set_thermostat(*compute.thermostat());
+        demo_app.set_barostat(*compute.barostat());
+        demo_app.set_integrator(*compute.integrator(), simulation.config().integrator.substeps);
+        demo_app.set_timestep(*compute.timestep());
+        demo_app.set_max_force(compute.max_force());
+        demo_app.set_constraints(*compute.constraints());
 
         let time = time::Instant::now();
         let frame_time = time::Instant::now();
 
-        Self {
+        Ok(Self {
             device,
             queue,
             surface,
@@ -143,7 +133,7 @@ impl State {
             encoder: None,
             window,
             render,
-            compute,
+            simulation,
             platform,
             egui_rpass,
             demo_app,
@@ -153,7 +143,7 @@ impl State {
             frame_count: 0,
             paused: false,
             dt: Duration::from_millis(16),
-        }
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -217,20 +207,19 @@ impl State {
         );
 
         if !self.paused {
-            self.compute.update(
-                self.encoder.as_mut().unwrap(),
-                &self.device,
-                &self.queue,
-                 self.frame_count as usize
-            );
+            self.simulation.encode_frame(self.encoder.as_mut().unwrap());
+            // the barostat changes the box
+            if self.simulation.compute().barostat().kind != BarostatKind::Off {
+                self.render.set_box(&self.queue, &self.simulation.simulation_box());
+            }
         }
 
         // time left over from last frame
         let time_left = 1.0 / FPS - self.frame_time.elapsed().as_secs_f32();
-        if self.frame_count % 60 == 0 {
+        if self.frame_count % self.simulation.config().output.report_interval == 0 {
             let used_time_fraction = 1.0 - time_left / (1.0 / FPS);
             let used_time = used_time_fraction * 1.0 / FPS;
-            println!("========================== 60 frames elapsed ============================");
+            println!("========================== {} frames elapsed ============================", self.simulation.config().output.report_interval);
             print!(
                 "used time: {} / {} ms,  fraction: {}%, ",
                 used_time * 1000.0,
@@ -240,9 +229,9 @@ impl State {
         }
         // wait till the end of the frame to reset the timer
         while self.frame_time.elapsed().as_secs_f32() < 1.0 / FPS {}
-        if self.frame_count % 60 == 0 {
+        if self.frame_count % self.simulation.config().output.report_interval == 0 {
             println!("FPS: {}", 1.0 / self.frame_time.elapsed().as_secs_f32());
-            pollster::block_on(self.compute.debug(&self.device, &self.queue));
+            self.simulation.report();
         }
         self.dt = self.frame_time.elapsed();
         self.frame_time = time::Instant::now();
@@ -252,7 +241,7 @@ impl State {
         self.platform.update_time(self.time.elapsed().as_secs_f64());
         let mut frame = self.render.render(
             self.encoder.as_mut().unwrap(),
-            self.compute.get_particle_buffer(self.frame_count),
+            self.simulation.compute().get_particle_buffer(),
             &self.surface,
         );
 
@@ -282,7 +271,39 @@ impl State {
             .create_view(&wgpu::TextureViewDescriptor::default());
 
         self.platform.begin_frame();
-        self.demo_app.ui(&self.platform.context(), self.compute.get_history());
+        self.demo_app.ui(&self.platform.context(), self.simulation.history());
+        if let Some((force_model, life)) = self.demo_app.take_particle_life() {
+            self.simulation.set_param(Param::ParticleLife(life.clone()));
+            self.simulation.set_param(Param::ForceModel(force_model));
+            // the pipeline keeps its model when the tabulated one has no tables and its radius
+            // when the box has no room for the bins
+            let compute = self.simulation.compute();
+            if compute.force_model() != force_model || *compute.particle_life() != life {
+                self.demo_app.set_particle_life(compute.force_model(), compute.particle_life().clone(), compute.max_life_radius());
+            }
+        }
+        if let Some(thermostat) = self.demo_app.take_thermostat() {
+            self.simulation.set_param(Param::Thermostat(thermostat));
+        }
+        if let Some(barostat) = self.demo_app.take_barostat() {
+            self.simulation.set_param(Param::Barostat(barostat));
+        }
+        if let Some(integrator) = self.demo_app.take_integrator() {
+            self.simulation.set_param(Param::Integrator(integrator));
+        }
+        if let Some(timestep) = self.demo_app.take_timestep() {
+            self.simulation.set_param(Param::Timestep(timestep));
+        }
+        if let Some(max_force) = self.demo_app.take_max_force() {
+            self.simulation.set_param(Param::MaxForce(max_force));
+        }
+        if let Some(constraints) = self.demo_app.take_constraints() {
+            self.simulation.set_param(Param::Constraints(constraints));
+        }
+        if let Some(minimizer) = self.demo_app.take_minimizer() {
+            self.simulation.minimize(minimizer);
+        }
+        self.demo_app.set_minimizer_progress(self.simulation.compute().is_minimizing(), self.simulation.compute().minimizer_state());
         let full_output = self.platform.end_frame(Some(&self.window));
         let paint_jobs = self.platform.context().tessellate(full_output.shapes);
 
@@ -314,6 +335,6 @@ impl State {
     }
 
     pub fn exit(&self) {
-        self.compute.write_stats();
+        self.simulation.compute().write_stats();
     }
 }
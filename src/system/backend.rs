@@ -0,0 +1,240 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::system::cpu::parallel::ParallelBackend;
+use crate::system::cpu::reference::ReferenceBackend;
+use crate::system::particle::Particle;
+use crate::system::simulation::Simulation;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::stats::{Stat, Stats};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// the wgpu compute pipeline
+    Gpu,
+    /// the single threaded reference implementation
+    Cpu,
+    /// the multithreaded cpu implementation
+    Parallel,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gpu" => Ok(BackendKind::Gpu),
+            "cpu" => Ok(BackendKind::Cpu),
+            "parallel" => Ok(BackendKind::Parallel),
+            _ => Err(format!("unknown backend {}, expected gpu, cpu or parallel", s)),
+        }
+    }
+}
+
+/// What a driver like the headless runner needs from an implementation of the pipeline.
+pub trait Backend {
+    fn name(&self) -> String;
+    /// runs one frame of `integrator.substeps` substeps
+    fn step(&mut self);
+    /// the summed stats of the newest configuration in mU
+    fn stat(&mut self) -> Stat;
+    /// the stats of the newest frame in eV and bar, the gpu lags one frame behind
+    fn stats(&self) -> Stats;
+    fn particles(&mut self) -> Vec<Particle>;
+    fn set_particles(&mut self, particles: &[Particle]);
+    fn simulation_box(&self) -> SimulationBox;
+    fn bin_size(&self) -> f32;
+    /// temperature of the newest frame in K
+    fn temperature(&self) -> f32;
+    /// writes the stats history to csv, returns the file name
+    fn save_stats(&mut self) -> Result<String, Box<dyn Error>>;
+}
+
+impl Backend for Simulation {
+    fn name(&self) -> String {
+        "gpu".to_string()
+    }
+
+    fn step(&mut self) {
+        Simulation::step(self, 1)
+    }
+
+    fn stat(&mut self) -> Stat {
+        self.compute().read_stat(self.device(), self.queue())
+    }
+
+    fn stats(&self) -> Stats {
+        self.compute().stats()
+    }
+
+    fn particles(&mut self) -> Vec<Particle> {
+        Simulation::particles(self)
+    }
+
+    fn set_particles(&mut self, particles: &[Particle]) {
+        Simulation::set_particles(self, particles)
+    }
+
+    fn simulation_box(&self) -> SimulationBox {
+        Simulation::simulation_box(self)
+    }
+
+    fn bin_size(&self) -> f32 {
+        self.compute().bin_size()
+    }
+
+    fn temperature(&self) -> f32 {
+        self.compute().temperature()
+    }
+
+    fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        Simulation::save_stats(self)
+    }
+}
+
+impl Backend for ReferenceBackend {
+    fn name(&self) -> String {
+        "cpu reference".to_string()
+    }
+
+    fn step(&mut self) {
+        ReferenceBackend::step(self)
+    }
+
+    fn stat(&mut self) -> Stat {
+        ReferenceBackend::stat(self)
+    }
+
+    fn stats(&self) -> Stats {
+        ReferenceBackend::stats(self)
+    }
+
+    fn particles(&mut self) -> Vec<Particle> {
+        ReferenceBackend::particles(self).to_vec()
+    }
+
+    fn set_particles(&mut self, particles: &[Particle]) {
+        ReferenceBackend::set_particles(self, particles)
+    }
+
+    fn simulation_box(&self) -> SimulationBox {
+        ReferenceBackend::simulation_box(self)
+    }
+
+    fn bin_size(&self) -> f32 {
+        ReferenceBackend::bin_size(self)
+    }
+
+    fn temperature(&self) -> f32 {
+        ReferenceBackend::temperature(self)
+    }
+
+    fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        ReferenceBackend::save_stats(self)
+    }
+}
+
+impl Backend for ParallelBackend {
+    fn name(&self) -> String {
+        format!("cpu parallel ({} threads)", rayon::current_num_threads())
+    }
+
+    fn step(&mut self) {
+        ParallelBackend::step(self)
+    }
+
+    fn stat(&mut self) -> Stat {
+        ParallelBackend::stat(self)
+    }
+
+    fn stats(&self) -> Stats {
+        ParallelBackend::stats(self)
+    }
+
+    fn particles(&mut self) -> Vec<Particle> {
+        ParallelBackend::particles(self)
+    }
+
+    fn set_particles(&mut self, particles: &[Particle]) {
+        ParallelBackend::set_particles(self, particles)
+    }
+
+    fn simulation_box(&self) -> SimulationBox {
+        ParallelBackend::simulation_box(self)
+    }
+
+    fn bin_size(&self) -> f32 {
+        ParallelBackend::bin_size(self)
+    }
+
+    fn temperature(&self) -> f32 {
+        ParallelBackend::temperature(self)
+    }
+
+    fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        ParallelBackend::save_stats(self)
+    }
+}
+
+/// How far two backends drifted apart after running the same start configuration.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Comparison {
+    pub frames: usize,
+    pub max_deviation: f32, // largest distance between the two positions of a particle in nm
+    pub rms_deviation: f32, // in nm
+    pub energy: [f32; 2],   // KE + PE of both backends in mU
+    pub kinetic: [f32; 2],  // in mU
+}
+
+impl Comparison {
+    /// the difference of the total energies relative to the mean kinetic energy, which is
+    /// never close to zero in a running system
+    pub fn relative_energy_difference(&self) -> f32 {
+        (self.energy[0] - self.energy[1]).abs() / (0.5 * (self.kinetic[0] + self.kinetic[1])).max(f32::MIN_POSITIVE)
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "after {} frames: position deviation max {:e} nm, rms {:e} nm - energy {} vs {} mU (relative difference {:e})",
+            self.frames,
+            self.max_deviation,
+            self.rms_deviation,
+            self.energy[0],
+            self.energy[1],
+            self.relative_energy_difference()
+        )
+    }
+}
+
+/// Starts `b` from the particles of `a`, runs both for `frames` frames and compares the
+/// results. The trajectories are chaotic, over long runs only the energies stay close.
+pub fn compare(a: &mut dyn Backend, b: &mut dyn Backend, frames: usize) -> Comparison {
+    let start = a.particles();
+    b.set_particles(&start);
+    for _ in 0..frames {
+        a.step();
+        b.step();
+    }
+    let (particles_a, particles_b) = (a.particles(), b.particles());
+    let simulation_box = a.simulation_box();
+    let mut comparison = Comparison { frames, ..Default::default() };
+    let mut sum = 0.0f64;
+    for (p, q) in particles_a.iter().zip(particles_b.iter()) {
+        let d = simulation_box.minimum_image([0, 1, 2].map(|i| p.position[i] - q.position[i]), [true; 3]);
+        let dist = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        comparison.max_deviation = comparison.max_deviation.max(dist);
+        sum += (dist * dist) as f64;
+    }
+    comparison.rms_deviation = (sum / particles_a.len().max(1) as f64).sqrt() as f32;
+    for (i, stat) in [a.stat(), b.stat()].iter().enumerate() {
+        comparison.energy[i] = stat.KE + stat.PE;
+        comparison.kinetic[i] = stat.KE;
+    }
+    comparison
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::system::consts::*;
+
+/// How the pressure is controlled, the box and all coordinates are scaled together.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BarostatKind {
+    /// NVT or NVE, the box stays as it is
+    Off = 0,
+    /// weak coupling, relaxes the pressure exponentially but gives the wrong volume fluctuations
+    Berendsen = 1,
+    /// random volume moves accepted with the NPT Boltzmann factor, samples the correct ensemble
+    MonteCarlo = 2,
+}
+
+/// Which box lengths the barostat changes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PressureCoupling {
+    /// all axes with the same factor, the shape of the box is kept
+    Isotropic,
+    /// every axis on its own (the kinetic part of the pressure is taken as isotropic)
+    PerAxis,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Barostat {
+    pub kind: BarostatKind,
+    pub coupling: PressureCoupling,
+    pub pressure: f32,        // target pressure in bar
+    pub tau: f32,             // coupling time in ps, Berendsen only
+    pub compressibility: f32, // in 1 / bar, Berendsen only
+    pub interval: u32,        // frames between two Monte-Carlo moves
+    pub max_strain: f32,      // largest relative change of ln(V) in one Monte-Carlo move, adapted at runtime
+}
+
+impl Barostat {
+    pub fn off() -> Self {
+        Self {
+            kind: BarostatKind::Off,
+            coupling: PressureCoupling::Isotropic,
+            pressure: 1.0,
+            tau: 2.0,
+            compressibility: 4.5e-5,
+            interval: 5,
+            max_strain: 0.01,
+        }
+    }
+
+    pub fn berendsen(pressure: f32, tau: f32, coupling: PressureCoupling) -> Self {
+        Self {
+            kind: BarostatKind::Berendsen,
+            coupling,
+            pressure,
+            tau,
+            ..Self::off()
+        }
+    }
+
+    pub fn monte_carlo(pressure: f32, interval: u32, coupling: PressureCoupling) -> Self {
+        Self {
+            kind: BarostatKind::MonteCarlo,
+            coupling,
+            pressure,
+            interval: interval.max(1),
+            ..Self::off()
+        }
+    }
+
+    /// the scale factors of the box lengths for one Berendsen step of length dt,
+    /// `pressure` holds the diagonal of the pressure tensor in bar
+    pub fn berendsen_scale(&self, pressure: [f32; 3], dt: f32) -> [f32; 3] {
+        let mean = pressure.iter().sum::<f32>() / 3.0;
+        let factor = self.compressibility * dt / (3.0 * self.tau);
+        pressure.map(|p| {
+            let p = match self.coupling {
+                PressureCoupling::Isotropic => mean,
+                PressureCoupling::PerAxis => p,
+            };
+            // a single step never changes a length by more than 1%
+            (1.0 - factor * (self.pressure - p)).clamp(0.99, 1.01)
+        })
+    }
+
+    /// scale factors of a Monte-Carlo move from two uniform random numbers in [0, 1),
+    /// isotropic moves change ln(V) evenly, per axis moves change ln(L) of one random axis
+    pub fn trial_scale(&self, u_axis: f32, u_strain: f32) -> [f32; 3] {
+        let strain = self.max_strain * (2.0 * u_strain - 1.0);
+        match self.coupling {
+            PressureCoupling::Isotropic => [(strain / 3.0).exp(); 3],
+            PressureCoupling::PerAxis => {
+                let mut scale = [1.0; 3];
+                scale[((u_axis * 3.0) as usize).min(2)] = strain.exp();
+                scale
+            }
+        }
+    }
+
+    /// Metropolis criterion of a volume move in ln(V), energies in mU and volumes in nm^3
+    pub fn accept_probability(&self, delta_energy: f64, volume_old: f64, volume_new: f64, particles: u32, temperature: f32) -> f64 {
+        let kt = (BOLTZMANN_CONSTANT_MU * temperature) as f64;
+        let pv = self.pressure as f64 / BAR_PER_MU_NM3 as f64 * (volume_new - volume_old);
+        let exponent = -(delta_energy + pv) / kt + (particles as f64 + 1.0) * (volume_new / volume_old).ln();
+        exponent.min(0.0).exp()
+    }
+}
+
+impl Default for Barostat {
+    fn default() -> Self {
+        Self::off()
+    }
+}
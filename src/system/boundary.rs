@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens to a particle that crosses the box face of one axis.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoundaryMode {
+    /// leaves on one side and comes back on the other, interactions use the minimum image
+    Periodic = 0,
+    /// specular reflection, the normal velocity is flipped
+    Reflective = 1,
+    /// diffuse reflection, the velocity is drawn anew from the wall temperature
+    Thermal = 2,
+    /// absorbing, the particle is frozen outside the box and no longer interacts
+    Open = 3,
+}
+
+impl BoundaryMode {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => BoundaryMode::Reflective,
+            2 => BoundaryMode::Thermal,
+            3 => BoundaryMode::Open,
+            _ => BoundaryMode::Periodic,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Boundary {
+    pub modes: [BoundaryMode; 3], // x, y, z
+    pub wall_temperature: f32,    // in K, only used by thermal walls
+}
+
+impl Boundary {
+    pub fn new(modes: [BoundaryMode; 3], wall_temperature: f32) -> Self {
+        Self {
+            modes,
+            wall_temperature,
+        }
+    }
+
+    pub fn periodic() -> Self {
+        Self::new([BoundaryMode::Periodic; 3], 0.0)
+    }
+
+    /// a closed container with specular walls
+    pub fn reflective() -> Self {
+        Self::new([BoundaryMode::Reflective; 3], 0.0)
+    }
+
+    /// periodic in x and y, walls at the top and bottom (a slit pore)
+    pub fn slit(mode: BoundaryMode, wall_temperature: f32) -> Self {
+        Self::new([BoundaryMode::Periodic, BoundaryMode::Periodic, mode], wall_temperature)
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        self.modes.iter().all(|m| *m == BoundaryMode::Periodic)
+    }
+
+    /// the axes the minimum image and the wrapping apply to
+    pub fn periodic_axes(&self) -> [bool; 3] {
+        self.modes.map(|m| m == BoundaryMode::Periodic)
+    }
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Self::periodic()
+    }
+}
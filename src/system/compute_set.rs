@@ -1,13 +1,30 @@
+use std::error::Error;
 use std::ops::Deref;
 use std::sync::{Mutex, Arc};
 
+use crate::system::barostat::{Barostat, BarostatKind};
+use crate::system::boundary::Boundary;
+use crate::system::config::{Config, OutputConfig};
 use crate::system::consts::*;
-use crate::system::params::Params;
+use crate::system::constraints::Constraints;
+use crate::system::cutoff::{Cutoff, CutoffScheme, TailCorrection};
+use crate::system::electrostatics::{self, CoulombEnergy, Ewald, KVector, MAX_KVECTORS};
+use crate::system::force_field::{ForceField, Pair, MAX_TABLE_ENTRIES};
+use crate::system::integrator::{Integrator, IntegratorKind, Pass};
+use crate::system::life::{ForceModel, ParticleLife};
+use crate::system::minimizer::{Minimizer, MinimizerState, MinimizerStatus};
+use crate::system::neighbour_list::{self, NeighbourListState};
+use crate::system::params::{Atom, Params};
 use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
 use crate::system::stats::Stat;
+use crate::system::thermostat::{Thermostat, ThermostatKind, ThermostatParticle, ThermostatState};
+use crate::system::timestep::{Timestep, TimestepState};
+use crate::system::topology::{AngleType, BondType, Constraint, Dihedral, DihedralType, Angle, Bond, Topology};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::sync::mpsc::channel;
 use wgpu::util::{DeviceExt, DownloadBuffer};
-use wgpu::{BufferAsyncError, CommandEncoder, Device, Queue, Features};
+use wgpu::{BufferAsyncError, CommandEncoder, Device, Queue};
 
 use super::stats::{Stats, StatHistory};
 
@@ -41,7 +58,7 @@ macro_rules! storage_buffer_empty {
     ($device:expr, $label:expr, $null_data:expr, $size:expr) => {
         $device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some($label),
-            contents: bytemuck::cast_slice(&[$null_data; $size as usize]),
+            contents: bytemuck::cast_slice(&vec![$null_data; $size as usize]),
             usage: wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_DST
                 | wgpu::BufferUsages::COPY_SRC,
@@ -49,6 +66,29 @@ macro_rules! storage_buffer_empty {
     };
 }
 
+// storage buffers can not be empty, a list without entries gets a single default entry
+macro_rules! storage_buffer_padded {
+    ($device:expr, $label:expr, $data:expr, $empty:expr) => {{
+        let mut data = $data.clone();
+        if data.is_empty() {
+            data.push($empty);
+        }
+        $device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some($label),
+            contents: bytemuck::cast_slice(data.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        })
+    }};
+}
+
+// the workgroup counts of the binning passes
+#[derive(Debug, Clone, Copy)]
+enum Workgroups {
+    Cells,     // a thread per bin, a block of the scan
+    Particles, // 64 particles
+    Single,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Timings {
     pub empty_bins: f32,
@@ -63,11 +103,82 @@ pub struct ComputeSet {
     particle_bind_groups: Vec<wgpu::BindGroup>,
     params: Params,
     params_buffer: wgpu::Buffer,
-    bin_load_buffer: wgpu::Buffer,
-    depth_buffer: wgpu::Buffer,
+    force_field: ForceField,
+    cutoff: Cutoff,
+    boundary: Boundary,
+    simulation_box: SimulationBox,
+    thermostat: Thermostat,
+    integrator: Integrator,
+    integrator_particles_buffer: wgpu::Buffer,
+    thermostat_bind_groups: Vec<wgpu::BindGroup>,
+    collide_pipeline: wgpu::ComputePipeline,
+    thermostat_pipeline: wgpu::ComputePipeline,
+    rescale_pipeline: wgpu::ComputePipeline,
+    timestep: Timestep,
+    timestep_buffer: wgpu::Buffer,
+    timestep_backup_buffer: wgpu::Buffer,
+    timestep_bind_groups: Vec<wgpu::BindGroup>,
+    timestep_pipeline: wgpu::ComputePipeline,
+    minimizer: Option<Minimizer>, // running minimization
+    minimizer_state: Option<MinimizerState>, // progress of the last or running minimization
+    minimizer_buffer: wgpu::Buffer,
+    minimizer_bind_groups: Vec<wgpu::BindGroup>,
+    minimizer_reduce_pipeline: wgpu::ComputePipeline,
+    minimizer_step_pipeline: wgpu::ComputePipeline,
+    barostat: Barostat,
+    barostat_rng: StdRng,
+    barostat_moves: [u32; 2], // attempted and accepted Monte-Carlo moves since the last adaption
+    barostat_scale_buffer: wgpu::Buffer,
+    barostat_bind_groups: Vec<wgpu::BindGroup>,
+    barostat_pipeline: wgpu::ComputePipeline,
+    particle_backup_buffer: wgpu::Buffer,
+    reorder_interval: u32, // in frames, 0 keeps the order
+    order_buffer: wgpu::Buffer,
+    reorder_cells_buffer: wgpu::Buffer,
+    sorted_order_buffer: wgpu::Buffer,
+    sorted_integrator_buffer: wgpu::Buffer,
+    sorted_thermostat_buffer: wgpu::Buffer,
+    thermostat_particles_buffer: wgpu::Buffer,
+    reorder_bind_groups: Vec<wgpu::BindGroup>,
+    reorder_count_pipeline: wgpu::ComputePipeline,
+    reorder_scan_pipeline: wgpu::ComputePipeline,
+    reorder_scatter_pipeline: wgpu::ComputePipeline,
+    max_bin_counts: [u32; 3],
+    type_counts: Vec<u32>,
+    tail_correction: TailCorrection,
+    atoms_buffer: wgpu::Buffer,
+    pairs_buffer: wgpu::Buffer,
+    tables_buffer: wgpu::Buffer,
+    life: ParticleLife,
+    life_matrix_buffer: wgpu::Buffer,
+    ewald: Ewald,
+    kvectors_buffer: wgpu::Buffer,
+    ewald_bind_groups: Vec<wgpu::BindGroup>,
+    ewald_pipeline: wgpu::ComputePipeline,
+    topology: Topology,
+    exclusions_buffer: wgpu::Buffer,
+    bonded_buffer: wgpu::Buffer,
+    bonded_bind_groups: Vec<wgpu::BindGroup>,
+    bonded_pipeline: wgpu::ComputePipeline,
+    constraints: Constraints,
+    constraint_cluster_count: u32,
+    constraint_bind_groups: Vec<wgpu::BindGroup>,
+    shake_pipeline: wgpu::ComputePipeline,
+    rattle_pipeline: wgpu::ComputePipeline,
+    cell_start_buffer: wgpu::Buffer,
+    cell_particles_buffer: wgpu::Buffer, // followed by the Verlet list
+    slot_buffer: wgpu::Buffer,
+    list_capacity: u32, // neighbours per particle, 0 without a Verlet list
+    list_positions_buffer: wgpu::Buffer,
+    list_state_buffer: wgpu::Buffer,
+    list_check_bind_groups: Vec<wgpu::BindGroup>,
+    list_build_bind_groups: Vec<wgpu::BindGroup>,
+    list_check_pipeline: wgpu::ComputePipeline,
+    list_build_pipeline: wgpu::ComputePipeline,
     verlet_bind_groups: Vec<wgpu::BindGroup>,
     empty_bins_bind_group: wgpu::BindGroup,
     bining_bind_groups: Vec<wgpu::BindGroup>,
+    scan_bind_group: wgpu::BindGroup,
     stats_bind_groups: Vec<wgpu::BindGroup>,
     stats_buffers: Vec<wgpu::Buffer>,
     stats_final_buffer: wgpu::Buffer,
@@ -76,21 +187,32 @@ pub struct ComputeSet {
     work_group_count: u32,
     verlet_pipeline: wgpu::ComputePipeline,
     compute_pipeline: wgpu::ComputePipeline,
+    slow_pipeline: wgpu::ComputePipeline,
+    slow_kick_pipeline: wgpu::ComputePipeline,
     empty_bins_pipeline: wgpu::ComputePipeline,
     binning_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    scan_blocks_pipeline: wgpu::ComputePipeline,
+    scan_sums_pipeline: wgpu::ComputePipeline,
+    add_offsets_pipeline: wgpu::ComputePipeline,
     stats_pipeline: wgpu::ComputePipeline,
     total_iterations: u32,
+    current_buffer: usize,
+    substeps: u32, // per frame
+    output: OutputConfig,
 }
 
 impl ComputeSet {
     /*
     pipeline layout:
     1. empty bins
-    2. calculate bin load and the index for each particle in the bin
-    3. sort particles into bins
-    4. do the actual collision detection and update particles
+    2. count the particles of each bin and the index of each particle in its bin
+    3. prefix sum of the counts, the start of each bin
+    4. sort particles into bins
+    5. do the actual collision detection and update particles
      */
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, config: &Config) -> Self {
+        let number_particles = config.system.particles;
         // ------------------ emptying bins shader setup ------------------ //
         let empty_bins =
             device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\empty_bins.wgsl"));
@@ -99,10 +221,8 @@ impl ComputeSet {
                 entries: &[
                     // params
                     Params::desc(),
-                    // bin_load_buffer
+                    // cell_start_buffer
                     compute_storage_descriptor!(1, 4, false),
-                    // depth_buffer
-                    compute_storage_descriptor!(2, 4, false),
                 ],
                 label: Some("empty_bins_bind_group_layout"),
             });
@@ -134,11 +254,13 @@ impl ComputeSet {
                     // params
                     Params::desc(),
                     // particle_buffer
-                    Particle::desc(1, NUMBER_PARTICLES.into(), true),
-                    // bin_load_buffer
+                    Particle::desc(1, number_particles.into(), true),
+                    // cell_start_buffer
                     compute_storage_descriptor!(2, 4, false),
-                    // depth_buffer
+                    // cell_particles_buffer
                     compute_storage_descriptor!(3, 4, false),
+                    // slot_buffer
+                    compute_storage_descriptor!(4, 4, false),
                 ],
                 label: Some("binning_bind_group_layout"),
             });
@@ -157,6 +279,82 @@ impl ComputeSet {
             entry_point: "main",
         });
 
+        let scatter_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Scatter Pipeline"),
+            layout: Some(&binning_pipeline_layout),
+            module: &binning_shader,
+            entry_point: "scatter",
+        });
+
+        // ------------------ prefix sum shader setup ------------------ //
+
+        let scan_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\scan.wgsl"));
+        let scan_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    // cell_start_buffer
+                    compute_storage_descriptor!(1, 4, false),
+                    // block_sums_buffer
+                    compute_storage_descriptor!(2, 4, false),
+                ],
+                label: Some("scan_bind_group_layout"),
+            });
+
+        let scan_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Scan Pipeline Layout"),
+                bind_group_layouts: &[&scan_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let [scan_blocks_pipeline, scan_sums_pipeline, add_offsets_pipeline] =
+            ["scan_blocks", "scan_sums", "add_offsets"].map(|entry_point| {
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&scan_pipeline_layout),
+                    module: &scan_shader,
+                    entry_point,
+                })
+            });
+
+        // ------------------ verlet list shader setup ------------------ //
+
+        let list_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\neighbours.wgsl"));
+        let list_entries = [
+            Params::desc(),
+            Particle::desc(1, number_particles.into(), true),
+            // cell_start_buffer
+            compute_storage_descriptor!(2, 4, true),
+            // cell_particles_buffer
+            compute_storage_descriptor!(3, 4, false),
+            // list_positions_buffer
+            compute_storage_descriptor!(4, 16, false),
+            // list_state_buffer, only bound by the check, the build is dispatched indirectly from it
+            compute_storage_descriptor!(5, NeighbourListState::size(), false),
+        ];
+        let [list_check_bind_group_layout, list_build_bind_group_layout] = [&list_entries[..], &list_entries[..5]].map(|entries| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries,
+                label: Some("list_bind_group_layout"),
+            })
+        });
+
+        let [list_check_pipeline, list_build_pipeline] = [("check", &list_check_bind_group_layout), ("build", &list_build_bind_group_layout)]
+            .map(|(entry_point, layout)| {
+                let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Verlet List Pipeline Layout"),
+                    bind_group_layouts: &[layout],
+                    push_constant_ranges: &[],
+                });
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &list_shader,
+                    entry_point,
+                })
+            });
+
         // ------------------ particle update shader setup ------------------ //
 
         let particle_update_shader =
@@ -165,14 +363,32 @@ impl ComputeSet {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     Params::desc(),
-                    Particle::desc(1, NUMBER_PARTICLES.into(), true),
-                    Particle::desc(2, NUMBER_PARTICLES.into(), false),
-                    // bin_load_buffer
+                    Particle::desc(1, number_particles.into(), true),
+                    Particle::desc(2, number_particles.into(), false),
+                    // cell_start_buffer
                     compute_storage_descriptor!(3, 4, true),
-                    // depth_buffer
+                    // cell_particles_buffer
                     compute_storage_descriptor!(4, 4, true),
                     // stats_buffer
-                    Stat::desc(5, NUMBER_PARTICLES.into(), false)
+                    Stat::desc(5, number_particles.into(), false),
+                    // atoms_buffer
+                    compute_storage_descriptor!(6, std::mem::size_of::<Atom>() as u64, true),
+                    // pairs_buffer
+                    compute_storage_descriptor!(7, std::mem::size_of::<Pair>() as u64, true),
+                    // life_matrix_buffer
+                    compute_storage_descriptor!(8, 4, true),
+                    // kvectors_buffer
+                    compute_storage_descriptor!(9, std::mem::size_of::<KVector>() as u64, true),
+                    // tables_buffer
+                    compute_storage_descriptor!(10, 8, true),
+                    // exclusions_buffer
+                    compute_storage_descriptor!(11, 4, true),
+                    // bonded_buffer
+                    compute_storage_descriptor!(12, 32, true),
+                    // integrator_particles_buffer
+                    compute_storage_descriptor!(13, 32, false),
+                    // timestep_buffer
+                    compute_storage_descriptor!(14, std::mem::size_of::<TimestepState>() as u64, true),
                 ],
                 label: Some("compute_bind_group_layout"),
             });
@@ -184,15 +400,37 @@ impl ComputeSet {
                 push_constant_ranges: &[],
             });
 
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &particle_update_shader,
-            entry_point: "main",
+        // the slow passes of r-RESPA live in the same shader
+        let [compute_pipeline, slow_pipeline, slow_kick_pipeline] = ["main", "slow", "slow_kick"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("Compute Pipeline ({})", entry_point)),
+                layout: Some(&compute_pipeline_layout),
+                module: &particle_update_shader,
+                entry_point,
+            })
         });
 
-        // let params = Params::new((NUMBER_PARTICLES as f32).sqrt() * BOX_SIZE);
-        let params = Params::new();
+        let force_field = config.force_field().expect("the config is validated before the pipelines are built");
+        let simulation_box = config.simulation_box();
+        let life = config.particle_life(force_field.type_count());
+        let ewald = if force_field.is_charged() {
+            Ewald::with_tolerance(config.force_field.cutoff, &simulation_box, config.force_field.ewald_tolerance)
+        } else {
+            Ewald::disabled()
+        };
+        let cutoff = config.cutoff();
+        let boundary = config.boundary();
+        if ewald.enabled && !boundary.is_periodic() {
+            println!("warning: the Ewald sum assumes a periodic system, the coulomb energy near walls is not exact");
+        }
+        let thermostat = config.thermostat();
+        let integrator = config.integrator();
+        let topology = config.topology();
+        let constraints = Constraints::default();
+        let mut params = Params::new(config, &force_field, &simulation_box);
+        params.set_particle_life(&life);
+        params.set_ewald(&ewald, &simulation_box);
+        params.set_constraints(&constraints, topology.constraints.len() as u32);
         let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Params Buffer"),
             // contents: bytemuck::cast_slice(&params.raw()),
@@ -200,6 +438,40 @@ impl ComputeSet {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let atoms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Atoms Buffer"),
+            contents: force_field.serialize_atoms(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let pairs_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pairs Buffer"),
+            contents: bytemuck::cast_slice(force_field.pairs(&cutoff).as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        // allocated at full size, so tables can be swapped without new bind groups
+        let mut tables = force_field.serialize_tables();
+        tables.resize(MAX_TABLE_ENTRIES, [0.0; 2]);
+        let tables_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tables Buffer"),
+            contents: bytemuck::cast_slice(tables.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let life_matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Life Matrix Buffer"),
+            contents: life.serialize(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // the buffer can not be empty, even without electrostatics. With electrostatics it is
+        // allocated at full size, the number of k vectors changes with the box
+        let mut kvectors = ewald.kvectors(&simulation_box);
+        kvectors.resize(if ewald.enabled { MAX_KVECTORS } else { 1 }, KVector::default());
+        let kvectors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("KVectors Buffer"),
+            contents: bytemuck::cast_slice(kvectors.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
         // ------------------ reduction (energy) pipeline setup ------------------ //
 
         let reduction_shader =
@@ -208,9 +480,9 @@ impl ComputeSet {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     // energy_buffer (read only)
-                    Stat::desc(0, NUMBER_PARTICLES.into(), true),
+                    Stat::desc(0, number_particles.into(), true),
                     // energy_buffer (read/write)
-                    Stat::desc(1, NUMBER_PARTICLES.into(), false),
+                    Stat::desc(1, number_particles.into(), false),
                     // final_energy_buffer (read/write)
                     Stat::desc(2, 1, false),
                 ],
@@ -233,7 +505,11 @@ impl ComputeSet {
 
         // ------------------ bin load texture setup ------------------ //
 
-        let initial_particle_data = Particle::create_particles(NUMBER_PARTICLES.into(), &params);
+        let mut initial_particle_data = Particle::create_particles(&config.system, &params, &force_field);
+        topology.arrange_chains(&mut initial_particle_data, &simulation_box);
+        let type_counts = Particle::type_counts(&initial_particle_data, force_field.type_count());
+        let volume = simulation_box.volume();
+        let tail_correction = TailCorrection::new(&force_field, &type_counts, volume, cutoff.r_cut);
         let initial_particle_data = Particle::serialize_all(&initial_particle_data);
 
         // two buffers for ping-ponging
@@ -244,32 +520,61 @@ impl ComputeSet {
                 device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some(&format!("Particle Buffer {}", i)),
                     contents: initial_particle_data,
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+                    usage: wgpu::BufferUsages::VERTEX
+                        | wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_SRC
+                        | wgpu::BufferUsages::COPY_DST,
                 }),
             )
         }
+        // the configuration before a Monte-Carlo volume move, restored if the move is rejected
+        let particle_backup_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Backup Buffer"),
+            size: particle_buffers[0].size(),
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
-        let bin_load_buffer = storage_buffer_empty!(device, "Bin Load Texture", 0u32, BIN_COUNT * BIN_COUNT * BIN_COUNT);
-        let depth_buffer = storage_buffer_empty!(device, "Depth Texture", 0i32, BIN_COUNT * BIN_COUNT * BIN_COUNT* BIN_DEPTH);
-        let stats_final_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        // room for the bins of a box that grew by 25% along every axis
+        let max_bin_counts = simulation_box.scaled([1.25; 3]).bin_counts(params.bin_size());
+        let max_bin_total = max_bin_counts.iter().product::<u32>();
+        // one more entry than bins, after the scan it holds the end of the last bin
+        let cell_start_buffer = storage_buffer_empty!(device, "Cell Start Buffer", 0u32, max_bin_total + 1);
+        // room for the neighbour count and list_capacity neighbours of every particle behind them
+        let list_capacity = if params.verlet_skin > 0.0 {
+            let radius = params.neghborhood_size.max(params.life_radius) + params.verlet_skin;
+            neighbour_list::initial_capacity(number_particles, simulation_box.volume(), radius)
+        } else {
+            0
+        };
+        let list_length = if list_capacity > 0 { list_capacity + 1 } else { 0 };
+        let cell_particles_buffer =
+            storage_buffer_empty!(device, "Cell Particles Buffer", 0u32, number_particles * (1 + list_length));
+        let list_positions_buffer = storage_buffer_empty!(device, "List Positions Buffer", [0f32; 4], number_particles);
+        let list_state_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("List State Buffer"),
+            contents: bytemuck::bytes_of(&NeighbourListState::new()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let slot_buffer = storage_buffer_empty!(device, "Slot Buffer", 0u32, number_particles);
+        let block_sums_buffer =
+            storage_buffer_empty!(device, "Block Sums Buffer", 0u32, (max_bin_total + 1).div_ceil(SCAN_BLOCK));
+        // the reduced stats, followed by copies of the timestep and the Verlet list state so all are read back together
+        let stats_final_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Stats Final Buffer"),
-            contents: bytemuck::cast_slice(Stat::create_stats(1).as_slice()),
+            size: Stat::size() + std::mem::size_of::<TimestepState>() as u64 + NeighbourListState::size(),
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
         });
 
         let mut stats_buffers = Vec::<wgpu::Buffer>::new();
         let mut stats_bind_groups = Vec::<wgpu::BindGroup>::new();
-        const NUM_PARTICLES_: usize =
-            (1 << (u32::BITS - NUMBER_PARTICLES.leading_zeros())) as usize;
-        println!(
-            "num particles: {}, NUMBER_PARTICLES: {}",
-            NUM_PARTICLES_, NUMBER_PARTICLES
-        );
+        let stats_length = (1 << (u32::BITS - number_particles.leading_zeros())) as usize;
         for i in 0..2 {
             stats_buffers.push(
                 device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some(&format!("Stats Buffer {}", i)),
-                    contents: bytemuck::cast_slice(Stat::create_stats(NUM_PARTICLES_).as_slice()),
+                    contents: bytemuck::cast_slice(Stat::create_stats(stats_length).as_slice()),
                     usage: wgpu::BufferUsages::STORAGE
                         | wgpu::BufferUsages::COPY_DST
                         | wgpu::BufferUsages::COPY_SRC,
@@ -288,6 +593,101 @@ impl ComputeSet {
             }));
         }
 
+        // ------------------ bonded interactions shader setup ------------------ //
+
+        // none of the buffers can be empty, even without any bonded terms
+        let exclusions_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Exclusions Buffer"),
+            contents: bytemuck::cast_slice(topology.serialize_exclusions(number_particles).as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bonded_terms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bonded Terms Buffer"),
+            contents: bytemuck::cast_slice(topology.serialize_terms(number_particles).as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bond_types_buffer = storage_buffer_padded!(device, "Bond Types Buffer", topology.bond_types, BondType::default());
+        let angle_types_buffer = storage_buffer_padded!(device, "Angle Types Buffer", topology.angle_types, AngleType::default());
+        let dihedral_types_buffer = storage_buffer_padded!(device, "Dihedral Types Buffer", topology.dihedral_types, DihedralType::default());
+        let bonds_buffer = storage_buffer_padded!(device, "Bonds Buffer", topology.bonds, Bond::default());
+        let angles_buffer = storage_buffer_padded!(device, "Angles Buffer", topology.angles, Angle::default());
+        let dihedrals_buffer = storage_buffer_padded!(device, "Dihedrals Buffer", topology.dihedrals, Dihedral::default());
+        let bonded_buffer = storage_buffer_empty!(device, "Bonded Buffer", [0f32; 8], number_particles);
+
+        let bonded_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\bonded.wgsl"));
+        let bonded_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    Particle::desc(1, number_particles.into(), true),
+                    // bond_types_buffer
+                    compute_storage_descriptor!(2, std::mem::size_of::<BondType>() as u64, true),
+                    // angle_types_buffer
+                    compute_storage_descriptor!(3, std::mem::size_of::<AngleType>() as u64, true),
+                    // dihedral_types_buffer
+                    compute_storage_descriptor!(4, std::mem::size_of::<DihedralType>() as u64, true),
+                    // bonds_buffer
+                    compute_storage_descriptor!(5, std::mem::size_of::<Bond>() as u64, true),
+                    // angles_buffer
+                    compute_storage_descriptor!(6, std::mem::size_of::<Angle>() as u64, true),
+                    // dihedrals_buffer
+                    compute_storage_descriptor!(7, std::mem::size_of::<Dihedral>() as u64, true),
+                    // bonded_terms_buffer
+                    compute_storage_descriptor!(8, 4, true),
+                    // bonded_buffer
+                    compute_storage_descriptor!(9, 32, false),
+                ],
+                label: Some("bonded_bind_group_layout"),
+            });
+
+        let mut bonded_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for particle_buffer in particle_buffers.iter() {
+            bonded_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bonded_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, params_buffer),
+                    bind_group_entry!(1, particle_buffer),
+                    bind_group_entry!(2, bond_types_buffer),
+                    bind_group_entry!(3, angle_types_buffer),
+                    bind_group_entry!(4, dihedral_types_buffer),
+                    bind_group_entry!(5, bonds_buffer),
+                    bind_group_entry!(6, angles_buffer),
+                    bind_group_entry!(7, dihedrals_buffer),
+                    bind_group_entry!(8, bonded_terms_buffer),
+                    bind_group_entry!(9, bonded_buffer),
+                ],
+                label: Some("bonded_bind_group"),
+            }));
+        }
+
+        let bonded_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Bonded Pipeline Layout"),
+                bind_group_layouts: &[&bonded_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let bonded_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Bonded Pipeline"),
+            layout: Some(&bonded_pipeline_layout),
+            module: &bonded_shader,
+            entry_point: "main",
+        });
+
+        let integrator_particles_buffer = storage_buffer_empty!(device, "Integrator Particles Buffer", [0f32; 8], number_particles);
+        let timestep = config.timestep();
+        let timestep_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Timestep Buffer"),
+            contents: bytemuck::bytes_of(&TimestepState::new(&timestep, force_field.min_sigma())),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        });
+        let timestep_backup_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestep Backup Buffer"),
+            size: timestep_buffer.size(),
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         // create two bind groups, one for each buffer as the src
         // where the alternate buffer is used as the dst
         for i in 0..2 {
@@ -297,27 +697,87 @@ impl ComputeSet {
                     bind_group_entry!(0, params_buffer),
                     bind_group_entry!(1, particle_buffers[i]),
                     bind_group_entry!(2, particle_buffers[(i + 1) % 2]),
-                    bind_group_entry!(3, bin_load_buffer),
-                    bind_group_entry!(4, depth_buffer),
+                    bind_group_entry!(3, cell_start_buffer),
+                    bind_group_entry!(4, cell_particles_buffer),
                     bind_group_entry!(5, stats_buffers[1]),
+                    bind_group_entry!(6, atoms_buffer),
+                    bind_group_entry!(7, pairs_buffer),
+                    bind_group_entry!(8, life_matrix_buffer),
+                    bind_group_entry!(9, kvectors_buffer),
+                    bind_group_entry!(10, tables_buffer),
+                    bind_group_entry!(11, exclusions_buffer),
+                    bind_group_entry!(12, bonded_buffer),
+                    bind_group_entry!(13, integrator_particles_buffer),
+                    bind_group_entry!(14, timestep_buffer),
                 ],
                 label: None,
             }));
         }
 
+        // ------------------ ewald structure factor shader setup ------------------ //
+
+        let ewald_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\ewald.wgsl"));
+
+        let ewald_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    Particle::desc(1, number_particles.into(), true),
+                    // atoms_buffer
+                    compute_storage_descriptor!(2, std::mem::size_of::<Atom>() as u64, true),
+                    // kvectors_buffer
+                    compute_storage_descriptor!(3, std::mem::size_of::<KVector>() as u64, false),
+                ],
+                label: Some("ewald_bind_group_layout"),
+            });
+
+        let mut ewald_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for particle_buffer in particle_buffers.iter() {
+            ewald_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &ewald_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, params_buffer),
+                    bind_group_entry!(1, particle_buffer),
+                    bind_group_entry!(2, atoms_buffer),
+                    bind_group_entry!(3, kvectors_buffer),
+                ],
+                label: Some("ewald_bind_group"),
+            }));
+        }
+
+        let ewald_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Ewald Pipeline Layout"),
+                bind_group_layouts: &[&ewald_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let ewald_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Ewald Pipeline"),
+            layout: Some(&ewald_pipeline_layout),
+            module: &ewald_shader,
+            entry_point: "main",
+        });
+
         let verlet_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\varlets.wgsl"));
 
         let verlet_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     Params::desc(),
-                    Particle::desc(1, NUMBER_PARTICLES.into(), false),
+                    Particle::desc(1, number_particles.into(), false),
+                    // atoms_buffer, for the thermal walls
+                    compute_storage_descriptor!(2, std::mem::size_of::<Atom>() as u64, true),
+                    // integrator_particles_buffer
+                    compute_storage_descriptor!(3, 32, true),
+                    // timestep_buffer
+                    compute_storage_descriptor!(4, std::mem::size_of::<TimestepState>() as u64, true),
                 ],
                 label: Some("verlet pipeline bind group layout"),
             });
 
         let mut verlet_bind_groups = Vec::<wgpu::BindGroup>::new();
-        for i in 0..2 {
+        for particle_buffer in particle_buffers.iter() {
             verlet_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &verlet_bind_group_layout,
                 entries: &[
@@ -327,8 +787,11 @@ impl ComputeSet {
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: particle_buffers[i].as_entire_binding(),
+                        resource: particle_buffer.as_entire_binding(),
                     },
+                    bind_group_entry!(2, atoms_buffer),
+                    bind_group_entry!(3, integrator_particles_buffer),
+                    bind_group_entry!(4, timestep_buffer),
                 ],
                 label: Some("verlet pipeline bind group"),
             }));
@@ -357,20 +820,26 @@ impl ComputeSet {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: bin_load_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: depth_buffer.as_entire_binding(),
+                    resource: cell_start_buffer.as_entire_binding(),
                 },
             ],
             label: Some("empty bins bind group"),
         });
 
+        let scan_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &scan_bind_group_layout,
+            entries: &[
+                bind_group_entry!(0, params_buffer),
+                bind_group_entry!(1, cell_start_buffer),
+                bind_group_entry!(2, block_sums_buffer),
+            ],
+            label: Some("scan bind group"),
+        });
+
 
 
         let mut bining_bind_groups = Vec::<wgpu::BindGroup>::new();
-        for i in 0..2 {
+        for particle_buffer in particle_buffers.iter() {
             bining_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &binning_bind_group_layout,
                 entries: &[
@@ -380,24 +849,425 @@ impl ComputeSet {
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: particle_buffers[i].as_entire_binding(),
+                        resource: particle_buffer.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
-                        resource: bin_load_buffer.as_entire_binding(),
+                        resource: cell_start_buffer.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 3,
-                        resource: depth_buffer.as_entire_binding(),
+                        resource: cell_particles_buffer.as_entire_binding(),
                     },
+                    bind_group_entry!(4, slot_buffer),
                 ],
                 label: None,
             }));
         }
 
-        let work_group_count = (NUMBER_PARTICLES as f32 / 64.0).ceil() as u32;
+        let mut list_check_bind_groups = Vec::<wgpu::BindGroup>::new();
+        let mut list_build_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for particle_buffer in particle_buffers.iter() {
+            let entries = [
+                bind_group_entry!(0, params_buffer),
+                bind_group_entry!(1, particle_buffer),
+                bind_group_entry!(2, cell_start_buffer),
+                bind_group_entry!(3, cell_particles_buffer),
+                bind_group_entry!(4, list_positions_buffer),
+                bind_group_entry!(5, list_state_buffer),
+            ];
+            list_check_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &list_check_bind_group_layout,
+                entries: &entries,
+                label: Some("verlet list check bind group"),
+            }));
+            list_build_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &list_build_bind_group_layout,
+                entries: &entries[..5],
+                label: Some("verlet list build bind group"),
+            }));
+        }
+
+        // ------------------ thermostat shader setup ------------------ //
+
+        let thermostat_particles_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thermostat Particles Buffer"),
+            contents: bytemuck::cast_slice(ThermostatParticle::create(number_particles, 0).as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let thermostat_state_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thermostat State Buffer"),
+            contents: bytemuck::bytes_of(&ThermostatState::new(0)),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let thermostat_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\thermostat.wgsl"));
+        let thermostat_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    Particle::desc(1, number_particles.into(), false),
+                    // atoms_buffer
+                    compute_storage_descriptor!(2, std::mem::size_of::<Atom>() as u64, true),
+                    // thermostat_particles_buffer
+                    compute_storage_descriptor!(3, std::mem::size_of::<ThermostatParticle>() as u64, false),
+                    // stats_buffer
+                    Stat::desc(4, number_particles.into(), false),
+                    // thermostat_state_buffer
+                    compute_storage_descriptor!(5, std::mem::size_of::<ThermostatState>() as u64, false),
+                    // stats_final_buffer
+                    Stat::desc(6, 1, false),
+                    // timestep_buffer
+                    compute_storage_descriptor!(7, std::mem::size_of::<TimestepState>() as u64, true),
+                ],
+                label: Some("thermostat_bind_group_layout"),
+            });
+
+        // bind group i works on the particles written by particle_bind_groups[i]
+        let mut thermostat_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            thermostat_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &thermostat_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, params_buffer),
+                    bind_group_entry!(1, particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(2, atoms_buffer),
+                    bind_group_entry!(3, thermostat_particles_buffer),
+                    bind_group_entry!(4, stats_buffers[1]),
+                    bind_group_entry!(5, thermostat_state_buffer),
+                    bind_group_entry!(6, stats_final_buffer),
+                    bind_group_entry!(7, timestep_buffer),
+                ],
+                label: Some("thermostat_bind_group"),
+            }));
+        }
+
+        let thermostat_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Thermostat Pipeline Layout"),
+                bind_group_layouts: &[&thermostat_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let [collide_pipeline, thermostat_pipeline, rescale_pipeline] = ["collide", "update", "rescale"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("Thermostat Pipeline ({})", entry_point)),
+                layout: Some(&thermostat_pipeline_layout),
+                module: &thermostat_shader,
+                entry_point,
+            })
+        });
+
+        // ------------------ barostat shader setup ------------------ //
+
+        let barostat_scale_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Barostat Scale Buffer"),
+            contents: bytemuck::cast_slice(&[1f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let barostat_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\barostat.wgsl"));
+        let barostat_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    // barostat_scale_buffer
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(16),
+                        },
+                        count: None,
+                    },
+                    Particle::desc(1, number_particles.into(), false),
+                ],
+                label: Some("barostat_bind_group_layout"),
+            });
+
+        let mut barostat_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for particle_buffer in particle_buffers.iter() {
+            barostat_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &barostat_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, barostat_scale_buffer),
+                    bind_group_entry!(1, particle_buffer),
+                ],
+                label: Some("barostat_bind_group"),
+            }));
+        }
+
+        let barostat_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Barostat Pipeline Layout"),
+                bind_group_layouts: &[&barostat_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let barostat_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Barostat Pipeline"),
+            layout: Some(&barostat_pipeline_layout),
+            module: &barostat_shader,
+            entry_point: "main",
+        });
+
+        // ------------------ timestep shader setup ------------------ //
+
+        let timestep_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\timestep.wgsl"));
+        let timestep_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Particle::desc(0, number_particles.into(), true),
+                    // timestep_buffer
+                    compute_storage_descriptor!(1, std::mem::size_of::<TimestepState>() as u64, false),
+                ],
+                label: Some("timestep_bind_group_layout"),
+            });
+
+        // bind group i reads the particles written by particle_bind_groups[i]
+        let mut timestep_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            timestep_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &timestep_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(1, timestep_buffer),
+                ],
+                label: Some("timestep_bind_group"),
+            }));
+        }
+
+        let timestep_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Timestep Pipeline Layout"),
+                bind_group_layouts: &[&timestep_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let timestep_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Timestep Pipeline"),
+            layout: Some(&timestep_pipeline_layout),
+            module: &timestep_shader,
+            entry_point: "main",
+        });
+
+        // ------------------ minimizer shader setup ------------------ //
+
+        let minimizer_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Minimizer Buffer"),
+            contents: bytemuck::bytes_of(&MinimizerState::default()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let minimizer_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\minimize.wgsl"));
+        let minimizer_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Particle::desc(0, number_particles.into(), false),
+                    // atoms_buffer
+                    compute_storage_descriptor!(1, std::mem::size_of::<Atom>() as u64, true),
+                    // stats_buffer
+                    Stat::desc(2, number_particles.into(), true),
+                    // minimizer_buffer
+                    compute_storage_descriptor!(3, std::mem::size_of::<MinimizerState>() as u64, false),
+                    // timestep_buffer
+                    compute_storage_descriptor!(4, std::mem::size_of::<TimestepState>() as u64, false),
+                ],
+                label: Some("minimizer_bind_group_layout"),
+            });
+
+        // bind group i works on the particles written by particle_bind_groups[i]
+        let mut minimizer_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            minimizer_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &minimizer_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(1, atoms_buffer),
+                    bind_group_entry!(2, stats_buffers[1]),
+                    bind_group_entry!(3, minimizer_buffer),
+                    bind_group_entry!(4, timestep_buffer),
+                ],
+                label: Some("minimizer_bind_group"),
+            }));
+        }
+
+        let minimizer_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Minimizer Pipeline Layout"),
+                bind_group_layouts: &[&minimizer_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let [minimizer_reduce_pipeline, minimizer_step_pipeline] = ["reduce", "step"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("Minimizer Pipeline ({})", entry_point)),
+                layout: Some(&minimizer_pipeline_layout),
+                module: &minimizer_shader,
+                entry_point,
+            })
+        });
+
+        // ------------------ constraints shader setup ------------------ //
+
+        let constraints_buffer = storage_buffer_padded!(device, "Constraints Buffer", topology.constraints, Constraint::default());
+        let clusters = topology.serialize_constraint_clusters();
+        let constraint_cluster_count = clusters[0] - 1;
+        let clusters_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Constraint Clusters Buffer"),
+            contents: bytemuck::cast_slice(clusters.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let cluster_states_buffer = storage_buffer_empty!(device, "Constraint Cluster States Buffer", [0f32; 4], constraint_cluster_count.max(1));
+
+        let constraints_shader = device.create_shader_module(wgpu::include_wgsl!("..\\shaders\\constraints.wgsl"));
+        let constraints_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    Particle::desc(1, number_particles.into(), false),
+                    Particle::desc(2, number_particles.into(), false),
+                    // atoms_buffer
+                    compute_storage_descriptor!(3, std::mem::size_of::<Atom>() as u64, true),
+                    // constraints_buffer
+                    compute_storage_descriptor!(4, std::mem::size_of::<Constraint>() as u64, true),
+                    // clusters_buffer
+                    compute_storage_descriptor!(5, 4, true),
+                    // cluster_states_buffer
+                    compute_storage_descriptor!(6, 16, false),
+                    // stats_buffer
+                    Stat::desc(7, number_particles.into(), false),
+                    // timestep_buffer
+                    compute_storage_descriptor!(8, std::mem::size_of::<TimestepState>() as u64, true),
+                ],
+                label: Some("constraints_bind_group_layout"),
+            });
+
+        // bind group i shakes the particles drifted by verlet_bind_groups[i] and rattles the
+        // ones written by particle_bind_groups[i]
+        let mut constraint_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            constraint_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &constraints_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, params_buffer),
+                    bind_group_entry!(1, particle_buffers[i]),
+                    bind_group_entry!(2, particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(3, atoms_buffer),
+                    bind_group_entry!(4, constraints_buffer),
+                    bind_group_entry!(5, clusters_buffer),
+                    bind_group_entry!(6, cluster_states_buffer),
+                    bind_group_entry!(7, stats_buffers[1]),
+                    bind_group_entry!(8, timestep_buffer),
+                ],
+                label: Some("constraints_bind_group"),
+            }));
+        }
+
+        let constraints_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Constraints Pipeline Layout"),
+                bind_group_layouts: &[&constraints_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let [shake_pipeline, rattle_pipeline] = ["shake", "rattle"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("Constraints Pipeline ({})", entry_point)),
+                layout: Some(&constraints_pipeline_layout),
+                module: &constraints_shader,
+                entry_point,
+            })
+        });
+
+        // ------------------ reorder shader setup ------------------ //
+
+        // the grid size also sizes the cell buffer below, so the shader takes it from consts.rs
+        let reorder_source = format!("const MORTON_BITS: u32 = {}u;\n{}", MORTON_BITS, include_str!("..\\shaders\\reorder.wgsl"));
+        let reorder_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("..\\shaders\\reorder.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(reorder_source.into()),
+        });
+        let reorder_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    Params::desc(),
+                    Particle::desc(1, number_particles.into(), true),
+                    Particle::desc(2, number_particles.into(), false),
+                    // reorder_cells_buffer
+                    compute_storage_descriptor!(3, 4, false),
+                    // slot_buffer
+                    compute_storage_descriptor!(4, 4, false),
+                    // order_buffer
+                    compute_storage_descriptor!(5, 4, true),
+                    // sorted_order_buffer
+                    compute_storage_descriptor!(6, 4, false),
+                    // integrator_particles_buffer
+                    compute_storage_descriptor!(7, 32, true),
+                    // sorted_integrator_buffer
+                    compute_storage_descriptor!(8, 32, false),
+                    // thermostat_particles_buffer
+                    compute_storage_descriptor!(9, 8, true),
+                    // sorted_thermostat_buffer
+                    compute_storage_descriptor!(10, 8, false),
+                ],
+                label: Some("reorder_bind_group_layout"),
+            });
+
+        // the identity of the particle at every index
+        let order_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Order Buffer"),
+            contents: bytemuck::cast_slice(&(0..number_particles).collect::<Vec<u32>>()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        });
+        // the cells of the Morton grid and one for the absorbed particles
+        let reorder_cells_buffer = storage_buffer_empty!(device, "Reorder Cells Buffer", 0u32, (1 << (3 * MORTON_BITS)) + 1);
+        let sorted_order_buffer = storage_buffer_empty!(device, "Sorted Order Buffer", 0u32, number_particles);
+        let sorted_integrator_buffer = storage_buffer_empty!(device, "Sorted Integrator Buffer", [0f32; 8], number_particles);
+        let sorted_thermostat_buffer = storage_buffer_empty!(device, "Sorted Thermostat Buffer", [0u32; 2], number_particles);
+
+        // bind group i sorts the particles of particle_buffers[i] into the other buffer
+        let mut reorder_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            reorder_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &reorder_bind_group_layout,
+                entries: &[
+                    bind_group_entry!(0, params_buffer),
+                    bind_group_entry!(1, particle_buffers[i]),
+                    bind_group_entry!(2, particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(3, reorder_cells_buffer),
+                    bind_group_entry!(4, slot_buffer),
+                    bind_group_entry!(5, order_buffer),
+                    bind_group_entry!(6, sorted_order_buffer),
+                    bind_group_entry!(7, integrator_particles_buffer),
+                    bind_group_entry!(8, sorted_integrator_buffer),
+                    bind_group_entry!(9, thermostat_particles_buffer),
+                    bind_group_entry!(10, sorted_thermostat_buffer),
+                ],
+                label: Some("reorder_bind_group"),
+            }));
+        }
+
+        let reorder_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Reorder Pipeline Layout"),
+                bind_group_layouts: &[&reorder_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let [reorder_count_pipeline, reorder_scan_pipeline, reorder_scatter_pipeline] = ["count", "scan", "scatter"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("Reorder Pipeline ({})", entry_point)),
+                layout: Some(&reorder_pipeline_layout),
+                module: &reorder_shader,
+                entry_point,
+            })
+        });
+
+        let work_group_count = (number_particles as f32 / 64.0).ceil() as u32;
         let stats = Arc::new(Mutex::new(Stats::default()));
-        let st_hist = StatHistory::new(params.clone());
+        let st_hist = StatHistory::new(params, &force_field);
         let stats_history = Arc::new(Mutex::new(st_hist));
 
         Self {
@@ -405,11 +1275,82 @@ impl ComputeSet {
             particle_bind_groups,
             params,
             params_buffer,
-            bin_load_buffer,
-            depth_buffer,
+            force_field,
+            cutoff,
+            boundary,
+            simulation_box,
+            thermostat,
+            integrator,
+            integrator_particles_buffer,
+            timestep,
+            timestep_buffer,
+            timestep_backup_buffer,
+            timestep_bind_groups,
+            timestep_pipeline,
+            minimizer: None,
+            minimizer_state: None,
+            minimizer_buffer,
+            minimizer_bind_groups,
+            minimizer_reduce_pipeline,
+            minimizer_step_pipeline,
+            thermostat_bind_groups,
+            collide_pipeline,
+            thermostat_pipeline,
+            rescale_pipeline,
+            barostat: config.barostat(),
+            barostat_rng: StdRng::seed_from_u64(0),
+            barostat_moves: [0; 2],
+            barostat_scale_buffer,
+            barostat_bind_groups,
+            barostat_pipeline,
+            particle_backup_buffer,
+            reorder_interval: config.gpu.reorder_interval,
+            order_buffer,
+            reorder_cells_buffer,
+            sorted_order_buffer,
+            sorted_integrator_buffer,
+            sorted_thermostat_buffer,
+            thermostat_particles_buffer,
+            reorder_bind_groups,
+            reorder_count_pipeline,
+            reorder_scan_pipeline,
+            reorder_scatter_pipeline,
+            max_bin_counts,
+            type_counts,
+            tail_correction,
+            atoms_buffer,
+            pairs_buffer,
+            tables_buffer,
+            life,
+            life_matrix_buffer,
+            ewald,
+            kvectors_buffer,
+            ewald_bind_groups,
+            ewald_pipeline,
+            topology,
+            exclusions_buffer,
+            bonded_buffer,
+            bonded_bind_groups,
+            bonded_pipeline,
+            constraints,
+            constraint_cluster_count,
+            constraint_bind_groups,
+            shake_pipeline,
+            rattle_pipeline,
+            cell_start_buffer,
+            cell_particles_buffer,
+            slot_buffer,
+            list_capacity,
+            list_positions_buffer,
+            list_state_buffer,
+            list_check_bind_groups,
+            list_build_bind_groups,
+            list_check_pipeline,
+            list_build_pipeline,
             verlet_bind_groups,
             empty_bins_bind_group,
             bining_bind_groups,
+            scan_bind_group,
             stats_bind_groups,
             stats_buffers,
             stats_final_buffer,
@@ -418,64 +1359,777 @@ impl ComputeSet {
             work_group_count,
             verlet_pipeline,
             compute_pipeline,
+            slow_pipeline,
+            slow_kick_pipeline,
             empty_bins_pipeline,
             binning_pipeline,
+            scatter_pipeline,
+            scan_blocks_pipeline,
+            scan_sums_pipeline,
+            add_offsets_pipeline,
             stats_pipeline,
             total_iterations: 0,
+            current_buffer: 0,
+            substeps: config.integrator.substeps,
+            output: config.output.clone(),
         }
     }
 
     pub fn update(&mut self, encoder: &mut CommandEncoder, device: &Device, queue: &Queue, frame: usize) {
+        // a particle had more neighbours at the last build than its list holds
+        let max_neighbours = self.stats.lock().unwrap().max_neighbours;
+        if self.list_capacity > 0 && max_neighbours > self.list_capacity {
+            self.grow_neighbour_list(device, queue, max_neighbours);
+        }
+        if self.minimizer.is_some() && self.update_minimizer(encoder, device, queue, frame) {
+            return;
+        }
+        // the global thermostats and the Berendsen barostat follow the dt of the last frame
+        if self.timestep.adaptive {
+            let dt = self.stats.lock().unwrap().dt;
+            if dt > 0.0 && dt != self.params.dt {
+                self.params.set_dt(dt, self.substeps);
+                queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+            }
+        }
+        match self.barostat.kind {
+            BarostatKind::Berendsen => self.berendsen_step(encoder, queue),
+            BarostatKind::MonteCarlo if frame != 0 && frame.is_multiple_of(self.barostat.interval as usize) => {
+                self.monte_carlo_move(device, queue)
+            }
+            _ => {}
+        }
+        if self.reorder_interval > 0 && frame != 0 && frame.is_multiple_of(self.reorder_interval as usize) {
+            self.encode_reorder(encoder, queue);
+        }
+
+        // the passes of every substep, the integrator picks them from the step count
+        let substeps: Vec<Vec<Pass>> = (0..self.substeps)
+            .map(|i| {
+                self.integrator.passes(
+                    self.total_iterations + i,
+                    self.params.electrostatics == 1,
+                    !self.topology.is_empty(),
+                    !self.topology.constraints.is_empty(),
+                )
+            })
+            .collect();
+        self.total_iterations += self.substeps;
+        // substep i reads the particles of group (first + i) % 2 and writes the other buffer
+        let first = self.current_buffer;
+
         encoder.push_debug_group("compute gravity and update positions");
         {
             let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some(format!("Compute Pass").as_str()),
-            });
-            for i in 0..ITERATIONS as usize {
-                self.total_iterations += 1;
-
-                // empty bins
-                compute_pass.set_pipeline(&self.empty_bins_pipeline);
-                compute_pass.set_bind_group(0, &self.empty_bins_bind_group, &[]);
-                compute_pass.dispatch_workgroups(
-                    ((BIN_COUNT * BIN_COUNT * BIN_COUNT) as f32 / 256.0).ceil() as u32,
-                    1,
-                    1,
-                );
-
-                // bin particles
-                compute_pass.set_pipeline(&self.binning_pipeline);
-                compute_pass.set_bind_group(0, &self.bining_bind_groups[(frame + i) % 2], &[]);
-                compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+                label: Some("Compute Pass"),
+            });
+            for (i, passes) in substeps.into_iter().enumerate() {
+                for pass in passes {
+                    self.encode_pass(&mut compute_pass, pass, (first + i) % 2);
+                }
 
-                // verlet 1
-                compute_pass.set_pipeline(&self.verlet_pipeline);
-                compute_pass.set_bind_group(0, &self.verlet_bind_groups[(frame + i) % 2], &[]);
-                compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+                // Langevin and Andersen
+                if self.thermostat.kind.is_local() {
+                    compute_pass.set_pipeline(&self.collide_pipeline);
+                    compute_pass.set_bind_group(0, &self.thermostat_bind_groups[(first + i) % 2], &[]);
+                    compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+                }
 
-                // collisions
-                compute_pass.set_pipeline(&self.compute_pipeline);
-                compute_pass.set_bind_group(0, &self.particle_bind_groups[(frame + i) % 2], &[]);
+                // simulated time and the dt of the next substep
+                compute_pass.set_pipeline(&self.timestep_pipeline);
+                compute_pass.set_bind_group(0, &self.timestep_bind_groups[(first + i) % 2], &[]);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+            }
+
+            self.encode_stats_reduction(&mut compute_pass);
+
+            // global thermostats, also keeps the thermostat energy in the stats up to date
+            let last = (first + self.substeps as usize - 1) % 2;
+            compute_pass.set_pipeline(&self.thermostat_pipeline);
+            compute_pass.set_bind_group(0, &self.thermostat_bind_groups[last], &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+            if self.thermostat.kind.is_global() {
+                compute_pass.set_pipeline(&self.rescale_pipeline);
                 compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
             }
+        }
+        encoder.pop_debug_group();
+        self.copy_states(encoder);
+        // the last substep wrote into the other buffer of its pair
+        self.current_buffer = (first + self.substeps as usize) % 2;
+        if frame != 0 {
+            self.download_stats(device, queue);
+        }
+    }
+
+    /// the timestep and the Verlet list state go behind the reduced stats, they are read back together
+    fn copy_states(&self, encoder: &mut CommandEncoder) {
+        let offset = Stat::size() + self.timestep_buffer.size();
+        encoder.copy_buffer_to_buffer(&self.timestep_buffer, 0, &self.stats_final_buffer, Stat::size(), self.timestep_buffer.size());
+        encoder.copy_buffer_to_buffer(&self.list_state_buffer, 0, &self.stats_final_buffer, offset, NeighbourListState::size());
+    }
+
+    /// Makes room for `max_neighbours` and a quarter more in the Verlet list of every particle,
+    /// as far as the device can bind. Until then those particles search the bins instead.
+    fn grow_neighbour_list(&mut self, device: &Device, queue: &Queue, max_neighbours: u32) {
+        let particles = self.params.N as u64;
+        let limit = device.limits().max_storage_buffer_binding_size as u64 / (4 * particles);
+        let capacity = (neighbour_list::grown_capacity(max_neighbours, self.list_capacity) as u64).min(limit - 2) as u32;
+        if capacity <= self.list_capacity {
+            return;
+        }
+        println!(
+            "warning: a particle has {} neighbours, the Verlet list grows from {} to {} per particle",
+            max_neighbours, self.list_capacity, capacity
+        );
+        self.cell_particles_buffer =
+            storage_buffer_empty!(device, "Cell Particles Buffer", 0u32, self.params.N * (capacity + 2));
+        self.list_capacity = capacity;
+        self.create_cell_bind_groups(device);
+        self.rebuild_neighbour_list(queue);
+    }
+
+    /// Sorts the particles of the current buffer along a Morton curve into both buffers, see
+    /// reorder.wgsl. The state the integrators and thermostats keep per particle moves along.
+    fn encode_reorder(&self, encoder: &mut CommandEncoder, queue: &Queue) {
+        let current = self.current_buffer;
+        let sorted = &self.particle_buffers[(current + 1) % 2];
+        encoder.clear_buffer(&self.reorder_cells_buffer, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Reorder Pass"),
+            });
+            compute_pass.set_bind_group(0, &self.reorder_bind_groups[current], &[]);
+            compute_pass.set_pipeline(&self.reorder_count_pipeline);
+            compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+            compute_pass.set_pipeline(&self.reorder_scan_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+            compute_pass.set_pipeline(&self.reorder_scatter_pipeline);
+            compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(sorted, 0, &self.particle_buffers[current], 0, sorted.size());
+        for (from, to) in [
+            (&self.sorted_order_buffer, &self.order_buffer),
+            (&self.sorted_integrator_buffer, &self.integrator_particles_buffer),
+            (&self.sorted_thermostat_buffer, &self.thermostat_particles_buffer),
+        ] {
+            encoder.copy_buffer_to_buffer(from, 0, to, 0, from.size());
+        }
+        // the list holds the old indices
+        self.rebuild_neighbour_list(queue);
+    }
 
-            // stats
-            let iterations = (u32::BITS - NUMBER_PARTICLES.leading_zeros()) as usize;
-            for (i, index) in (0..iterations).rev().enumerate() {
-                compute_pass.set_pipeline(&self.stats_pipeline);
-                compute_pass.set_bind_group(0, &self.stats_bind_groups[(i + 1) % 2], &[]);
-                let work_group_count = ((1 << (index + 1)) as f32 / 64.0).ceil() as u32;
-                compute_pass.dispatch_workgroups(work_group_count, 1, 1);
+    /// the bind groups that hold the cell particles buffer, after it was reallocated
+    fn create_cell_bind_groups(&mut self, device: &Device) {
+        let binning_layout = self.binning_pipeline.get_bind_group_layout(0);
+        let check_layout = self.list_check_pipeline.get_bind_group_layout(0);
+        let build_layout = self.list_build_pipeline.get_bind_group_layout(0);
+        let compute_layout = self.compute_pipeline.get_bind_group_layout(0);
+        for i in 0..2 {
+            self.bining_bind_groups[i] = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &binning_layout,
+                entries: &[
+                    bind_group_entry!(0, self.params_buffer),
+                    bind_group_entry!(1, self.particle_buffers[i]),
+                    bind_group_entry!(2, self.cell_start_buffer),
+                    bind_group_entry!(3, self.cell_particles_buffer),
+                    bind_group_entry!(4, self.slot_buffer),
+                ],
+                label: None,
+            });
+            let entries = [
+                bind_group_entry!(0, self.params_buffer),
+                bind_group_entry!(1, self.particle_buffers[i]),
+                bind_group_entry!(2, self.cell_start_buffer),
+                bind_group_entry!(3, self.cell_particles_buffer),
+                bind_group_entry!(4, self.list_positions_buffer),
+                bind_group_entry!(5, self.list_state_buffer),
+            ];
+            self.list_check_bind_groups[i] = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &check_layout,
+                entries: &entries,
+                label: Some("verlet list check bind group"),
+            });
+            self.list_build_bind_groups[i] = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &build_layout,
+                entries: &entries[..5],
+                label: Some("verlet list build bind group"),
+            });
+            self.particle_bind_groups[i] = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &compute_layout,
+                entries: &[
+                    bind_group_entry!(0, self.params_buffer),
+                    bind_group_entry!(1, self.particle_buffers[i]),
+                    bind_group_entry!(2, self.particle_buffers[(i + 1) % 2]),
+                    bind_group_entry!(3, self.cell_start_buffer),
+                    bind_group_entry!(4, self.cell_particles_buffer),
+                    bind_group_entry!(5, self.stats_buffers[1]),
+                    bind_group_entry!(6, self.atoms_buffer),
+                    bind_group_entry!(7, self.pairs_buffer),
+                    bind_group_entry!(8, self.life_matrix_buffer),
+                    bind_group_entry!(9, self.kvectors_buffer),
+                    bind_group_entry!(10, self.tables_buffer),
+                    bind_group_entry!(11, self.exclusions_buffer),
+                    bind_group_entry!(12, self.bonded_buffer),
+                    bind_group_entry!(13, self.integrator_particles_buffer),
+                    bind_group_entry!(14, self.timestep_buffer),
+                ],
+                label: None,
+            });
+        }
+    }
+
+    /// the next substep rebuilds the Verlet list, for changes that moved the particles or the
+    /// bins without the displacement check noticing
+    fn rebuild_neighbour_list(&self, queue: &Queue) {
+        if self.list_capacity > 0 {
+            queue.write_buffer(&self.list_state_buffer, NeighbourListState::REBUILD_OFFSET, bytemuck::bytes_of(&1u32));
+        }
+    }
+
+    /// Minimizes the energy in place of the dynamics until the minimizer stops, then the
+    /// dynamics continue from the relaxed particles at rest.
+    pub fn minimize(&mut self, queue: &Queue, minimizer: Minimizer) {
+        queue.write_buffer(&self.minimizer_buffer, 0, bytemuck::bytes_of(&MinimizerState::new(&minimizer)));
+        self.params.integrator = IntegratorKind::Minimize as u32;
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.minimizer = Some(minimizer);
+        self.minimizer_state = None;
+    }
+
+    pub fn is_minimizing(&self) -> bool {
+        self.minimizer.is_some()
+    }
+
+    /// progress of the running minimization, or the result of the last one
+    pub fn minimizer_state(&self) -> Option<MinimizerState> {
+        self.minimizer_state
+    }
+
+    /// One frame of minimization, returns false once the minimizer has stopped and the
+    /// frame should be dynamics. Reads the minimizer state back, so this blocks until the gpu is done.
+    fn update_minimizer(&mut self, encoder: &mut CommandEncoder, device: &Device, queue: &Queue, frame: usize) -> bool {
+        let state = self.read_minimizer_state(device, queue);
+        self.minimizer_state = Some(state);
+        if state.status() != MinimizerStatus::Running {
+            println!(
+                "minimizer stopped after {} steps ({:?}), energy: {} mU, largest force: {} mU/nm",
+                state.steps,
+                state.status(),
+                state.energy,
+                state.max_force
+            );
+            self.minimizer = None;
+            self.params.set_integrator(&self.integrator);
+            self.set_timestep(queue, self.timestep);
+            return false;
+        }
+
+        let mut passes = Integrator::default().passes(
+            0,
+            self.params.electrostatics == 1,
+            !self.topology.is_empty(),
+            !self.topology.constraints.is_empty(),
+        );
+        passes.push(Pass::Minimize);
+        self.total_iterations += self.substeps;
+        let first = self.current_buffer;
+
+        encoder.push_debug_group("minimize");
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Minimizer Pass"),
+            });
+            for i in 0..self.substeps as usize {
+                for pass in passes.iter() {
+                    self.encode_pass(&mut compute_pass, *pass, (first + i) % 2);
+                }
             }
+            self.encode_stats_reduction(&mut compute_pass);
         }
         encoder.pop_debug_group();
+        self.copy_states(encoder);
+        self.current_buffer = (first + self.substeps as usize) % 2;
         if frame != 0 {
             self.download_stats(device, queue);
         }
+        true
+    }
+
+    fn read_minimizer_state(&self, device: &Device, queue: &Queue) -> MinimizerState {
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(device, queue, &self.minimizer_buffer.slice(..), move |r| {
+            let state = r.map(|data| bytemuck::pod_read_unaligned::<MinimizerState>(&data[..]));
+            sender.send(state).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().expect("could not read the minimizer buffer")
+    }
+
+    pub fn force_field(&self) -> &ForceField {
+        &self.force_field
+    }
+
+    /// replaces the atom table, pair parameters and pair tables, the number of types has to stay the same
+    pub fn set_force_field(&mut self, queue: &Queue, force_field: ForceField) {
+        assert_eq!(force_field.type_count(), self.params.type_count);
+        queue.write_buffer(&self.atoms_buffer, 0, force_field.serialize_atoms());
+        queue.write_buffer(&self.pairs_buffer, 0, bytemuck::cast_slice(force_field.pairs(&self.cutoff).as_slice()));
+        queue.write_buffer(&self.tables_buffer, 0, bytemuck::cast_slice(force_field.serialize_tables().as_slice()));
+        self.force_field = force_field;
+    }
+
+    pub fn cutoff(&self) -> &Cutoff {
+        &self.cutoff
+    }
+
+    /// switches the cutoff scheme, r_cut itself is fixed by the bin size
+    pub fn set_cutoff_scheme(&mut self, queue: &Queue, scheme: CutoffScheme, r_switch: f32) {
+        self.cutoff = Cutoff::new(scheme, self.cutoff.r_cut, r_switch);
+        self.params.cutoff_scheme = scheme as u32;
+        self.params.r_switch = r_switch;
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        queue.write_buffer(&self.pairs_buffer, 0, bytemuck::cast_slice(self.force_field.pairs(&self.cutoff).as_slice()));
+    }
+
+    pub fn boundary(&self) -> &Boundary {
+        &self.boundary
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        self.simulation_box
+    }
+
+    /// switches the boundary mode of every axis, takes effect with the next position update
+    pub fn set_boundary(&mut self, queue: &Queue, boundary: Boundary) {
+        if self.ewald.enabled && !boundary.is_periodic() {
+            println!("warning: the Ewald sum assumes a periodic system, the coulomb energy near walls is not exact");
+        }
+        self.params.set_boundary(&boundary);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.rebuild_neighbour_list(queue);
+        self.boundary = boundary;
+    }
+
+    pub fn thermostat(&self) -> &Thermostat {
+        &self.thermostat
+    }
+
+    /// switches the thermostat, the chain and the energy already exchanged with the bath are kept
+    pub fn set_thermostat(&mut self, queue: &Queue, thermostat: Thermostat) {
+        if thermostat.kind != ThermostatKind::Off && self.params.force_model == ForceModel::ParticleLife as u32 {
+            println!("warning: particle life has its own friction, the thermostat will fight it");
+        }
+        self.params.set_thermostat(&thermostat);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.thermostat = thermostat;
+    }
+
+    pub fn integrator(&self) -> &Integrator {
+        &self.integrator
+    }
+
+    /// switches the integrator, takes effect with the next substep
+    pub fn set_integrator(&mut self, queue: &Queue, integrator: Integrator) {
+        if integrator.kind == IntegratorKind::Respa && self.params.electrostatics == 0 {
+            println!("warning: r-RESPA only splits off the reciprocal Ewald forces, without them it is velocity Verlet");
+        }
+        if integrator.kind == IntegratorKind::Respa && !self.substeps.is_multiple_of(integrator.respa_steps) {
+            println!(
+                "warning: the {} substeps of a frame are no multiple of {} inner steps, keeping the integrator",
+                self.substeps, integrator.respa_steps
+            );
+            return;
+        }
+        self.integrator = integrator;
+        // the minimizer switches back to it once it is done
+        if self.minimizer.is_none() {
+            self.params.set_integrator(&integrator);
+            queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        }
+    }
+
+    /// largest pair force in mU / nm, zero if the forces are not capped
+    pub fn max_force(&self) -> f32 {
+        self.params.max_force
+    }
+
+    /// caps every pair force at `max_force` to relax bad starting structures, zero turns it off.
+    /// The energies are left alone, so the capped forces are no longer their derivatives.
+    pub fn set_max_force(&mut self, queue: &Queue, max_force: f32) {
+        self.params.set_max_force(max_force);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+    }
+
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+
+    /// sets the tolerance and iteration limit of SHAKE and RATTLE, the constraints themselves
+    /// are part of the topology
+    pub fn set_constraints(&mut self, queue: &Queue, constraints: Constraints) {
+        self.params.set_constraints(&constraints, self.topology.constraints.len() as u32);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.constraints = constraints;
+    }
+
+    pub fn timestep(&self) -> &Timestep {
+        &self.timestep
+    }
+
+    /// switches between a fixed and an adaptive timestep, the simulated time keeps running
+    pub fn set_timestep(&mut self, queue: &Queue, timestep: Timestep) {
+        let state = TimestepState::new(&timestep, self.force_field.min_sigma());
+        let offset = TimestepState::CONTROL_OFFSET;
+        queue.write_buffer(&self.timestep_buffer, offset, &bytemuck::bytes_of(&state)[offset as usize..]);
+        self.params.set_dt(timestep.dt, self.substeps);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.timestep = timestep;
+    }
+
+    pub fn barostat(&self) -> &Barostat {
+        &self.barostat
+    }
+
+    /// switches the barostat, the box keeps its current size
+    pub fn set_barostat(&mut self, barostat: Barostat) {
+        if barostat.kind != BarostatKind::Off {
+            if !self.boundary.is_periodic() {
+                println!("warning: the barostat needs a periodic box, the walls do not move with it");
+            }
+            if barostat.kind == BarostatKind::MonteCarlo && self.thermostat.kind == ThermostatKind::Off {
+                println!("warning: Monte-Carlo volume moves need a thermostat, they use its target temperature");
+            }
+        }
+        self.barostat = barostat;
+        self.barostat_moves = [0; 2];
+    }
+
+    /// Takes over a new box and rebuilds the bin grid, the k vectors and the tail correction,
+    /// the particles are moved by `encode_box_scaling`. Returns false if the box would get
+    /// too small for the bins.
+    fn set_box(&mut self, queue: &Queue, simulation_box: SimulationBox) -> bool {
+        if simulation_box.heights().iter().any(|h| *h < 3.0 * self.params.bin_size()) {
+            println!("warning: the box {} is too small for the bin grid, the barostat keeps the old one", simulation_box);
+            return false;
+        }
+        self.params.set_box(&simulation_box, self.max_bin_counts);
+        let kvectors = self.ewald.kvectors(&simulation_box);
+        if self.ewald.enabled {
+            self.params.kvector_count = kvectors.len() as u32;
+            queue.write_buffer(&self.kvectors_buffer, 0, bytemuck::cast_slice(kvectors.as_slice()));
+        }
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        self.rebuild_neighbour_list(queue);
+        self.tail_correction = TailCorrection::new(&self.force_field, &self.type_counts, simulation_box.volume(), self.cutoff.r_cut);
+        self.simulation_box = simulation_box;
+        true
+    }
+
+    /// only one scaling per submit, the factors live in a single uniform buffer
+    fn encode_box_scaling(&self, encoder: &mut CommandEncoder, queue: &Queue, scale: [f32; 3]) {
+        queue.write_buffer(&self.barostat_scale_buffer, 0, bytemuck::cast_slice(&[scale[0], scale[1], scale[2], 1.0]));
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Barostat Pass"),
+        });
+        compute_pass.set_pipeline(&self.barostat_pipeline);
+        compute_pass.set_bind_group(0, &self.barostat_bind_groups[self.current_buffer], &[]);
+        compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+    }
+
+    /// scales the box once per frame from the pressure of the last frame
+    fn berendsen_step(&mut self, encoder: &mut CommandEncoder, queue: &Queue) {
+        let stats = *self.stats.lock().unwrap();
+        if stats.iteration == 0 {
+            return;
+        }
+        let scale = self.barostat.berendsen_scale(stats.pressure_tensor, self.params.dt * self.substeps as f32);
+        if self.set_box(queue, self.simulation_box.scaled(scale)) {
+            self.encode_box_scaling(encoder, queue, scale);
+        }
+    }
+
+    /// Tries a random volume change and keeps it with the NPT acceptance probability.
+    /// Evaluates the energy of the trial box right away, so this blocks until the gpu is done.
+    fn monte_carlo_move(&mut self, device: &Device, queue: &Queue) {
+        let old = self.read_stat(device, queue);
+        let old_box = self.simulation_box;
+        let old_tail = self.tail_correction.energy;
+        let scale = self.barostat.trial_scale(self.barostat_rng.gen(), self.barostat_rng.gen());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Monte-Carlo Barostat Encoder"),
+        });
+        let current = &self.particle_buffers[self.current_buffer];
+        encoder.copy_buffer_to_buffer(current, 0, &self.particle_backup_buffer, 0, current.size());
+        if !self.set_box(queue, old_box.scaled(scale)) {
+            return;
+        }
+        self.encode_box_scaling(&mut encoder, queue, scale);
+        self.encode_evaluation(&mut encoder, queue);
+        queue.submit(Some(encoder.finish()));
+        // dt was set to zero for the evaluation
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        let new = self.read_stat(device, queue);
+
+        let potential = |stat: &Stat, tail: f64| (stat.PE + stat.PE_real + stat.PE_recip + stat.PE_bonded) as f64 + tail;
+        let delta_energy = potential(&new, self.tail_correction.energy) - potential(&old, old_tail);
+        let probability = self.barostat.accept_probability(
+            delta_energy,
+            old_box.volume(),
+            self.simulation_box.volume(),
+            self.params.N,
+            self.thermostat.temperature,
+        );
+        let accepted = self.barostat_rng.gen::<f64>() < probability;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Monte-Carlo Barostat Encoder"),
+        });
+        let current = &self.particle_buffers[self.current_buffer];
+        if accepted {
+            // the evaluation wrote the scaled particles with their new forces into the other buffer
+            let other = &self.particle_buffers[(self.current_buffer + 1) % 2];
+            encoder.copy_buffer_to_buffer(other, 0, current, 0, current.size());
+        } else {
+            encoder.copy_buffer_to_buffer(&self.particle_backup_buffer, 0, current, 0, current.size());
+            self.set_box(queue, old_box);
+        }
+        queue.submit(Some(encoder.finish()));
+        self.adapt_max_strain(accepted);
+    }
+
+    /// Evaluates forces and energies of the current particles without moving them, the result
+    /// goes to the other particle buffer. Sets dt to zero, it has to be written back after the submit.
+    fn encode_evaluation(&self, encoder: &mut CommandEncoder, queue: &Queue) {
+        let mut params = self.params;
+        params.dt = 0.0;
+        params.integrator = IntegratorKind::VelocityVerlet as u32;
+        queue.write_buffer(&self.params_buffer, 0, params.serialize());
+        // the shaders take dt from the timestep buffer, it is restored after the evaluation
+        encoder.copy_buffer_to_buffer(&self.timestep_buffer, 0, &self.timestep_backup_buffer, 0, self.timestep_buffer.size());
+        encoder.clear_buffer(&self.timestep_buffer, TimestepState::CONTROL_OFFSET, wgpu::BufferSize::new(4));
+        let current = self.current_buffer;
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Evaluation Pass"),
+        });
+        let passes = Integrator::default().passes(
+            0,
+            self.params.electrostatics == 1,
+            !self.topology.is_empty(),
+            !self.topology.constraints.is_empty(),
+        );
+        for pass in passes.into_iter().filter(|pass| !matches!(pass, Pass::Drift | Pass::Shake)) {
+            self.encode_pass(&mut compute_pass, pass, current);
+        }
+        self.encode_stats_reduction(&mut compute_pass);
+        drop(compute_pass);
+        encoder.copy_buffer_to_buffer(&self.timestep_backup_buffer, 0, &self.timestep_buffer, 0, self.timestep_buffer.size());
+    }
+
+    /// sums the stats of all particles into the final stats
+    fn encode_stats_reduction<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>) {
+        let iterations = (u32::BITS - self.params.N.leading_zeros()) as usize;
+        for (i, index) in (0..iterations).rev().enumerate() {
+            compute_pass.set_pipeline(&self.stats_pipeline);
+            compute_pass.set_bind_group(0, &self.stats_bind_groups[(i + 1) % 2], &[]);
+            let work_group_count = ((1 << (index + 1)) as f32 / 64.0).ceil() as u32;
+            compute_pass.dispatch_workgroups(work_group_count, 1, 1);
+        }
+    }
+
+    /// records one pass of a substep, `group` is the parity of the substep
+    fn encode_pass<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, pass: Pass, group: usize) {
+        match pass {
+            // runs before the drift, so the newest velocities are in the buffer the drift reads
+            Pass::SlowKick => {
+                compute_pass.set_pipeline(&self.slow_kick_pipeline);
+                compute_pass.set_bind_group(0, &self.particle_bind_groups[(group + 1) % 2], &[]);
+            }
+            // with a Verlet list the bins are only rebuilt together with the list, before the force pass
+            Pass::EmptyBins => {
+                if self.list_capacity == 0 {
+                    self.encode_empty_bins(compute_pass);
+                }
+                return;
+            }
+            Pass::Binning => {
+                if self.list_capacity == 0 {
+                    self.encode_binning(compute_pass, group);
+                }
+                return;
+            }
+            Pass::Drift => {
+                compute_pass.set_pipeline(&self.verlet_pipeline);
+                compute_pass.set_bind_group(0, &self.verlet_bind_groups[group], &[]);
+            }
+            // one thread per cluster of constraints
+            Pass::Shake | Pass::Rattle => {
+                let pipeline = if pass == Pass::Shake { &self.shake_pipeline } else { &self.rattle_pipeline };
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.set_bind_group(0, &self.constraint_bind_groups[group], &[]);
+                compute_pass.dispatch_workgroups((self.constraint_cluster_count as f32 / 64.0).ceil() as u32, 1, 1);
+                return;
+            }
+            // the slow structure factors use the positions of the other buffer
+            Pass::Ewald | Pass::SlowEwald => {
+                let group = if pass == Pass::Ewald { group } else { (group + 1) % 2 };
+                compute_pass.set_pipeline(&self.ewald_pipeline);
+                compute_pass.set_bind_group(0, &self.ewald_bind_groups[group], &[]);
+                compute_pass.dispatch_workgroups((self.params.kvector_count as f32 / 64.0).ceil() as u32, 1, 1);
+                return;
+            }
+            Pass::Bonded => {
+                compute_pass.set_pipeline(&self.bonded_pipeline);
+                compute_pass.set_bind_group(0, &self.bonded_bind_groups[group], &[]);
+            }
+            Pass::Force => {
+                if self.list_capacity > 0 {
+                    self.encode_neighbour_list(compute_pass, group);
+                }
+                compute_pass.set_pipeline(&self.compute_pipeline);
+                compute_pass.set_bind_group(0, &self.particle_bind_groups[group], &[]);
+            }
+            Pass::SlowForce => {
+                compute_pass.set_pipeline(&self.slow_pipeline);
+                compute_pass.set_bind_group(0, &self.particle_bind_groups[group], &[]);
+            }
+            Pass::Minimize => {
+                compute_pass.set_pipeline(&self.minimizer_reduce_pipeline);
+                compute_pass.set_bind_group(0, &self.minimizer_bind_groups[group], &[]);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+                compute_pass.set_pipeline(&self.minimizer_step_pipeline);
+            }
+        }
+        compute_pass.dispatch_workgroups(self.work_group_count, 1, 1);
+    }
+
+    fn encode_empty_bins<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>) {
+        compute_pass.set_pipeline(&self.empty_bins_pipeline);
+        compute_pass.set_bind_group(0, &self.empty_bins_bind_group, &[]);
+        self.dispatch(compute_pass, Workgroups::Cells);
+    }
+
+    /// count, scan the counts into the start of every bin, then scatter the particles
+    fn encode_binning<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, group: usize) {
+        compute_pass.set_pipeline(&self.binning_pipeline);
+        compute_pass.set_bind_group(0, &self.bining_bind_groups[group], &[]);
+        self.dispatch(compute_pass, Workgroups::Particles);
+        compute_pass.set_bind_group(0, &self.scan_bind_group, &[]);
+        compute_pass.set_pipeline(&self.scan_blocks_pipeline);
+        self.dispatch(compute_pass, Workgroups::Cells);
+        compute_pass.set_pipeline(&self.scan_sums_pipeline);
+        self.dispatch(compute_pass, Workgroups::Single);
+        compute_pass.set_pipeline(&self.add_offsets_pipeline);
+        self.dispatch(compute_pass, Workgroups::Cells);
+        compute_pass.set_pipeline(&self.scatter_pipeline);
+        compute_pass.set_bind_group(0, &self.bining_bind_groups[group], &[]);
+        self.dispatch(compute_pass, Workgroups::Particles);
+    }
+
+    /// Checks how far the particles moved since the last build of the Verlet list. Only if
+    /// one moved more than half the skin, the bins and the list are rebuilt; the check writes
+    /// the workgroup counts of those passes, so nothing has to be read back.
+    fn encode_neighbour_list<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, group: usize) {
+        compute_pass.set_pipeline(&self.list_check_pipeline);
+        compute_pass.set_bind_group(0, &self.list_check_bind_groups[group], &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        self.encode_empty_bins(compute_pass);
+        self.encode_binning(compute_pass, group);
+        compute_pass.set_pipeline(&self.list_build_pipeline);
+        compute_pass.set_bind_group(0, &self.list_build_bind_groups[group], &[]);
+        self.dispatch(compute_pass, Workgroups::Particles);
+    }
+
+    /// dispatches directly without a Verlet list, with one indirectly from the counts of the last check
+    fn dispatch<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, workgroups: Workgroups) {
+        if self.list_capacity > 0 {
+            let offset = match workgroups {
+                Workgroups::Cells => NeighbourListState::CELLS_OFFSET,
+                Workgroups::Particles => NeighbourListState::PARTICLES_OFFSET,
+                Workgroups::Single => NeighbourListState::SINGLE_OFFSET,
+            };
+            compute_pass.dispatch_workgroups_indirect(&self.list_state_buffer, offset);
+            return;
+        }
+        let count = match workgroups {
+            Workgroups::Cells => (self.params.bin_total() + 1).div_ceil(SCAN_BLOCK),
+            Workgroups::Particles => self.work_group_count,
+            Workgroups::Single => 1,
+        };
+        compute_pass.dispatch_workgroups(count, 1, 1);
+    }
+
+    /// keeps the acceptance rate of the Monte-Carlo moves between 25% and 75%
+    fn adapt_max_strain(&mut self, accepted: bool) {
+        self.barostat_moves[0] += 1;
+        self.barostat_moves[1] += accepted as u32;
+        if self.barostat_moves[0] < 10 {
+            return;
+        }
+        let rate = self.barostat_moves[1] as f32 / self.barostat_moves[0] as f32;
+        if rate < 0.25 {
+            self.barostat.max_strain *= 0.9;
+        } else if rate > 0.75 {
+            self.barostat.max_strain = (self.barostat.max_strain * 1.1).min(0.1);
+        }
+        self.barostat_moves = [0; 2];
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
     }
 
-    pub fn get_particle_buffer(&self, frame: u32) -> &wgpu::Buffer {
-        &self.particle_buffers[frame as usize % 2]
+    pub fn tail_correction(&self) -> TailCorrection {
+        self.tail_correction
+    }
+
+    pub fn force_model(&self) -> ForceModel {
+        ForceModel::from_u32(self.params.force_model)
+    }
+
+    pub fn set_force_model(&mut self, queue: &Queue, force_model: ForceModel) {
+        if force_model == ForceModel::Tabulated && !self.force_field.has_tables() {
+            println!("warning: the force field has no pair tables, keeping the {:?} model", ForceModel::from_u32(self.params.force_model));
+            return;
+        }
+        self.params.force_model = force_model as u32;
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+    }
+
+    pub fn particle_life(&self) -> &ParticleLife {
+        &self.life
+    }
+
+    /// in nm, the largest particle life radius the box has room for three bins of
+    pub fn max_life_radius(&self) -> f32 {
+        let height = self.simulation_box.heights().into_iter().fold(f32::INFINITY, f32::min);
+        height / 3.0 - self.params.verlet_skin
+    }
+
+    /// uploads a new attraction matrix and the radius, force and friction of the particle life
+    /// model, the bins follow the radius. A radius beyond `max_life_radius` is refused.
+    pub fn set_particle_life(&mut self, queue: &Queue, mut life: ParticleLife) {
+        assert_eq!(life.size(), self.params.type_count);
+        if life.radius > self.max_life_radius() {
+            println!(
+                "warning: the box {} is too small for the bins of a {} nm particle life radius, keeping {} nm",
+                self.simulation_box, life.radius, self.life.radius
+            );
+            life.radius = self.life.radius;
+        }
+        self.params.set_particle_life(&life);
+        self.params.set_box(&self.simulation_box, self.max_bin_counts);
+        queue.write_buffer(&self.params_buffer, 0, self.params.serialize());
+        queue.write_buffer(&self.life_matrix_buffer, 0, life.serialize());
+        // the list reaches the particle life radius
+        self.rebuild_neighbour_list(queue);
+        self.life = life;
+    }
+
+    /// the buffer the last frame wrote, the one to draw
+    pub fn get_particle_buffer(&self) -> &wgpu::Buffer {
+        &self.particle_buffers[self.current_buffer]
     }
 
     pub fn print_stats(&self) {
@@ -491,35 +2145,195 @@ impl ComputeSet {
                 let stats = self.stats.clone();
                 let stats_history = self.stats_history.clone();
                 let itters = self.total_iterations as usize;
+                let tail_correction = self.tail_correction;
+                let volume = self.simulation_box.volume();
+                let constraint_count = self.topology.constraints.len();
                 move |r| {
                     let data = r.unwrap();
                     let mut stats_ = stats.lock().unwrap();
                     let mut stats_history_ = stats_history.lock().unwrap();
-                    let stat = bytemuck::cast_slice::<u8, Stat>(&data[..])[0] as Stat;
-                    stats_.KE = stat.KE / eV_over_mU;
-                    stats_.PE = stat.PE / eV_over_mU;
-                    stats_.iteration = itters;
+                    let stat: Stat = bytemuck::pod_read_unaligned(&data[..Stat::size() as usize]);
+                    let list_offset = Stat::size() as usize + std::mem::size_of::<TimestepState>();
+                    let timestep: TimestepState = bytemuck::pod_read_unaligned(&data[Stat::size() as usize..list_offset]);
+                    let list: NeighbourListState = bytemuck::pod_read_unaligned(&data[list_offset..]);
+                    *stats_ = Stats {
+                        iteration: itters,
+                        time: timestep.elapsed(),
+                        dt: timestep.dt,
+                        v_max: timestep.v_max,
+                        a_max: timestep.a_max,
+                        rebuilds: list.rebuilds,
+                        max_neighbours: list.max_neighbours,
+                        ..Stats::from_stat(&stat, volume, &tail_correction, constraint_count)
+                    };
                     stats_history_.add(*stats_);
                 }
             }
         );
     }
 
+    /// replaces the particles in both ping-pong buffers, e.g. to start from a saved configuration
+    pub fn set_particles(&mut self, queue: &Queue, particles: &[Particle]) {
+        assert_eq!(particles.len(), self.params.N as usize, "expected one entry per particle");
+        for buffer in self.particle_buffers.iter() {
+            queue.write_buffer(buffer, 0, Particle::serialize_all(particles));
+        }
+        // they come in the order of their identities
+        let order: Vec<u32> = (0..self.params.N).collect();
+        queue.write_buffer(&self.order_buffer, 0, bytemuck::cast_slice(&order));
+        self.rebuild_neighbour_list(queue);
+    }
+
+    /// reads the newest particle data back from the gpu, blocks until the copy is done. The
+    /// particles come in the order they were created in, even after the buffers were sorted.
+    pub fn read_particles(&self, device: &Device, queue: &Queue) -> Vec<Particle> {
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(
+            device,
+            queue,
+            &self.particle_buffers[self.current_buffer].slice(..),
+            move |r| {
+                let particles = r.map(|data| bytemuck::cast_slice::<u8, Particle>(&data[..]).to_vec());
+                sender.send(particles).unwrap();
+            },
+        );
+        device.poll(wgpu::Maintain::Wait);
+        let particles = receiver.recv().unwrap().expect("could not read the particle buffer");
+        if self.reorder_interval == 0 {
+            return particles;
+        }
+
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(device, queue, &self.order_buffer.slice(..), move |r| {
+            let order = r.map(|data| bytemuck::cast_slice::<u8, u32>(&data[..]).to_vec());
+            sender.send(order).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        let order = receiver.recv().unwrap().expect("could not read the order buffer");
+        let mut unsorted = particles.clone();
+        for (particle, identity) in particles.into_iter().zip(order) {
+            unsorted[identity as usize] = particle;
+        }
+        unsorted
+    }
+
+    /// reads the reduced stats of the newest configuration (in mU), blocks until the copy is done
+    pub fn read_stat(&self, device: &Device, queue: &Queue) -> Stat {
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(device, queue, &self.stats_final_buffer.slice(..), move |r| {
+            let stat = r.map(|data| bytemuck::pod_read_unaligned::<Stat>(&data[..Stat::size() as usize]));
+            sender.send(stat).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().expect("could not read the stats buffer")
+    }
+
+    /// like `download_stats`, but blocks until the copy is done and leaves the history alone
+    pub fn read_stats(&self, device: &Device, queue: &Queue) -> Stats {
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(device, queue, &self.stats_final_buffer.slice(..), move |r| {
+            let data = r.map(|data| {
+                let stat: Stat = bytemuck::pod_read_unaligned(&data[..Stat::size() as usize]);
+                let list_offset = Stat::size() as usize + std::mem::size_of::<TimestepState>();
+                let timestep: TimestepState = bytemuck::pod_read_unaligned(&data[Stat::size() as usize..list_offset]);
+                let list: NeighbourListState = bytemuck::pod_read_unaligned(&data[list_offset..]);
+                (stat, timestep, list)
+            });
+            sender.send(data).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        let (stat, timestep, list) = receiver.recv().unwrap().expect("could not read the stats buffer");
+        Stats {
+            iteration: self.total_iterations as usize,
+            time: timestep.elapsed(),
+            dt: timestep.dt,
+            v_max: timestep.v_max,
+            a_max: timestep.a_max,
+            rebuilds: list.rebuilds,
+            max_neighbours: list.max_neighbours,
+            ..Stats::from_stat(&stat, self.simulation_box.volume(), &self.tail_correction, self.topology.constraints.len())
+        }
+    }
+
+    /// 3N minus one for every constraint
+    pub fn degrees_of_freedom(&self) -> f32 {
+        3.0 * self.params.N as f32 - self.params.constraint_count as f32
+    }
+
+    /// Compares the coulomb energy of the gpu with the cpu ewald sum and a direct sum over
+    /// `shells` periodic images. Both cpu sums are O(N^2), so only use this on small systems.
+    /// Returns (gpu, cpu ewald, cpu direct sum), all in mU.
+    pub fn validate_electrostatics(&self, device: &Device, queue: &Queue, shells: i32) -> (CoulombEnergy, CoulombEnergy, f64) {
+        let particles = self.read_particles(device, queue);
+        let stat = self.read_stat(device, queue);
+        let positions = particles.iter().map(|p| p.position).collect::<Vec<_>>();
+        let charges = particles
+            .iter()
+            .map(|p| self.force_field.atom(p.type_ as u32).charge as f32)
+            .collect::<Vec<_>>();
+
+        let gpu = CoulombEnergy {
+            real: stat.PE_real as f64,
+            recip: stat.PE_recip as f64,
+        };
+        let cpu = electrostatics::ewald_energy(&positions, &charges, &self.simulation_box, self.params.neghborhood_size, &self.ewald);
+        let direct = electrostatics::direct_sum_energy(&positions, &charges, &self.simulation_box, shells);
+        println!(
+            "coulomb energy (mU) gpu: {:?} ({}), cpu ewald: {:?} ({}), cpu direct sum: {}",
+            gpu,
+            gpu.total(),
+            cpu,
+            cpu.total(),
+            direct
+        );
+        (gpu, cpu, direct)
+    }
+
+    /// Compares the bonded forces and energy of bonded.wgsl with `Topology::forces` for the
+    /// newest configuration. Returns the energies of the gpu and the cpu in mU and the largest
+    /// deviation of a force relative to the largest force.
+    pub fn validate_bonded(&self, device: &Device, queue: &Queue) -> (f64, f64, f64) {
+        let particles = self.read_particles(device, queue);
+        let (sender, receiver) = channel();
+        DownloadBuffer::read_buffer(device, queue, &self.bonded_buffer.slice(..), move |r| {
+            // force, energy and virial with the padding of the wgsl struct
+            let bonded = r.map(|data| bytemuck::cast_slice::<u8, [f32; 8]>(&data[..]).to_vec());
+            sender.send(bonded).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        let bonded = receiver.recv().unwrap().expect("could not read the bonded buffer");
+
+        let positions = particles.iter().map(|p| p.position).collect::<Vec<_>>();
+        let (forces, cpu) = self.topology.forces(&positions, &self.simulation_box);
+        let gpu = bonded.iter().map(|b| b[3] as f64).sum::<f64>();
+        let deviation = bonded
+            .iter()
+            .zip(&forces)
+            .map(|(b, f)| (0..3).map(|x| (b[x] as f64 - f[x]).abs()).fold(0.0, f64::max))
+            .fold(0.0, f64::max)
+            / forces.iter().flatten().fold(f64::EPSILON, |a, f| a.max(f.abs()));
+        println!("bonded energy (mU) gpu: {}, cpu: {}, largest relative force deviation: {}", gpu, cpu, deviation);
+        (gpu, cpu, deviation)
+    }
+
     pub fn get_history(&self) -> StatHistory {
         // self.stats_history.clone().lock().unwrap() -> std::sync::MutexGuard<'_, StatHistory>
         self.stats_history.clone().lock().unwrap().deref().clone()
     }
 
     pub async fn debug(&mut self, device: &Device, queue: &Queue) {
-        let elapsed = self.total_iterations as f32 * DT;
+        let elapsed = self.stats.lock().unwrap().time;
         print!("Time elapsed: {:.2} ps - iterations: {}k - ", elapsed, self.total_iterations / 1000);
 
 
         wgpu::util::DownloadBuffer::read_buffer(
             device,
             queue,
-            &self.bin_load_buffer.slice(..),
-            Self::print_data_load_buffer,
+            &self.cell_start_buffer.slice(..),
+            {
+                let bin_total = self.params.bin_total() as usize;
+                move |r| Self::print_data_load_buffer(r, bin_total)
+            },
         );
         // wgpu::util::DownloadBuffer::read_buffer(
         //     device,
@@ -527,19 +2341,56 @@ impl ComputeSet {
         //     &self.energy_final_buffer.slice(..),
         //     Self::print_energy,
         // );
-        println!("Stats: {:?}", self.stats.lock().unwrap());
+        let stats = *self.stats.lock().unwrap();
+        println!("Stats: {:?}", stats);
+        if stats.capped > 0 {
+            println!("warning: {} pair forces were capped at {} mU/nm in the last step", stats.capped, self.params.max_force);
+        }
+        if stats.rebuilds > 0 {
+            println!(
+                "Verlet list: rebuilt every {:.1} substeps, at most {} of {} neighbours per particle",
+                self.total_iterations as f32 / stats.rebuilds as f32,
+                stats.max_neighbours,
+                self.list_capacity
+            );
+        }
+        if stats.unconverged > 0 {
+            println!(
+                "warning: SHAKE/RATTLE did not converge on {} constraint clusters within {} iterations, rms constraint error: {}",
+                stats.unconverged, self.constraints.max_iterations, stats.constraint_error
+            );
+        }
+    }
+
+    pub fn output(&self) -> &OutputConfig {
+        &self.output
+    }
+
+    /// in nm, the thickness of the bins and the farthest the neighbour search looks
+    pub fn bin_size(&self) -> f32 {
+        self.params.bin_size()
+    }
+
+    /// the stats of the last downloaded frame
+    pub fn stats(&self) -> Stats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// temperature of the last downloaded frame in K
+    pub fn temperature(&self) -> f32 {
+        self.stats_history.lock().unwrap().temperature()
+    }
+
+    /// writes the stats history to csv, returns the file name
+    pub fn save_stats(&self) -> Result<String, Box<dyn Error>> {
+        let file_name = self.output.stats_file_name();
+        self.stats_history.lock().unwrap().save(file_name.as_str())?;
+        Ok(file_name)
     }
 
     pub fn write_stats(&self) {
-        // write stats to csv file
-        let date_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let file_name = format!("stats_{}.csv", date_time);
-
-        match self.stats_history.lock().unwrap().save(file_name.as_str()) {
-            Ok(_) => {
+        match self.save_stats() {
+            Ok(file_name) => {
                 println!("Stats saved to file: {}", file_name);
             }
             Err(e) => {
@@ -579,8 +2430,8 @@ impl ComputeSet {
             Ok(buffer) => {
                 let mut tot_energy = 0.0;
                 let energies = bytemuck::cast_slice::<u8, f32>(&buffer[..]);
-                for i in 0..NUMBER_PARTICLES as usize {
-                    let mut energy = energies[i] as f32;
+                for (i, energy) in energies.iter().enumerate() {
+                    let mut energy = *energy;
                     energy *= eV_over_mU;
                     tot_energy += energy;
                     println!("energy {}: {energy} eV", i);
@@ -608,28 +2459,13 @@ impl ComputeSet {
         }
     }
 
-    fn print_data_load_buffer(r: Result<DownloadBuffer, BufferAsyncError>) {
+    fn print_data_load_buffer(r: Result<DownloadBuffer, BufferAsyncError>, bin_total: usize) {
         match r {
             Ok(buffer) => {
-                let mut maxim = 0;
+                // the bins start where the one before ends
                 let data = bytemuck::cast_slice::<u8, u32>(&buffer[..]);
-                // println!("load buffer:");
-                for y in 0..BIN_COUNT {
-                    // print!("y({}): ", y);
-                    for x in 0..BIN_COUNT {
-                        for z in 0..BIN_COUNT {
-                            let i = x + y * BIN_COUNT + z * BIN_COUNT * BIN_COUNT;
-                            let d = data[i as usize];
-                            if d > maxim {
-                                maxim = d;
-                            }
-                        }
-                    }
-                }
+                let maxim = data[..=bin_total].windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0);
                 println!("max particles per bin: {}", maxim);
-                if maxim > BIN_DEPTH {
-                    println!("max particles per bin exceeded: {}", maxim);
-                }
             }
             Err(e) => {
                 println!("error: {:?}", e);
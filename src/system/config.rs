@@ -0,0 +1,876 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::system::barostat::{Barostat, BarostatKind, PressureCoupling};
+use crate::system::boundary::{Boundary, BoundaryMode};
+use crate::system::consts::*;
+use crate::system::cutoff::{Cutoff, CutoffScheme};
+use crate::system::device::GraphicsApi;
+use crate::system::force_field::ForceField;
+use crate::system::integrator::{Integrator, IntegratorKind};
+use crate::system::life::{ForceModel, ParticleLife};
+use crate::system::minimizer::{Minimizer, MinimizerKind};
+use crate::system::neighbour_list;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::tables::PairTable;
+use crate::system::thermostat::{Thermostat, ThermostatKind, MAX_CHAIN_LENGTH};
+use crate::system::timestep::Timestep;
+use crate::system::topology::{AngleType, BondStyle, BondType, DihedralType, Topology};
+
+/// Everything that describes a run, read from a TOML or JSON file and the command line.
+///
+/// Every field has a default (the values in `consts.rs`), so a file only lists what it
+/// changes. `validate` checks the values before `ComputeSet::new` builds the pipelines.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub system: SystemConfig,
+    pub topology: TopologyConfig,
+    pub force_field: ForceFieldConfig,
+    pub integrator: IntegratorConfig,
+    pub timestep: TimestepConfig,
+    pub minimizer: MinimizerConfig,
+    pub thermostat: ThermostatConfig,
+    pub boundary: BoundaryConfig,
+    pub barostat: BarostatConfig,
+    pub output: OutputConfig,
+    pub gpu: GpuConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemConfig {
+    pub particles: u32,
+    pub box_size: Option<f32>, // in nm, edge of the cubic box, by default it fits the initial grid
+    pub box_lengths: Option<[f32; 3]>, // in nm, lx, ly and lz of an orthorhombic or triclinic box instead of the cube
+    pub box_tilt: [f32; 3],    // in nm, the tilt factors xy, xz and yz, see `SimulationBox`
+    pub temperature: f32,      // in K, of the initial velocities
+    pub spacing: f32,          // in nm, half the distance of two grid points of the initial configuration
+    pub margin: f32,           // in nm, between the box walls and the grid
+    pub chain_length: u32,     // particles per molecule of the generated chains, 1 gives free atoms, see `TopologyConfig`
+    pub constrain_bonds: bool, // the bonds become constraints, of the chains and of an explicit topology
+    pub angle_stiffness: f32,  // in epsilon / rad^2 of the first atom type, 0 gives the chains no angle terms
+    pub angle: f32,            // in degrees, rest angle of the angle terms
+    pub dihedral_stiffness: f32, // in epsilon of the first atom type, 0 gives the chains no dihedral terms
+    pub dihedral_phase: f32,   // in degrees
+    pub dihedral_multiplicity: u32,
+}
+
+impl Default for SystemConfig {
+    fn default() -> Self {
+        Self {
+            particles: NUMBER_PARTICLES,
+            box_size: None,
+            box_lengths: None,
+            box_tilt: [0.0; 3],
+            temperature: INIT_TEMPERATURE,
+            spacing: INIT_SPACING,
+            margin: EXES_SPACING,
+            chain_length: CHAIN_LENGTH,
+            constrain_bonds: CONSTRAIN_BONDS,
+            angle_stiffness: 0.0,
+            angle: 120.0,
+            dihedral_stiffness: 0.0,
+            dihedral_phase: 0.0,
+            dihedral_multiplicity: 3,
+        }
+    }
+}
+
+/// An explicit list of bonded terms, in the units of `Topology`. With no terms at all the
+/// chains of `system.chain_length` are generated instead.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopologyConfig {
+    pub bond_types: Vec<BondTypeConfig>,
+    pub angle_types: Vec<AngleTypeConfig>,
+    pub dihedral_types: Vec<DihedralTypeConfig>,
+    pub bonds: Vec<[u32; 3]>,     // i, j and the index into bond_types
+    pub angles: Vec<[u32; 4]>,    // i, j, k with j at the apex and the index into angle_types
+    pub dihedrals: Vec<[u32; 5]>, // i, j, k, l and the index into dihedral_types
+}
+
+impl TopologyConfig {
+    pub fn is_explicit(&self) -> bool {
+        !(self.bonds.is_empty() && self.angles.is_empty() && self.dihedrals.is_empty())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BondTypeConfig {
+    pub style: BondStyle,
+    pub k: f32,       // in mU / nm^2
+    pub r0: f32,      // in nm, rest length (harmonic) or maximum extension (FENE)
+    pub epsilon: f32, // in mU, WCA part of FENE
+    pub sigma: f32,   // in nm, WCA part of FENE
+}
+
+impl Default for BondTypeConfig {
+    fn default() -> Self {
+        Self {
+            style: BondStyle::Harmonic,
+            k: 0.0,
+            r0: 0.0,
+            epsilon: 0.0,
+            sigma: 0.0,
+        }
+    }
+}
+
+impl BondTypeConfig {
+    pub fn bond_type(&self) -> BondType {
+        match self.style {
+            BondStyle::Harmonic => BondType::harmonic(self.k, self.r0),
+            BondStyle::Fene => BondType::fene(self.k, self.r0, self.epsilon, self.sigma),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AngleTypeConfig {
+    pub k: f32,      // in mU / rad^2
+    pub theta0: f32, // in degrees
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DihedralTypeConfig {
+    pub k: f32,    // in mU
+    pub phi0: f32, // in degrees
+    pub n: u32,    // multiplicity
+}
+
+impl Default for DihedralTypeConfig {
+    fn default() -> Self {
+        Self { k: 0.0, phi0: 0.0, n: 1 }
+    }
+}
+
+/// The atom types `ForceField` comes with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForceFieldPreset {
+    /// four types of helium with a deep well
+    Default,
+    NobleGases,
+    SodiumChloride,
+}
+
+impl ForceFieldPreset {
+    pub fn build(&self) -> ForceField {
+        match self {
+            ForceFieldPreset::Default => ForceField::default(),
+            ForceFieldPreset::NobleGases => ForceField::noble_gases(),
+            ForceFieldPreset::SodiumChloride => ForceField::sodium_chloride(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForceFieldConfig {
+    pub preset: ForceFieldPreset,
+    pub model: ForceModel,
+    pub cutoff: f32, // in nm, the bins are at least twice as thick
+    pub cutoff_scheme: CutoffScheme,
+    pub r_switch: Option<f32>, // in nm, 0.9 * cutoff by default
+    pub ewald_tolerance: f32,  // relative error of the Ewald sum, only used with charges
+    pub max_force: f32,        // in mU / nm, zero turns the capping off
+    pub life_seed: u64,        // of the random particle life matrix
+    pub life_radius: f32,      // in nm, range of the particle life force, the bins grow with it
+    pub verlet_skin: f32,      // in nm, zero searches the bins every substep instead of keeping a Verlet list
+    pub tables: Vec<TableConfig>, // the pair potentials of the tabulated model
+}
+
+impl Default for ForceFieldConfig {
+    fn default() -> Self {
+        Self {
+            preset: ForceFieldPreset::Default,
+            model: ForceModel::LennardJones,
+            cutoff: NEIGHBORHOOD_SIZE,
+            cutoff_scheme: CutoffScheme::Shifted,
+            r_switch: None,
+            ewald_tolerance: 1e-4,
+            max_force: 0.0,
+            life_seed: 0,
+            life_radius: NEIGHBORHOOD_SIZE,
+            verlet_skin: 0.0,
+            tables: Vec::new(),
+        }
+    }
+}
+
+/// A tabulated pair potential from a file, see `PairTable` for the formats.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableConfig {
+    pub types: [u32; 2],         // the pair of atom types, the table applies to both orders
+    pub file: String,
+    pub keyword: Option<String>, // the section of a LAMMPS table file, without one the file has the simple r V F format
+    pub r_scale: f32,            // to nm, 0.1 for Angstrom
+    pub energy_scale: f32,       // to mU (kJ / mol), 4.184 for kcal / mol
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        Self {
+            types: [0, 0],
+            file: String::new(),
+            keyword: None,
+            r_scale: 1.0,
+            energy_scale: 1.0,
+        }
+    }
+}
+
+impl TableConfig {
+    pub fn load(&self) -> Result<PairTable, Box<dyn Error>> {
+        let table = match &self.keyword {
+            Some(keyword) => PairTable::load_lammps(&self.file, keyword)?,
+            None => PairTable::load(&self.file)?,
+        };
+        Ok(table.scaled(self.r_scale, self.energy_scale))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegratorConfig {
+    pub kind: IntegratorKind,
+    pub dt: f32,          // in ps
+    pub substeps: u32,    // per frame
+    pub respa_steps: u32, // inner steps per outer step, only used by r-RESPA
+}
+
+impl Default for IntegratorConfig {
+    fn default() -> Self {
+        Self {
+            kind: IntegratorKind::VelocityVerlet,
+            dt: DT,
+            substeps: ITERATIONS,
+            respa_steps: 4,
+        }
+    }
+}
+
+/// The adaptive timestep, see `Timestep`. It starts from `integrator.dt`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimestepConfig {
+    pub adaptive: bool,
+    pub dt_min: Option<f32>, // in ps, 0.01 * integrator.dt by default
+    pub dt_max: Option<f32>, // in ps, 2 * integrator.dt by default
+    pub displacement: f32,   // largest move in one substep, as a fraction of the smallest sigma
+    pub growth: f32,         // largest factor dt grows by from one substep to the next
+}
+
+impl Default for TimestepConfig {
+    fn default() -> Self {
+        let timestep = Timestep::default();
+        Self {
+            adaptive: false,
+            dt_min: None,
+            dt_max: None,
+            displacement: timestep.displacement,
+            growth: timestep.growth,
+        }
+    }
+}
+
+/// An energy minimization before the dynamics, see `Minimizer`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinimizerConfig {
+    pub kind: Option<MinimizerKind>, // none by default, the dynamics start right away
+    pub force_tolerance: f32,        // in mU / nm, done once the largest force is below
+    pub energy_tolerance: f32,       // done once the relative energy change of a step is below
+    pub max_steps: u32,
+    pub step: Option<f32>,     // steepest descent: first largest move in nm, FIRE: first dt in ps, by default that of the kind
+    pub step_max: Option<f32>, // upper bound of the step
+}
+
+impl Default for MinimizerConfig {
+    fn default() -> Self {
+        let minimizer = Minimizer::default();
+        Self {
+            kind: None,
+            force_tolerance: minimizer.force_tolerance,
+            energy_tolerance: minimizer.energy_tolerance,
+            max_steps: minimizer.max_steps,
+            step: None,
+            step_max: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThermostatConfig {
+    pub kind: ThermostatKind,
+    pub temperature: f32, // target temperature in K
+    pub tau: f32,         // coupling time in ps
+    pub chain_length: u32, // only used by Nosé–Hoover
+}
+
+impl Default for ThermostatConfig {
+    fn default() -> Self {
+        let thermostat = Thermostat::off();
+        Self {
+            kind: thermostat.kind,
+            temperature: thermostat.temperature,
+            tau: thermostat.tau,
+            chain_length: thermostat.chain_length,
+        }
+    }
+}
+
+/// The faces of the box, see `Boundary`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoundaryConfig {
+    pub modes: [BoundaryMode; 3], // x, y, z
+    pub wall_temperature: f32,    // in K, only used by thermal walls
+}
+
+impl Default for BoundaryConfig {
+    fn default() -> Self {
+        let boundary = Boundary::periodic();
+        Self {
+            modes: boundary.modes,
+            wall_temperature: boundary.wall_temperature,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BarostatConfig {
+    pub kind: BarostatKind,
+    pub coupling: PressureCoupling,
+    pub pressure: f32,        // target pressure in bar
+    pub tau: f32,             // coupling time in ps, Berendsen only
+    pub compressibility: f32, // in 1 / bar, Berendsen only
+    pub interval: u32,        // frames between two Monte-Carlo moves
+    pub max_strain: f32,      // largest change of ln(V) in one Monte-Carlo move at the start
+}
+
+impl Default for BarostatConfig {
+    fn default() -> Self {
+        let barostat = Barostat::off();
+        Self {
+            kind: barostat.kind,
+            coupling: barostat.coupling,
+            pressure: barostat.pressure,
+            tau: barostat.tau,
+            compressibility: barostat.compressibility,
+            interval: barostat.interval,
+            max_strain: barostat.max_strain,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub stats_file: Option<String>,      // written on exit, stats_<unix time>.csv by default
+    pub report_interval: u32,            // frames between two printed reports
+    pub trajectory_file: Option<String>, // extended XYZ, only written by the headless runner
+    pub trajectory_interval: u32,        // frames between two trajectory frames
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            stats_file: None,
+            report_interval: 60,
+            trajectory_file: None,
+            trajectory_interval: 10,
+        }
+    }
+}
+
+impl OutputConfig {
+    /// the configured stats file or stats_<unix time>.csv
+    pub fn stats_file_name(&self) -> String {
+        self.stats_file.clone().unwrap_or_else(|| {
+            let date_time = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            format!("stats_{}.csv", date_time)
+        })
+    }
+}
+
+/// Which adapter runs the pipelines, see `device::select_adapter`, and how the particles are
+/// laid out in its memory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpuConfig {
+    pub api: GraphicsApi,
+    pub adapter: Option<String>, // part of the adapter name, ignoring case, any adapter by default
+    pub fallback: bool,          // only use the software adapter
+    pub reorder_interval: u32,   // frames between two sorts of the particles along a Morton curve, 0 keeps their order
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            api: GraphicsApi::All,
+            adapter: None,
+            fallback: false,
+            reorder_interval: 0,
+        }
+    }
+}
+
+impl Config {
+    /// reads a TOML file, or a JSON file if the extension is `.json`
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let table = Self::read_table(path)?;
+        let config = toml::Value::Table(table).try_into::<Config>()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Builds the config from command line arguments:
+    /// `--config <file>` reads a file, `--set <section.key>=<value>` overrides single values
+    /// (the value is TOML, strings without quotes are fine). `--gpu-api`, `--adapter` and
+    /// `--fallback` are short for the keys of the gpu section. Later arguments win.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Box<dyn Error>> {
+        let mut table = toml::Table::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value_of = |flag: &str| args.next().ok_or_else(|| format!("{} expects a value", flag));
+            match arg.as_str() {
+                "--config" | "-c" => {
+                    let path = value_of(&arg)?;
+                    merge(&mut table, Self::read_table(Path::new(&path))?);
+                }
+                "--set" | "-s" => {
+                    let assignment = value_of(&arg)?;
+                    let (key, value) = assignment
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected <key>=<value>, got \"{}\"", assignment))?;
+                    set(&mut table, key.trim(), parse_value(value.trim()))?;
+                }
+                "--gpu-api" => set(&mut table, "gpu.api", toml::Value::String(value_of(&arg)?))?,
+                "--adapter" => set(&mut table, "gpu.adapter", toml::Value::String(value_of(&arg)?))?,
+                "--fallback" => set(&mut table, "gpu.fallback", toml::Value::Boolean(true))?,
+                _ => return Err(format!("unknown argument \"{}\"\n{}", arg, Self::USAGE).into()),
+            }
+        }
+        let config = toml::Value::Table(table).try_into::<Config>()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub const USAGE: &'static str = "usage: ParticleLife3D [--config <file.toml|file.json>] [--set <section.key>=<value>]... \
+[--gpu-api all|vulkan|gl|dx12|metal] [--adapter <name>] [--fallback] [--list-adapters]";
+
+    fn read_table(path: &Path) -> Result<toml::Table, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("can not read {}: {}", path.display(), e))?;
+        let table = if path.extension().is_some_and(|e| e == "json") {
+            let json: serde_json::Value = serde_json::from_str(&text)?;
+            match toml::Value::try_from(json)? {
+                toml::Value::Table(table) => table,
+                _ => return Err(format!("{} does not contain an object", path.display()).into()),
+            }
+        } else {
+            toml::from_str(&text)?
+        };
+        Ok(table)
+    }
+
+    /// checks the values that would otherwise fail deep inside the pipeline setup
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let system = &self.system;
+        let force_field = &self.force_field;
+        let integrator = &self.integrator;
+        let thermostat = &self.thermostat;
+        let barostat = &self.barostat;
+        let timestep = self.timestep();
+        let minimizer = self.minimizer().unwrap_or_default();
+        let checks = [
+            (system.particles > 0, "system.particles has to be positive"),
+            (system.temperature >= 0.0, "system.temperature can not be negative"),
+            (system.spacing > 0.0, "system.spacing has to be positive"),
+            (system.margin >= 0.0, "system.margin can not be negative"),
+            (system.chain_length > 0, "system.chain_length has to be positive"),
+            (system.angle_stiffness >= 0.0, "system.angle_stiffness can not be negative"),
+            (0.0 < system.angle && system.angle < 180.0, "system.angle has to be between 0 and 180 degrees"),
+            (system.dihedral_stiffness >= 0.0, "system.dihedral_stiffness can not be negative"),
+            (system.dihedral_multiplicity > 0, "system.dihedral_multiplicity has to be positive"),
+            (force_field.cutoff > 0.0, "force_field.cutoff has to be positive"),
+            (force_field.life_radius > 0.0, "force_field.life_radius has to be positive"),
+            (
+                force_field.cutoff_scheme != CutoffScheme::Switched || (0.0 < self.r_switch() && self.r_switch() < force_field.cutoff),
+                "force_field.r_switch has to be between 0 and the cutoff",
+            ),
+            (
+                0.0 < force_field.ewald_tolerance && force_field.ewald_tolerance < 1.0,
+                "force_field.ewald_tolerance has to be between 0 and 1",
+            ),
+            (force_field.max_force >= 0.0, "force_field.max_force can not be negative"),
+            (
+                force_field.model != ForceModel::Tabulated || !force_field.tables.is_empty(),
+                "force_field.model = \"tabulated\" needs at least one table in force_field.tables",
+            ),
+            (
+                force_field.tables.iter().all(|t| !t.file.is_empty() && t.r_scale > 0.0 && t.energy_scale > 0.0),
+                "every entry of force_field.tables needs a file and positive scales",
+            ),
+            (
+                0.0 <= force_field.verlet_skin && force_field.verlet_skin <= force_field.cutoff,
+                "force_field.verlet_skin has to be between 0 and the cutoff",
+            ),
+            (integrator.dt > 0.0, "integrator.dt has to be positive"),
+            (integrator.substeps > 0, "integrator.substeps has to be positive"),
+            (integrator.respa_steps > 0, "integrator.respa_steps has to be positive"),
+            // the stats of a frame would hold the slow energy of an older outer step
+            (
+                integrator.kind != IntegratorKind::Respa || integrator.substeps.is_multiple_of(integrator.respa_steps),
+                "integrator.substeps has to be a multiple of integrator.respa_steps, so every frame ends with an outer step",
+            ),
+            (
+                0.0 < timestep.dt_min && timestep.dt_min <= integrator.dt && integrator.dt <= timestep.dt_max,
+                "timestep.dt_min and timestep.dt_max have to enclose integrator.dt",
+            ),
+            (timestep.displacement > 0.0, "timestep.displacement has to be positive"),
+            (timestep.growth >= 1.0, "timestep.growth can not be below 1"),
+            (minimizer.force_tolerance > 0.0, "minimizer.force_tolerance has to be positive"),
+            (minimizer.energy_tolerance >= 0.0, "minimizer.energy_tolerance can not be negative"),
+            (minimizer.max_steps > 0, "minimizer.max_steps has to be positive"),
+            (
+                0.0 < minimizer.step && minimizer.step <= minimizer.step_max,
+                "minimizer.step has to be positive and at most minimizer.step_max",
+            ),
+            (thermostat.temperature >= 0.0, "thermostat.temperature can not be negative"),
+            (thermostat.tau > 0.0, "thermostat.tau has to be positive"),
+            (
+                (1..=MAX_CHAIN_LENGTH).contains(&thermostat.chain_length),
+                "thermostat.chain_length is out of range",
+            ),
+            (self.boundary.wall_temperature >= 0.0, "boundary.wall_temperature can not be negative"),
+            (barostat.tau > 0.0, "barostat.tau has to be positive"),
+            (barostat.compressibility > 0.0, "barostat.compressibility has to be positive"),
+            (barostat.interval > 0, "barostat.interval has to be positive"),
+            // adapted at runtime up to the same limit
+            (
+                0.0 < barostat.max_strain && barostat.max_strain <= 0.1,
+                "barostat.max_strain has to be between 0 and 0.1",
+            ),
+            (
+                barostat.kind == BarostatKind::Off || self.boundary().is_periodic(),
+                "the barostat needs a periodic box, the walls do not move with it",
+            ),
+            (
+                barostat.kind != BarostatKind::MonteCarlo || thermostat.kind != ThermostatKind::Off,
+                "Monte-Carlo volume moves need a thermostat, they use its target temperature",
+            ),
+            (self.output.report_interval > 0, "output.report_interval has to be positive"),
+            (self.output.trajectory_interval > 0, "output.trajectory_interval has to be positive"),
+            (
+                !(self.topology.is_explicit() && system.chain_length > 1),
+                "system.chain_length has to be 1 with an explicit topology, the chains are generated without one",
+            ),
+            (
+                self.gpu.reorder_interval == 0 || !self.has_bonded_terms(),
+                "gpu.reorder_interval needs free atoms, the bonds refer to the particle order",
+            ),
+        ];
+        if let Some((_, message)) = checks.iter().find(|(ok, _)| !ok) {
+            return Err((*message).into());
+        }
+        let [lx, ly, _] = self.box_lengths();
+        let [xy, xz, yz] = system.box_tilt;
+        if self.box_lengths().iter().any(|l| *l <= 0.0) {
+            return Err("system.box_lengths have to be positive".into());
+        }
+        // beyond half a box length the minimum image convention breaks down
+        if xy.abs() > 0.5 * lx || xz.abs() > 0.5 * lx || yz.abs() > 0.5 * ly {
+            return Err("system.box_tilt has to be within half a box length (|xy|, |xz| <= lx / 2, |yz| <= ly / 2)".into());
+        }
+        self.validate_topology()?;
+        // reads the table files, so a missing or broken one fails here
+        self.force_field()?;
+        // the bin grid needs at least three bins per axis
+        let simulation_box = self.simulation_box();
+        if simulation_box.heights().iter().any(|h| *h < 3.0 * self.bin_size()) {
+            return Err(format!(
+                "the box ({}) has to be at least three bins ({} nm) wide along every axis",
+                simulation_box,
+                3.0 * self.bin_size()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// in nm, the configured size or enough bins for the initial grid plus the margin
+    pub fn box_size(&self) -> f32 {
+        let system = &self.system;
+        self.system.box_size.unwrap_or_else(|| {
+            let grid = (system.particles as f32).cbrt() * system.spacing + system.margin;
+            self.bin_size() * (grid / system.spacing).floor()
+        })
+    }
+
+    /// in nm, the configured lengths or a cube of `box_size`
+    pub fn box_lengths(&self) -> [f32; 3] {
+        self.system.box_lengths.unwrap_or([self.box_size(); 3])
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        SimulationBox::triclinic(self.box_lengths(), self.system.box_tilt)
+    }
+
+    /// the atom types of the preset with the pair tables of the config
+    pub fn force_field(&self) -> Result<ForceField, Box<dyn Error>> {
+        let mut force_field = self.force_field.preset.build();
+        for table in &self.force_field.tables {
+            let [i, j] = table.types;
+            if i.max(j) >= force_field.type_count() {
+                return Err(format!("force_field.tables: the force field has no atom type {}", i.max(j)).into());
+            }
+            let pair_table = table.load().map_err(|e| format!("force_field.tables ({}, {}): {}", i, j, e))?;
+            force_field.set_table(i, j, pair_table);
+        }
+        Ok(force_field)
+    }
+
+    /// true with generated chains or an explicit topology
+    pub fn has_bonded_terms(&self) -> bool {
+        self.system.chain_length > 1 || self.topology.is_explicit()
+    }
+
+    /// the explicit topology of the config, or Kremer-Grest bead-spring chains of the first
+    /// atom type stiffened by the angle and dihedral terms of `system` without one
+    pub fn topology(&self) -> Topology {
+        let mut topology = if self.topology.is_explicit() {
+            let config = &self.topology;
+            let mut topology = Topology::new();
+            for bond_type in &config.bond_types {
+                topology.add_bond_type(bond_type.bond_type());
+            }
+            for angle_type in &config.angle_types {
+                topology.add_angle_type(AngleType { k: angle_type.k, theta0: angle_type.theta0.to_radians() });
+            }
+            for dihedral_type in &config.dihedral_types {
+                topology.add_dihedral_type(DihedralType {
+                    k: dihedral_type.k,
+                    phi0: dihedral_type.phi0.to_radians(),
+                    n: dihedral_type.n,
+                });
+            }
+            for &[i, j, type_] in &config.bonds {
+                topology.add_bond(i, j, type_);
+            }
+            for &[i, j, k, type_] in &config.angles {
+                topology.add_angle(i, j, k, type_);
+            }
+            for &[i, j, k, l, type_] in &config.dihedrals {
+                topology.add_dihedral(i, j, k, l, type_);
+            }
+            topology
+        } else {
+            let system = &self.system;
+            let atom = *self.force_field.preset.build().atom(0);
+            let bond = BondType::fene(30.0 * atom.epsilon / (atom.sigma * atom.sigma), 1.5 * atom.sigma, atom.epsilon, atom.sigma);
+            let angle = (system.angle_stiffness > 0.0).then(|| AngleType {
+                k: system.angle_stiffness * atom.epsilon,
+                theta0: system.angle.to_radians(),
+            });
+            let dihedral = (system.dihedral_stiffness > 0.0).then(|| DihedralType {
+                k: system.dihedral_stiffness * atom.epsilon,
+                phi0: system.dihedral_phase.to_radians(),
+                n: system.dihedral_multiplicity,
+            });
+            Topology::linear_chains(system.particles, system.chain_length, bond, angle, dihedral)
+        };
+        if self.system.constrain_bonds {
+            topology.constrain_bonds();
+        }
+        topology
+    }
+
+    /// every term of an explicit topology refers to distinct particles of the system and to
+    /// a type with usable parameters
+    fn validate_topology(&self) -> Result<(), Box<dyn Error>> {
+        let config = &self.topology;
+        for (n, bond_type) in config.bond_types.iter().enumerate() {
+            let valid = match bond_type.style {
+                BondStyle::Harmonic => bond_type.k >= 0.0 && bond_type.r0 > 0.0,
+                BondStyle::Fene => {
+                    bond_type.k > 0.0 && bond_type.epsilon >= 0.0 && 0.0 < bond_type.sigma && bond_type.sigma < bond_type.r0
+                }
+            };
+            if !valid {
+                return Err(format!("topology.bond_types[{}]: k and r0 have to be positive, FENE needs 0 < sigma < r0", n).into());
+            }
+        }
+        for (n, angle_type) in config.angle_types.iter().enumerate() {
+            if angle_type.k < 0.0 || !(0.0..=180.0).contains(&angle_type.theta0) {
+                return Err(format!("topology.angle_types[{}]: k can not be negative and theta0 is between 0 and 180 degrees", n).into());
+            }
+        }
+        for (n, dihedral_type) in config.dihedral_types.iter().enumerate() {
+            if dihedral_type.k < 0.0 || dihedral_type.n == 0 {
+                return Err(format!("topology.dihedral_types[{}]: k can not be negative and n has to be positive", n).into());
+            }
+        }
+        let terms = config.bonds.iter().map(|t| ("bonds", &t[..], config.bond_types.len()));
+        let terms = terms.chain(config.angles.iter().map(|t| ("angles", &t[..], config.angle_types.len())));
+        let terms = terms.chain(config.dihedrals.iter().map(|t| ("dihedrals", &t[..], config.dihedral_types.len())));
+        for (name, term, type_count) in terms {
+            let (particles, type_) = term.split_at(term.len() - 1);
+            if type_[0] as usize >= type_count {
+                return Err(format!("topology.{} {:?}: there is no type {}", name, term, type_[0]).into());
+            }
+            if let Some(i) = particles.iter().find(|i| **i >= self.system.particles) {
+                return Err(format!("topology.{} {:?}: particle {} is beyond system.particles", name, term, i).into());
+            }
+            if particles.iter().enumerate().any(|(a, i)| particles[..a].contains(i)) {
+                return Err(format!("topology.{} {:?}: a term needs distinct particles", name, term).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// in nm, see `neighbour_list::bin_size`
+    pub fn bin_size(&self) -> f32 {
+        let force_field = &self.force_field;
+        neighbour_list::bin_size(force_field.cutoff, force_field.life_radius, force_field.verlet_skin)
+    }
+
+    /// the random particle life matrix of `life_seed` with the configured radius
+    pub fn particle_life(&self, type_count: u32) -> ParticleLife {
+        let mut life = ParticleLife::random(type_count, self.force_field.life_seed);
+        life.radius = self.force_field.life_radius;
+        life
+    }
+
+    pub fn r_switch(&self) -> f32 {
+        self.force_field.r_switch.unwrap_or(0.9 * self.force_field.cutoff)
+    }
+
+    pub fn cutoff(&self) -> Cutoff {
+        Cutoff::new(self.force_field.cutoff_scheme, self.force_field.cutoff, self.r_switch())
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        Integrator {
+            kind: self.integrator.kind,
+            respa_steps: self.integrator.respa_steps,
+        }
+    }
+
+    pub fn timestep(&self) -> Timestep {
+        let dt = self.integrator.dt;
+        let timestep = &self.timestep;
+        Timestep {
+            adaptive: timestep.adaptive,
+            dt,
+            dt_min: timestep.dt_min.unwrap_or(0.01 * dt),
+            dt_max: timestep.dt_max.unwrap_or(2.0 * dt),
+            displacement: timestep.displacement,
+            growth: timestep.growth,
+        }
+    }
+
+    /// the configured minimization, None runs the dynamics right away
+    pub fn minimizer(&self) -> Option<Minimizer> {
+        let config = &self.minimizer;
+        let minimizer = match config.kind? {
+            MinimizerKind::SteepestDescent => Minimizer::steepest_descent(config.force_tolerance),
+            MinimizerKind::Fire => Minimizer::fire(config.force_tolerance),
+        };
+        Some(Minimizer {
+            energy_tolerance: config.energy_tolerance,
+            max_steps: config.max_steps,
+            step: config.step.unwrap_or(minimizer.step),
+            step_max: config.step_max.unwrap_or(minimizer.step_max),
+            ..minimizer
+        })
+    }
+
+    pub fn thermostat(&self) -> Thermostat {
+        let thermostat = &self.thermostat;
+        Thermostat {
+            chain_length: thermostat.chain_length,
+            ..Thermostat::new(thermostat.kind, thermostat.temperature, thermostat.tau)
+        }
+    }
+
+    pub fn barostat(&self) -> Barostat {
+        let barostat = &self.barostat;
+        Barostat {
+            kind: barostat.kind,
+            coupling: barostat.coupling,
+            pressure: barostat.pressure,
+            tau: barostat.tau,
+            compressibility: barostat.compressibility,
+            interval: barostat.interval,
+            max_strain: barostat.max_strain,
+        }
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        Boundary::new(self.boundary.modes, self.boundary.wall_temperature)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_toml())
+    }
+}
+
+/// copies every value of `other` into `table`, tables are merged key by key
+fn merge(table: &mut toml::Table, other: toml::Table) {
+    for (key, value) in other {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(inner)), toml::Value::Table(other_inner)) => merge(inner, other_inner),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// sets the value at a dotted key like `system.particles`
+fn set(table: &mut toml::Table, key: &str, value: toml::Value) -> Result<(), Box<dyn Error>> {
+    match key.split_once('.') {
+        Some((section, rest)) => {
+            let inner = table
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            match inner {
+                toml::Value::Table(inner) => set(inner, rest, value),
+                _ => Err(format!("{} is not a section", section).into()),
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+}
+
+/// a TOML value, or a plain string if it is none
+fn parse_value(text: &str) -> toml::Value {
+    format!("value = {}", text)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.to_string()))
+}
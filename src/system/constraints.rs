@@ -0,0 +1,26 @@
+/// How the constraints of the topology are kept, see `constraints.wgsl`.
+///
+/// SHAKE moves the particles back onto their constraints after the position update and
+/// RATTLE removes the velocity along the constraints after the velocity update. Both
+/// iterate until every constraint is within `tolerance` or `max_iterations` is reached.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Constraints {
+    pub tolerance: f32, // largest relative deviation of a constraint length
+    pub max_iterations: u32,
+}
+
+impl Constraints {
+    pub fn new(tolerance: f32, max_iterations: u32) -> Self {
+        assert!(tolerance > 0.0 && max_iterations > 0, "expected a positive tolerance and iteration limit");
+        Self {
+            tolerance,
+            max_iterations,
+        }
+    }
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::new(1e-5, 100)
+    }
+}
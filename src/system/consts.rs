@@ -9,22 +9,20 @@ force: eV / nm or nm * amu / ps^2 or 1.660539040 pN
  */
 
 
+// defaults of the run configuration, see config.rs
 pub const NUMBER_PARTICLES: u32 = 10000;
-pub const NUMBER_PARTICLES_CUBED: f32 = 21.544346900318832;
 pub const PARTICLE_SIZE: f32 = 0.2551; // in nm
 pub const NEIGHBORHOOD_SIZE: f32 = PARTICLE_SIZE * 2.5; // in nm
 pub const INIT_TEMPERATURE: f32 = 10.0; // in Kelvin
 pub const INIT_SPACING: f32 = PARTICLE_SIZE * 1.0; // in nm
 pub const EXES_SPACING: f32 = PARTICLE_SIZE * 3.0; // in nm
+pub const CHAIN_LENGTH: u32 = 1; // particles per molecule, 1 gives free atoms
+pub const CONSTRAIN_BONDS: bool = false; // the chains get constraints instead of bonds
 
 pub const DT: f32 = 1.0e-3; // in picoseconds
-pub const ITERATIONS: u32 = 31; 
-pub const BIN_DEPTH: u32 = 100;
-pub const BIN_SIZE: f32 = NEIGHBORHOOD_SIZE;
-
-const TRUE_BOX_SIZE: u32 = ((NUMBER_PARTICLES_CUBED * INIT_SPACING + EXES_SPACING) / PARTICLE_SIZE) as u32;  // which is 
-pub const BOX_SIZE: f32 = BIN_SIZE * (TRUE_BOX_SIZE as f32);
-pub const BIN_COUNT: u32 = TRUE_BOX_SIZE as u32; // for each dimension
+pub const ITERATIONS: u32 = 31; // substeps per frame
+pub const SCAN_BLOCK: u32 = 256; // cells per workgroup of scan.wgsl
+pub const MORTON_BITS: u32 = 5; // the grid of reorder.wgsl has 2^MORTON_BITS cells per axis
 
 pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
     r: 0.03,
@@ -41,6 +39,9 @@ pub const eV_over_mU: f32 = 96.48533216;
 pub const BOLTZMANN_CONSTANT_J: f32 = 1.38064852e-23; // in J / K
 pub const BOLTZMANN_CONSTANT_EV: f32 = 8.617333262145e-5; // in eV / K
 pub const BOLTZMANN_CONSTANT: f32 = BOLTZMANN_CONSTANT_EV * mU_over_eV; // in mU / K
+pub const BOLTZMANN_CONSTANT_MU: f32 = BOLTZMANN_CONSTANT_EV * eV_over_mU; // in mU / K (= kJ / mol / K)
+pub const BAR_PER_MU_NM3: f32 = 16.6054; // 1 kJ / mol / nm^3 in bar
+pub const COULOMB_CONSTANT: f32 = 138.935458; // 1 / (4 pi eps0) in mU * nm / e^2 (kJ / mol = mU)
 
 /*
 0.14417405 nm / ps
@@ -0,0 +1,129 @@
+//! The pair interactions of `compute.wgsl` in f32, so the cpu backends follow the gpu as
+//! closely as the summation order allows.
+
+use crate::system::force_field::Pair;
+use crate::system::params::Params;
+
+/// The result of one pair, counted half on both particles like on the gpu.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PairForce {
+    pub force: f32,  // along d / |d|, positive pushes the particles apart, in mU / nm
+    pub energy: f32, // in mU
+    pub capped: f32, // 0.5 for every capped side
+}
+
+pub fn lennard_jones(dist: f32, sigma: f32, epsilon: f32) -> f32 {
+    let r6 = (sigma / dist).powf(6.0);
+    let r12 = r6 * r6;
+    4.0 * epsilon * (r12 - r6)
+}
+
+pub fn lennard_jones_force(dist: f32, sigma: f32, epsilon: f32) -> f32 {
+    let r6 = (sigma / dist).powf(6.0);
+    let r12 = r6 * r6;
+    24.0 * epsilon * (2.0 * r12 - r6) / dist
+}
+
+/// lennard jones with the cutoff scheme of `params`, returns (force, energy)
+pub fn lennard_jones_cut(params: &Params, dist: f32, pair: &Pair) -> (f32, f32) {
+    let mut energy = lennard_jones(dist, pair.sigma, pair.epsilon);
+    let mut force = lennard_jones_force(dist, pair.sigma, pair.epsilon);
+    let r_cut = params.neghborhood_size;
+    match params.cutoff_scheme {
+        1 => energy -= pair.e_shift,
+        2 => {
+            energy = energy - pair.e_shift + (dist - r_cut) * pair.f_shift;
+            force -= pair.f_shift;
+        }
+        3 if dist > params.r_switch => {
+            // CHARMM switching function, goes from 1 at r_switch to 0 at r_cut
+            let rc2 = r_cut * r_cut;
+            let rs2 = params.r_switch * params.r_switch;
+            let r2 = dist * dist;
+            let denom = (rc2 - rs2).powf(3.0);
+            let s = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denom;
+            let ds = 12.0 * dist * (rc2 - r2) * (rs2 - r2) / denom;
+            force = force * s - energy * ds;
+            energy *= s;
+        }
+        _ => {}
+    }
+    (force, energy)
+}
+
+/// linear interpolation in the (V, F) table of the pair, returns (force, energy)
+pub fn tabulated(tables: &[[f32; 2]], dist: f32, pair: &Pair) -> (f32, f32) {
+    let x = (dist - pair.table_r_min).max(0.0) * pair.table_inv_dr;
+    if x > (pair.table_len - 1) as f32 {
+        return (0.0, 0.0);
+    }
+    let i = (x as u32).min(pair.table_len - 2);
+    let t = (x - i as f32).min(1.0);
+    let a = tables[(pair.table_offset + i) as usize];
+    let b = tables[(pair.table_offset + i + 1) as usize];
+    let energy = a[0] + (b[0] - a[0]) * t;
+    let force = a[1] + (b[1] - a[1]) * t;
+    (force, energy)
+}
+
+/// piecewise linear: repulsion below beta, a triangle with height attraction above it
+pub fn particle_life_force(params: &Params, dist: f32, attraction: f32) -> f32 {
+    let r = dist / params.life_radius;
+    if r < params.life_beta {
+        return r / params.life_beta - 1.0;
+    }
+    if r < 1.0 {
+        return attraction * (1.0 - (2.0 * r - 1.0 - params.life_beta).abs() / (1.0 - params.life_beta));
+    }
+    0.0
+}
+
+/// cuts a pair force to params.max_force, a max_force of zero turns the capping off
+pub fn cap(params: &Params, force: f32, capped: &mut f32) -> f32 {
+    if params.max_force <= 0.0 || force.abs() <= params.max_force {
+        return force;
+    }
+    *capped += 0.5;
+    force.clamp(-params.max_force, params.max_force)
+}
+
+/// The short range interaction of particle i with j at distance `dist`, or None if they
+/// do not interact. `life_matrix` is only read by the particle life model.
+pub fn pair_force(
+    params: &Params,
+    pairs: &[Pair],
+    tables: &[[f32; 2]],
+    life_matrix: &[f32],
+    type_i: u32,
+    type_j: u32,
+    dist: f32,
+) -> Option<PairForce> {
+    if params.force_model == 1 {
+        if dist <= 0.0 || dist >= params.life_radius {
+            return None;
+        }
+        let attraction = life_matrix[(type_i * params.type_count + type_j) as usize];
+        return Some(PairForce {
+            force: -particle_life_force(params, dist, attraction) * params.life_force,
+            ..Default::default()
+        });
+    }
+    if dist >= params.neghborhood_size {
+        return None;
+    }
+    // on top of each other, there is no direction to push them apart, the diverging force
+    // only counts as capped while the capping is on
+    if dist <= 0.0 {
+        let capped = if params.max_force > 0.0 { 0.5 } else { 0.0 };
+        return Some(PairForce { capped, ..Default::default() });
+    }
+    let pair = &pairs[(type_i * params.type_count + type_j) as usize];
+    let (force, energy) = if params.force_model == 2 && pair.table_len > 0 {
+        tabulated(tables, dist, pair)
+    } else {
+        lennard_jones_cut(params, dist, pair)
+    };
+    let mut capped = 0.0;
+    let force = cap(params, force, &mut capped);
+    Some(PairForce { force, energy: 0.5 * energy, capped })
+}
@@ -0,0 +1,85 @@
+//! CPU implementations of the particle pipeline, they work on the same `Particle` and
+//! `Params` as the shaders and share the pair interactions in `kernels`.
+
+pub mod kernels;
+pub mod parallel;
+pub mod reference;
+
+use std::error::Error;
+
+use crate::system::boundary::{Boundary, BoundaryMode};
+use crate::system::config::{Config, OutputConfig};
+use crate::system::cutoff::TailCorrection;
+use crate::system::force_field::Pair;
+use crate::system::params::Params;
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::stats::StatHistory;
+use crate::system::thermostat::ThermostatKind;
+
+/// The parts of the gpu pipeline the cpu backends leave out. They run plain NVE or
+/// particle life between periodic, reflective or open faces, a configuration with anything
+/// else is refused.
+pub fn check_supported(config: &Config) -> Result<(), Box<dyn Error>> {
+    if config.force_field.preset.build().is_charged() {
+        return Err("the cpu backends have no electrostatics, choose an uncharged force field".into());
+    }
+    if config.has_bonded_terms() || config.system.constrain_bonds {
+        return Err("the cpu backends have no bonded interactions or constraints".into());
+    }
+    if config.thermostat.kind != ThermostatKind::Off {
+        return Err("the cpu backends have no thermostats, set thermostat.kind = \"off\"".into());
+    }
+    if config.timestep.adaptive || config.minimizer.kind.is_some() {
+        return Err("the cpu backends have no adaptive timestep or minimizer".into());
+    }
+    if config.boundary.modes.contains(&BoundaryMode::Thermal) {
+        return Err("the cpu backends have no thermal walls, choose periodic, reflective or open faces".into());
+    }
+    Ok(())
+}
+
+/// The system `ComputeSet::new` builds from a config, in the form the cpu backends use.
+pub(crate) struct Setup {
+    pub params: Params,
+    pub simulation_box: SimulationBox,
+    pub boundary: Boundary,
+    pub masses: Vec<f32>, // per atom type
+    pub pairs: Vec<Pair>,
+    pub tables: Vec<[f32; 2]>,
+    pub life_matrix: Vec<f32>,
+    pub particles: Vec<Particle>,
+    pub tail_correction: TailCorrection,
+    pub stats_history: StatHistory,
+    pub substeps: u32,
+    pub output: OutputConfig,
+}
+
+impl Setup {
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        check_supported(config)?;
+        let force_field = config.force_field()?;
+        let simulation_box = config.simulation_box();
+        let life = config.particle_life(force_field.type_count());
+        let cutoff = config.cutoff();
+        let mut params = Params::new(config, &force_field, &simulation_box);
+        params.set_particle_life(&life);
+        let particles = Particle::create_particles(&config.system, &params, &force_field);
+        let type_counts = Particle::type_counts(&particles, force_field.type_count());
+        Ok(Self {
+            simulation_box,
+            boundary: config.boundary(),
+            masses: (0..force_field.type_count()).map(|t| force_field.atom(t).mass).collect(),
+            pairs: force_field.pairs(&cutoff),
+            tables: force_field.serialize_tables(),
+            life_matrix: bytemuck::cast_slice(life.serialize()).to_vec(),
+            particles,
+            tail_correction: TailCorrection::new(&force_field, &type_counts, simulation_box.volume(), cutoff.r_cut),
+            stats_history: StatHistory::new(params, &force_field),
+            substeps: config.integrator.substeps,
+            output: config.output.clone(),
+            params,
+        })
+    }
+}
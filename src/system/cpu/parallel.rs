@@ -0,0 +1,611 @@
+use std::error::Error;
+
+use rayon::prelude::*;
+
+use crate::system::config::{Config, OutputConfig};
+use crate::system::cpu::kernels::pair_force;
+use crate::system::cpu::Setup;
+use crate::system::cutoff::TailCorrection;
+use crate::system::force_field::Pair;
+use crate::system::params::Params;
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::stats::{Stat, StatHistory, Stats};
+
+/// width of the pair loops, eight f32 fill an AVX register and two SSE registers
+const LANES: usize = 8;
+
+/// The parts of a particle the pair loops do not read.
+#[derive(Copy, Clone, Debug, Default)]
+struct Kinematics {
+    velocity: [f32; 3],
+    acceleration: [f32; 3],
+    previous_acc: [f32; 3], // the acceleration before the last one, for Beeman
+}
+
+/// Per particle sums of a substep, reduced over all particles.
+#[derive(Copy, Clone, Debug, Default)]
+struct Sums {
+    ke: f64,
+    pe: f64,
+    virial: [f64; 3],
+    capped: f64,
+    v2_max: f32,
+    a2_max: f32,
+}
+
+impl Sums {
+    fn add(self, other: Self) -> Self {
+        Self {
+            ke: self.ke + other.ke,
+            pe: self.pe + other.pe,
+            virial: [0, 1, 2].map(|a| self.virial[a] + other.virial[a]),
+            capped: self.capped + other.capped,
+            v2_max: self.v2_max.max(other.v2_max),
+            a2_max: self.a2_max.max(other.a2_max),
+        }
+    }
+}
+
+/// The force on one particle from the pair loops, the energy and virial count half.
+#[derive(Copy, Clone, Debug, Default)]
+struct Accumulator {
+    force: [f32; 3],
+    pe: f32,
+    virial: [f32; 3],
+    capped: f32,
+}
+
+/// A multithreaded version of the reference backend for systems of 10^4 to 10^6 particles.
+///
+/// The positions and types are kept as separate arrays (SoA) and sorted by cell with a
+/// counting sort every substep, so the particles of a cell are a contiguous range. Every
+/// occupied cell copies its 27 neighbour cells into one padded buffer, the Lennard-Jones
+/// loops run over it `LANES` candidates at a time without branches and vectorize; particle
+/// life and tabulated pairs go through the scalar `pair_force`. The force pass is parallel
+/// over the cells with rayon, RAYON_NUM_THREADS sets the threads.
+pub struct ParallelBackend {
+    params: Params,
+    simulation_box: SimulationBox,
+    masses: Vec<f32>, // per atom type
+    pairs: Vec<Pair>,
+    tables: Vec<[f32; 2]>,
+    life_matrix: Vec<f32>,
+    // in cell order
+    x: Vec<f32>,
+    y: Vec<f32>,
+    z: Vec<f32>,
+    type_: Vec<u32>,
+    kinematics: Vec<Kinematics>,
+    id: Vec<u32>,         // the index of the particle in `particles()`
+    cell_start: Vec<u32>, // the particles of cell c are cell_start[c]..cell_start[c + 1]
+    occupied: Vec<u32>,   // the cells with particles, in order
+    colors: Vec<[f32; 3]>, // by id, only passed through
+    cell_counts: [u32; 3],
+    stat: Stat,
+    stats: Stats,
+    stats_history: StatHistory,
+    tail_correction: TailCorrection,
+    substeps: u32,
+    iteration: usize,
+    time: f64,
+    output: OutputConfig,
+}
+
+impl ParallelBackend {
+    /// sets up the same system as `ComputeSet::new`, fails for the features the cpu lacks
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let setup = Setup::new(config)?;
+        if !setup.boundary.is_periodic() {
+            return Err("the parallel backend only runs periodic boxes, the reference backend has the walls".into());
+        }
+        // the cells are built after the drift, so they only have to be as thick as the
+        // interaction range and not twice as thick like the gpu bins
+        let range = if setup.params.force_model == 1 {
+            setup.params.life_radius
+        } else {
+            setup.params.neghborhood_size
+        };
+        let cell_counts = setup.simulation_box.bin_counts(range);
+        let mut backend = Self {
+            params: setup.params,
+            simulation_box: setup.simulation_box,
+            masses: setup.masses,
+            pairs: setup.pairs,
+            tables: setup.tables,
+            life_matrix: setup.life_matrix,
+            x: Vec::new(),
+            y: Vec::new(),
+            z: Vec::new(),
+            type_: Vec::new(),
+            kinematics: Vec::new(),
+            id: Vec::new(),
+            cell_start: Vec::new(),
+            occupied: Vec::new(),
+            colors: Vec::new(),
+            cell_counts,
+            stat: Stat::new(),
+            stats: Stats::default(),
+            stats_history: setup.stats_history,
+            tail_correction: setup.tail_correction,
+            substeps: setup.substeps,
+            iteration: 0,
+            time: 0.0,
+            output: setup.output,
+        };
+        backend.set_particles(&setup.particles);
+        Ok(backend)
+    }
+
+    /// runs one frame of `substeps` substeps
+    pub fn step(&mut self) {
+        let mut sums = Sums::default();
+        for _ in 0..self.substeps {
+            self.drift();
+            self.sort();
+            sums = self.force();
+            self.time += self.params.dt as f64;
+        }
+        self.iteration += self.substeps as usize;
+        self.stat = Stat {
+            KE: sums.ke as f32,
+            PE: sums.pe as f32,
+            W_x: sums.virial[0] as f32,
+            W_y: sums.virial[1] as f32,
+            W_z: sums.virial[2] as f32,
+            capped: sums.capped as f32,
+            ..Stat::new()
+        };
+        self.stats = Stats {
+            iteration: self.iteration,
+            time: self.time,
+            dt: self.params.dt,
+            v_max: sums.v2_max.sqrt(),
+            a_max: sums.a2_max.sqrt(),
+            ..Stats::from_stat(&self.stat, self.simulation_box.volume(), &self.tail_correction, 0)
+        };
+        self.stats_history.add(self.stats);
+    }
+
+    fn drift(&mut self) {
+        let dt = self.params.dt;
+        let integrator = self.params.integrator;
+        let simulation_box = self.simulation_box;
+        let origin = simulation_box.origin();
+        self.x
+            .par_iter_mut()
+            .zip(self.y.par_iter_mut())
+            .zip(self.z.par_iter_mut())
+            .zip(self.kinematics.par_iter_mut())
+            .for_each(|(((x, y), z), k)| {
+                let mut pos = [*x, *y, *z];
+                for (a, p) in pos.iter_mut().enumerate() {
+                    match integrator {
+                        1 => {
+                            k.velocity[a] += k.acceleration[a] * dt;
+                            *p += k.velocity[a] * dt;
+                        }
+                        2 => *p += k.velocity[a] * dt + (4.0 * k.acceleration[a] - k.previous_acc[a]) * dt * dt / 6.0,
+                        _ => *p += k.velocity[a] * dt + k.acceleration[a] * dt * dt * 0.5,
+                    }
+                }
+                let mut s = simulation_box.to_fractional([0, 1, 2].map(|a| pos[a] - origin[a]));
+                for s in s.iter_mut() {
+                    if *s < 0.0 || *s > 1.0 {
+                        *s -= s.floor();
+                    }
+                }
+                let d = simulation_box.from_fractional(s);
+                [*x, *y, *z] = [0, 1, 2].map(|a| origin[a] + d[a]);
+            });
+    }
+
+    /// counting sort of all particle arrays by cell: count, exclusive prefix sum, scatter
+    fn sort(&mut self) {
+        let simulation_box = self.simulation_box;
+        let counts = self.cell_counts;
+        let cells: Vec<u32> = (0..self.x.len())
+            .into_par_iter()
+            .map(|i| {
+                let s = simulation_box.fractional_position([self.x[i], self.y[i], self.z[i]]);
+                let [cx, cy, cz] = [0, 1, 2].map(|a| ((s[a] * counts[a] as f32).floor() as i32).clamp(0, counts[a] as i32 - 1) as u32);
+                cx + cy * counts[0] + cz * counts[0] * counts[1]
+            })
+            .collect();
+
+        let mut cell_start = vec![0u32; (counts[0] * counts[1] * counts[2]) as usize + 1];
+        for &cell in cells.iter() {
+            cell_start[cell as usize + 1] += 1;
+        }
+        for c in 1..cell_start.len() {
+            cell_start[c] += cell_start[c - 1];
+        }
+        let mut next = cell_start.clone();
+        let mut order = vec![0u32; cells.len()];
+        for (i, &cell) in cells.iter().enumerate() {
+            order[next[cell as usize] as usize] = i as u32;
+            next[cell as usize] += 1;
+        }
+
+        fn permute<T: Copy + Send + Sync>(values: &[T], order: &[u32]) -> Vec<T> {
+            order.par_iter().map(|&i| values[i as usize]).collect()
+        }
+        self.x = permute(&self.x, &order);
+        self.y = permute(&self.y, &order);
+        self.z = permute(&self.z, &order);
+        self.type_ = permute(&self.type_, &order);
+        self.kinematics = permute(&self.kinematics, &order);
+        self.id = permute(&self.id, &order);
+        self.occupied = (0..cell_start.len() as u32 - 1)
+            .filter(|&c| cell_start[c as usize + 1] > cell_start[c as usize])
+            .collect();
+        self.cell_start = cell_start;
+    }
+
+    /// new accelerations and velocities of all particles, returns the sums of the substep.
+    /// Parallel over the occupied cells, each gathers its 27 neighbour cells once.
+    fn force(&mut self) -> Sums {
+        let neighbours = Neighbours {
+            // only Lennard-Jones pairs take the vectorized path
+            lanes: self.params.force_model == 0
+                || (self.params.force_model == 2 && self.pairs.iter().all(|pair| pair.table_len == 0)),
+            params: &self.params,
+            simulation_box: &self.simulation_box,
+            pairs: &self.pairs,
+            tables: &self.tables,
+            life_matrix: &self.life_matrix,
+            x: &self.x,
+            y: &self.y,
+            z: &self.z,
+            type_: &self.type_,
+            cell_start: &self.cell_start,
+            cell_counts: self.cell_counts,
+        };
+        let masses = &self.masses;
+        // the particles of a cell are contiguous, so are their kinematics
+        let mut rest = &mut self.kinematics[..];
+        let mut cells = Vec::with_capacity(self.occupied.len());
+        for &cell in self.occupied.iter() {
+            let len = (self.cell_start[cell as usize + 1] - self.cell_start[cell as usize]) as usize;
+            let (kinematics, tail) = std::mem::take(&mut rest).split_at_mut(len);
+            cells.push((cell, kinematics));
+            rest = tail;
+        }
+        cells
+            .into_par_iter()
+            .map_init(Candidates::default, |candidates, (cell, kinematics)| {
+                neighbours.gather(cell, candidates);
+                let start = neighbours.cell_start[cell as usize] as usize;
+                kinematics
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(offset, k)| {
+                        let i = start + offset;
+                        let pair = neighbours.accumulate(i, candidates);
+                        integrate(neighbours.params, masses[neighbours.type_[i] as usize], k, &pair)
+                    })
+                    .fold(Sums::default(), Sums::add)
+            })
+            .reduce(Sums::default, Sums::add)
+    }
+
+    /// the summed stats of the last substep in mU
+    pub fn stat(&self) -> Stat {
+        self.stat
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// the particles in the order they were created or set
+    pub fn particles(&self) -> Vec<Particle> {
+        let mut particles = vec![Particle::default(); self.x.len()];
+        for slot in 0..self.x.len() {
+            let id = self.id[slot] as usize;
+            particles[id] = Particle {
+                position: [self.x[slot], self.y[slot], self.z[slot]],
+                velocity: self.kinematics[slot].velocity,
+                last_acceleration: self.kinematics[slot].acceleration,
+                color: self.colors[id],
+                type_: self.type_[slot] as f32,
+            };
+        }
+        particles
+    }
+
+    pub fn set_particles(&mut self, particles: &[Particle]) {
+        assert!(
+            self.x.is_empty() || particles.len() == self.x.len(),
+            "expected one entry per particle"
+        );
+        self.x = particles.iter().map(|p| p.position[0]).collect();
+        self.y = particles.iter().map(|p| p.position[1]).collect();
+        self.z = particles.iter().map(|p| p.position[2]).collect();
+        self.type_ = particles.iter().map(|p| p.type_ as u32).collect();
+        self.kinematics = particles
+            .iter()
+            .map(|p| Kinematics {
+                velocity: p.velocity,
+                acceleration: p.last_acceleration,
+                previous_acc: [0.0; 3],
+            })
+            .collect();
+        self.id = (0..particles.len() as u32).collect();
+        self.colors = particles.iter().map(|p| p.color).collect();
+        self.sort();
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        self.simulation_box
+    }
+
+    pub fn bin_size(&self) -> f32 {
+        self.params.bin_size()
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.stats_history.temperature()
+    }
+
+    /// writes the stats history to csv, returns the file name
+    pub fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        let file_name = self.output.stats_file_name();
+        self.stats_history.save(&file_name)?;
+        Ok(file_name)
+    }
+}
+
+/// The second half of the substep for one particle: new acceleration and velocity.
+fn integrate(params: &Params, mass: f32, k: &mut Kinematics, pair: &Accumulator) -> Sums {
+    let dt = params.dt;
+    let acc = pair.force.map(|f| f / mass);
+    let old_acc = k.acceleration;
+    let mut ke_vel = k.velocity;
+    for a in 0..3 {
+        match params.integrator {
+            1 => ke_vel[a] = k.velocity[a] + acc[a] * dt * 0.5,
+            2 => {
+                k.velocity[a] += (2.0 * acc[a] + 5.0 * old_acc[a] - k.previous_acc[a]) * dt / 6.0;
+                ke_vel[a] = k.velocity[a];
+            }
+            _ => {
+                k.velocity[a] += (acc[a] + old_acc[a]) * dt * 0.5;
+                ke_vel[a] = k.velocity[a];
+            }
+        }
+    }
+    if params.force_model == 1 {
+        let friction = (-params.life_friction * dt).exp();
+        k.velocity = k.velocity.map(|v| v * friction);
+        ke_vel = ke_vel.map(|v| v * friction);
+    }
+    if params.integrator == 2 {
+        k.previous_acc = old_acc;
+    }
+    k.acceleration = acc;
+
+    let norm2 = |v: [f32; 3]| v[0] * v[0] + v[1] * v[1] + v[2] * v[2];
+    Sums {
+        ke: (0.5 * norm2(ke_vel) * mass) as f64,
+        pe: pair.pe as f64,
+        virial: pair.virial.map(|w| w as f64),
+        capped: pair.capped as f64,
+        v2_max: norm2(k.velocity),
+        a2_max: norm2(acc),
+    }
+}
+
+/// marks the padding at the end of `Candidates`
+const NO_PARTICLE: u32 = u32::MAX;
+
+/// The particles of the 27 cells around a cell, copied next to each other and padded to a
+/// multiple of LANES, so the pair loops only do aligned full width loads.
+#[derive(Default)]
+struct Candidates {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    z: Vec<f32>,
+    type_: Vec<u32>,
+    index: Vec<u32>,
+}
+
+/// Read only view of the sorted particles for the pair loops.
+struct Neighbours<'a> {
+    lanes: bool,
+    params: &'a Params,
+    simulation_box: &'a SimulationBox,
+    pairs: &'a [Pair],
+    tables: &'a [[f32; 2]],
+    life_matrix: &'a [f32],
+    x: &'a [f32],
+    y: &'a [f32],
+    z: &'a [f32],
+    type_: &'a [u32],
+    cell_start: &'a [u32],
+    cell_counts: [u32; 3],
+}
+
+impl Neighbours<'_> {
+    fn gather(&self, cell: u32, candidates: &mut Candidates) {
+        let [nx, ny, nz] = self.cell_counts.map(|c| c as i32);
+        let cell = cell as i32;
+        let [cx, cy, cz] = [cell % nx, cell / nx % ny, cell / (nx * ny)];
+        candidates.x.clear();
+        candidates.y.clear();
+        candidates.z.clear();
+        candidates.type_.clear();
+        candidates.index.clear();
+        for offset in 0..27 {
+            let x = (cx + offset % 3 - 1).rem_euclid(nx);
+            let y = (cy + offset / 3 % 3 - 1).rem_euclid(ny);
+            let z = (cz + offset / 9 - 1).rem_euclid(nz);
+            let neighbour = (x + y * nx + z * nx * ny) as usize;
+            let range = self.cell_start[neighbour] as usize..self.cell_start[neighbour + 1] as usize;
+            candidates.x.extend_from_slice(&self.x[range.clone()]);
+            candidates.y.extend_from_slice(&self.y[range.clone()]);
+            candidates.z.extend_from_slice(&self.z[range.clone()]);
+            candidates.type_.extend_from_slice(&self.type_[range.clone()]);
+            candidates.index.extend(range.map(|j| j as u32));
+        }
+        let padded = candidates.index.len().div_ceil(LANES) * LANES;
+        candidates.x.resize(padded, 0.0);
+        candidates.y.resize(padded, 0.0);
+        candidates.z.resize(padded, 0.0);
+        candidates.type_.resize(padded, 0);
+        candidates.index.resize(padded, NO_PARTICLE);
+    }
+
+    /// the interactions of particle i with the gathered candidates
+    fn accumulate(&self, i: usize, candidates: &Candidates) -> Accumulator {
+        let mut accumulator = Accumulator::default();
+        if !self.lanes {
+            self.scalar(i, candidates, &mut accumulator);
+            return accumulator;
+        }
+        match self.params.cutoff_scheme {
+            1 => self.lennard_jones::<1>(i, candidates, &mut accumulator),
+            2 => self.lennard_jones::<2>(i, candidates, &mut accumulator),
+            3 => self.lennard_jones::<3>(i, candidates, &mut accumulator),
+            _ => self.lennard_jones::<0>(i, candidates, &mut accumulator),
+        }
+        accumulator
+    }
+
+    /// the minimum image of LANES displacements, the positions are inside the box so every
+    /// fractional component is within (-1, 1)
+    #[inline(always)]
+    fn minimum_image(&self, d: &mut [[f32; LANES]; 3]) {
+        let [lx, ly, lz] = self.simulation_box.lengths;
+        let [xy, xz, yz] = self.simulation_box.tilt;
+        let [inv_lx, inv_ly, inv_lz] = [1.0 / lx, 1.0 / ly, 1.0 / lz];
+        let [d_x, d_y, d_z] = d;
+        for ((x, y), z) in d_x.iter_mut().zip(d_y.iter_mut()).zip(d_z.iter_mut()) {
+            let mut s_z = *z * inv_lz;
+            let mut s_y = (*y - yz * s_z) * inv_ly;
+            let mut s_x = (*x - xy * s_y - xz * s_z) * inv_lx;
+            s_x -= (s_x > 0.5) as u32 as f32 - (s_x < -0.5) as u32 as f32;
+            s_y -= (s_y > 0.5) as u32 as f32 - (s_y < -0.5) as u32 as f32;
+            s_z -= (s_z > 0.5) as u32 as f32 - (s_z < -0.5) as u32 as f32;
+            *x = lx * s_x + xy * s_y + xz * s_z;
+            *y = ly * s_y + yz * s_z;
+            *z = lz * s_z;
+        }
+    }
+
+    /// Lennard-Jones with the cutoff scheme SCHEME on LANES candidates at a time
+    fn lennard_jones<const SCHEME: u32>(&self, i: usize, candidates: &Candidates, accumulator: &mut Accumulator) {
+        let params = self.params;
+        let r_cut = params.neghborhood_size;
+        let r_switch = params.r_switch;
+        let (rc2, rs2) = (r_cut * r_cut, r_switch * r_switch);
+        let inv_switch_denom = 1.0 / ((rc2 - rs2) * (rc2 - rs2) * (rc2 - rs2));
+        let max_force = params.max_force;
+        let row = &self.pairs[(self.type_[i] * params.type_count) as usize..][..params.type_count as usize];
+        let position = [self.x[i], self.y[i], self.z[i]];
+
+        // one partial sum per lane, added up at the end, so the lanes stay independent
+        let mut force_sum = [[0.0f32; LANES]; 3];
+        let mut virial_sum = [[0.0f32; LANES]; 3];
+        let mut pe_sum = [0.0f32; LANES];
+        let mut capped_sum = [0.0f32; LANES];
+        for base in (0..candidates.index.len()).step_by(LANES) {
+            let x: &[f32; LANES] = candidates.x[base..base + LANES].try_into().unwrap();
+            let y: &[f32; LANES] = candidates.y[base..base + LANES].try_into().unwrap();
+            let z: &[f32; LANES] = candidates.z[base..base + LANES].try_into().unwrap();
+            let index: &[u32; LANES] = candidates.index[base..base + LANES].try_into().unwrap();
+            let type_: &[u32; LANES] = candidates.type_[base..base + LANES].try_into().unwrap();
+            let mut d = [[0.0f32; LANES]; 3];
+            let mut valid = [0.0f32; LANES];
+            let (mut sigma2, mut epsilon, mut e_shift, mut f_shift) = ([0.0f32; LANES], [0.0f32; LANES], [0.0f32; LANES], [0.0f32; LANES]);
+            for l in 0..LANES {
+                d[0][l] = position[0] - x[l];
+                d[1][l] = position[1] - y[l];
+                d[2][l] = position[2] - z[l];
+                valid[l] = (index[l] != i as u32 && index[l] != NO_PARTICLE) as u32 as f32;
+                let pair = &row[type_[l] as usize];
+                sigma2[l] = pair.sigma * pair.sigma;
+                epsilon[l] = pair.epsilon;
+                e_shift[l] = pair.e_shift;
+                f_shift[l] = pair.f_shift;
+            }
+            self.minimum_image(&mut d);
+
+            for l in 0..LANES {
+                let r2 = d[0][l] * d[0][l] + d[1][l] * d[1][l] + d[2][l] * d[2][l];
+                // on top of each other, there is no direction to push them apart, the
+                // diverging force only counts as capped while the capping is on
+                capped_sum[l] += 0.5 * valid[l] * (max_force > 0.0 && r2 == 0.0) as u32 as f32;
+                let inside = valid[l] * (r2 < rc2 && r2 > 0.0) as u32 as f32;
+                let r2 = if inside > 0.0 { r2 } else { rc2 };
+                let inv_r2 = 1.0 / r2;
+                let r = r2.sqrt();
+                let inv_r = r * inv_r2;
+                let sr2 = sigma2[l] * inv_r2;
+                let sr6 = sr2 * sr2 * sr2;
+                let sr12 = sr6 * sr6;
+                let mut energy = 4.0 * epsilon[l] * (sr12 - sr6);
+                let mut force = 24.0 * epsilon[l] * (2.0 * sr12 - sr6) * inv_r;
+                match SCHEME {
+                    1 => energy -= e_shift[l],
+                    2 => {
+                        energy = energy - e_shift[l] + (r - r_cut) * f_shift[l];
+                        force -= f_shift[l];
+                    }
+                    3 => {
+                        // CHARMM switching function, goes from 1 at r_switch to 0 at r_cut
+                        let s = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) * inv_switch_denom;
+                        let ds = 12.0 * r * (rc2 - r2) * (rs2 - r2) * inv_switch_denom;
+                        let switched = (r > r_switch) as u32 as f32;
+                        force += switched * (force * s - energy * ds - force);
+                        energy += switched * (energy * s - energy);
+                    }
+                    _ => {}
+                }
+                if max_force > 0.0 {
+                    capped_sum[l] += 0.5 * inside * (force.abs() > max_force) as u32 as f32;
+                    force = force.clamp(-max_force, max_force);
+                }
+                let f_over_r = inside * force * inv_r;
+                for a in 0..3 {
+                    force_sum[a][l] += f_over_r * d[a][l];
+                    virial_sum[a][l] += 0.5 * f_over_r * d[a][l] * d[a][l];
+                }
+                pe_sum[l] += 0.5 * inside * energy;
+            }
+        }
+        for a in 0..3 {
+            accumulator.force[a] += force_sum[a].iter().sum::<f32>();
+            accumulator.virial[a] += virial_sum[a].iter().sum::<f32>();
+        }
+        accumulator.pe += pe_sum.iter().sum::<f32>();
+        accumulator.capped += capped_sum.iter().sum::<f32>();
+    }
+
+    /// one candidate at a time through the shared kernel, for particle life and tables
+    fn scalar(&self, i: usize, candidates: &Candidates, accumulator: &mut Accumulator) {
+        let type_i = self.type_[i];
+        for (c, &j) in candidates.index.iter().enumerate() {
+            if j == i as u32 || j == NO_PARTICLE {
+                continue;
+            }
+            let d = self.simulation_box.minimum_image(
+                [self.x[i] - candidates.x[c], self.y[i] - candidates.y[c], self.z[i] - candidates.z[c]],
+                [true; 3],
+            );
+            let dist = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+            let Some(pair) = pair_force(self.params, self.pairs, self.tables, self.life_matrix, type_i, candidates.type_[c], dist) else {
+                continue;
+            };
+            accumulator.pe += pair.energy;
+            accumulator.capped += pair.capped;
+            if dist > 0.0 {
+                for ((force, virial), d) in accumulator.force.iter_mut().zip(accumulator.virial.iter_mut()).zip(d) {
+                    let f = pair.force * d / dist;
+                    *force += f;
+                    *virial += 0.5 * f * d;
+                }
+            }
+        }
+    }
+}
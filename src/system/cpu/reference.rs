@@ -0,0 +1,519 @@
+use std::error::Error;
+
+use crate::system::boundary::{Boundary, BoundaryMode};
+use crate::system::config::{Config, OutputConfig};
+use crate::system::cpu::kernels::pair_force;
+use crate::system::cpu::Setup;
+use crate::system::cutoff::TailCorrection;
+use crate::system::force_field::Pair;
+use crate::system::params::Params;
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::stats::{Stat, StatHistory, Stats};
+
+/// A single threaded, step by step port of the gpu pipeline (empty_bins.wgsl, calc_grid.wgsl,
+/// varlets.wgsl, compute.wgsl, timestep.wgsl and reduce.wgsl). It is written for clarity,
+/// not speed, and is the reference the gpu and the other backends are checked against.
+/// See `check_supported` for the parts of the pipeline it leaves out.
+pub struct ReferenceBackend {
+    params: Params,
+    simulation_box: SimulationBox,
+    boundary: Boundary,
+    masses: Vec<f32>, // per atom type
+    pairs: Vec<Pair>,
+    tables: Vec<[f32; 2]>,
+    life_matrix: Vec<f32>,
+    particles: Vec<Particle>,
+    previous_acc: Vec<[f32; 3]>, // the acceleration before the last one, for Beeman
+    cell_start: Vec<u32>,     // one more than bins, the particles of bin b are cell_particles[cell_start[b]..cell_start[b + 1]]
+    cell_particles: Vec<u32>, // the particle indices sorted by bin
+    stat: Stat,
+    stats: Stats,
+    stats_history: StatHistory,
+    tail_correction: TailCorrection,
+    substeps: u32,
+    iteration: usize,
+    time: f64,
+    output: OutputConfig,
+}
+
+impl ReferenceBackend {
+    /// sets up the same system as `ComputeSet::new`, fails for the features the cpu lacks
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let setup = Setup::new(config)?;
+        let bin_total = setup.params.bin_total() as usize;
+        Ok(Self {
+            params: setup.params,
+            simulation_box: setup.simulation_box,
+            boundary: setup.boundary,
+            masses: setup.masses,
+            pairs: setup.pairs,
+            tables: setup.tables,
+            life_matrix: setup.life_matrix,
+            previous_acc: vec![[0.0; 3]; setup.particles.len()],
+            cell_start: vec![0; bin_total + 1],
+            cell_particles: vec![0; setup.particles.len()],
+            particles: setup.particles,
+            stat: Stat::new(),
+            stats: Stats::default(),
+            stats_history: setup.stats_history,
+            tail_correction: setup.tail_correction,
+            substeps: setup.substeps,
+            iteration: 0,
+            time: 0.0,
+            output: setup.output,
+        })
+    }
+
+    /// runs one frame of `substeps` substeps
+    pub fn step(&mut self) {
+        for _ in 0..self.substeps {
+            self.empty_bins();
+            self.binning();
+            self.drift();
+            self.stat = self.force();
+            self.time += self.params.dt as f64;
+        }
+        self.iteration += self.substeps as usize;
+
+        let max_norm = |vectors: &mut dyn Iterator<Item = [f32; 3]>| {
+            vectors.map(|v| v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).fold(0.0f32, f32::max).sqrt()
+        };
+        self.stats = Stats {
+            iteration: self.iteration,
+            time: self.time,
+            dt: self.params.dt,
+            v_max: max_norm(&mut self.particles.iter().map(|p| p.velocity)),
+            a_max: max_norm(&mut self.particles.iter().map(|p| p.last_acceleration)),
+            ..Stats::from_stat(&self.stat, self.simulation_box.volume(), &self.tail_correction, 0)
+        };
+        self.stats_history.add(self.stats);
+    }
+
+    fn empty_bins(&mut self) {
+        self.cell_start.fill(0);
+    }
+
+    /// the bin of a particle, None if an open boundary absorbed it
+    fn bin_of(&self, position: [f32; 3]) -> Option<[i32; 3]> {
+        if is_absorbed(&self.simulation_box, position) {
+            return None;
+        }
+        let s = self.simulation_box.fractional_position(position);
+        let counts = [self.params.bin_count_x, self.params.bin_count_y, self.params.bin_count_z];
+        Some([0, 1, 2].map(|a| ((s[a] * counts[a] as f32).floor() as i32).min(counts[a] as i32 - 1)))
+    }
+
+    /// the linear index of a bin, wrapped around the periodic faces, bins beyond a wall or
+    /// an open face do not exist
+    fn wrap_bin(&self, bin: [i32; 3]) -> Option<usize> {
+        let counts = [self.params.bin_count_x, self.params.bin_count_y, self.params.bin_count_z].map(|c| c as i32);
+        let periodic = self.boundary.periodic_axes();
+        if (0..3).any(|a| !periodic[a] && !(0..counts[a]).contains(&bin[a])) {
+            return None;
+        }
+        let [x, y, z] = [0, 1, 2].map(|a| bin[a].rem_euclid(counts[a]));
+        Some((x + y * counts[0] + z * counts[0] * counts[1]) as usize)
+    }
+
+    /// counts the particles of every bin, scans the counts into the start of every bin and
+    /// scatters the particles, like calc_grid.wgsl and scan.wgsl
+    fn binning(&mut self) {
+        let bins: Vec<Option<usize>> = self.particles.iter().map(|p| self.bin_of(p.position).and_then(|b| self.wrap_bin(b))).collect();
+        let mut slots = Vec::with_capacity(bins.len());
+        for &bin in bins.iter().flatten() {
+            slots.push(self.cell_start[bin]);
+            self.cell_start[bin] += 1;
+        }
+        let mut sum = 0;
+        for start in self.cell_start.iter_mut() {
+            let count = *start;
+            *start = sum;
+            sum += count;
+        }
+        let binned = bins.iter().enumerate().filter_map(|(index, bin)| bin.map(|bin| (index, bin)));
+        for ((index, bin), slot) in binned.zip(slots) {
+            self.cell_particles[(self.cell_start[bin] + slot) as usize] = index as u32;
+        }
+    }
+
+    fn drift(&mut self) {
+        let dt = self.params.dt;
+        let origin = self.simulation_box.origin();
+        for (particle, previous_acc) in self.particles.iter_mut().zip(self.previous_acc.iter()) {
+            // already absorbed by an open boundary
+            if is_absorbed(&self.simulation_box, particle.position) {
+                continue;
+            }
+            let (pos, vel, acc) = (&mut particle.position, &mut particle.velocity, &mut particle.last_acceleration);
+            for a in 0..3 {
+                match self.params.integrator {
+                    1 => {
+                        vel[a] += acc[a] * dt;
+                        pos[a] += vel[a] * dt;
+                    }
+                    2 => pos[a] += vel[a] * dt + (4.0 * acc[a] - previous_acc[a]) * dt * dt / 6.0,
+                    _ => pos[a] += vel[a] * dt + acc[a] * dt * dt * 0.5,
+                }
+            }
+            let mut s = self.simulation_box.to_fractional([0, 1, 2].map(|a| pos[a] - origin[a]));
+            for (axis, x) in s.iter_mut().enumerate() {
+                if (0.0..=1.0).contains(x) {
+                    continue;
+                }
+                let normal = self.simulation_box.face_normal(axis);
+                match self.boundary.modes[axis] {
+                    BoundaryMode::Periodic => *x -= x.floor(),
+                    // specular reflection, the old acceleration is mirrored as well so the
+                    // second half kick in `force` does not undo the reflection
+                    BoundaryMode::Reflective => {
+                        *x = if *x < 0.0 { -*x } else { 2.0 - *x };
+                        *vel = mirrored(*vel, normal);
+                        *acc = mirrored(*acc, normal);
+                    }
+                    // open (thermal walls are refused by `check_supported`), the particle
+                    // stops outside the box
+                    _ => {
+                        *vel = [0.0; 3];
+                        *acc = [0.0; 3];
+                    }
+                }
+            }
+            let d = self.simulation_box.from_fractional(s);
+            *pos = [0, 1, 2].map(|a| origin[a] + d[a]);
+        }
+    }
+
+    /// new accelerations and velocities of all particles, returns the summed stats
+    fn force(&mut self) -> Stat {
+        let params = &self.params;
+        let dt = params.dt;
+        let mut updated = self.particles.clone();
+        let (mut ke, mut pe, mut capped) = (0.0f64, 0.0f64, 0.0f64);
+        let mut virial = [0.0f64; 3];
+
+        for (index, particle) in self.particles.iter().enumerate() {
+            // absorbed by an open boundary, the particle stays where it left the box
+            let Some(bin) = self.bin_of(particle.position) else {
+                continue;
+            };
+            let type_i = particle.type_ as u32;
+            let mass = self.masses[type_i as usize];
+            let mut force = [0.0f32; 3];
+            let mut particle_virial = [0.0f32; 3];
+            let (mut particle_pe, mut particle_capped) = (0.0f32, 0.0f32);
+
+            for offset in 0..27 {
+                let Some(neighbour) = self.wrap_bin([bin[0] + offset % 3 - 1, bin[1] + offset / 3 % 3 - 1, bin[2] + offset / 9 - 1]) else {
+                    continue;
+                };
+                for &j in &self.cell_particles[self.cell_start[neighbour] as usize..self.cell_start[neighbour + 1] as usize] {
+                    if j as usize == index {
+                        continue;
+                    }
+                    let other = &self.particles[j as usize];
+                    let d = self.simulation_box.minimum_image(
+                        [0, 1, 2].map(|a| particle.position[a] - other.position[a]),
+                        self.boundary.periodic_axes(),
+                    );
+                    let dist = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                    let Some(pair) = pair_force(params, &self.pairs, &self.tables, &self.life_matrix, type_i, other.type_ as u32, dist) else {
+                        continue;
+                    };
+                    particle_pe += pair.energy;
+                    particle_capped += pair.capped;
+                    if dist > 0.0 {
+                        for a in 0..3 {
+                            let f = pair.force * d[a] / dist;
+                            force[a] += f;
+                            particle_virial[a] += 0.5 * f * d[a];
+                        }
+                    }
+                }
+            }
+
+            let acc = force.map(|f| f / mass);
+            let old_acc = particle.last_acceleration;
+            let mut vel = particle.velocity;
+            let mut ke_vel = vel;
+            for a in 0..3 {
+                match params.integrator {
+                    1 => ke_vel[a] = vel[a] + acc[a] * dt * 0.5,
+                    2 => {
+                        vel[a] += (2.0 * acc[a] + 5.0 * old_acc[a] - self.previous_acc[index][a]) * dt / 6.0;
+                        ke_vel[a] = vel[a];
+                    }
+                    _ => {
+                        vel[a] += (acc[a] + old_acc[a]) * dt * 0.5;
+                        ke_vel[a] = vel[a];
+                    }
+                }
+            }
+            if params.force_model == 1 {
+                let friction = (-params.life_friction * dt).exp();
+                vel = vel.map(|v| v * friction);
+                ke_vel = ke_vel.map(|v| v * friction);
+            }
+
+            ke += (0.5 * (ke_vel[0] * ke_vel[0] + ke_vel[1] * ke_vel[1] + ke_vel[2] * ke_vel[2]) * mass) as f64;
+            pe += particle_pe as f64;
+            capped += particle_capped as f64;
+            for a in 0..3 {
+                virial[a] += particle_virial[a] as f64;
+            }
+            updated[index].velocity = vel;
+            updated[index].last_acceleration = acc;
+        }
+
+        if params.integrator == 2 {
+            for (previous, particle) in self.previous_acc.iter_mut().zip(self.particles.iter()) {
+                *previous = particle.last_acceleration;
+            }
+        }
+        self.particles = updated;
+        Stat {
+            KE: ke as f32,
+            PE: pe as f32,
+            W_x: virial[0] as f32,
+            W_y: virial[1] as f32,
+            W_z: virial[2] as f32,
+            capped: capped as f32,
+            ..Stat::new()
+        }
+    }
+
+    /// the summed stats of the last substep in mU
+    pub fn stat(&self) -> Stat {
+        self.stat
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn set_particles(&mut self, particles: &[Particle]) {
+        assert_eq!(particles.len(), self.particles.len(), "expected one entry per particle");
+        self.particles = particles.to_vec();
+        self.previous_acc.fill([0.0; 3]);
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        self.simulation_box
+    }
+
+    pub fn boundary(&self) -> &Boundary {
+        &self.boundary
+    }
+
+    pub fn bin_size(&self) -> f32 {
+        self.params.bin_size()
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.stats_history.temperature()
+    }
+
+    /// writes the stats history to csv, returns the file name
+    pub fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        let file_name = self.output.stats_file_name();
+        self.stats_history.save(&file_name)?;
+        Ok(file_name)
+    }
+}
+
+/// outside the box, which only happens behind an open face
+fn is_absorbed(simulation_box: &SimulationBox, position: [f32; 3]) -> bool {
+    simulation_box.fractional_position(position).iter().any(|s| !(0.0..=1.0).contains(s))
+}
+
+/// v with its component along the unit normal flipped
+fn mirrored(v: [f32; 3], normal: [f32; 3]) -> [f32; 3] {
+    let along = v[0] * normal[0] + v[1] * normal[1] + v[2] * normal[2];
+    [0, 1, 2].map(|a| v[a] - 2.0 * along * normal[a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::integrator::IntegratorKind;
+    use crate::system::life::ForceModel;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// a small, dense and warm system with a single substep per frame
+    fn config(kind: IntegratorKind) -> Config {
+        let mut config = Config::default();
+        config.system.particles = 1000;
+        config.system.box_size = Some(4.0);
+        config.system.temperature = 100.0;
+        config.integrator.kind = kind;
+        config.integrator.substeps = 1;
+        config
+    }
+
+    fn backend(kind: IntegratorKind) -> ReferenceBackend {
+        ReferenceBackend::new(&config(kind)).expect("the test config runs on the cpu")
+    }
+
+    /// the force on every particle and the total energy from the kernels, summed over all
+    /// pairs instead of the bins
+    fn all_pairs(backend: &ReferenceBackend) -> (Vec<[f32; 3]>, f64) {
+        let mut forces = vec![[0.0f32; 3]; backend.particles.len()];
+        let mut energy = 0.0;
+        for (i, particle) in backend.particles.iter().enumerate() {
+            for (j, other) in backend.particles.iter().enumerate() {
+                let d = backend.simulation_box.minimum_image(
+                    [0, 1, 2].map(|a| particle.position[a] - other.position[a]),
+                    backend.boundary.periodic_axes(),
+                );
+                let dist = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                if i == j || dist <= 0.0 {
+                    continue;
+                }
+                let (type_i, type_j) = (particle.type_ as u32, other.type_ as u32);
+                let params = &backend.params;
+                if let Some(pair) = pair_force(params, &backend.pairs, &backend.tables, &backend.life_matrix, type_i, type_j, dist) {
+                    energy += pair.energy as f64;
+                    for a in 0..3 {
+                        forces[i][a] += pair.force * d[a] / dist;
+                    }
+                }
+            }
+        }
+        (forces, energy)
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32, what: &str) {
+        assert!((a - b).abs() <= tolerance, "{}: {} instead of {}", what, a, b);
+    }
+
+    #[test]
+    fn binning_lists_every_particle_once_in_its_bin() {
+        let mut backend = backend(IntegratorKind::VelocityVerlet);
+        backend.empty_bins();
+        backend.binning();
+        assert_eq!(*backend.cell_start.last().unwrap() as usize, backend.particles.len());
+        let mut seen = vec![false; backend.particles.len()];
+        for bin in 0..backend.cell_start.len() - 1 {
+            let (start, end) = (backend.cell_start[bin] as usize, backend.cell_start[bin + 1] as usize);
+            for &index in &backend.cell_particles[start..end] {
+                let particle = &backend.particles[index as usize];
+                assert_eq!(backend.bin_of(particle.position).and_then(|b| backend.wrap_bin(b)), Some(bin), "particle {} is in the wrong bin", index);
+                assert!(!seen[index as usize], "particle {} is binned twice", index);
+                seen[index as usize] = true;
+            }
+        }
+    }
+
+    /// the forces of one binned force pass and its PE against `all_pairs`
+    fn assert_matches_all_pairs(mut backend: ReferenceBackend) {
+        backend.empty_bins();
+        backend.binning();
+        let stat = backend.force();
+        let (forces, energy) = all_pairs(&backend);
+        let largest = forces.iter().flatten().fold(0.0f32, |a, f| a.max(f.abs()));
+        for (particle, force) in backend.particles.iter().zip(&forces) {
+            let mass = backend.masses[particle.type_ as usize];
+            for a in 0..3 {
+                assert_close(particle.last_acceleration[a] * mass, force[a], 1e-5 * largest, "force");
+            }
+        }
+        assert!((stat.PE as f64 - energy).abs() <= 1e-5 * energy.abs(), "PE: {} instead of {}", stat.PE, energy);
+    }
+
+    // the 27 bins around a particle have to contain every partner within the cutoff
+    #[test]
+    fn forces_and_energy_match_the_sum_over_all_pairs() {
+        assert_matches_all_pairs(backend(IntegratorKind::VelocityVerlet));
+    }
+
+    // beyond twice the cutoff the bins grow with the particle life radius
+    #[test]
+    fn particle_life_beyond_two_cutoffs_matches_the_sum_over_all_pairs() {
+        let mut config = config(IntegratorKind::VelocityVerlet);
+        config.system.box_size = Some(6.0);
+        config.force_field.model = ForceModel::ParticleLife;
+        config.force_field.life_radius = 2.5 * config.force_field.cutoff;
+        let mut backend = ReferenceBackend::new(&config).expect("particle life runs on the cpu");
+        assert!(backend.bin_size() >= config.force_field.life_radius);
+        // spread over the whole box, the initial grid keeps every pair within one bin of each other
+        let mut rng = StdRng::seed_from_u64(0);
+        for particle in backend.particles.iter_mut() {
+            particle.position = [(); 3].map(|_| rng.gen_range(-3.0..3.0));
+        }
+        assert_matches_all_pairs(backend);
+    }
+
+    /// runs one substep stage by stage and checks the positions after the drift and the
+    /// velocities after the force against the update rule of the integrator
+    fn assert_integrates(kind: IntegratorKind) {
+        let mut backend = backend(kind);
+        // one step first, so the particles start with an acceleration
+        backend.step();
+        let before = backend.particles.clone();
+        let previous_acc = backend.previous_acc.clone();
+        let dt = backend.params.dt;
+
+        backend.empty_bins();
+        backend.binning();
+        backend.drift();
+        for ((particle, old), previous) in backend.particles.iter().zip(&before).zip(&previous_acc) {
+            let (x, v, acc) = (old.position, old.velocity, old.last_acceleration);
+            let expected = [0, 1, 2].map(|a| match kind {
+                IntegratorKind::Leapfrog => x[a] + (v[a] + acc[a] * dt) * dt,
+                IntegratorKind::Beeman => x[a] + v[a] * dt + (4.0 * acc[a] - previous[a]) * dt * dt / 6.0,
+                _ => x[a] + v[a] * dt + 0.5 * acc[a] * dt * dt,
+            });
+            let d = backend.simulation_box.minimum_image([0, 1, 2].map(|a| particle.position[a] - expected[a]), [true; 3]);
+            for deviation in d {
+                assert_close(deviation, 0.0, 1e-6, "position");
+            }
+        }
+
+        backend.force();
+        for ((particle, old), previous) in backend.particles.iter().zip(&before).zip(&previous_acc) {
+            let (v, acc, new_acc) = (old.velocity, old.last_acceleration, particle.last_acceleration);
+            let expected = [0, 1, 2].map(|a| match kind {
+                IntegratorKind::Leapfrog => v[a] + acc[a] * dt,
+                IntegratorKind::Beeman => v[a] + (2.0 * new_acc[a] + 5.0 * acc[a] - previous[a]) * dt / 6.0,
+                _ => v[a] + 0.5 * (acc[a] + new_acc[a]) * dt,
+            });
+            for a in 0..3 {
+                assert_close(particle.velocity[a], expected[a], 1e-4 * expected[a].abs().max(1.0), "velocity");
+            }
+        }
+    }
+
+    #[test]
+    fn velocity_verlet_integrates() {
+        assert_integrates(IntegratorKind::VelocityVerlet);
+    }
+
+    #[test]
+    fn leapfrog_integrates() {
+        assert_integrates(IntegratorKind::Leapfrog);
+    }
+
+    #[test]
+    fn beeman_integrates() {
+        assert_integrates(IntegratorKind::Beeman);
+    }
+
+    #[test]
+    fn stat_sums_the_particles() {
+        let mut backend = backend(IntegratorKind::VelocityVerlet);
+        backend.step();
+        let stat = backend.stat();
+        let ke = backend
+            .particles
+            .iter()
+            .map(|p| 0.5 * backend.masses[p.type_ as usize] as f64 * p.velocity.iter().map(|v| (v * v) as f64).sum::<f64>())
+            .sum::<f64>();
+        let (_, pe) = all_pairs(&backend);
+        assert!(((stat.KE as f64 - ke) / ke).abs() < 1e-5, "KE: {} instead of {}", stat.KE, ke);
+        assert!(((stat.PE as f64 - pe) / pe).abs() < 1e-5, "PE: {} instead of {}", stat.PE, pe);
+    }
+}
@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::system::force_field::ForceField;
+
+/// How the Lennard-Jones interaction is brought to zero at the cutoff.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CutoffScheme {
+    /// V(r) for r < r_cut, energy jumps at the cutoff
+    Truncated = 0,
+    /// V(r) - V(r_cut), the energy is continuous, the force still jumps
+    Shifted = 1,
+    /// V(r) - V(r_cut) - (r - r_cut) V'(r_cut), energy and force go smoothly to zero
+    ForceShifted = 2,
+    /// V(r) S(r) with the CHARMM switching function S between r_switch and r_cut
+    Switched = 3,
+}
+
+impl CutoffScheme {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => CutoffScheme::Shifted,
+            2 => CutoffScheme::ForceShifted,
+            3 => CutoffScheme::Switched,
+            _ => CutoffScheme::Truncated,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cutoff {
+    pub scheme: CutoffScheme,
+    pub r_cut: f32,    // in nm
+    pub r_switch: f32, // in nm, only used by the switched scheme
+}
+
+impl Cutoff {
+    pub fn new(scheme: CutoffScheme, r_cut: f32, r_switch: f32) -> Self {
+        assert!(r_cut > 0.0, "the cutoff has to be positive");
+        assert!(
+            scheme != CutoffScheme::Switched || (r_switch > 0.0 && r_switch < r_cut),
+            "r_switch has to be between 0 and r_cut"
+        );
+        Self {
+            scheme,
+            r_cut,
+            r_switch,
+        }
+    }
+}
+
+pub fn lennard_jones(dist: f64, sigma: f64, epsilon: f64) -> f64 {
+    let r6 = (sigma / dist).powi(6);
+    4.0 * epsilon * (r6 * r6 - r6)
+}
+
+// -dV/dr
+pub fn lennard_jones_force(dist: f64, sigma: f64, epsilon: f64) -> f64 {
+    let r6 = (sigma / dist).powi(6);
+    24.0 * epsilon * (2.0 * r6 * r6 - r6) / dist
+}
+
+/// Long range corrections for the part of the LJ interaction beyond r_cut, assuming
+/// g(r) = 1 there. They belong to the plain truncated potential, which is what most
+/// published LJ data uses.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TailCorrection {
+    pub energy: f64,   // total, in mU
+    pub pressure: f64, // in mU / nm^3
+}
+
+impl TailCorrection {
+    /// `type_counts[i]` is the number of particles of type i
+    pub fn new(force_field: &ForceField, type_counts: &[u32], volume: f64, r_cut: f32) -> Self {
+        let n: u32 = type_counts.iter().sum();
+        if n == 0 || volume <= 0.0 {
+            return Self::default();
+        }
+        let n = n as f64;
+        let density = n / volume;
+        let r_cut = r_cut as f64;
+
+        let mut energy_sum = 0.0;
+        let mut pressure_sum = 0.0;
+        for i in 0..force_field.type_count() {
+            for j in 0..force_field.type_count() {
+                let x_i = type_counts[i as usize] as f64 / n;
+                let x_j = type_counts[j as usize] as f64 / n;
+                let pair = force_field.pair(i, j);
+                let sigma = pair.sigma as f64;
+                let sigma3 = sigma.powi(3);
+                let sr3 = (sigma / r_cut).powi(3);
+                let sr9 = sr3.powi(3);
+                energy_sum += x_i * x_j * pair.epsilon as f64 * sigma3 * (sr9 / 3.0 - sr3);
+                pressure_sum += x_i * x_j * pair.epsilon as f64 * sigma3 * (2.0 * sr9 / 3.0 - sr3);
+            }
+        }
+        Self {
+            energy: 8.0 / 3.0 * PI * n * density * energy_sum,
+            pressure: 16.0 / 3.0 * PI * density * density * pressure_sum,
+        }
+    }
+}
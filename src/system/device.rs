@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use wgpu::{Adapter, Device, Queue};
+
+use crate::system::config::GpuConfig;
+
+/// compute.wgsl binds 14 storage buffers, more than the 8 of `wgpu::Limits::default()`
+pub const MIN_STORAGE_BUFFERS: u32 = 14;
+
+/// The graphics API wgpu runs on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GraphicsApi {
+    /// every API of the platform, or the ones in the WGPU_BACKEND environment variable
+    All,
+    Vulkan,
+    Gl,
+    Dx12,
+    Metal,
+}
+
+impl GraphicsApi {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            GraphicsApi::All => wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+            GraphicsApi::Vulkan => wgpu::Backends::VULKAN,
+            GraphicsApi::Gl => wgpu::Backends::GL,
+            GraphicsApi::Dx12 => wgpu::Backends::DX12,
+            GraphicsApi::Metal => wgpu::Backends::METAL,
+        }
+    }
+}
+
+pub fn create_instance(gpu: &GpuConfig) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: gpu.api.backends(),
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+/// discrete gpus first, the software adapter last
+fn preference(device_type: wgpu::DeviceType) -> u32 {
+    match device_type {
+        wgpu::DeviceType::DiscreteGpu => 0,
+        wgpu::DeviceType::IntegratedGpu => 1,
+        wgpu::DeviceType::VirtualGpu => 2,
+        wgpu::DeviceType::Other => 3,
+        wgpu::DeviceType::Cpu => 4,
+    }
+}
+
+fn describe(adapter: &Adapter) -> String {
+    let info = adapter.get_info();
+    format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+/// Picks the adapter the config asks for: on `gpu.api`, able to present to `surface`,
+/// with `gpu.adapter` in its name (ignoring case), the software one if `gpu.fallback` is
+/// set and with enough storage buffers. Hardware adapters are preferred, without one the
+/// software adapter is taken with a warning.
+pub fn select_adapter(instance: &wgpu::Instance, gpu: &GpuConfig, surface: Option<&wgpu::Surface>) -> Result<Adapter, Box<dyn Error>> {
+    let mut adapters: Vec<Adapter> = instance.enumerate_adapters(gpu.api.backends()).collect();
+    if adapters.is_empty() {
+        return Err(format!("no adapter found for gpu.api = {:?}", gpu.api).into());
+    }
+    let found = adapters.iter().map(describe).collect::<Vec<_>>().join(", ");
+
+    let mut rejected = Vec::new();
+    let mut filter = |adapters: &mut Vec<Adapter>, keep: &dyn Fn(&Adapter) -> bool, reason: String| {
+        let before = adapters.len();
+        adapters.retain(|adapter| keep(adapter));
+        if adapters.len() < before {
+            rejected.push(reason);
+        }
+    };
+    if let Some(surface) = surface {
+        filter(&mut adapters, &|a| a.is_surface_supported(surface), "can not present to the window".to_string());
+    }
+    if let Some(name) = &gpu.adapter {
+        let name = name.to_lowercase();
+        filter(
+            &mut adapters,
+            &|a| a.get_info().name.to_lowercase().contains(&name),
+            format!("name does not contain \"{}\"", name),
+        );
+    }
+    if gpu.fallback {
+        filter(&mut adapters, &|a| a.get_info().device_type == wgpu::DeviceType::Cpu, "not a software adapter".to_string());
+    }
+    filter(
+        &mut adapters,
+        &|a| a.limits().max_storage_buffers_per_shader_stage >= MIN_STORAGE_BUFFERS,
+        format!("fewer than {} storage buffers per shader stage", MIN_STORAGE_BUFFERS),
+    );
+
+    adapters.sort_by_key(|a| preference(a.get_info().device_type));
+    let adapter = adapters.into_iter().next().ok_or_else(|| {
+        format!(
+            "no suitable adapter among {} (rejected: {}), see --list-adapters",
+            found,
+            rejected.join("; ")
+        )
+    })?;
+    if adapter.get_info().device_type == wgpu::DeviceType::Cpu && !gpu.fallback {
+        println!("warning: no hardware adapter found, using the software adapter");
+    }
+    println!("adapter: {}", describe(&adapter));
+    Ok(adapter)
+}
+
+pub async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), Box<dyn Error>> {
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                // the particle update shader binds more than the default 8 storage buffers
+                limits: wgpu::Limits {
+                    max_storage_buffers_per_shader_stage: adapter.limits().max_storage_buffers_per_shader_stage,
+                    ..wgpu::Limits::default()
+                },
+                label: None,
+            },
+            None,
+        )
+        .await?;
+    Ok((device, queue))
+}
+
+/// Sets up a device without a surface, for the headless runner and the library.
+pub async fn create_device(gpu: &GpuConfig) -> Result<(Adapter, Device, Queue), Box<dyn Error>> {
+    let instance = create_instance(gpu);
+    let adapter = select_adapter(&instance, gpu, None)?;
+    let (device, queue) = request_device(&adapter).await?;
+    Ok((adapter, device, queue))
+}
+
+/// Every adapter on `gpu.api` with the limits the simulation depends on, for --list-adapters.
+pub fn list_adapters(gpu: &GpuConfig) -> String {
+    let instance = create_instance(gpu);
+    let mut text = String::new();
+    let adapters: Vec<Adapter> = instance.enumerate_adapters(gpu.api.backends()).collect();
+    if adapters.is_empty() {
+        return format!("no adapter found for gpu.api = {:?}\n", gpu.api);
+    }
+    for (i, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        let limits = adapter.limits();
+        let usable = limits.max_storage_buffers_per_shader_stage >= MIN_STORAGE_BUFFERS;
+        writeln!(text, "{}: {}", i, info.name).unwrap();
+        writeln!(text, "    backend: {:?}, type: {:?}", info.backend, info.device_type).unwrap();
+        writeln!(text, "    vendor: {:#06x}, device: {:#06x}, driver: {} {}", info.vendor, info.device, info.driver, info.driver_info).unwrap();
+        writeln!(
+            text,
+            "    storage buffers per stage: {}, storage binding size: {} MiB, buffer size: {} MiB",
+            limits.max_storage_buffers_per_shader_stage,
+            limits.max_storage_buffer_binding_size >> 20,
+            limits.max_buffer_size >> 20
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "    workgroup: {} invocations, {} workgroups per dimension",
+            limits.max_compute_invocations_per_workgroup, limits.max_compute_workgroups_per_dimension
+        )
+        .unwrap();
+        writeln!(text, "    usable: {}", if usable { "yes" } else { "no, too few storage buffers" }).unwrap();
+    }
+    text
+}
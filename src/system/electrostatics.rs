@@ -0,0 +1,322 @@
+use std::f64::consts::PI;
+
+use crate::system::consts::*;
+use crate::system::simulation_box::SimulationBox;
+
+/*
+Coulomb interactions with the Ewald summation, all energies are in mU:
+
+E = E_real + E_recip + E_self
+E_real  = sum_(i<j, r < r_cut) k_e q_i q_j erfc(alpha r) / r
+E_recip = sum_(half k space) A(k) |S(k)|^2,  A(k) = 4 pi k_e / V * exp(-k^2 / 4 alpha^2) / k^2
+E_self  = -k_e alpha / sqrt(pi) sum_i q_i^2
+
+with S(k) = sum_i q_i exp(i k.r_i). Only one of k and -k is stored, which is why there
+is no factor 1/2 in E_recip. The system has to be neutral.
+ */
+
+// one reciprocal vector with its prefactor A(k), the structure factor is written by ewald.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KVector {
+    pub kx: f32, // in 1 / nm
+    pub ky: f32,
+    pub kz: f32,
+    pub prefactor: f32, // in mU * nm
+    pub s_re: f32,      // in e
+    pub s_im: f32,
+    align: [f32; 2],
+}
+unsafe impl bytemuck::Pod for KVector {}
+unsafe impl bytemuck::Zeroable for KVector {}
+
+// more than this gets too slow with the direct k-space sum
+pub const MAX_KVECTORS: usize = 4096;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ewald {
+    pub enabled: bool,
+    pub alpha: f32, // splitting parameter in 1 / nm
+    pub kmax: i32,  // largest |n| of k = 2 pi n / L
+}
+
+impl Ewald {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            alpha: 0.0,
+            kmax: 0,
+        }
+    }
+
+    /// picks alpha and kmax so that both the real space and the reciprocal sum are
+    /// truncated at (roughly) the same relative error. If that takes more than MAX_KVECTORS
+    /// k vectors, kmax is lowered to fit and alpha is balanced for the smaller k space, which
+    /// gives a larger error.
+    pub fn with_tolerance(cutoff: f32, simulation_box: &SimulationBox, tolerance: f32) -> Self {
+        let s = (-tolerance.ln()).sqrt();
+        let alpha = s / cutoff;
+        let k_cut = 2.0 * alpha * s;
+        let length = longest_length(simulation_box);
+        let kmax = (k_cut * length / (2.0 * std::f32::consts::PI)).ceil() as i32;
+        let ewald = Self {
+            enabled: true,
+            alpha,
+            kmax,
+        };
+        let count = ewald.kvector_count(simulation_box);
+        if count <= MAX_KVECTORS {
+            return ewald;
+        }
+
+        // the largest kmax that fits, k_cut = 2 alpha s with alpha = s / cutoff again
+        let (mut low, mut high) = (0, kmax);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            let smaller = Self { kmax: middle, ..ewald };
+            if smaller.kvector_count(simulation_box) <= MAX_KVECTORS {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        let k_cut = 2.0 * std::f32::consts::PI * low as f32 / length;
+        let s = (0.5 * k_cut * cutoff).sqrt();
+        println!(
+            "warning: a tolerance of {:e} takes {} k vectors, only {} fit, the Ewald sum runs with a tolerance of {:.1e}",
+            tolerance,
+            count,
+            MAX_KVECTORS,
+            (-s * s).exp()
+        );
+        Self {
+            enabled: true,
+            alpha: s / cutoff,
+            kmax: low,
+        }
+    }
+
+    /// all k vectors with |k| <= 2 pi kmax / L in one half of k space, L is the longest cell
+    /// vector. For a cubic box these are the vectors with |n| <= kmax. Capped at MAX_KVECTORS.
+    pub fn kvectors(&self, simulation_box: &SimulationBox) -> Vec<KVector> {
+        let mut kvectors = Vec::new();
+        let volume = simulation_box.volume();
+        let alpha = self.alpha as f64;
+        self.for_each_k(simulation_box, |k, k2| {
+            let prefactor = 4.0 * PI * COULOMB_CONSTANT as f64 / volume * (-k2 / (4.0 * alpha * alpha)).exp() / k2;
+            kvectors.push(KVector {
+                kx: k[0] as f32,
+                ky: k[1] as f32,
+                kz: k[2] as f32,
+                prefactor: prefactor as f32,
+                ..Default::default()
+            });
+        });
+        if kvectors.len() > MAX_KVECTORS {
+            // only after the box deformed since `with_tolerance`, drop the vectors with the
+            // smallest contribution
+            println!(
+                "warning: {} k vectors requested, only the {} largest are used",
+                kvectors.len(),
+                MAX_KVECTORS
+            );
+            kvectors.sort_by(|a, b| b.prefactor.total_cmp(&a.prefactor));
+            kvectors.truncate(MAX_KVECTORS);
+        }
+        kvectors
+    }
+
+    fn kvector_count(&self, simulation_box: &SimulationBox) -> usize {
+        let mut count = 0;
+        self.for_each_k(simulation_box, |_, _| count += 1);
+        count
+    }
+
+    /// calls `f` with every k (in 1 / nm) of `kvectors` and k^2
+    fn for_each_k(&self, simulation_box: &SimulationBox, mut f: impl FnMut([f64; 3], f64)) {
+        if !self.enabled {
+            return;
+        }
+        let k_cut = 2.0 * PI * self.kmax as f64 / longest_length(simulation_box) as f64;
+        // k = 2 pi (n_x a* + n_y b* + n_z c*) with the reciprocal vectors (rows of the inverse cell matrix),
+        // n_i = k . a_i / 2 pi can not be larger than k_cut |a_i| / 2 pi
+        let reciprocal = reciprocal_vectors(simulation_box);
+        let n_max = simulation_box.vectors().map(|v| (k_cut * norm(v) as f64 / (2.0 * PI)).ceil() as i32);
+        for nx in 0..=n_max[0] {
+            for ny in -n_max[1]..=n_max[1] {
+                for nz in -n_max[2]..=n_max[2] {
+                    // keep one of every (k, -k) pair
+                    if nx == 0 && (ny < 0 || (ny == 0 && nz <= 0)) {
+                        continue;
+                    }
+                    let mut k = [0.0; 3];
+                    for a in 0..3 {
+                        k[a] = 2.0 * PI * (nx as f64 * reciprocal[0][a] + ny as f64 * reciprocal[1][a] + nz as f64 * reciprocal[2][a]);
+                    }
+                    let k2 = k[0] * k[0] + k[1] * k[1] + k[2] * k[2];
+                    if k2 <= k_cut * k_cut * (1.0 + 1e-9) {
+                        f(k, k2);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// energies of one configuration, in mU
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CoulombEnergy {
+    pub real: f64,
+    pub recip: f64, // includes the self energy
+}
+
+impl CoulombEnergy {
+    pub fn total(&self) -> f64 {
+        self.real + self.recip
+    }
+}
+
+fn norm(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn longest_length(simulation_box: &SimulationBox) -> f32 {
+    simulation_box.vectors().iter().map(|v| norm(*v)).fold(0.0, f32::max)
+}
+
+/// a*, b*, c* with a_i . b*_j = delta_ij, the rows of the inverse of the cell matrix
+fn reciprocal_vectors(simulation_box: &SimulationBox) -> [[f64; 3]; 3] {
+    let [lx, ly, lz] = simulation_box.lengths.map(|l| l as f64);
+    let [xy, xz, yz] = simulation_box.tilt.map(|t| t as f64);
+    [
+        [1.0 / lx, -xy / (lx * ly), (xy * yz - xz * ly) / (lx * ly * lz)],
+        [0.0, 1.0 / ly, -yz / (ly * lz)],
+        [0.0, 0.0, 1.0 / lz],
+    ]
+}
+
+/// Ewald sum on the cpu in double precision, same split as the gpu version.
+/// Positions are in nm inside the (fully periodic) simulation box.
+pub fn ewald_energy(
+    positions: &[[f32; 3]],
+    charges: &[f32],
+    simulation_box: &SimulationBox,
+    cutoff: f32,
+    ewald: &Ewald,
+) -> CoulombEnergy {
+    let n = positions.len();
+    let alpha = ewald.alpha as f64;
+    let k_e = COULOMB_CONSTANT as f64;
+
+    let mut real = 0.0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let mut d = [0.0; 3];
+            for a in 0..3 {
+                d[a] = positions[i][a] - positions[j][a];
+            }
+            let d = simulation_box.minimum_image(d, [true; 3]);
+            let r = (norm(d) as f64).max(1e-12);
+            if r < cutoff as f64 {
+                real += k_e * (charges[i] * charges[j]) as f64 * erfc(alpha * r) / r;
+            }
+        }
+    }
+
+    let mut recip = 0.0;
+    for k in ewald.kvectors(simulation_box) {
+        let (mut s_re, mut s_im) = (0.0, 0.0);
+        for i in 0..n {
+            let kr = k.kx as f64 * positions[i][0] as f64
+                + k.ky as f64 * positions[i][1] as f64
+                + k.kz as f64 * positions[i][2] as f64;
+            s_re += charges[i] as f64 * kr.cos();
+            s_im += charges[i] as f64 * kr.sin();
+        }
+        recip += k.prefactor as f64 * (s_re * s_re + s_im * s_im);
+    }
+    let q2: f64 = charges.iter().map(|q| (*q as f64) * (*q as f64)).sum();
+    recip -= k_e * alpha / PI.sqrt() * q2;
+
+    CoulombEnergy { real, recip }
+}
+
+/// Plain Coulomb sum over all periodic images with |n| <= shells, added shell by shell.
+/// Converges (slowly) to the Ewald result for neutral cells without a dipole moment.
+pub fn direct_sum_energy(positions: &[[f32; 3]], charges: &[f32], simulation_box: &SimulationBox, shells: i32) -> f64 {
+    let n = positions.len();
+    let vectors = simulation_box.vectors().map(|v| v.map(|x| x as f64));
+    let k_e = COULOMB_CONSTANT as f64;
+    let mut energy = 0.0;
+    for nx in -shells..=shells {
+        for ny in -shells..=shells {
+            for nz in -shells..=shells {
+                if nx * nx + ny * ny + nz * nz > shells * shells {
+                    continue;
+                }
+                let mut shift = [0.0; 3];
+                for a in 0..3 {
+                    shift[a] = nx as f64 * vectors[0][a] + ny as f64 * vectors[1][a] + nz as f64 * vectors[2][a];
+                }
+                for i in 0..n {
+                    for j in 0..n {
+                        if i == j && nx == 0 && ny == 0 && nz == 0 {
+                            continue;
+                        }
+                        let mut r2 = 0.0;
+                        for a in 0..3 {
+                            let d = positions[i][a] as f64 - positions[j][a] as f64 + shift[a];
+                            r2 += d * d;
+                        }
+                        energy += 0.5 * k_e * (charges[i] * charges[j]) as f64 / r2.sqrt();
+                    }
+                }
+            }
+        }
+    }
+    energy
+}
+
+/// rock salt lattice with `cells` conventional cells of side `lattice` (in nm) per axis,
+/// returns the positions (inside [-L/2, L/2)) and charges
+pub fn rock_salt(cells: u32, lattice: f32) -> (Vec<[f32; 3]>, Vec<f32>) {
+    let half = lattice / 2.0;
+    let side = 2 * cells;
+    let offset = cells as f32 * lattice / 2.0;
+    let mut positions = Vec::new();
+    let mut charges = Vec::new();
+    for i in 0..side {
+        for j in 0..side {
+            for k in 0..side {
+                positions.push([
+                    i as f32 * half - offset,
+                    j as f32 * half - offset,
+                    k as f32 * half - offset,
+                ]);
+                charges.push(if (i + j + k) % 2 == 0 { 1.0 } else { -1.0 });
+            }
+        }
+    }
+    (positions, charges)
+}
+
+pub const MADELUNG_NACL: f64 = 1.747564594633;
+
+/// Compares the cpu Ewald sum against the analytic Madelung energy of a NaCl crystal.
+/// Returns the relative error.
+pub fn validate_rock_salt(cells: u32, lattice: f32, ewald: &Ewald, cutoff: f32) -> f64 {
+    let (positions, charges) = rock_salt(cells, lattice);
+    let simulation_box = SimulationBox::cubic(cells as f32 * lattice);
+    let energy = ewald_energy(&positions, &charges, &simulation_box, cutoff, ewald).total();
+    // every ion pair has the energy -M k_e / r0
+    let pairs = positions.len() as f64 / 2.0;
+    let exact = -pairs * MADELUNG_NACL * COULOMB_CONSTANT as f64 / (lattice as f64 / 2.0);
+    ((energy - exact) / exact).abs()
+}
+
+/// complementary error function, Abramowitz & Stegun 7.1.26 (same as in compute.wgsl)
+pub fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
@@ -0,0 +1,240 @@
+use std::fmt::{Debug, Display};
+
+use crate::system::consts::*;
+use crate::system::cutoff::{self, Cutoff};
+use crate::system::params::Atom;
+use crate::system::tables::PairTable;
+
+// every table is resampled on at least this many equidistant points for the gpu
+const MIN_TABLE_POINTS: usize = 1000;
+// size of the table buffer on the gpu, in (V, F) entries
+pub const MAX_TABLE_ENTRIES: usize = 65536;
+
+// pair parameters as they are uploaded to the gpu, one entry for every (type_i, type_j)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pair {
+    pub sigma: f32,   // in nm
+    pub epsilon: f32, // nm^2 * u * ps^-2
+    pub e_shift: f32, // V(r_cut) in nm^2 * u * ps^-2
+    pub f_shift: f32, // -V'(r_cut) in nm * u * ps^-2
+    pub table_offset: u32, // first entry of this pair in the table buffer
+    pub table_len: u32,    // 0 if the pair has no table
+    pub table_r_min: f32,  // in nm
+    pub table_inv_dr: f32, // in 1 / nm
+}
+unsafe impl bytemuck::Pod for Pair {}
+unsafe impl bytemuck::Zeroable for Pair {}
+
+impl Pair {
+    pub fn new(sigma: f32, epsilon: f32) -> Self {
+        Self {
+            sigma,
+            epsilon,
+            ..Default::default()
+        }
+    }
+
+    /// fills in the energy and force at the cutoff, used by the shifted cutoff schemes
+    pub fn with_cutoff(self, r_cut: f32) -> Self {
+        let (sigma, epsilon, r_cut) = (self.sigma as f64, self.epsilon as f64, r_cut as f64);
+        Self {
+            e_shift: cutoff::lennard_jones(r_cut, sigma, epsilon) as f32,
+            f_shift: cutoff::lennard_jones_force(r_cut, sigma, epsilon) as f32,
+            ..self
+        }
+    }
+}
+
+/// All atom types of the system plus the rules to combine them into pair parameters.
+///
+/// The pair parameters follow the Lorentz–Berthelot mixing rules
+/// (sigma_ij = (sigma_i + sigma_j) / 2, epsilon_ij = sqrt(epsilon_i * epsilon_j))
+/// unless an explicit override is set for that pair.
+#[derive(Clone)]
+pub struct ForceField {
+    pub atoms: Vec<Atom>,
+    overrides: Vec<Option<Pair>>,
+    tables: Vec<Option<PairTable>>,
+}
+
+impl ForceField {
+    pub fn new(atoms: Vec<Atom>) -> Self {
+        let n = atoms.len();
+        assert!(n > 0, "a force field needs at least one atom type");
+        Self {
+            atoms,
+            overrides: vec![None; n * n],
+            tables: vec![None; n * n],
+        }
+    }
+
+    /// He, Ne, Ar and Kr with their literature LJ parameters
+    pub fn noble_gases() -> Self {
+        Self::new(vec![
+            Atom::new(0.2556, 4.0026, 0, 0.2556, 10.22 * BOLTZMANN_CONSTANT_MU),
+            Atom::new(0.2749, 20.180, 0, 0.2749, 35.60 * BOLTZMANN_CONSTANT_MU),
+            Atom::new(0.3405, 39.948, 0, 0.3405, 119.8 * BOLTZMANN_CONSTANT_MU),
+            Atom::new(0.3633, 83.798, 0, 0.3633, 166.7 * BOLTZMANN_CONSTANT_MU),
+        ])
+    }
+
+    /// Na+ / Cl- with the Smith & Dang LJ parameters, for molten salt and ionic crystals
+    pub fn sodium_chloride() -> Self {
+        Self::new(vec![
+            Atom::new(0.2350, 22.990, 1, 0.2350, 0.5439),
+            Atom::new(0.4400, 35.453, -1, 0.4400, 0.4184),
+        ])
+    }
+
+    pub fn is_charged(&self) -> bool {
+        self.atoms.iter().any(|a| a.charge != 0)
+    }
+
+    pub fn type_count(&self) -> u32 {
+        self.atoms.len() as u32
+    }
+
+    pub fn atom(&self, type_: u32) -> &Atom {
+        &self.atoms[type_ as usize]
+    }
+
+    /// overrides the mixing rule for the pair (i, j), the override is applied symmetrically
+    pub fn set_override(&mut self, i: u32, j: u32, sigma: f32, epsilon: f32) {
+        let n = self.type_count();
+        let pair = Pair::new(sigma, epsilon);
+        self.overrides[(i * n + j) as usize] = Some(pair);
+        self.overrides[(j * n + i) as usize] = Some(pair);
+    }
+
+    pub fn clear_override(&mut self, i: u32, j: u32) {
+        let n = self.type_count();
+        self.overrides[(i * n + j) as usize] = None;
+        self.overrides[(j * n + i) as usize] = None;
+    }
+
+    /// sets the tabulated potential of the pair (i, j), used when the force model is Tabulated.
+    /// Pairs without a table keep using Lennard-Jones.
+    pub fn set_table(&mut self, i: u32, j: u32, table: PairTable) {
+        let check = table.check_consistency();
+        if check.max_error > 0.01 {
+            println!(
+                "warning: the force of table ({}, {}) does not match -dV/dr, error of {:.1}% at r = {} nm",
+                i,
+                j,
+                check.max_error * 100.0,
+                check.at_r
+            );
+        }
+        let n = self.type_count();
+        self.tables[(i * n + j) as usize] = Some(table.clone());
+        self.tables[(j * n + i) as usize] = Some(table);
+    }
+
+    pub fn has_tables(&self) -> bool {
+        self.tables.iter().any(|t| t.is_some())
+    }
+
+    pub fn table(&self, i: u32, j: u32) -> Option<&PairTable> {
+        self.tables[(i * self.type_count() + j) as usize].as_ref()
+    }
+
+    pub fn pair(&self, i: u32, j: u32) -> Pair {
+        let n = self.type_count();
+        if let Some(pair) = self.overrides[(i * n + j) as usize] {
+            return pair;
+        }
+        let a = self.atom(i);
+        let b = self.atom(j);
+        Pair::new(0.5 * (a.sigma + b.sigma), (a.epsilon * b.epsilon).sqrt())
+    }
+
+    /// the full n x n pair table in row major order (index = type_i * n + type_j),
+    /// the table offsets point into serialize_tables
+    pub fn pairs(&self, cutoff: &Cutoff) -> Vec<Pair> {
+        let n = self.type_count();
+        let mut pairs = Vec::with_capacity((n * n) as usize);
+        let mut offset = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let mut pair = self.pair(i, j).with_cutoff(cutoff.r_cut);
+                if let Some(table) = self.table(i, j) {
+                    if table.r_max() > cutoff.r_cut {
+                        println!(
+                            "warning: table ({}, {}) reaches to {} nm, beyond the cutoff of {} nm",
+                            i,
+                            j,
+                            table.r_max(),
+                            cutoff.r_cut
+                        );
+                    }
+                    let points = table.r.len().max(MIN_TABLE_POINTS);
+                    pair.table_offset = offset;
+                    pair.table_len = points as u32;
+                    pair.table_r_min = table.r_min();
+                    pair.table_inv_dr = (points - 1) as f32 / (table.r_max() - table.r_min());
+                    offset += points as u32;
+                }
+                pairs.push(pair);
+            }
+        }
+        pairs
+    }
+
+    /// all tables as (V, F) pairs in the order of pairs(), never empty
+    pub fn serialize_tables(&self) -> Vec<[f32; 2]> {
+        let mut data = Vec::new();
+        for table in self.tables.iter().flatten() {
+            data.extend(table.resample(table.r.len().max(MIN_TABLE_POINTS)));
+        }
+        if data.is_empty() {
+            data.push([0.0; 2]);
+        }
+        assert!(
+            data.len() <= MAX_TABLE_ENTRIES,
+            "the pair tables need {} entries, only {} fit on the gpu",
+            data.len(),
+            MAX_TABLE_ENTRIES
+        );
+        data
+    }
+
+    /// the smallest sigma of all pairs in nm
+    pub fn min_sigma(&self) -> f32 {
+        let n = self.type_count();
+        (0..n * n).map(|k| self.pair(k / n, k % n).sigma).fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn mean_mass(&self) -> f32 {
+        self.atoms.iter().map(|a| a.mass).sum::<f32>() / self.atoms.len() as f32
+    }
+
+    pub fn serialize_atoms(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.atoms)
+    }
+}
+
+impl Default for ForceField {
+    fn default() -> Self {
+        // fake helium with a much deeper well, so it condenses at low temperatures
+        let helium = Atom::new(0.2551, 4.0, 0, 0.2551, 10.22 * BOLTZMANN_CONSTANT * 500.0);
+        Self::new(vec![helium; 4])
+    }
+}
+
+impl Display for ForceField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ForceField")
+            .field("atoms", &self.atoms)
+            .field("pairs", &(0..self.type_count() * self.type_count())
+                .map(|i| self.pair(i / self.type_count(), i % self.type_count()))
+                .collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Debug for ForceField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
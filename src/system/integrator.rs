@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// How positions and velocities are advanced, see `varlets.wgsl` and `compute.wgsl`.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegratorKind {
+    /// positions with the old acceleration, velocities with the mean of the old and the new one
+    VelocityVerlet = 0,
+    /// velocities live half a step ahead of the positions, the kinetic energy is estimated at the full step
+    Leapfrog = 1,
+    /// third order positions from the last two accelerations (Beeman 1976)
+    Beeman = 2,
+    /// r-RESPA, velocity Verlet on the short range forces inside an outer step for the reciprocal Ewald forces
+    Respa = 3,
+    /// used by the minimizer, the positions follow the velocities set in `minimize.wgsl`
+    #[serde(skip)]
+    Minimize = 4,
+}
+
+impl IntegratorKind {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => IntegratorKind::Leapfrog,
+            2 => IntegratorKind::Beeman,
+            3 => IntegratorKind::Respa,
+            4 => IntegratorKind::Minimize,
+            _ => IntegratorKind::VelocityVerlet,
+        }
+    }
+}
+
+/// One dispatch of a substep, `ComputeSet` maps them to pipelines and bind groups.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// first half of the slow kick of an outer r-RESPA step
+    SlowKick,
+    EmptyBins,
+    Binning,
+    /// position update and boundaries
+    Drift,
+    /// SHAKE, moves the drifted particles back onto their constraints
+    Shake,
+    /// structure factors of the drifted positions
+    Ewald,
+    Bonded,
+    /// forces and the velocity update
+    Force,
+    /// structure factors of the positions written by the force pass
+    SlowEwald,
+    /// reciprocal forces and the second half of the slow kick
+    SlowForce,
+    /// RATTLE, removes the velocities along the constraints after the velocity update
+    Rattle,
+    /// minimizer step on the forces of the force pass
+    Minimize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Integrator {
+    pub kind: IntegratorKind,
+    pub respa_steps: u32, // inner steps per outer step, only used by r-RESPA, they divide the substeps of a frame
+}
+
+impl Integrator {
+    pub fn new(kind: IntegratorKind) -> Self {
+        Self { kind, respa_steps: 4 }
+    }
+
+    pub fn respa(respa_steps: u32) -> Self {
+        assert!(respa_steps > 0, "an outer step needs at least one inner step");
+        Self {
+            kind: IntegratorKind::Respa,
+            respa_steps,
+        }
+    }
+
+    /// The passes of substep `step`, counted from the start of the run. Ewald, the
+    /// bonded terms and the constraints are only dispatched if the system has them.
+    pub fn passes(&self, step: u32, ewald: bool, bonded: bool, constraints: bool) -> Vec<Pass> {
+        let respa = self.kind == IntegratorKind::Respa && ewald;
+        let mut passes = Vec::new();
+        if respa && step.is_multiple_of(self.respa_steps) {
+            passes.push(Pass::SlowKick);
+        }
+        passes.extend([Pass::EmptyBins, Pass::Binning, Pass::Drift]);
+        if constraints {
+            passes.push(Pass::Shake);
+        }
+        if ewald && !respa {
+            passes.push(Pass::Ewald);
+        }
+        if bonded {
+            passes.push(Pass::Bonded);
+        }
+        passes.push(Pass::Force);
+        if respa && (step + 1).is_multiple_of(self.respa_steps) {
+            passes.extend([Pass::SlowEwald, Pass::SlowForce]);
+        }
+        if constraints {
+            passes.push(Pass::Rattle);
+        }
+        passes
+    }
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Self::new(IntegratorKind::VelocityVerlet)
+    }
+}
@@ -0,0 +1,91 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::system::consts::*;
+
+/// Which pair force `compute.wgsl` evaluates.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForceModel {
+    LennardJones = 0,
+    ParticleLife = 1,
+    /// interpolated from the pair tables, pairs without a table use Lennard-Jones
+    Tabulated = 2,
+}
+
+impl ForceModel {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => ForceModel::ParticleLife,
+            2 => ForceModel::Tabulated,
+            _ => ForceModel::LennardJones,
+        }
+    }
+}
+
+/// Settings of the classic "particle life" model.
+///
+/// Every pair of types has an attraction value in [-1, 1], `matrix[i * n + j]` is how
+/// strongly type i is pulled towards type j, so the matrix does not have to be symmetric.
+/// Below `beta * radius` all particles repel each other, between `beta * radius` and
+/// `radius` the force rises linearly to the attraction value and falls back to zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleLife {
+    size: u32,
+    matrix: Vec<f32>,
+    pub radius: f32,   // in nm
+    pub beta: f32,     // fraction of radius where the repulsion stops
+    pub force: f32,    // in nm * amu / ps^2
+    pub friction: f32, // in 1 / ps
+    pub seed: u64,
+}
+
+impl ParticleLife {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            matrix: vec![0.0; (size * size) as usize],
+            radius: NEIGHBORHOOD_SIZE,
+            beta: 0.3,
+            force: 50.0,
+            friction: 5.0,
+            seed: 0,
+        }
+    }
+
+    pub fn random(size: u32, seed: u64) -> Self {
+        let mut life = Self::new(size);
+        life.randomize(seed);
+        life
+    }
+
+    /// fills the matrix with uniform values in [-1, 1], the same seed always gives the same matrix
+    pub fn randomize(&mut self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for value in self.matrix.iter_mut() {
+            *value = rng.gen_range(-1.0..=1.0);
+        }
+        self.seed = seed;
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn get(&self, i: u32, j: u32) -> f32 {
+        self.matrix[(i * self.size + j) as usize]
+    }
+
+    pub fn set(&mut self, i: u32, j: u32, value: f32) {
+        self.matrix[(i * self.size + j) as usize] = value.clamp(-1.0, 1.0);
+    }
+
+    pub fn get_mut(&mut self, i: u32, j: u32) -> &mut f32 {
+        &mut self.matrix[(i * self.size + j) as usize]
+    }
+
+    pub fn serialize(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.matrix)
+    }
+}
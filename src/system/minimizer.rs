@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// How the energy is minimized, see `minimize.wgsl`.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MinimizerKind {
+    /// moves every particle along its force, the largest move adapts to the energy
+    SteepestDescent = 1,
+    /// fast inertial relaxation engine (Bitzek et al. 2006), damped dynamics downhill
+    Fire = 2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Minimizer {
+    pub kind: MinimizerKind,
+    pub force_tolerance: f32,  // in mU / nm, done once the largest force is below
+    pub energy_tolerance: f32, // done once the relative energy change of a step is below
+    pub max_steps: u32,
+    pub step: f32,     // steepest descent: first largest move in nm, FIRE: first dt in ps
+    pub step_max: f32, // upper bound of the step
+}
+
+impl Minimizer {
+    pub fn steepest_descent(force_tolerance: f32) -> Self {
+        Self {
+            kind: MinimizerKind::SteepestDescent,
+            force_tolerance,
+            energy_tolerance: 1e-6,
+            max_steps: 10000,
+            step: 0.01,
+            step_max: 0.05,
+        }
+    }
+
+    pub fn fire(force_tolerance: f32) -> Self {
+        Self {
+            kind: MinimizerKind::Fire,
+            force_tolerance,
+            energy_tolerance: 1e-6,
+            max_steps: 10000,
+            step: 1e-3,
+            step_max: 1e-2,
+        }
+    }
+}
+
+impl Default for Minimizer {
+    fn default() -> Self {
+        Self::fire(10.0)
+    }
+}
+
+/// Why the minimizer stopped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MinimizerStatus {
+    Running,
+    /// the largest force is below the tolerance
+    ForceConverged,
+    /// the energy no longer changes
+    EnergyConverged,
+    /// stopped after `max_steps` without converging
+    MaxSteps,
+}
+
+/// The minimizer on the gpu, the first part is written by `minimize.wgsl` and the control
+/// part at the end by `ComputeSet::minimize`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MinimizerState {
+    pub energy: f32,    // in mU
+    pub max_force: f32, // in mU / nm
+    pub power: f32,     // sum of F . v
+    pub v_norm: f32,
+    pub f_norm: f32,
+    pub alpha: f32,
+    pub step: f32,
+    pub positive_steps: u32,
+    pub steps: u32,
+    pub status: u32,
+    pub mixing: u32,
+    pub kind: u32,
+    pub force_tolerance: f32,
+    pub energy_tolerance: f32,
+    pub max_steps: u32,
+    pub step_max: f32,
+}
+unsafe impl bytemuck::Pod for MinimizerState {}
+unsafe impl bytemuck::Zeroable for MinimizerState {}
+
+impl MinimizerState {
+    pub fn new(minimizer: &Minimizer) -> Self {
+        Self {
+            step: minimizer.step,
+            kind: minimizer.kind as u32,
+            force_tolerance: minimizer.force_tolerance,
+            energy_tolerance: minimizer.energy_tolerance,
+            max_steps: minimizer.max_steps,
+            step_max: minimizer.step_max,
+            ..Default::default()
+        }
+    }
+
+    pub fn status(&self) -> MinimizerStatus {
+        match self.status {
+            1 => MinimizerStatus::ForceConverged,
+            2 => MinimizerStatus::EnergyConverged,
+            3 => MinimizerStatus::MaxSteps,
+            _ => MinimizerStatus::Running,
+        }
+    }
+}
@@ -1,6 +1,27 @@
+pub mod backend;
+pub mod barostat;
+pub mod boundary;
 pub mod compute_set;
+pub mod config;
 pub mod consts;
+pub mod constraints;
+pub mod cpu;
+pub mod cutoff;
+pub mod device;
+pub mod electrostatics;
+pub mod force_field;
+pub mod integrator;
+pub mod life;
+pub mod minimizer;
+pub mod neighbour_list;
 pub mod params;
 pub mod particle;
 pub mod stats;
+pub mod tables;
+pub mod thermostat;
+pub mod timestep;
+pub mod topology;
+pub mod trajectory;
+pub mod simulation;
+pub mod simulation_box;
 pub mod pipeline;
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::f32::consts::PI;
+
+/// The Verlet list on the gpu, see `neighbours.wgsl`. `check` writes the workgroup counts of
+/// the passes that rebin the particles and rebuild the list, they are dispatched indirectly
+/// from this buffer and stay zero while no particle moved further than half the skin and
+/// every list fits.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NeighbourListState {
+    pub cells: [u32; 3],     // a thread per bin, also the blocks of scan.wgsl
+    pub particles: [u32; 3], // a thread per particle
+    pub single: [u32; 3],    // one workgroup
+    pub rebuild: u32,        // set by `ComputeSet`, the next check rebuilds the list
+    pub rebuilds: u32,       // builds so far
+    pub max_neighbours: u32, // most neighbours a particle has in the list, found by the check
+    pub max_displacement: f32, // in nm, furthest a particle moved since the build before the last check
+    pub _padding: u32,
+}
+unsafe impl bytemuck::Pod for NeighbourListState {}
+unsafe impl bytemuck::Zeroable for NeighbourListState {}
+
+impl NeighbourListState {
+    // byte offsets of the indirect dispatches and the rebuild flag
+    pub const CELLS_OFFSET: u64 = 0;
+    pub const PARTICLES_OFFSET: u64 = 12;
+    pub const SINGLE_OFFSET: u64 = 24;
+    pub const REBUILD_OFFSET: u64 = 36;
+
+    /// builds the list at the first check
+    pub fn new() -> Self {
+        Self {
+            rebuild: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn size() -> wgpu::BufferAddress {
+        std::mem::size_of::<Self>() as wgpu::BufferAddress
+    }
+}
+
+/// in nm, the thickness of the bins: the 27 bins around a particle cover the cutoff sphere
+/// twice over and always the whole list radius, max(cutoff, life_radius) + skin
+pub fn bin_size(cutoff: f32, life_radius: f32, verlet_skin: f32) -> f32 {
+    (2.0 * cutoff).max(cutoff.max(life_radius) + verlet_skin)
+}
+
+/// Neighbours per particle the list has room for at the start, twice the average number
+/// within `radius` (in nm) at the density of the whole box, at least 32.
+pub fn initial_capacity(particles: u32, volume: f64, radius: f32) -> u32 {
+    let average = particles as f64 / volume * 4.0 / 3.0 * PI as f64 * (radius as f64).powi(3);
+    ((2.0 * average).ceil() as u32).max(32)
+}
+
+/// a quarter more room than the fullest list needed, so a slowly growing cluster does not
+/// trigger a reallocation every frame
+pub fn grown_capacity(max_neighbours: u32, capacity: u32) -> u32 {
+    (max_neighbours + max_neighbours / 4).max(capacity + 1)
+}
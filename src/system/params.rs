@@ -1,10 +1,21 @@
 use std::fmt::{Debug, Display};
 
-use crate::system::consts::*;
+use crate::system::boundary::{Boundary, BoundaryMode};
+use crate::system::config::Config;
+use crate::system::constraints::Constraints;
+use crate::system::cutoff::CutoffScheme;
+use crate::system::electrostatics::Ewald;
+use crate::system::force_field::ForceField;
+use crate::system::integrator::{Integrator, IntegratorKind};
+use crate::system::life::{ForceModel, ParticleLife};
+use crate::system::neighbour_list;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::thermostat::{Thermostat, ThermostatKind};
 
 // https://openkim.org/files/MO_959249795837_003/LennardJones612_UniversalShifted.params
 // https://link.springer.com/content/pdf/bbm:978-1-4757-1696-2/1.pdf <- beter
-#[repr(C, align(16))]
+// stored in a storage buffer (one entry per particle type), so no extra alignment
+#[repr(C)]
 #[derive(Copy, Clone)]
 pub struct Atom {
     pub size: f32,    // in nm
@@ -16,6 +27,18 @@ pub struct Atom {
 unsafe impl bytemuck::Pod for Atom {}
 unsafe impl bytemuck::Zeroable for Atom {}
 
+impl Atom {
+    pub fn new(size: f32, mass: f32, charge: i32, sigma: f32, epsilon: f32) -> Self {
+        Self {
+            size,
+            mass,
+            charge,
+            sigma,
+            epsilon,
+        }
+    }
+}
+
 #[repr(C, align(16))]
 #[derive(Copy, Clone)]
 pub struct Params {
@@ -23,46 +46,203 @@ pub struct Params {
     pub dt: f32,               // in ps
     pub neghborhood_size: f32, // in nm
     pub max_force: f32,        // in nm * amu / ps^2
-    pub friction: f32,         // in amu / ps
-    pub box_size: f32,         // in nm
-    pub bin_size: f32,         // in nm
-    pub bin_count: u32,
-    pub bin_capacity: u32,
-    align: [u32; 3],
-    pub helium: Atom,
+    pub friction: f32,         // in 1 / ps, Langevin friction
+    pub box_lx: f32,           // in nm, the cell vectors are (lx, 0, 0), (xy, ly, 0), (xz, yz, lz)
+    pub box_ly: f32,
+    pub box_lz: f32,
+    pub tilt_xy: f32, // in nm
+    pub tilt_xz: f32,
+    pub tilt_yz: f32,
+    pub bin_count_x: u32,
+    pub bin_count_y: u32,
+    pub bin_count_z: u32,
+    pub type_count: u32,
+    pub force_model: u32,
+    pub life_radius: f32,   // in nm
+    pub life_beta: f32,
+    pub life_force: f32,    // in nm * amu / ps^2
+    pub life_friction: f32, // in 1 / ps
+    pub electrostatics: u32, // 0: off, 1: ewald
+    pub ewald_alpha: f32,    // in 1 / nm
+    pub kvector_count: u32,
+    pub cutoff_scheme: u32, // see CutoffScheme, the cutoff itself is neghborhood_size
+    pub r_switch: f32,      // in nm
+    pub boundary_x: u32,    // see BoundaryMode
+    pub boundary_y: u32,
+    pub boundary_z: u32,
+    pub wall_temperature: f32, // in K
+    pub thermostat: u32,       // see ThermostatKind
+    pub target_temperature: f32, // in K
+    pub thermostat_tau: f32,     // in ps
+    pub thermostat_chain: u32,   // length of the Nosé–Hoover chain
+    pub thermostat_interval: f32, // in ps, time between two global rescalings
+    pub integrator: u32,          // see IntegratorKind
+    pub respa_steps: u32,         // inner steps per outer r-RESPA step
+    pub constraint_count: u32,    // each removes one degree of freedom
+    pub shake_tolerance: f32,     // largest relative deviation of a constraint length
+    pub shake_iterations: u32,    // iteration limit of SHAKE and RATTLE
+    pub verlet_skin: f32,         // in nm, the Verlet list reaches this far beyond the cutoff, 0 turns it off
 }
 unsafe impl bytemuck::Pod for Params {}
 unsafe impl bytemuck::Zeroable for Params {}
 
 impl Params {
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
+    /// the parameters of a fresh system from the config, with the particle life radius of the
+    /// config but without the rest of particle life, electrostatics or constraints, see
+    /// `set_particle_life` and `set_ewald`
+    pub fn new(config: &Config, force_field: &ForceField, simulation_box: &SimulationBox) -> Self {
+        let cutoff = config.cutoff();
+        let integrator = config.integrator();
+        // the 27 bins around a particle have to cover the whole cutoff sphere and the list radius
+        let [bin_count_x, bin_count_y, bin_count_z] = simulation_box.bin_counts(config.bin_size());
         Self {
-            N: NUMBER_PARTICLES,
-            dt: DT,
-            neghborhood_size: NEIGHBORHOOD_SIZE,
+            N: config.system.particles,
+            dt: config.integrator.dt,
+            neghborhood_size: cutoff.r_cut,
             max_force: 0.0,
             friction: 0.0,
-            box_size: BOX_SIZE,
-            bin_size: BIN_SIZE,
-            bin_count: BIN_COUNT as u32,
-            bin_capacity: BIN_DEPTH as u32,
-            align: [0; 3],
-            // helium: Atom {
-            //     size: 0.2551,
-            //     mass: 4.0,
-            //     charge: 0,
-            //     sigma: 0.2551,
-            //     epsilon: 10.22 * BOLTZMANN_CONSTANT,
-            // },
-            helium: Atom {  // fake
-                size: 0.2551,
-                mass: 4.0,
-                charge: 0,
-                sigma: 0.2551,
-                epsilon: 10.22 * BOLTZMANN_CONSTANT * 500.0,
-            },
+            box_lx: simulation_box.lengths[0],
+            box_ly: simulation_box.lengths[1],
+            box_lz: simulation_box.lengths[2],
+            tilt_xy: simulation_box.tilt[0],
+            tilt_xz: simulation_box.tilt[1],
+            tilt_yz: simulation_box.tilt[2],
+            bin_count_x,
+            bin_count_y,
+            bin_count_z,
+            type_count: force_field.type_count(),
+            force_model: config.force_field.model as u32,
+            life_radius: config.force_field.life_radius,
+            life_beta: 0.0,
+            life_force: 0.0,
+            life_friction: 0.0,
+            electrostatics: 0,
+            ewald_alpha: 0.0,
+            kvector_count: 0,
+            cutoff_scheme: cutoff.scheme as u32,
+            r_switch: cutoff.r_switch,
+            boundary_x: 0,
+            boundary_y: 0,
+            boundary_z: 0,
+            wall_temperature: 0.0,
+            thermostat: 0,
+            target_temperature: 0.0,
+            thermostat_tau: 0.0,
+            thermostat_chain: 0,
+            // the global thermostats act once per frame
+            thermostat_interval: config.integrator.dt * config.integrator.substeps as f32,
+            integrator: integrator.kind as u32,
+            respa_steps: integrator.respa_steps,
+            constraint_count: 0,
+            shake_tolerance: 0.0,
+            shake_iterations: 0,
+            verlet_skin: config.force_field.verlet_skin,
         }
+        .with_max_force(config.force_field.max_force)
+        .with_boundary(&config.boundary())
+        .with_thermostat(&config.thermostat())
+        .with_constraints(&Constraints::default(), 0)
+    }
+
+    fn with_max_force(mut self, max_force: f32) -> Self {
+        self.set_max_force(max_force);
+        self
+    }
+
+    fn with_boundary(mut self, boundary: &Boundary) -> Self {
+        self.set_boundary(boundary);
+        self
+    }
+
+    fn with_thermostat(mut self, thermostat: &Thermostat) -> Self {
+        self.set_thermostat(thermostat);
+        self
+    }
+
+    fn with_constraints(mut self, constraints: &Constraints, count: u32) -> Self {
+        self.set_constraints(constraints, count);
+        self
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        SimulationBox::triclinic(
+            [self.box_lx, self.box_ly, self.box_lz],
+            [self.tilt_xy, self.tilt_xz, self.tilt_yz],
+        )
+    }
+
+    /// in nm, the bins are at least this thick, see `neighbour_list::bin_size`
+    pub fn bin_size(&self) -> f32 {
+        neighbour_list::bin_size(self.neghborhood_size, self.life_radius, self.verlet_skin)
+    }
+
+    pub fn bin_total(&self) -> u32 {
+        self.bin_count_x * self.bin_count_y * self.bin_count_z
+    }
+
+    /// takes over a new box and rebuilds the bin grid for it, never with more bins per axis
+    /// than `max_bin_counts` (the bin buffers are allocated for those)
+    pub fn set_box(&mut self, simulation_box: &SimulationBox, max_bin_counts: [u32; 3]) {
+        let bin_counts = simulation_box.bin_counts(self.bin_size());
+        [self.box_lx, self.box_ly, self.box_lz] = simulation_box.lengths;
+        [self.tilt_xy, self.tilt_xz, self.tilt_yz] = simulation_box.tilt;
+        self.bin_count_x = bin_counts[0].min(max_bin_counts[0]);
+        self.bin_count_y = bin_counts[1].min(max_bin_counts[1]);
+        self.bin_count_z = bin_counts[2].min(max_bin_counts[2]);
+    }
+
+    pub fn set_boundary(&mut self, boundary: &Boundary) {
+        self.boundary_x = boundary.modes[0] as u32;
+        self.boundary_y = boundary.modes[1] as u32;
+        self.boundary_z = boundary.modes[2] as u32;
+        self.wall_temperature = boundary.wall_temperature;
+    }
+
+    /// pair forces above max_force are cut to it, zero turns the capping off
+    pub fn set_max_force(&mut self, max_force: f32) {
+        self.max_force = max_force.max(0.0);
+    }
+
+    /// the frame length of the global thermostats follows dt
+    pub fn set_dt(&mut self, dt: f32, substeps: u32) {
+        self.dt = dt;
+        self.thermostat_interval = dt * substeps as f32;
+    }
+
+    pub fn set_thermostat(&mut self, thermostat: &Thermostat) {
+        self.thermostat = thermostat.kind as u32;
+        self.target_temperature = thermostat.temperature;
+        self.thermostat_tau = thermostat.tau;
+        self.thermostat_chain = thermostat.chain_length;
+        self.friction = match thermostat.kind {
+            ThermostatKind::Langevin => 1.0 / thermostat.tau,
+            _ => 0.0,
+        };
+    }
+
+    pub fn set_integrator(&mut self, integrator: &Integrator) {
+        self.integrator = integrator.kind as u32;
+        self.respa_steps = integrator.respa_steps;
+    }
+
+    pub fn set_constraints(&mut self, constraints: &Constraints, count: u32) {
+        self.constraint_count = count;
+        self.shake_tolerance = constraints.tolerance;
+        self.shake_iterations = constraints.max_iterations;
+    }
+
+    /// the k vectors follow the box, see `set_box`
+    pub fn set_ewald(&mut self, ewald: &Ewald, simulation_box: &SimulationBox) {
+        self.electrostatics = ewald.enabled as u32;
+        self.ewald_alpha = ewald.alpha;
+        self.kvector_count = ewald.kvectors(simulation_box).len() as u32;
+    }
+
+    pub fn set_particle_life(&mut self, life: &ParticleLife) {
+        self.life_radius = life.radius;
+        self.life_beta = life.beta;
+        self.life_force = life.force;
+        self.life_friction = life.friction;
     }
 
     pub fn desc() -> wgpu::BindGroupLayoutEntry {
@@ -96,12 +276,28 @@ impl Display for Params {
             .field("neghborhood_size", &self.neghborhood_size)
             .field("max_force", &self.max_force)
             .field("friction", &self.friction)
-            .field("box_size", &self.box_size)
-            .field("bin_size", &self.bin_size)
-            .field("bin_count", &self.bin_count)
-            .field("bin_capacity", &self.bin_capacity)
+            .field("box", &self.simulation_box().to_string())
+            .field("bin_count", &[self.bin_count_x, self.bin_count_y, self.bin_count_z])
             .field("number_particles", &self.N)
-            .field("helium", &self.helium)
+            .field("type_count", &self.type_count)
+            .field("force_model", &ForceModel::from_u32(self.force_model))
+            .field("electrostatics", &self.electrostatics)
+            .field("ewald_alpha", &self.ewald_alpha)
+            .field("kvector_count", &self.kvector_count)
+            .field("cutoff_scheme", &CutoffScheme::from_u32(self.cutoff_scheme))
+            .field("r_switch", &self.r_switch)
+            .field("boundary", &[self.boundary_x, self.boundary_y, self.boundary_z].map(BoundaryMode::from_u32))
+            .field("wall_temperature", &self.wall_temperature)
+            .field("thermostat", &ThermostatKind::from_u32(self.thermostat))
+            .field("target_temperature", &self.target_temperature)
+            .field("thermostat_tau", &self.thermostat_tau)
+            .field("thermostat_chain", &self.thermostat_chain)
+            .field("integrator", &IntegratorKind::from_u32(self.integrator))
+            .field("respa_steps", &self.respa_steps)
+            .field("constraint_count", &self.constraint_count)
+            .field("shake_tolerance", &self.shake_tolerance)
+            .field("shake_iterations", &self.shake_iterations)
+            .field("verlet_skin", &self.verlet_skin)
             .finish()
     }
 }
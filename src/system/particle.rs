@@ -1,6 +1,7 @@
-use crate::system::consts::*;
+use crate::system::config::SystemConfig;
 use crate::utils::utils::maxwell_boltzmann_sampler;
 
+use super::force_field::ForceField;
 use super::params::Params;
 
 #[repr(C)]
@@ -66,29 +67,42 @@ impl Particle {
         }
     }
 
-    pub fn create_particles(num_particles: u64, params: &Params) -> Vec<Particle> {
+    pub fn create_particles(system: &SystemConfig, params: &Params, force_field: &ForceField) -> Vec<Particle> {
         // space particles evenly in a grid
+        let num_particles = system.particles as u64;
         let mut particles = Vec::with_capacity(num_particles as usize);
 
-        let side = (num_particles as f32).cbrt().ceil() as u32;
-        // let spacing = params.box_size / side as f32;
-        let spacing = INIT_SPACING;
-        // let ofset = BOX_SIZE / 2.0 - spacing * side as f32 / 2.0;
-        let ofset = EXES_SPACING;
-        for i in 0..side {
-            for j in 0..side {
-                for k in 0..side {
-                    if i * side * side + j * side + k >= num_particles as u32 {
+        // the grid follows the shape of the box, for a cube this is cbrt(N) points per axis
+        let simulation_box = params.simulation_box();
+        let density = (num_particles as f64 / simulation_box.volume()).cbrt();
+        let mut side = simulation_box.lengths.map(|l| (l as f64 * density).ceil() as u32);
+        while (side[0] as u64) * (side[1] as u64) * (side[2] as u64) < num_particles {
+            side = side.map(|n| n + 1);
+        }
+        let ofset = system.margin;
+        // system.spacing apart unless the box is too small for that
+        let spacing = [0, 1, 2].map(|a| system.spacing.min((simulation_box.lengths[a] - ofset) / (2.0 * side[a] as f32)));
+        let [side_x, side_y, side_z] = side;
+        for i in 0..side_x {
+            for j in 0..side_y {
+                for k in 0..side_z {
+                    if (i * side_y + j) * side_z + k >= num_particles as u32 {
                         break;
                     }
-                    let x = (i as f32) * spacing * 2.0 - params.box_size + ofset;
-                    let y = (j as f32) * spacing * 2.0 - params.box_size + ofset;
-                    let z = (k as f32) * spacing * 2.0 - params.box_size + ofset;
-                    let _type = (i + j * side + k * side * side) as u32 % Self::MAX_TYPES;
+                    // snake through the grid, so consecutive particles are always neighbours
+                    let j_ = if i % 2 == 1 { side_y - 1 - j } else { j };
+                    let k_ = if (i * side_y + j) % 2 == 1 { side_z - 1 - k } else { k };
+                    // in fractional coordinates, so the grid is sheared with a triclinic box
+                    let s = [
+                        ((i as f32) * spacing[0] * 2.0 + ofset) / simulation_box.lengths[0],
+                        ((j_ as f32) * spacing[1] * 2.0 + ofset) / simulation_box.lengths[1],
+                        ((k_ as f32) * spacing[2] * 2.0 + ofset) / simulation_box.lengths[2],
+                    ];
+                    let _type = (i + j * side_x + k * side_x * side_y) as u32 % force_field.type_count();
                     particles.push(Particle::new(
                         _type as f32,
-                        [x, y, z],
-                        maxwell_boltzmann_sampler(INIT_TEMPERATURE, params.helium.mass),
+                        simulation_box.position(s),
+                        maxwell_boltzmann_sampler(system.temperature, force_field.atom(_type).mass),
                     ));
                 }
             }
@@ -97,6 +111,17 @@ impl Particle {
     }
 }
 
+impl Particle {
+    /// number of particles of every type, used for the composition dependent corrections
+    pub fn type_counts(particles: &[Particle], type_count: u32) -> Vec<u32> {
+        let mut counts = vec![0; type_count as usize];
+        for particle in particles {
+            counts[particle.type_ as usize] += 1;
+        }
+        counts
+    }
+}
+
 impl Default for Particle {
     fn default() -> Self {
         Self {
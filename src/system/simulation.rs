@@ -0,0 +1,249 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use wgpu::{CommandEncoder, Device, Queue};
+
+use crate::system::barostat::Barostat;
+use crate::system::boundary::Boundary;
+use crate::system::compute_set::ComputeSet;
+use crate::system::config::{Config, ForceFieldConfig, GpuConfig, IntegratorConfig, OutputConfig, SystemConfig, ThermostatConfig};
+use crate::system::consts::BOLTZMANN_CONSTANT_EV;
+use crate::system::constraints::Constraints;
+use crate::system::cutoff::CutoffScheme;
+use crate::system::device;
+use crate::system::force_field::ForceField;
+use crate::system::integrator::Integrator;
+use crate::system::life::{ForceModel, ParticleLife};
+use crate::system::minimizer::Minimizer;
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+use crate::system::stats::{StatHistory, Stats};
+use crate::system::thermostat::Thermostat;
+use crate::system::timestep::Timestep;
+
+/// The engine without a window: the gpu pipeline of `ComputeSet` with its own device.
+/// `Simulation::builder().system(..).build()` sets one up, `step` runs it and
+/// `particles` and `observables` read the results back. The viewer is one user of it.
+pub struct Simulation {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    compute: ComputeSet,
+    config: Config,
+    frame: usize,
+}
+
+/// Collects the sections of a `Config`, every section left out keeps its defaults.
+#[derive(Clone, Debug, Default)]
+pub struct SimulationBuilder {
+    config: Config,
+}
+
+impl SimulationBuilder {
+    /// replaces every section at once, e.g. with a config read by `Config::load`
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn system(mut self, system: SystemConfig) -> Self {
+        self.config.system = system;
+        self
+    }
+
+    /// an orthorhombic or triclinic box in place of the cube of `system.box_size`
+    pub fn simulation_box(mut self, simulation_box: SimulationBox) -> Self {
+        self.config.system.box_lengths = Some(simulation_box.lengths);
+        self.config.system.box_tilt = simulation_box.tilt;
+        self
+    }
+
+    pub fn force_field(mut self, force_field: ForceFieldConfig) -> Self {
+        self.config.force_field = force_field;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: IntegratorConfig) -> Self {
+        self.config.integrator = integrator;
+        self
+    }
+
+    pub fn thermostat(mut self, thermostat: ThermostatConfig) -> Self {
+        self.config.thermostat = thermostat;
+        self
+    }
+
+    pub fn output(mut self, output: OutputConfig) -> Self {
+        self.config.output = output;
+        self
+    }
+
+    pub fn gpu(mut self, gpu: GpuConfig) -> Self {
+        self.config.gpu = gpu;
+        self
+    }
+
+    /// validates the config and sets up a device of its own, picked by the gpu section
+    pub fn build(self) -> Result<Simulation, Box<dyn Error>> {
+        self.config.validate()?;
+        let (_adapter, device, queue) = pollster::block_on(device::create_device(&self.config.gpu))?;
+        Ok(Simulation::new(Arc::new(device), Arc::new(queue), self.config))
+    }
+
+    /// runs on a device the caller already has, like the viewer that also renders with it
+    pub fn build_on(self, device: Arc<Device>, queue: Arc<Queue>) -> Result<Simulation, Box<dyn Error>> {
+        self.config.validate()?;
+        Ok(Simulation::new(device, queue, self.config))
+    }
+}
+
+/// What `Simulation::observables` measures on the newest frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Observables {
+    pub stats: Stats,
+    pub temperature: f32, // in K, from the kinetic energy
+}
+
+/// The settings that can change while the simulation runs, see `Simulation::set_param`.
+#[derive(Clone)]
+pub enum Param {
+    Thermostat(Thermostat),
+    Barostat(Barostat),
+    Integrator(Integrator),
+    Timestep(Timestep),
+    /// largest pair force in mU / nm, zero turns the capping off
+    MaxForce(f32),
+    Constraints(Constraints),
+    ForceModel(ForceModel),
+    ParticleLife(ParticleLife),
+    /// the scheme and r_switch in nm
+    CutoffScheme(CutoffScheme, f32),
+    Boundary(Boundary),
+    /// with the same number of types
+    ForceField(ForceField),
+}
+
+impl Simulation {
+    pub fn builder() -> SimulationBuilder {
+        SimulationBuilder::default()
+    }
+
+    fn new(device: Arc<Device>, queue: Arc<Queue>, config: Config) -> Self {
+        let mut compute = ComputeSet::new(&device, &config);
+        if let Some(minimizer) = config.minimizer() {
+            compute.minimize(&queue, minimizer);
+        }
+        Self {
+            device,
+            queue,
+            compute,
+            config,
+            frame: 0,
+        }
+    }
+
+    /// runs `frames` frames of `integrator.substeps` substeps and waits for the gpu
+    pub fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Simulation Encoder"),
+            });
+            self.encode_frame(&mut encoder);
+            self.queue.submit(std::iter::once(encoder.finish()));
+            // the stats of the last frame are downloaded once the gpu is done
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+    }
+
+    /// records one frame into `encoder` without submitting it, for callers that draw the
+    /// particles in the same submission
+    pub fn encode_frame(&mut self, encoder: &mut CommandEncoder) {
+        self.compute.update(encoder, &self.device, &self.queue, self.frame);
+        self.frame += 1;
+    }
+
+    /// frames run so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// reads the newest particles back from the gpu
+    pub fn particles(&self) -> Vec<Particle> {
+        self.compute.read_particles(&self.device, &self.queue)
+    }
+
+    /// replaces the particles, `particles` needs one entry per particle of the system
+    pub fn set_particles(&mut self, particles: &[Particle]) {
+        self.compute.set_particles(&self.queue, particles);
+    }
+
+    /// the energies, pressure and temperature of the newest frame, waits for the gpu
+    pub fn observables(&self) -> Observables {
+        let stats = self.compute.read_stats(&self.device, &self.queue);
+        let temperature = stats.KE * 2.0 / self.compute.degrees_of_freedom() / BOLTZMANN_CONSTANT_EV;
+        Observables { stats, temperature }
+    }
+
+    /// the stats of every frame so far, the gpu ones lag one frame behind
+    pub fn history(&self) -> StatHistory {
+        self.compute.get_history()
+    }
+
+    /// changes a setting, it takes effect with the next frame
+    pub fn set_param(&mut self, param: Param) {
+        let queue = &self.queue;
+        match param {
+            Param::Thermostat(thermostat) => self.compute.set_thermostat(queue, thermostat),
+            Param::Barostat(barostat) => self.compute.set_barostat(barostat),
+            Param::Integrator(integrator) => self.compute.set_integrator(queue, integrator),
+            Param::Timestep(timestep) => self.compute.set_timestep(queue, timestep),
+            Param::MaxForce(max_force) => self.compute.set_max_force(queue, max_force),
+            Param::Constraints(constraints) => self.compute.set_constraints(queue, constraints),
+            Param::ForceModel(force_model) => self.compute.set_force_model(queue, force_model),
+            Param::ParticleLife(life) => self.compute.set_particle_life(queue, life),
+            Param::CutoffScheme(scheme, r_switch) => self.compute.set_cutoff_scheme(queue, scheme, r_switch),
+            Param::Boundary(boundary) => self.compute.set_boundary(queue, boundary),
+            Param::ForceField(force_field) => self.compute.set_force_field(queue, force_field),
+        }
+    }
+
+    /// relaxes the particles with `minimizer` in place of the next frames, see `ComputeSet::minimize`
+    pub fn minimize(&mut self, minimizer: Minimizer) {
+        self.compute.minimize(&self.queue, minimizer);
+    }
+
+    pub fn simulation_box(&self) -> SimulationBox {
+        self.compute.simulation_box()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// the pipeline itself, for the getters and the buffers the viewer draws from
+    pub fn compute(&self) -> &ComputeSet {
+        &self.compute
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
+    /// prints the time, the bin load and the newest stats with their warnings
+    pub fn report(&mut self) {
+        pollster::block_on(self.compute.debug(&self.device, &self.queue));
+    }
+
+    /// writes the stats history to csv, returns the file name
+    pub fn save_stats(&mut self) -> Result<String, Box<dyn Error>> {
+        // the stats lag one frame behind, fetch the ones of the last frame
+        if self.frame > 0 {
+            self.compute.download_stats(&self.device, &self.queue);
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+        self.compute.save_stats()
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::Display;
+
+/// The simulation cell, centred on the origin.
+///
+/// The cell vectors follow the LAMMPS convention a = (lx, 0, 0), b = (xy, ly, 0) and
+/// c = (xz, yz, lz), so an orthorhombic box has all tilt factors at zero. Positions are
+/// handled in fractional coordinates s in [0, 1)^3 with r = origin + s_x a + s_y b + s_z c.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SimulationBox {
+    pub lengths: [f32; 3], // lx, ly, lz in nm
+    pub tilt: [f32; 3],    // xy, xz, yz in nm
+}
+
+impl SimulationBox {
+    pub fn cubic(length: f32) -> Self {
+        Self::orthorhombic(length, length, length)
+    }
+
+    pub fn orthorhombic(lx: f32, ly: f32, lz: f32) -> Self {
+        Self::triclinic([lx, ly, lz], [0.0; 3])
+    }
+
+    /// the tilt factors are limited to half a box length, beyond that the minimum image
+    /// convention breaks down and the box should be flipped to an equivalent one
+    pub fn triclinic(lengths: [f32; 3], tilt: [f32; 3]) -> Self {
+        assert!(lengths.iter().all(|l| *l > 0.0), "box lengths have to be positive");
+        let [lx, ly, _] = lengths;
+        let [xy, xz, yz] = tilt;
+        assert!(
+            xy.abs() <= 0.5 * lx && xz.abs() <= 0.5 * lx && yz.abs() <= 0.5 * ly,
+            "tilt factors have to be within half a box length"
+        );
+        Self { lengths, tilt }
+    }
+
+    /// the box with every cell vector scaled by the factor of its axis, a position r maps to
+    /// (scale_x r_x, scale_y r_y, scale_z r_z) in the new box
+    pub fn scaled(&self, scale: [f32; 3]) -> Self {
+        let [xy, xz, yz] = self.tilt;
+        Self::triclinic(
+            [self.lengths[0] * scale[0], self.lengths[1] * scale[1], self.lengths[2] * scale[2]],
+            [xy * scale[0], xz * scale[0], yz * scale[1]],
+        )
+    }
+
+    pub fn is_orthorhombic(&self) -> bool {
+        self.tilt == [0.0; 3]
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.lengths.iter().map(|l| *l as f64).product()
+    }
+
+    /// the cell vectors a, b and c
+    pub fn vectors(&self) -> [[f32; 3]; 3] {
+        let [lx, ly, lz] = self.lengths;
+        let [xy, xz, yz] = self.tilt;
+        [[lx, 0.0, 0.0], [xy, ly, 0.0], [xz, yz, lz]]
+    }
+
+    /// the corner of the cell at s = (0, 0, 0)
+    pub fn origin(&self) -> [f32; 3] {
+        self.from_fractional([-0.5; 3])
+    }
+
+    /// distance between the two opposite faces of every axis, the largest cutoff a
+    /// single bin can cover is the height divided by the number of bins
+    pub fn heights(&self) -> [f32; 3] {
+        let [a, b, c] = self.vectors();
+        let volume = self.volume() as f32;
+        [
+            volume / norm(cross(b, c)),
+            volume / norm(cross(c, a)),
+            volume / norm(cross(a, b)),
+        ]
+    }
+
+    /// unit normal of the two faces of an axis, pointing towards increasing s
+    pub fn face_normal(&self, axis: usize) -> [f32; 3] {
+        let [a, b, c] = self.vectors();
+        let normal = match axis {
+            0 => cross(b, c),
+            1 => cross(c, a),
+            _ => cross(a, b),
+        };
+        let length = norm(normal);
+        normal.map(|n| n / length)
+    }
+
+    /// the number of bins per axis such that every bin is at least `width` thick
+    pub fn bin_counts(&self, width: f32) -> [u32; 3] {
+        let counts = self.heights().map(|h| (h / width).floor() as u32);
+        // with fewer bins the 27 neighbour bins would contain the same bin twice
+        assert!(
+            counts.iter().all(|c| *c >= 3),
+            "the box {:?} is too small for bins of {} nm",
+            self,
+            width
+        );
+        counts
+    }
+
+    /// fractional coordinates of a displacement (not of a position, see `fractional_position`)
+    pub fn to_fractional(&self, d: [f32; 3]) -> [f32; 3] {
+        let [lx, ly, lz] = self.lengths;
+        let [xy, xz, yz] = self.tilt;
+        let s_z = d[2] / lz;
+        let s_y = (d[1] - yz * s_z) / ly;
+        let s_x = (d[0] - xy * s_y - xz * s_z) / lx;
+        [s_x, s_y, s_z]
+    }
+
+    pub fn from_fractional(&self, s: [f32; 3]) -> [f32; 3] {
+        let [lx, ly, lz] = self.lengths;
+        let [xy, xz, yz] = self.tilt;
+        [lx * s[0] + xy * s[1] + xz * s[2], ly * s[1] + yz * s[2], lz * s[2]]
+    }
+
+    pub fn fractional_position(&self, r: [f32; 3]) -> [f32; 3] {
+        let origin = self.origin();
+        self.to_fractional([r[0] - origin[0], r[1] - origin[1], r[2] - origin[2]])
+    }
+
+    pub fn position(&self, s: [f32; 3]) -> [f32; 3] {
+        let origin = self.origin();
+        let d = self.from_fractional(s);
+        [origin[0] + d[0], origin[1] + d[1], origin[2] + d[2]]
+    }
+
+    /// the shortest image of a displacement along the periodic axes
+    pub fn minimum_image(&self, d: [f32; 3], periodic: [bool; 3]) -> [f32; 3] {
+        let mut s = self.to_fractional(d);
+        for axis in 0..3 {
+            if periodic[axis] {
+                s[axis] -= s[axis].round();
+            }
+        }
+        self.from_fractional(s)
+    }
+
+    /// maps a position back into the box along the periodic axes
+    pub fn wrap(&self, r: [f32; 3], periodic: [bool; 3]) -> [f32; 3] {
+        let mut s = self.fractional_position(r);
+        for axis in 0..3 {
+            if periodic[axis] {
+                s[axis] -= s[axis].floor();
+            }
+        }
+        self.position(s)
+    }
+}
+
+impl Display for SimulationBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [lx, ly, lz] = self.lengths;
+        if self.is_orthorhombic() {
+            write!(f, "{} x {} x {} nm", lx, ly, lz)
+        } else {
+            let [xy, xz, yz] = self.tilt;
+            write!(f, "{} x {} x {} nm, tilt ({}, {}, {}) nm", lx, ly, lz, xy, xz, yz)
+        }
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn norm(a: [f32; 3]) -> f32 {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
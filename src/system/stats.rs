@@ -1,4 +1,6 @@
 use core::fmt::Debug;
+use crate::system::cutoff::TailCorrection;
+use crate::system::force_field::ForceField;
 use crate::system::params::*;
 use csv::Writer;
 use crate::system::consts::*;
@@ -8,6 +10,16 @@ use crate::system::consts::*;
 pub struct Stat {
     pub KE: f32,
     pub PE: f32,
+    pub PE_real: f32,  // real space coulomb energy
+    pub PE_recip: f32, // reciprocal coulomb energy, including the self energy
+    pub PE_bonded: f32, // bonds, angles and dihedrals
+    pub E_thermostat: f32, // energy the thermostat took out of the system
+    pub W_x: f32, // diagonal of the virial tensor, sum of r_x F_x
+    pub W_y: f32,
+    pub W_z: f32,
+    pub capped: f32, // number of pair forces cut to max_force in the last substep
+    pub constraint_error: f32, // sum of the squared relative deviations of the constraint lengths
+    pub unconverged: f32, // constraint clusters that hit the iteration limit in the last substep
 }
 unsafe impl bytemuck::Pod for Stat {}
 unsafe impl bytemuck::Zeroable for Stat {}
@@ -17,9 +29,25 @@ impl Stat {
         Self {
             KE: 0.0,
             PE: 0.0,
+            PE_real: 0.0,
+            PE_recip: 0.0,
+            PE_bonded: 0.0,
+            E_thermostat: 0.0,
+            W_x: 0.0,
+            W_y: 0.0,
+            W_z: 0.0,
+            capped: 0.0,
+            constraint_error: 0.0,
+            unconverged: 0.0,
         }
     }
 
+    /// the diagonal of the pressure tensor in bar, the kinetic part is taken as isotropic
+    pub fn pressure_tensor(&self, volume: f64, tail_pressure: f64) -> [f32; 3] {
+        let kinetic = 2.0 * self.KE as f64 / 3.0;
+        [self.W_x, self.W_y, self.W_z].map(|w| ((kinetic + w as f64) / volume + tail_pressure) as f32 * BAR_PER_MU_NM3)
+    }
+
     pub fn create_stats(N: usize) -> Vec<Stat> {
         let mut stats = Vec::new();
         for _ in 0..N {
@@ -51,8 +79,48 @@ pub struct Stats {
     pub iteration: usize,
     pub KE: f32,
     pub PE: f32,
+    pub PE_real: f32,
+    pub PE_recip: f32,
+    pub PE_tail: f32, // long range LJ correction, constant for a fixed box
+    pub PE_bonded: f32,
+    pub E_thermostat: f32, // KE + PE + E_thermostat is conserved
+    pub volume: f32,   // in nm^3
+    pub pressure: f32, // in bar
+    pub pressure_tensor: [f32; 3], // diagonal, in bar
+    pub time: f64,  // simulated time in ps, the sum of all substeps
+    pub dt: f32,    // in ps, length of the next substep
+    pub v_max: f32, // in nm/ps
+    pub a_max: f32, // in nm/ps^2
+    pub capped: u32, // pair forces cut to max_force in the last substep
+    pub constraint_error: f32, // rms of the relative deviations of the constraint lengths
+    pub unconverged: u32, // constraint clusters that hit the iteration limit in the last substep
+    pub rebuilds: u32, // builds of the Verlet list so far, 0 without a list
+    pub max_neighbours: u32, // most neighbours a particle had at the last build of the Verlet list
 }
 
+impl Stats {
+    /// The summed stats of a frame (in mU) in eV and bar, `constraint_count` turns the summed
+    /// squared deviations into an rms. The time keeping fields are left at zero.
+    pub fn from_stat(stat: &Stat, volume: f64, tail_correction: &TailCorrection, constraint_count: usize) -> Self {
+        let pressure_tensor = stat.pressure_tensor(volume, tail_correction.pressure);
+        Self {
+            KE: stat.KE / eV_over_mU,
+            PE: stat.PE / eV_over_mU,
+            PE_real: stat.PE_real / eV_over_mU,
+            PE_recip: stat.PE_recip / eV_over_mU,
+            PE_tail: tail_correction.energy as f32 / eV_over_mU,
+            PE_bonded: stat.PE_bonded / eV_over_mU,
+            E_thermostat: stat.E_thermostat / eV_over_mU,
+            volume: volume as f32,
+            pressure: pressure_tensor.iter().sum::<f32>() / 3.0,
+            pressure_tensor,
+            capped: stat.capped.round() as u32,
+            constraint_error: (stat.constraint_error / constraint_count.max(1) as f32).sqrt(),
+            unconverged: stat.unconverged.round() as u32,
+            ..Default::default()
+        }
+    }
+}
 
 impl Debug for Stats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -60,24 +128,62 @@ impl Debug for Stats {
             .field("iteration", &self.iteration)
             .field("KE", &self.KE)
             .field("PE", &self.PE)
+            .field("PE_real", &self.PE_real)
+            .field("PE_recip", &self.PE_recip)
+            .field("PE_tail", &self.PE_tail)
+            .field("PE_bonded", &self.PE_bonded)
+            .field("E_thermostat", &self.E_thermostat)
+            .field("volume", &self.volume)
+            .field("pressure", &self.pressure)
+            .field("time", &self.time)
+            .field("dt", &self.dt)
+            .field("capped", &self.capped)
+            .field("constraint_error", &self.constraint_error)
+            .field("unconverged", &self.unconverged)
+            .field("rebuilds", &self.rebuilds)
+            .field("max_neighbours", &self.max_neighbours)
             .finish()
     }
 }
 
 pub struct StatHistory {
     params: Params,
+    mean_mass: f32,
     itaration: Vec<usize>,
     KE: Vec<f32>,
     PE: Vec<f32>,
+    PE_real: Vec<f32>,
+    PE_recip: Vec<f32>,
+    PE_tail: Vec<f32>,
+    PE_bonded: Vec<f32>,
+    E_thermostat: Vec<f32>,
+    volume: Vec<f32>,
+    pressure: Vec<f32>,
+    time: Vec<f64>,
+    dt: Vec<f32>,
+    capped: Vec<u32>,
+    constraint_error: Vec<f32>,
 }
 
 impl StatHistory {
-    pub fn new(params: Params) -> Self {
+    pub fn new(params: Params, force_field: &ForceField) -> Self {
         Self {
             params,
+            mean_mass: force_field.mean_mass(),
             itaration: Vec::new(),
             KE: Vec::new(),
             PE: Vec::new(),
+            PE_real: Vec::new(),
+            PE_recip: Vec::new(),
+            PE_tail: Vec::new(),
+            PE_bonded: Vec::new(),
+            E_thermostat: Vec::new(),
+            volume: Vec::new(),
+            pressure: Vec::new(),
+            time: Vec::new(),
+            dt: Vec::new(),
+            capped: Vec::new(),
+            constraint_error: Vec::new(),
         }
     }
 
@@ -85,22 +191,55 @@ impl StatHistory {
         self.itaration.push(stats.iteration);
         self.KE.push(stats.KE);
         self.PE.push(stats.PE);
+        self.PE_real.push(stats.PE_real);
+        self.PE_recip.push(stats.PE_recip);
+        self.PE_tail.push(stats.PE_tail);
+        self.PE_bonded.push(stats.PE_bonded);
+        self.E_thermostat.push(stats.E_thermostat);
+        self.volume.push(stats.volume);
+        self.pressure.push(stats.pressure);
+        self.time.push(stats.time);
+        self.dt.push(stats.dt);
+        self.capped.push(stats.capped);
+        self.constraint_error.push(stats.constraint_error);
     }
 
     fn sort(&mut self) {
         // sort by iteration
         let mut vec = Vec::new();
         for index in 0..self.itaration.len() {
-            vec.push((self.itaration[index], self.KE[index], self.PE[index]));
+            vec.push((self.itaration[index], self.KE[index], self.PE[index], self.PE_real[index], self.PE_recip[index], self.PE_tail[index], self.PE_bonded[index], self.E_thermostat[index], self.volume[index], self.pressure[index], self.time[index], self.dt[index], self.capped[index], self.constraint_error[index]));
         }
         vec.sort_by(|a, b| a.0.cmp(&b.0));
         self.itaration.clear();
         self.KE.clear();
         self.PE.clear();
+        self.PE_real.clear();
+        self.PE_recip.clear();
+        self.PE_tail.clear();
+        self.PE_bonded.clear();
+        self.E_thermostat.clear();
+        self.volume.clear();
+        self.pressure.clear();
+        self.time.clear();
+        self.dt.clear();
+        self.capped.clear();
+        self.constraint_error.clear();
         for index in 0..vec.len() {
             self.itaration.push(vec[index].0);
             self.KE.push(vec[index].1);
             self.PE.push(vec[index].2);
+            self.PE_real.push(vec[index].3);
+            self.PE_recip.push(vec[index].4);
+            self.PE_tail.push(vec[index].5);
+            self.PE_bonded.push(vec[index].6);
+            self.E_thermostat.push(vec[index].7);
+            self.volume.push(vec[index].8);
+            self.pressure.push(vec[index].9);
+            self.time.push(vec[index].10);
+            self.dt.push(vec[index].11);
+            self.capped.push(vec[index].12);
+            self.constraint_error.push(vec[index].13);
         }
     }
 
@@ -113,14 +252,25 @@ impl StatHistory {
 
         let mut wtr = Writer::from_path(filename)?;
         // header data
-        wtr.write_record(&[self.params.to_string(), "".to_string(), "".to_string()])?;
-        wtr.write_record(&["iteration", "KE", "PE"])?;
+        wtr.write_record(&[self.params.to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string()])?;
+        wtr.write_record(&["iteration", "KE", "PE", "PE_real", "PE_recip", "PE_tail", "PE_bonded", "E_thermostat", "volume", "pressure", "time", "dt", "capped", "constraint_error"])?;
         // data
         for index in 0..self.itaration.len() {
             wtr.write_record(&[
                 self.itaration[index].to_string(),
                 self.KE[index].to_string(),
                 self.PE[index].to_string(),
+                self.PE_real[index].to_string(),
+                self.PE_recip[index].to_string(),
+                self.PE_tail[index].to_string(),
+                self.PE_bonded[index].to_string(),
+                self.E_thermostat[index].to_string(),
+                self.volume[index].to_string(),
+                self.pressure[index].to_string(),
+                self.time[index].to_string(),
+                self.dt[index].to_string(),
+                self.capped[index].to_string(),
+                self.constraint_error[index].to_string(),
             ])?;
         }
         wtr.flush()?;
@@ -130,9 +280,21 @@ impl StatHistory {
     pub fn clone(&self) -> Self {
         Self {
             params: self.params,
+            mean_mass: self.mean_mass,
             itaration: self.itaration.clone(),
             KE: self.KE.clone(),
             PE: self.PE.clone(),
+            PE_real: self.PE_real.clone(),
+            PE_recip: self.PE_recip.clone(),
+            PE_tail: self.PE_tail.clone(),
+            PE_bonded: self.PE_bonded.clone(),
+            E_thermostat: self.E_thermostat.clone(),
+            volume: self.volume.clone(),
+            pressure: self.pressure.clone(),
+            time: self.time.clone(),
+            dt: self.dt.clone(),
+            capped: self.capped.clone(),
+            constraint_error: self.constraint_error.clone(),
         }
     }
 
@@ -155,6 +317,25 @@ impl StatHistory {
         graph
     }
 
+    pub fn graph_coulomb(&self, sample_rate: usize) -> Vec<[f64; 2]> {
+        let mut graph = Vec::new();
+        let iter = self.itaration.len()/sample_rate;
+        for index in 0..iter {
+            let i = index*sample_rate;
+            graph.push([self.itaration[i] as f64, (self.PE_real[i] + self.PE_recip[i]) as f64]);
+        }
+        graph
+    }
+
+    pub fn graph_bonded(&self, sample_rate: usize) -> Vec<[f64; 2]> {
+        let mut graph = Vec::new();
+        let iter = self.itaration.len()/sample_rate;
+        for index in 0..iter {
+            graph.push([self.itaration[index*sample_rate] as f64, self.PE_bonded[index*sample_rate] as f64]);
+        }
+        graph
+    }
+
     pub fn graph_TE(&self, sample_rate: usize) -> Vec<[f64; 2]> {
         let mut graph = Vec::new();
         let iter = self.itaration.len()/sample_rate;
@@ -162,19 +343,84 @@ impl StatHistory {
             if index*sample_rate >= self.itaration.len() {
                 break;
             }
-            graph.push([self.itaration[index*sample_rate] as f64, (self.KE[index*sample_rate] + self.PE[index*sample_rate]) as f64]);
+            let i = index*sample_rate;
+            graph.push([self.itaration[i] as f64, (self.KE[i] + self.PE[i] + self.PE_real[i] + self.PE_recip[i] + self.PE_bonded[i]) as f64]);
+        }
+        graph
+    }
+
+    /// total energy plus the energy taken out by the thermostat, constant for a working thermostat
+    pub fn graph_conserved(&self, sample_rate: usize) -> Vec<[f64; 2]> {
+        let mut graph = Vec::new();
+        let iter = self.itaration.len()/sample_rate;
+        for index in 0..iter {
+            let i = index*sample_rate;
+            graph.push([self.itaration[i] as f64, (self.KE[i] + self.PE[i] + self.PE_real[i] + self.PE_recip[i] + self.PE_bonded[i] + self.E_thermostat[i]) as f64]);
+        }
+        graph
+    }
+
+    pub fn thermostat_energy(&self) -> f32 {
+        self.E_thermostat.last().copied().unwrap_or(0.0)
+    }
+
+    /// the newest volume in nm^3
+    pub fn volume(&self) -> f32 {
+        self.volume.last().copied().unwrap_or(0.0)
+    }
+
+    /// the newest pressure in bar
+    pub fn pressure(&self) -> f32 {
+        self.pressure.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn graph_pressure(&self, sample_rate: usize) -> Vec<[f64; 2]> {
+        let mut graph = Vec::new();
+        let iter = self.itaration.len()/sample_rate;
+        for index in 0..iter {
+            graph.push([self.itaration[index*sample_rate] as f64, self.pressure[index*sample_rate] as f64]);
+        }
+        graph
+    }
+
+    /// the newest simulated time in ps
+    pub fn time(&self) -> f64 {
+        self.time.last().copied().unwrap_or(0.0)
+    }
+
+    /// the newest dt in ps
+    pub fn dt(&self) -> f32 {
+        self.dt.last().copied().unwrap_or(0.0)
+    }
+
+    /// the number of capped pair forces in the newest stats
+    pub fn capped(&self) -> u32 {
+        self.capped.last().copied().unwrap_or(0)
+    }
+
+    /// rms of the relative deviations of the constraint lengths in the newest stats
+    pub fn constraint_error(&self) -> f32 {
+        self.constraint_error.last().copied().unwrap_or(0.0)
+    }
+
+    /// dt over the simulated time
+    pub fn graph_dt(&self, sample_rate: usize) -> Vec<[f64; 2]> {
+        let mut graph = Vec::new();
+        let iter = self.itaration.len()/sample_rate;
+        for index in 0..iter {
+            graph.push([self.time[index*sample_rate], self.dt[index*sample_rate] as f64]);
         }
         graph
     }
 
     pub fn temperature(&self) -> f32 {
-        // get last temperature ½m<v2> = (3/2)kBT
+        // get last temperature KE = (n_f/2)kBT, every constraint takes one of the 3N degrees of freedom
         if self.itaration.len() == 0 {
             return 0.0;
         }
         let index = self.itaration.len() - 1;
-        let v2 = self.KE[index] / self.params.N as f32;
-        let kBT = v2 * 2.0 / 3.0 / BOLTZMANN_CONSTANT_EV;
+        let degrees_of_freedom = 3.0 * self.params.N as f32 - self.params.constraint_count as f32;
+        let kBT = self.KE[index] * 2.0 / degrees_of_freedom / BOLTZMANN_CONSTANT_EV;
         kBT
     }
 
@@ -184,9 +430,9 @@ impl StatHistory {
             return 0.0;
         }
         // let index = self.itaration.len() - 1;
-        // let v2 = self.KE[index] / self.params.N as f32 * 2.0 / self.params.helium.mass;
+        // let v2 = self.KE[index] / self.params.N as f32 * 2.0 / self.mean_mass;
         // let v = v2.sqrt();  // in nm/ps
         // v * 1e-3 // in m/s
-        (self.temperature() * 3.0 * BOLTZMANN_CONSTANT_J / (self.params.helium.mass * 1.66053906660e-27)).sqrt()
+        (self.temperature() * 3.0 * BOLTZMANN_CONSTANT_J / (self.mean_mass * 1.66053906660e-27)).sqrt()
     }
 }
\ No newline at end of file
@@ -0,0 +1,290 @@
+use std::error::Error;
+use std::fs;
+
+/*
+Tabulated pair potentials. Two file formats are understood:
+
+simple format, one point per line (lines starting with # are comments):
+    r  V(r)  F(r)
+
+LAMMPS pair_style table format, one or more sections of the form:
+    KEYWORD
+    N 500 R 0.1 1.2        (or RSQ instead of R, FP is ignored)
+
+    1 0.1 V F
+    2 ...
+
+r is in nm, V in mU (kJ / mol) and F = -dV/dr in mU / nm, use `scaled` to convert
+tables in other units.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairTable {
+    pub r: Vec<f32>,
+    pub energy: Vec<f32>,
+    pub force: Vec<f32>,
+}
+
+/// the largest deviation between the tabulated force and -dV/dr
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TableCheck {
+    pub max_error: f32, // relative to the largest tabulated force
+    pub at_r: f32,      // in nm
+}
+
+impl PairTable {
+    pub fn new(r: Vec<f32>, energy: Vec<f32>, force: Vec<f32>) -> Result<Self, Box<dyn Error>> {
+        if r.len() < 2 || r.len() != energy.len() || r.len() != force.len() {
+            return Err(format!(
+                "a table needs at least 2 points and equally long columns (r: {}, V: {}, F: {})",
+                r.len(),
+                energy.len(),
+                force.len()
+            )
+            .into());
+        }
+        if r.windows(2).any(|w| w[1] <= w[0]) {
+            return Err("the r column of a table has to be strictly increasing".into());
+        }
+        Ok(Self { r, energy, force })
+    }
+
+    /// samples V and F = -dV/dr from a function returning (V, F) at n equidistant points
+    pub fn from_function(r_min: f32, r_max: f32, n: usize, f: impl Fn(f64) -> (f64, f64)) -> Self {
+        let mut table = Self {
+            r: Vec::with_capacity(n),
+            energy: Vec::with_capacity(n),
+            force: Vec::with_capacity(n),
+        };
+        for i in 0..n {
+            let r = r_min as f64 + (r_max - r_min) as f64 * i as f64 / (n - 1) as f64;
+            let (energy, force) = f(r);
+            table.r.push(r as f32);
+            table.energy.push(energy as f32);
+            table.force.push(force as f32);
+        }
+        table
+    }
+
+    /// loads the simple "r V F" format
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let (mut r, mut energy, mut force) = (Vec::new(), Vec::new(), Vec::new());
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns = parse_columns(line).map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+            if columns.len() < 3 {
+                return Err(format!("{}:{}: expected 3 columns (r V F)", path, number + 1).into());
+            }
+            r.push(columns[0]);
+            energy.push(columns[1]);
+            force.push(columns[2]);
+        }
+        Self::new(r, energy, force)
+    }
+
+    /// loads the section `keyword` of a LAMMPS pair_style table file
+    pub fn load_lammps(path: &str, keyword: &str) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let mut lines = content
+            .lines()
+            .map(|l| l.split('#').next().unwrap_or("").trim())
+            .filter(|l| !l.is_empty());
+
+        // find the section
+        loop {
+            match lines.next() {
+                Some(line) if line == keyword => break,
+                Some(_) => continue,
+                None => return Err(format!("{}: no table called {}", path, keyword).into()),
+            }
+        }
+
+        let header = lines.next().ok_or(format!("{}: table {} has no parameter line", path, keyword))?;
+        let words = header.split_whitespace().collect::<Vec<_>>();
+        let mut n = 0usize;
+        let mut spacing: Option<(bool, f32, f32)> = None; // (rsq, lo, hi)
+        let mut i = 0;
+        while i < words.len() {
+            match words[i] {
+                "N" => {
+                    n = words.get(i + 1).ok_or("N without a value")?.parse()?;
+                    i += 2;
+                }
+                "R" | "RSQ" => {
+                    let lo = words.get(i + 1).ok_or("R without values")?.parse()?;
+                    let hi = words.get(i + 2).ok_or("R without values")?.parse()?;
+                    spacing = Some((words[i] == "RSQ", lo, hi));
+                    i += 3;
+                }
+                "FP" => i += 3,
+                "BITMAP" => return Err(format!("{}: BITMAP tables are not supported", path).into()),
+                other => return Err(format!("{}: unknown table parameter {}", path, other).into()),
+            }
+        }
+        if n < 2 {
+            return Err(format!("{}: table {} needs N >= 2", path, keyword).into());
+        }
+
+        let (mut r, mut energy, mut force) = (Vec::new(), Vec::new(), Vec::new());
+        for _ in 0..n {
+            let line = lines.next().ok_or(format!("{}: table {} has less than {} points", path, keyword, n))?;
+            let columns = parse_columns(line)?;
+            if columns.len() < 4 {
+                return Err(format!("{}: expected 4 columns (index r V F), got: {}", path, line).into());
+            }
+            r.push(columns[1]);
+            energy.push(columns[2]);
+            force.push(columns[3]);
+        }
+
+        // with R / RSQ the distances in the file are ignored by LAMMPS as well
+        if let Some((rsq, lo, hi)) = spacing {
+            for (index, r) in r.iter_mut().enumerate() {
+                let x = index as f32 / (n - 1) as f32;
+                *r = if rsq {
+                    (lo * lo + x * (hi * hi - lo * lo)).sqrt()
+                } else {
+                    lo + x * (hi - lo)
+                };
+            }
+        }
+        Self::new(r, energy, force)
+    }
+
+    /// converts a table from other units, e.g. LAMMPS real units (Angstrom, kcal/mol)
+    /// with scaled(0.1, 4.184)
+    pub fn scaled(mut self, r_factor: f32, energy_factor: f32) -> Self {
+        for i in 0..self.r.len() {
+            self.r[i] *= r_factor;
+            self.energy[i] *= energy_factor;
+            self.force[i] *= energy_factor / r_factor;
+        }
+        self
+    }
+
+    pub fn r_min(&self) -> f32 {
+        self.r[0]
+    }
+
+    pub fn r_max(&self) -> f32 {
+        self.r[self.r.len() - 1]
+    }
+
+    /// linear interpolation of (V, F) at r, zero beyond the table and clamped below it
+    pub fn evaluate(&self, r: f32) -> (f32, f32) {
+        if r > self.r_max() {
+            return (0.0, 0.0);
+        }
+        if r <= self.r_min() {
+            return (self.energy[0], self.force[0]);
+        }
+        let i = (self.r.partition_point(|x| *x <= r) - 1).min(self.r.len() - 2);
+        let t = (r - self.r[i]) / (self.r[i + 1] - self.r[i]);
+        (
+            self.energy[i] + t * (self.energy[i + 1] - self.energy[i]),
+            self.force[i] + t * (self.force[i + 1] - self.force[i]),
+        )
+    }
+
+    /// the table resampled on n equidistant points as (V, F) pairs for the gpu
+    pub fn resample(&self, n: usize) -> Vec<[f32; 2]> {
+        (0..n)
+            .map(|i| {
+                let r = self.r_min() + (self.r_max() - self.r_min()) * i as f32 / (n - 1) as f32;
+                let (energy, force) = self.evaluate(r.min(self.r_max()));
+                [energy, force]
+            })
+            .collect()
+    }
+
+    /// compares F with the central difference -dV/dr at every inner point
+    pub fn check_consistency(&self) -> TableCheck {
+        let max_force = self.force.iter().fold(0.0f32, |m, f| m.max(f.abs())).max(f32::EPSILON);
+        let mut check = TableCheck::default();
+        for i in 1..self.r.len() - 1 {
+            let derivative = -(self.energy[i + 1] - self.energy[i - 1]) / (self.r[i + 1] - self.r[i - 1]);
+            let error = (derivative - self.force[i]).abs() / max_force;
+            if error > check.max_error {
+                check = TableCheck {
+                    max_error: error,
+                    at_r: self.r[i],
+                };
+            }
+        }
+        check
+    }
+}
+
+fn parse_columns(line: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+    let mut columns = Vec::new();
+    for word in line.split_whitespace() {
+        columns.push(word.parse::<f32>().map_err(|e| format!("could not parse '{}': {}", word, e))?);
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// V = 4 (r^-12 - r^-6) and F = -dV/dr in reduced units
+    fn lennard_jones(r: f64) -> (f64, f64) {
+        let r6 = r.powi(-6);
+        (4.0 * (r6 * r6 - r6), 24.0 * (2.0 * r6 * r6 - r6) / r)
+    }
+
+    /// writes `content` to a file of its own in the temp directory, returns the path
+    fn write_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        fs::write(&path, content).expect("the temp directory is writable");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn lammps_table_is_parsed() {
+        let mut content = String::from("# two tables, the second one is read\n\nLJ_A\nN 2\n\n1 1.0 0.0 0.0\n2 2.0 0.0 0.0\n\nLJ_B\nN 5 R 1.0 2.0\n\n");
+        // the distances in the file are overruled by R
+        for i in 0..5 {
+            let r = 1.0 + 0.25 * i as f64;
+            let (energy, force) = lennard_jones(r);
+            content += &format!("{} {} {} {} # point {}\n", i + 1, 9.9, energy, force, i);
+        }
+        let path = write_file("lammps.table", &content);
+        let table = PairTable::load_lammps(&path, "LJ_B").expect("the table is valid");
+        assert_eq!(table.r, vec![1.0, 1.25, 1.5, 1.75, 2.0]);
+        for (i, r) in table.r.iter().enumerate() {
+            let (energy, force) = lennard_jones(*r as f64);
+            assert!((table.energy[i] as f64 - energy).abs() < 1e-6, "V({}) = {} instead of {}", r, table.energy[i], energy);
+            assert!((table.force[i] as f64 - force).abs() < 1e-5, "F({}) = {} instead of {}", r, table.force[i], force);
+        }
+        assert!(PairTable::load_lammps(&path, "LJ_C").is_err());
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn rsq_spacing_is_even_in_r_squared() {
+        let path = write_file("rsq.table", "LJ\nN 3 RSQ 1.0 2.0\n1 0 1 0\n2 0 1 0\n3 0 1 0\n");
+        let table = PairTable::load_lammps(&path, "LJ").expect("the table is valid");
+        let r2 = table.r.iter().map(|r| r * r).collect::<Vec<_>>();
+        assert!((r2[1] - 2.5).abs() < 1e-6 && (r2[2] - 4.0).abs() < 1e-6, "r^2 = {:?}", r2);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn consistency_check_flags_a_force_that_is_not_the_derivative() {
+        let mut table = PairTable::from_function(1.0, 2.5, 301, lennard_jones);
+        // against the 1% `ForceField::set_table` warns above
+        let check = table.check_consistency();
+        assert!(check.max_error < 0.01, "an exact table is off by {:?}", check);
+
+        // 4% of the largest force at r = 1
+        table.force[150] += 1.0;
+        let check = table.check_consistency();
+        assert!(check.max_error > 0.01, "a wrong force passes with {:?}", check);
+        assert_eq!(check.at_r, table.r[150]);
+    }
+}
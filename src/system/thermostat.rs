@@ -0,0 +1,156 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::system::consts::*;
+
+// the longest Nosé–Hoover chain the gpu state has room for
+pub const MAX_CHAIN_LENGTH: u32 = 5;
+
+/// How the temperature is controlled, see `thermostat.wgsl`.
+///
+/// The global thermostats rescale all velocities once per frame from the reduced kinetic
+/// energy, Langevin and Andersen act on every particle in every substep.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThermostatKind {
+    /// NVE, the velocities are left alone
+    Off = 0,
+    /// weak coupling, relaxes the temperature exponentially but samples no proper ensemble
+    Berendsen = 1,
+    /// canonical sampling through velocity rescaling (Bussi, Donadio, Parrinello 2007)
+    Csvr = 2,
+    /// friction plus random kicks on every particle
+    Langevin = 3,
+    /// a chain of Nosé–Hoover thermostats coupled to the total kinetic energy
+    NoseHoover = 4,
+    /// every particle collides with the heat bath with a rate of 1 / tau
+    Andersen = 5,
+}
+
+impl ThermostatKind {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => ThermostatKind::Berendsen,
+            2 => ThermostatKind::Csvr,
+            3 => ThermostatKind::Langevin,
+            4 => ThermostatKind::NoseHoover,
+            5 => ThermostatKind::Andersen,
+            _ => ThermostatKind::Off,
+        }
+    }
+
+    /// rescales all velocities with one factor computed from the total kinetic energy
+    pub fn is_global(&self) -> bool {
+        matches!(self, ThermostatKind::Berendsen | ThermostatKind::Csvr | ThermostatKind::NoseHoover)
+    }
+
+    /// acts on every particle on its own
+    pub fn is_local(&self) -> bool {
+        matches!(self, ThermostatKind::Langevin | ThermostatKind::Andersen)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Thermostat {
+    pub kind: ThermostatKind,
+    pub temperature: f32, // target temperature in K
+    pub tau: f32,         // coupling time in ps, 1 / friction for Langevin
+    pub chain_length: u32, // only used by Nosé–Hoover
+}
+
+impl Thermostat {
+    pub fn new(kind: ThermostatKind, temperature: f32, tau: f32) -> Self {
+        assert!(tau > 0.0, "the coupling time has to be positive");
+        Self {
+            kind,
+            temperature,
+            tau,
+            chain_length: 3,
+        }
+    }
+
+    pub fn off() -> Self {
+        Self::new(ThermostatKind::Off, INIT_TEMPERATURE, 0.1)
+    }
+
+    pub fn berendsen(temperature: f32, tau: f32) -> Self {
+        Self::new(ThermostatKind::Berendsen, temperature, tau)
+    }
+
+    pub fn csvr(temperature: f32, tau: f32) -> Self {
+        Self::new(ThermostatKind::Csvr, temperature, tau)
+    }
+
+    pub fn langevin(temperature: f32, tau: f32) -> Self {
+        Self::new(ThermostatKind::Langevin, temperature, tau)
+    }
+
+    pub fn nose_hoover(temperature: f32, tau: f32, chain_length: u32) -> Self {
+        assert!(
+            (1..=MAX_CHAIN_LENGTH).contains(&chain_length),
+            "the chain length has to be between 1 and {}",
+            MAX_CHAIN_LENGTH
+        );
+        Self {
+            chain_length,
+            ..Self::new(ThermostatKind::NoseHoover, temperature, tau)
+        }
+    }
+
+    pub fn andersen(temperature: f32, tau: f32) -> Self {
+        Self::new(ThermostatKind::Andersen, temperature, tau)
+    }
+}
+
+impl Default for Thermostat {
+    fn default() -> Self {
+        Self::off()
+    }
+}
+
+/// State of the global thermostats, lives on the gpu and is only written by `thermostat.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ThermostatState {
+    pub lambda: f32,          // velocity scale factor of the last rescaling
+    pub energy: f32,          // in mU, energy the global thermostats took out of the system
+    pub particle_energy: f32, // in mU, the same for Langevin and Andersen (summed over all particles)
+    pub seed: u32,
+    pub xi: [f32; MAX_CHAIN_LENGTH as usize],   // Nosé–Hoover chain positions
+    pub v_xi: [f32; MAX_CHAIN_LENGTH as usize], // and velocities in 1 / ps
+}
+unsafe impl bytemuck::Pod for ThermostatState {}
+unsafe impl bytemuck::Zeroable for ThermostatState {}
+
+impl ThermostatState {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            lambda: 1.0,
+            seed: StdRng::seed_from_u64(seed).gen(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Per particle random number state and the energy Langevin and Andersen exchanged with it.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ThermostatParticle {
+    pub seed: u32,
+    pub energy: f32, // in mU
+}
+unsafe impl bytemuck::Pod for ThermostatParticle {}
+unsafe impl bytemuck::Zeroable for ThermostatParticle {}
+
+impl ThermostatParticle {
+    pub fn create(num_particles: u32, seed: u64) -> Vec<ThermostatParticle> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..num_particles)
+            .map(|_| ThermostatParticle {
+                seed: rng.gen(),
+                energy: 0.0,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::system::consts::*;
+
+/// How long a substep is, see `timestep.wgsl`.
+///
+/// A fixed timestep uses `dt` for every substep. The adaptive one picks the longest dt
+/// between `dt_min` and `dt_max` that moves no particle further than `displacement` times
+/// the smallest sigma, from the largest speed and acceleration of the last substep.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Timestep {
+    pub adaptive: bool,
+    pub dt: f32,           // in ps, the fixed timestep and the first one of the adaptive mode
+    pub dt_min: f32,       // in ps
+    pub dt_max: f32,       // in ps
+    pub displacement: f32, // largest move in one substep, as a fraction of sigma
+    pub growth: f32,       // largest factor dt grows by from one substep to the next
+}
+
+impl Timestep {
+    pub fn fixed(dt: f32) -> Self {
+        assert!(dt > 0.0, "the timestep has to be positive");
+        Self {
+            adaptive: false,
+            dt,
+            dt_min: dt * 0.01,
+            dt_max: dt * 2.0,
+            displacement: 0.05,
+            growth: 1.05,
+        }
+    }
+
+    pub fn adaptive(dt_min: f32, dt_max: f32, displacement: f32) -> Self {
+        assert!(0.0 < dt_min && dt_min <= dt_max, "expected 0 < dt_min <= dt_max");
+        Self {
+            adaptive: true,
+            dt: dt_min,
+            dt_min,
+            dt_max,
+            displacement,
+            growth: 1.05,
+        }
+    }
+}
+
+impl Default for Timestep {
+    fn default() -> Self {
+        Self::fixed(DT)
+    }
+}
+
+/// The timestep on the gpu. The first four fields are only written by `timestep.wgsl`,
+/// the rest is the control part written by `ComputeSet::set_timestep`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimestepState {
+    pub time: f32,          // whole ps of simulated time
+    pub time_fraction: f32, // the rest in ps, kept apart so small steps are not lost in the sum
+    pub v_max: f32,         // in nm/ps, largest speed after the last substep
+    pub a_max: f32,         // in nm/ps^2, largest acceleration after the last substep
+    pub dt: f32,            // in ps, length of the next substep
+    pub adaptive: u32,
+    pub dt_min: f32,
+    pub dt_max: f32,
+    pub max_displacement: f32, // in nm
+    pub growth: f32,
+    pub _padding: [f32; 2],
+}
+unsafe impl bytemuck::Pod for TimestepState {}
+unsafe impl bytemuck::Zeroable for TimestepState {}
+
+impl TimestepState {
+    // byte offset of the control part, it starts with dt
+    pub const CONTROL_OFFSET: u64 = 16;
+
+    pub fn new(timestep: &Timestep, sigma: f32) -> Self {
+        Self {
+            dt: timestep.dt,
+            adaptive: timestep.adaptive as u32,
+            dt_min: timestep.dt_min,
+            dt_max: timestep.dt_max,
+            max_displacement: timestep.displacement * sigma,
+            growth: timestep.growth,
+            ..Default::default()
+        }
+    }
+
+    /// simulated time in ps
+    pub fn elapsed(&self) -> f64 {
+        self.time as f64 + self.time_fraction as f64
+    }
+}
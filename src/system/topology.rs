@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+
+/*
+Bonded interactions, all energies in mU and lengths in nm:
+
+harmonic bond:   V = k / 2 (r - r0)^2
+FENE bond:       V = -k / 2 r0^2 ln(1 - (r / r0)^2) + WCA(epsilon, sigma), continued linearly
+                 beyond r_c = sqrt(0.99) r0 so an overstretched bond has a finite energy
+harmonic angle:  V = k / 2 (theta - theta0)^2
+dihedral:        V = k (1 + cos(n phi - phi0))
+
+Atoms that are bonded (1-2) or share a bonded neighbour (1-3) do not interact through
+the nonbonded potentials. The bonded forces are evaluated by bonded.wgsl once per particle:
+every particle walks over the terms it takes part in and keeps its own share of the force.
+
+Constraints fix the distance of a pair instead of adding a potential, they are kept by
+SHAKE and RATTLE in constraints.wgsl and exclude their pairs like bonds. Pairs that are
+connected through constraints form a cluster, which is solved by a single thread.
+ */
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BondStyle {
+    Harmonic = 0,
+    Fene = 1,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BondType {
+    pub style: u32,
+    pub k: f32,       // in mU / nm^2
+    pub r0: f32,      // rest length (harmonic) or maximum extension (FENE) in nm
+    pub epsilon: f32, // WCA part of FENE, in mU
+    pub sigma: f32,   // in nm
+}
+unsafe impl bytemuck::Pod for BondType {}
+unsafe impl bytemuck::Zeroable for BondType {}
+
+impl BondType {
+    pub fn harmonic(k: f32, r0: f32) -> Self {
+        Self {
+            style: BondStyle::Harmonic as u32,
+            k,
+            r0,
+            ..Default::default()
+        }
+    }
+
+    /// Kremer-Grest style bond, usually k = 30 epsilon / sigma^2 and r0 = 1.5 sigma
+    pub fn fene(k: f32, r0: f32, epsilon: f32, sigma: f32) -> Self {
+        Self {
+            style: BondStyle::Fene as u32,
+            k,
+            r0,
+            epsilon,
+            sigma,
+        }
+    }
+
+    /// the distance at which the bond force vanishes
+    pub fn rest_length(&self) -> f32 {
+        match self.style {
+            0 => self.r0,
+            // minimum of FENE + WCA for the usual Kremer-Grest parameters
+            _ => 0.97 * self.sigma,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AngleType {
+    pub k: f32,      // in mU / rad^2
+    pub theta0: f32, // in rad
+}
+unsafe impl bytemuck::Pod for AngleType {}
+unsafe impl bytemuck::Zeroable for AngleType {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DihedralType {
+    pub k: f32,    // in mU
+    pub phi0: f32, // in rad
+    pub n: u32,    // multiplicity
+}
+unsafe impl bytemuck::Pod for DihedralType {}
+unsafe impl bytemuck::Zeroable for DihedralType {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bond {
+    pub i: u32,
+    pub j: u32,
+    pub type_: u32,
+}
+unsafe impl bytemuck::Pod for Bond {}
+unsafe impl bytemuck::Zeroable for Bond {}
+
+/// the angle i-j-k with j in the middle
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Angle {
+    pub i: u32,
+    pub j: u32,
+    pub k: u32,
+    pub type_: u32,
+}
+unsafe impl bytemuck::Pod for Angle {}
+unsafe impl bytemuck::Zeroable for Angle {}
+
+/// the dihedral i-j-k-l around the j-k axis
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dihedral {
+    pub i: u32,
+    pub j: u32,
+    pub k: u32,
+    pub l: u32,
+    pub type_: u32,
+}
+unsafe impl bytemuck::Pod for Dihedral {}
+unsafe impl bytemuck::Zeroable for Dihedral {}
+
+/// a fixed distance between the particles i and j
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Constraint {
+    pub i: u32,
+    pub j: u32,
+    pub length: f32, // in nm
+}
+unsafe impl bytemuck::Pod for Constraint {}
+unsafe impl bytemuck::Zeroable for Constraint {}
+
+// kind of an entry in the per particle term list, stored in the top two bits
+const TERM_BOND: u32 = 0;
+const TERM_ANGLE: u32 = 1;
+const TERM_DIHEDRAL: u32 = 2;
+const TERM_SHIFT: u32 = 30;
+
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    pub bond_types: Vec<BondType>,
+    pub angle_types: Vec<AngleType>,
+    pub dihedral_types: Vec<DihedralType>,
+    pub bonds: Vec<Bond>,
+    pub angles: Vec<Angle>,
+    pub dihedrals: Vec<Dihedral>,
+    pub constraints: Vec<Constraint>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// linear chains of `length` consecutive particles, with an angle on every triple and
+    /// a dihedral on every quadruple if the types are given. A length of 1 gives free atoms.
+    pub fn linear_chains(
+        num_particles: u32,
+        length: u32,
+        bond_type: BondType,
+        angle_type: Option<AngleType>,
+        dihedral_type: Option<DihedralType>,
+    ) -> Self {
+        let mut topology = Self::new();
+        if length < 2 {
+            return topology;
+        }
+        let bond = topology.add_bond_type(bond_type);
+        let angle = angle_type.map(|t| topology.add_angle_type(t));
+        let dihedral = dihedral_type.map(|t| topology.add_dihedral_type(t));
+        for start in (0..num_particles - num_particles % length).step_by(length as usize) {
+            for i in start..start + length {
+                let end = start + length;
+                if i + 1 < end {
+                    topology.add_bond(i, i + 1, bond);
+                }
+                if let Some(angle) = angle {
+                    if i + 2 < end {
+                        topology.add_angle(i, i + 1, i + 2, angle);
+                    }
+                }
+                if let Some(dihedral) = dihedral {
+                    if i + 3 < end {
+                        topology.add_dihedral(i, i + 1, i + 2, i + 3, dihedral);
+                    }
+                }
+            }
+        }
+        topology
+    }
+
+    pub fn add_bond_type(&mut self, bond_type: BondType) -> u32 {
+        self.bond_types.push(bond_type);
+        self.bond_types.len() as u32 - 1
+    }
+
+    pub fn add_angle_type(&mut self, angle_type: AngleType) -> u32 {
+        self.angle_types.push(angle_type);
+        self.angle_types.len() as u32 - 1
+    }
+
+    pub fn add_dihedral_type(&mut self, dihedral_type: DihedralType) -> u32 {
+        self.dihedral_types.push(dihedral_type);
+        self.dihedral_types.len() as u32 - 1
+    }
+
+    pub fn add_bond(&mut self, i: u32, j: u32, type_: u32) {
+        assert!((type_ as usize) < self.bond_types.len(), "unknown bond type {}", type_);
+        self.bonds.push(Bond { i, j, type_ });
+    }
+
+    pub fn add_angle(&mut self, i: u32, j: u32, k: u32, type_: u32) {
+        assert!((type_ as usize) < self.angle_types.len(), "unknown angle type {}", type_);
+        self.angles.push(Angle { i, j, k, type_ });
+    }
+
+    pub fn add_dihedral(&mut self, i: u32, j: u32, k: u32, l: u32, type_: u32) {
+        assert!((type_ as usize) < self.dihedral_types.len(), "unknown dihedral type {}", type_);
+        self.dihedrals.push(Dihedral { i, j, k, l, type_ });
+    }
+
+    pub fn add_constraint(&mut self, i: u32, j: u32, length: f32) {
+        assert!(i != j && length > 0.0, "a constraint needs two particles and a positive length");
+        self.constraints.push(Constraint { i, j, length });
+    }
+
+    /// replaces every bond by a constraint at the rest length of the bond
+    pub fn constrain_bonds(&mut self) {
+        for bond in std::mem::take(&mut self.bonds) {
+            let length = self.bond_types[bond.type_ as usize].rest_length();
+            self.add_constraint(bond.i, bond.j, length);
+        }
+    }
+
+    /// true without any bonded terms, constraints do not count
+    pub fn is_empty(&self) -> bool {
+        self.bonds.is_empty() && self.angles.is_empty() && self.dihedrals.is_empty()
+    }
+
+    /// the 1-2 and 1-3 partners of every particle
+    pub fn excluded_pairs(&self, num_particles: u32) -> Vec<BTreeSet<u32>> {
+        let mut neighbours = vec![BTreeSet::new(); num_particles as usize];
+        let pairs = self.bonds.iter().map(|b| (b.i, b.j)).chain(self.constraints.iter().map(|c| (c.i, c.j)));
+        for (i, j) in pairs {
+            neighbours[i as usize].insert(j);
+            neighbours[j as usize].insert(i);
+        }
+        let mut excluded = neighbours.clone();
+        for (center, partners) in neighbours.iter().enumerate() {
+            for a in partners {
+                for b in partners {
+                    if a != b {
+                        excluded[*a as usize].insert(*b);
+                    }
+                }
+            }
+            excluded[center].remove(&(center as u32));
+        }
+        for angle in &self.angles {
+            excluded[angle.i as usize].insert(angle.k);
+            excluded[angle.k as usize].insert(angle.i);
+        }
+        excluded
+    }
+
+    /// exclusions for the gpu: num_particles + 1 offsets followed by the partner lists
+    pub fn serialize_exclusions(&self, num_particles: u32) -> Vec<u32> {
+        let excluded = self.excluded_pairs(num_particles);
+        let mut data = Vec::with_capacity(num_particles as usize + 1);
+        let mut offset = num_particles + 1;
+        for partners in &excluded {
+            data.push(offset);
+            offset += partners.len() as u32;
+        }
+        data.push(offset);
+        for partners in &excluded {
+            data.extend(partners.iter());
+        }
+        data
+    }
+
+    /// the terms every particle takes part in for the gpu: num_particles + 1 offsets followed by
+    /// entries of the form (kind << 30 | index)
+    pub fn serialize_terms(&self, num_particles: u32) -> Vec<u32> {
+        let mut terms = vec![Vec::new(); num_particles as usize];
+        for (index, bond) in self.bonds.iter().enumerate() {
+            let entry = TERM_BOND << TERM_SHIFT | index as u32;
+            terms[bond.i as usize].push(entry);
+            terms[bond.j as usize].push(entry);
+        }
+        for (index, angle) in self.angles.iter().enumerate() {
+            let entry = TERM_ANGLE << TERM_SHIFT | index as u32;
+            for particle in [angle.i, angle.j, angle.k] {
+                terms[particle as usize].push(entry);
+            }
+        }
+        for (index, dihedral) in self.dihedrals.iter().enumerate() {
+            let entry = TERM_DIHEDRAL << TERM_SHIFT | index as u32;
+            for particle in [dihedral.i, dihedral.j, dihedral.k, dihedral.l] {
+                terms[particle as usize].push(entry);
+            }
+        }
+        let mut data = Vec::new();
+        let mut offset = num_particles + 1;
+        for list in &terms {
+            data.push(offset);
+            offset += list.len() as u32;
+        }
+        data.push(offset);
+        for list in &terms {
+            data.extend(list);
+        }
+        data
+    }
+
+    /// the constraints of every cluster of particles that are connected through constraints
+    pub fn constraint_clusters(&self) -> Vec<Vec<u32>> {
+        // union find over the particles of the constraints
+        let mut parent = HashMap::<u32, u32>::new();
+        fn root(parent: &mut HashMap<u32, u32>, i: u32) -> u32 {
+            let mut r = *parent.entry(i).or_insert(i);
+            while parent[&r] != r {
+                r = parent[&r];
+            }
+            parent.insert(i, r);
+            r
+        }
+        for constraint in &self.constraints {
+            let a = root(&mut parent, constraint.i);
+            let b = root(&mut parent, constraint.j);
+            parent.insert(a.max(b), a.min(b));
+        }
+        let mut clusters = BTreeMap::<u32, Vec<u32>>::new();
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            let r = root(&mut parent, constraint.i);
+            clusters.entry(r).or_default().push(index as u32);
+        }
+        clusters.into_values().collect()
+    }
+
+    /// clusters for the gpu: cluster_count + 1 offsets followed by the constraint indices of every cluster
+    pub fn serialize_constraint_clusters(&self) -> Vec<u32> {
+        let clusters = self.constraint_clusters();
+        let mut data = Vec::new();
+        let mut offset = clusters.len() as u32 + 1;
+        for cluster in &clusters {
+            data.push(offset);
+            offset += cluster.len() as u32;
+        }
+        data.push(offset);
+        for cluster in &clusters {
+            data.extend(cluster);
+        }
+        data
+    }
+
+    /// moves the particles of every chain next to each other as a planar zig-zag with the
+    /// rest length of their bonds (or the length of their constraints), starting from the
+    /// position of the first particle. The
+    /// chain follows the direction towards the original position of the next particle,
+    /// so it stays between the lattice sites it was created on.
+    pub fn arrange_chains(&self, particles: &mut [Particle], simulation_box: &SimulationBox) {
+        let theta = self.angle_types.first().map(|a| a.theta0).unwrap_or(PI);
+        let tilt = 0.5 * (PI - theta);
+        let original = particles.iter().map(|p| p.position).collect::<Vec<_>>();
+        let mut placed = vec![false; particles.len()];
+        let bonds = self.bonds.iter().map(|b| (b.i, b.j, self.bond_types[b.type_ as usize].rest_length()));
+        let pairs = bonds.chain(self.constraints.iter().map(|c| (c.i, c.j, c.length)));
+        for (n, (i, j, r0)) in pairs.enumerate() {
+            let (i, j) = (i as usize, j as usize);
+            if placed[j] {
+                continue;
+            }
+            placed[i] = true;
+            placed[j] = true;
+            let mut d = [0.0; 3];
+            for a in 0..3 {
+                d[a] = original[j][a] - original[i][a];
+            }
+            let mut axis = simulation_box.minimum_image(d, [true; 3]);
+            let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt().max(f32::EPSILON);
+            axis = axis.map(|x| x / length);
+            // any direction perpendicular to the axis, alternating for the zig-zag
+            let mut side = if axis[2].abs() < 0.9 { [-axis[1], axis[0], 0.0] } else { [0.0, -axis[2], axis[1]] };
+            let side_length = (side[0] * side[0] + side[1] * side[1] + side[2] * side[2]).sqrt();
+            side = side.map(|x| x / side_length * if n % 2 == 0 { 1.0 } else { -1.0 });
+            let mut position = [0.0; 3];
+            for a in 0..3 {
+                position[a] = particles[i].position[a] + r0 * (tilt.cos() * axis[a] + tilt.sin() * side[a]);
+            }
+            particles[j].position = simulation_box.wrap(position, [true; 3]);
+        }
+    }
+
+    /// bonded forces (in mU / nm) and the total bonded energy (in mU) on the cpu,
+    /// the same functional forms as bonded.wgsl
+    pub fn forces(&self, positions: &[[f32; 3]], simulation_box: &SimulationBox) -> (Vec<[f64; 3]>, f64) {
+        let mut forces = vec![[0.0f64; 3]; positions.len()];
+        let mut energy = 0.0;
+        let d = |a: usize, b: usize| -> [f64; 3] {
+            let mut v = [0.0; 3];
+            for x in 0..3 {
+                v[x] = positions[a][x] - positions[b][x];
+            }
+            simulation_box.minimum_image(v, [true; 3]).map(|x| x as f64)
+        };
+        for bond in &self.bonds {
+            let (i, j) = (bond.i as usize, bond.j as usize);
+            let r = d(i, j);
+            let dist = norm(r);
+            let (f, e) = bond_force(&self.bond_types[bond.type_ as usize], dist);
+            energy += e;
+            for x in 0..3 {
+                forces[i][x] += f * r[x] / dist;
+                forces[j][x] -= f * r[x] / dist;
+            }
+        }
+        for angle in &self.angles {
+            let (i, j, k) = (angle.i as usize, angle.j as usize, angle.k as usize);
+            let (f_i, f_k, e) = angle_forces(&self.angle_types[angle.type_ as usize], d(i, j), d(k, j));
+            energy += e;
+            for x in 0..3 {
+                forces[i][x] += f_i[x];
+                forces[k][x] += f_k[x];
+                forces[j][x] -= f_i[x] + f_k[x];
+            }
+        }
+        for dihedral in &self.dihedrals {
+            let (i, j, k, l) = (dihedral.i as usize, dihedral.j as usize, dihedral.k as usize, dihedral.l as usize);
+            let (f, e) = dihedral_forces(&self.dihedral_types[dihedral.type_ as usize], d(i, j), d(k, j), d(k, l));
+            energy += e;
+            for x in 0..3 {
+                forces[i][x] += f[0][x];
+                forces[j][x] += f[1][x];
+                forces[k][x] += f[2][x];
+                forces[l][x] += f[3][x];
+            }
+        }
+        (forces, energy)
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn norm(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// force along the bond (positive pushes the atoms apart) and energy
+fn bond_force(bond_type: &BondType, dist: f64) -> (f64, f64) {
+    let k = bond_type.k as f64;
+    let r0 = bond_type.r0 as f64;
+    if bond_type.style == BondStyle::Harmonic as u32 {
+        return (-k * (dist - r0), 0.5 * k * (dist - r0).powi(2));
+    }
+    // linear beyond r_c like in bonded.wgsl
+    let r_c = 0.99f64.sqrt() * r0;
+    let r = dist.min(r_c);
+    let x = (r / r0).powi(2);
+    let mut force = -k * r / (1.0 - x);
+    let mut energy = -0.5 * k * r0 * r0 * (1.0 - x).ln() - force * (dist - r_c).max(0.0);
+    let sigma = bond_type.sigma as f64;
+    let epsilon = bond_type.epsilon as f64;
+    if dist < 2f64.powf(1.0 / 6.0) * sigma {
+        let r6 = (sigma / dist).powi(6);
+        force += 24.0 * epsilon * (2.0 * r6 * r6 - r6) / dist;
+        energy += 4.0 * epsilon * (r6 * r6 - r6) + epsilon;
+    }
+    (force, energy)
+}
+
+/// forces on the outer atoms of the angle and its energy, r_ij = x_i - x_j and r_kj = x_k - x_j
+fn angle_forces(angle_type: &AngleType, r_ij: [f64; 3], r_kj: [f64; 3]) -> ([f64; 3], [f64; 3], f64) {
+    let a = norm(r_ij);
+    let b = norm(r_kj);
+    let cos = (dot(r_ij, r_kj) / (a * b)).clamp(-1.0, 1.0);
+    let theta = cos.acos();
+    let sin = (1.0 - cos * cos).sqrt().max(1e-6);
+    let delta = theta - angle_type.theta0 as f64;
+    let dv = angle_type.k as f64 * delta;
+    let mut f_i = [0.0; 3];
+    let mut f_k = [0.0; 3];
+    for x in 0..3 {
+        f_i[x] = dv / sin * (r_kj[x] / b - cos * r_ij[x] / a) / a;
+        f_k[x] = dv / sin * (r_ij[x] / a - cos * r_kj[x] / b) / b;
+    }
+    (f_i, f_k, 0.5 * angle_type.k as f64 * delta * delta)
+}
+
+/// forces on the four atoms and the energy, r_ij = x_i - x_j, r_kj = x_k - x_j, r_kl = x_k - x_l
+fn dihedral_forces(dihedral_type: &DihedralType, r_ij: [f64; 3], r_kj: [f64; 3], r_kl: [f64; 3]) -> ([[f64; 3]; 4], f64) {
+    let m = cross(r_ij, r_kj);
+    let n = cross(r_kj, r_kl);
+    let m2 = dot(m, m).max(1e-12);
+    let n2 = dot(n, n).max(1e-12);
+    let kj2 = dot(r_kj, r_kj);
+    let kj = kj2.sqrt();
+    let sign = if dot(r_ij, n) < 0.0 { -1.0 } else { 1.0 };
+    let phi = sign * norm(cross(m, n)).atan2(dot(m, n));
+    let multiplicity = dihedral_type.n as f64;
+    let phase = multiplicity * phi - dihedral_type.phi0 as f64;
+    let energy = dihedral_type.k as f64 * (1.0 + phase.cos());
+    let dv = -dihedral_type.k as f64 * multiplicity * phase.sin();
+
+    let mut f_i = [0.0; 3];
+    let mut f_l = [0.0; 3];
+    for x in 0..3 {
+        f_i[x] = -dv * kj / m2 * m[x];
+        f_l[x] = dv * kj / n2 * n[x];
+    }
+    let p = dot(r_ij, r_kj) / kj2;
+    let q = dot(r_kl, r_kj) / kj2;
+    let mut f_j = [0.0; 3];
+    let mut f_k = [0.0; 3];
+    for x in 0..3 {
+        let s = p * f_i[x] - q * f_l[x];
+        f_j[x] = f_i[x] - s;
+        f_k[x] = f_l[x] + s;
+    }
+    ([f_i, [-f_j[0], -f_j[1], -f_j[2]], [-f_k[0], -f_k[1], -f_k[2]], f_l], energy)
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::system::particle::Particle;
+use crate::system::simulation_box::SimulationBox;
+
+/// Writes frames in the extended XYZ format (OVITO, ASE and VMD read it). Lengths stay in
+/// nm and velocities in nm/ps, the species is the atom type.
+pub struct Trajectory {
+    writer: BufWriter<File>,
+    frames: u32,
+}
+
+impl Trajectory {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path).map_err(|e| format!("can not create {}: {}", path, e))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            frames: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, particles: &[Particle], simulation_box: &SimulationBox, time: f64) -> Result<(), Box<dyn Error>> {
+        let lattice = simulation_box
+            .vectors()
+            .iter()
+            .flatten()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let origin = simulation_box.origin().map(|x| x.to_string()).join(" ");
+        writeln!(self.writer, "{}", particles.len())?;
+        writeln!(
+            self.writer,
+            "Lattice=\"{}\" Origin=\"{}\" Properties=species:S:1:pos:R:3:vel:R:3 Time={} pbc=\"T T T\"",
+            lattice, origin, time
+        )?;
+        for particle in particles {
+            let [x, y, z] = particle.position;
+            let [vx, vy, vz] = particle.velocity;
+            writeln!(self.writer, "T{} {} {} {} {} {} {}", particle.type_ as u32, x, y, z, vx, vy, vz)?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
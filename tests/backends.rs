@@ -0,0 +1,284 @@
+//! Runs the gpu pipeline against the cpu reference, the bonded shader against
+//! `Topology::forces`, the gpu Ewald sum against the cpu one and an overflowed Verlet list
+//! against the bins. Every test skips itself when there is no adapter to run the pipeline on.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use ParticleLife3D::headless::COMPARE_TOLERANCE;
+use ParticleLife3D::system::backend::compare;
+use ParticleLife3D::system::boundary::BoundaryMode;
+use ParticleLife3D::system::config::{AngleTypeConfig, BondTypeConfig, Config, ForceFieldPreset};
+use ParticleLife3D::system::cpu::kernels::lennard_jones_force;
+use ParticleLife3D::system::cpu::reference::ReferenceBackend;
+use ParticleLife3D::system::life::ForceModel;
+use ParticleLife3D::system::particle::Particle;
+use ParticleLife3D::system::simulation::Simulation;
+use ParticleLife3D::system::topology::BondStyle;
+
+/// a small, dense and warm system, so the forces matter within a few frames
+fn config(substeps: u32) -> Config {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 100.0;
+    config.integrator.substeps = substeps;
+    config
+}
+
+/// the gpu pipeline, None without an adapter
+fn gpu(config: &Config) -> Option<Simulation> {
+    config.validate().expect("the test config is valid");
+    match Simulation::builder().config(config.clone()).build() {
+        Ok(simulation) => Some(simulation),
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            None
+        }
+    }
+}
+
+/// both backends follow the same trajectory for a few frames
+fn assert_agree(config: &Config, frames: usize) {
+    let Some(mut gpu) = gpu(config) else {
+        return;
+    };
+    let mut cpu = ReferenceBackend::new(config).expect("the test config runs on the cpu");
+    let comparison = compare(&mut gpu, &mut cpu, frames);
+    assert!(comparison.max_deviation < 1e-4, "{}", comparison);
+    assert!(comparison.relative_energy_difference() < COMPARE_TOLERANCE, "{}", comparison);
+}
+
+#[test]
+fn odd_substeps_agree() {
+    assert_agree(&config(3), 20);
+}
+
+// the ping-pong buffers have to follow the substeps, not the frames
+#[test]
+fn even_substeps_agree() {
+    assert_agree(&config(4), 20);
+}
+
+// a box shape from the config, all three heights above the 6 cutoffs of three bins
+#[test]
+fn triclinic_box_agrees() {
+    let mut config = config(3);
+    config.system.box_lengths = Some([4.2, 4.0, 4.4]);
+    config.system.box_tilt = [1.0, -0.8, 0.9];
+    assert_agree(&config, 20);
+}
+
+// the grid starts right at the faces, so particles cross them within a few frames
+#[test]
+fn walls_agree() {
+    let mut config = config(3);
+    config.system.margin = 0.02;
+    config.boundary.modes = [BoundaryMode::Reflective, BoundaryMode::Open, BoundaryMode::Periodic];
+    assert_agree(&config, 20);
+}
+
+// the list radius of 2.5 cutoffs plus the skin is beyond the 2 cutoffs of the default bins,
+// the particles are spread over the whole box so pairs reach across more than one bin
+#[test]
+fn particle_life_beyond_two_cutoffs_agrees() {
+    let mut config = config(3);
+    config.system.box_size = Some(6.0);
+    config.force_field.model = ForceModel::ParticleLife;
+    config.force_field.life_radius = 2.5 * config.force_field.cutoff;
+    config.force_field.verlet_skin = 0.1;
+    let Some(mut gpu) = gpu(&config) else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut particles = gpu.particles();
+    for particle in particles.iter_mut() {
+        particle.position = [(); 3].map(|_| rng.gen_range(-3.0..3.0));
+    }
+    gpu.set_particles(&particles);
+    let mut cpu = ReferenceBackend::new(&config).expect("particle life runs on the cpu");
+    let comparison = compare(&mut gpu, &mut cpu, 20);
+    assert!(comparison.max_deviation < 1e-4, "{}", comparison);
+}
+
+// after a single substep every stage has its own observable: the drift moves the positions,
+// the force pass sets the accelerations and velocities and the reduction sums KE and PE
+#[test]
+fn single_substep_agrees_stage_by_stage() {
+    let config = config(1);
+    let Some(mut gpu) = gpu(&config) else {
+        return;
+    };
+    let mut cpu = ReferenceBackend::new(&config).expect("the test config runs on the cpu");
+    cpu.set_particles(&gpu.particles());
+    gpu.step(1);
+    cpu.step();
+
+    let deviation = |field: fn(&Particle) -> [f32; 3]| {
+        let (gpu_particles, cpu_particles) = (gpu.particles(), cpu.particles());
+        let largest = cpu_particles.iter().flat_map(field).fold(f32::EPSILON, |a, x| a.max(x.abs()));
+        let deviation = gpu_particles
+            .iter()
+            .zip(cpu_particles)
+            .flat_map(|(g, c)| (0..3).map(move |a| (field(g)[a] - field(c)[a]).abs()))
+            .fold(0.0, f32::max);
+        deviation / largest
+    };
+    let positions = deviation(|p| p.position);
+    let accelerations = deviation(|p| p.last_acceleration);
+    let velocities = deviation(|p| p.velocity);
+    assert!(positions < 1e-6, "relative position deviation {}", positions);
+    assert!(accelerations < 1e-4, "relative acceleration deviation {}", accelerations);
+    assert!(velocities < 1e-5, "relative velocity deviation {}", velocities);
+
+    let (gpu_stat, cpu_stat) = (gpu.compute().read_stat(gpu.device(), gpu.queue()), cpu.stat());
+    assert!(((gpu_stat.KE - cpu_stat.KE) / cpu_stat.KE).abs() < 1e-5, "KE gpu: {}, cpu: {}", gpu_stat.KE, cpu_stat.KE);
+    assert!(((gpu_stat.PE - cpu_stat.PE) / cpu_stat.PE).abs() < 1e-4, "PE gpu: {}, cpu: {}", gpu_stat.PE, cpu_stat.PE);
+}
+
+// chains with every kind of bonded term and one bond stretched beyond the cut off logarithm
+#[test]
+fn bonded_forces_match_the_shader() {
+    let mut config = config(1);
+    config.system.chain_length = 4;
+    config.system.angle_stiffness = 2.0;
+    config.system.dihedral_stiffness = 0.5;
+    let Some(mut gpu) = gpu(&config) else {
+        return;
+    };
+    let sigma = config.force_field.preset.build().atom(0).sigma;
+    let mut particles = gpu.particles();
+    let d = gpu.simulation_box().minimum_image(
+        [0, 1, 2].map(|x| particles[1].position[x] - particles[0].position[x]),
+        [true; 3],
+    );
+    let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+    particles[1].position = [0, 1, 2].map(|x| particles[0].position[x] + 1.6 * sigma * d[x] / length);
+    gpu.set_particles(&particles);
+    gpu.step(1);
+
+    let (gpu_energy, cpu_energy, deviation) = gpu.compute().validate_bonded(gpu.device(), gpu.queue());
+    assert!(((gpu_energy - cpu_energy) / cpu_energy).abs() < 1e-4, "gpu: {} mU, cpu: {} mU", gpu_energy, cpu_energy);
+    assert!(deviation < 1e-4, "relative force deviation {}", deviation);
+}
+
+// stars of a centre and three arms from an explicit topology instead of the chains
+#[test]
+fn explicit_topology_matches_the_shader() {
+    let mut config = config(1);
+    let topology = &mut config.topology;
+    topology.bond_types.push(BondTypeConfig { style: BondStyle::Harmonic, k: 5000.0, r0: 0.3, ..Default::default() });
+    topology.angle_types.push(AngleTypeConfig { k: 50.0, theta0: 109.5 });
+    for centre in (0..config.system.particles).step_by(4) {
+        for arm in 1..4 {
+            topology.bonds.push([centre, centre + arm, 0]);
+            for other in arm + 1..4 {
+                topology.angles.push([centre + arm, centre, centre + other, 0]);
+            }
+        }
+    }
+    let Some(mut gpu) = gpu(&config) else {
+        return;
+    };
+    // the chain layout puts all three arms on the same side of the centre, near the
+    // singularity of the angles, so the arms get directions about 100 degrees apart
+    let arms = [[1.0, 0.1, 0.0], [-0.5, 0.8, 0.2], [-0.4, -0.7, 0.6]];
+    let mut particles = gpu.particles();
+    for centre in (0..particles.len()).step_by(4) {
+        for (arm, direction) in arms.iter().enumerate() {
+            let length = direction.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let position = [0, 1, 2].map(|x| particles[centre].position[x] + 0.31 * direction[x] / length);
+            particles[centre + 1 + arm].position = gpu.simulation_box().wrap(position, [true; 3]);
+        }
+    }
+    gpu.set_particles(&particles);
+    gpu.step(1);
+    let (gpu_energy, cpu_energy, deviation) = gpu.compute().validate_bonded(gpu.device(), gpu.queue());
+    assert!(cpu_energy > 0.0, "the stars have no bonded energy");
+    assert!(((gpu_energy - cpu_energy) / cpu_energy).abs() < 1e-4, "gpu: {} mU, cpu: {} mU", gpu_energy, cpu_energy);
+    assert!(deviation < 1e-4, "relative force deviation {}", deviation);
+}
+
+#[test]
+fn ewald_sum_matches_the_cpu() {
+    let mut config = config(1);
+    config.force_field.preset = ForceFieldPreset::SodiumChloride;
+    let Some(mut gpu) = gpu(&config) else {
+        return;
+    };
+    gpu.step(1);
+    // a liquid has a dipole moment, so the direct sum does not converge to the Ewald sum
+    // and a single cell is enough
+    let (gpu_energy, cpu_energy, _) = gpu.compute().validate_electrostatics(gpu.device(), gpu.queue(), 0);
+    let difference = (gpu_energy.total() - cpu_energy.total()) / cpu_energy.total();
+    assert!(difference.abs() < 1e-4, "gpu: {:?}, cpu: {:?}", gpu_energy, cpu_energy);
+}
+
+// a cluster at three times the density of the box overflows the first Verlet list, until it
+// grows at the next frame the overflowed particles search the bins, which have to be rebuilt
+// every substep to follow them
+#[test]
+fn overflowed_verlet_list_rebins_every_substep() {
+    let mut with_list = config(20);
+    with_list.force_field.verlet_skin = 0.1;
+    let Some(mut list) = gpu(&with_list) else {
+        return;
+    };
+    let mut particles = list.particles();
+    for particle in particles.iter_mut() {
+        particle.position = particle.position.map(|x| 0.7 * x);
+    }
+    list.set_particles(&particles);
+    list.step(1);
+    let stats = list.compute().read_stats(list.device(), list.queue());
+    assert_eq!(stats.rebuilds, 20, "{:?}", stats);
+    let from_list = list.particles();
+    // one gl context at a time
+    drop(list);
+
+    let mut bins = gpu(&config(20)).expect("the adapter is still there");
+    bins.set_particles(&particles);
+    bins.step(1);
+    let deviation = from_list
+        .iter()
+        .zip(bins.particles())
+        .flat_map(|(a, b)| (0..3).map(move |x| (a.position[x] - b.position[x]).abs()))
+        .fold(0.0, f32::max);
+    assert!(deviation < 1e-4, "position deviation {} nm", deviation);
+}
+
+/// the capped pair forces of the gpu and the cpu after one substep with a pair on top of each
+/// other and a pair at 0.8 sigma, the sparse grid keeps every other pair beyond 1.2 sigma
+fn capped_pairs(max_force: f32) -> Option<(u32, u32)> {
+    let mut config = config(1);
+    config.system.particles = 64;
+    config.force_field.max_force = max_force;
+    let sigma = config.force_field.preset.build().atom(0).sigma;
+    let mut gpu = gpu(&config)?;
+    let mut particles = gpu.particles();
+    // with the same velocity they drift together and stay on top of each other
+    particles[1].position = particles[0].position;
+    particles[1].velocity = particles[0].velocity;
+    particles[3].position = particles[2].position.map(|x| x + 0.8 * sigma / 3f32.sqrt());
+    gpu.set_particles(&particles);
+    gpu.step(1);
+    let gpu_capped = gpu.observables().stats.capped;
+    drop(gpu);
+
+    let mut cpu = ReferenceBackend::new(&config).expect("the test config runs on the cpu");
+    cpu.set_particles(&particles);
+    cpu.step();
+    Some((gpu_capped, cpu.stats().capped))
+}
+
+// both sides of a capped pair count half, the overlap only counts while the capping is on
+#[test]
+fn capped_count_follows_the_capping() {
+    let atom = *ForceFieldPreset::Default.build().atom(0);
+    let Some(without_capping) = capped_pairs(0.0) else {
+        return;
+    };
+    assert_eq!(without_capping, (0, 0), "capped pairs (gpu, cpu) without capping");
+    let max_force = lennard_jones_force(0.9 * atom.sigma, atom.sigma, atom.epsilon);
+    let with_capping = capped_pairs(max_force).expect("the adapter is still there");
+    assert_eq!(with_capping, (2, 2), "capped pairs (gpu, cpu) above {} mU / nm", max_force);
+}
@@ -0,0 +1,125 @@
+//! The barostats on the gpu: Berendsen moves the volume toward the target pressure and a
+//! rejected Monte-Carlo move leaves the box and the particles exactly as they were. Every
+//! test skips itself when there is no adapter to run the pipeline on.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use ParticleLife3D::system::barostat::{Barostat, BarostatKind};
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::simulation::Simulation;
+use ParticleLife3D::system::thermostat::ThermostatKind;
+
+/// a small, dense and warm system, about 160 bar without a barostat
+fn config() -> Config {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 100.0;
+    config.integrator.substeps = 10;
+    config
+}
+
+/// the gpu pipeline, None without an adapter
+fn gpu(config: &Config) -> Option<Simulation> {
+    config.validate().expect("the test config is valid");
+    match Simulation::builder().config(config.clone()).build() {
+        Ok(simulation) => Some(simulation),
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            None
+        }
+    }
+}
+
+/// (volume, pressure) after the first frame and after `frames` more
+fn berendsen(pressure: f32, frames: usize) -> Option<((f32, f32), (f32, f32))> {
+    let mut config = config();
+    config.barostat.kind = BarostatKind::Berendsen;
+    config.barostat.pressure = pressure;
+    // strong coupling, so the box moves within a few frames
+    config.barostat.tau = 0.1;
+    config.barostat.compressibility = 1e-3;
+    let mut gpu = gpu(&config)?;
+    gpu.step(1);
+    let first = gpu.observables().stats;
+    gpu.step(frames);
+    let last = gpu.observables().stats;
+    Some(((first.volume, first.pressure), (last.volume, last.pressure)))
+}
+
+#[test]
+fn berendsen_moves_the_volume_toward_the_target_pressure() {
+    let Some(((v_0, p_0), (v_1, p_1))) = berendsen(-2000.0, 20) else {
+        return;
+    };
+    assert!(v_1 > v_0, "the box has to grow toward -2000 bar: {} nm^3 at {} bar, then {} nm^3", v_0, p_0, v_1);
+    assert!(p_1 < p_0, "the pressure has to drop toward -2000 bar: {} bar, then {} bar", p_0, p_1);
+
+    // until the box gets too small for the bins
+    let ((v_0, p_0), (v_1, _)) = berendsen(2000.0, 20).expect("the adapter is still there");
+    assert!(v_1 < v_0, "the box has to shrink toward 2000 bar: {} nm^3 at {} bar, then {} nm^3", v_0, p_0, v_1);
+}
+
+/// the box and the particles right after the first Monte-Carlo move, before the substeps of
+/// its frame run, with the target pressure on the side of the first trial (`accepted`) or
+/// against it
+fn first_monte_carlo_move(accepted: bool) -> Option<[(Vec<[f32; 9]>, [f32; 6]); 2]> {
+    let mut config = config();
+    config.thermostat.kind = ThermostatKind::Berendsen;
+    config.barostat.kind = BarostatKind::MonteCarlo;
+    config.barostat.interval = 1;
+    // the same trial as the barostat, the pipeline seeds it with 0
+    let mut rng = StdRng::seed_from_u64(0);
+    let scale = config.barostat().trial_scale(rng.gen(), rng.gen());
+    // a million bar makes the p dV term decide the move alone
+    let expands = scale[0] > 1.0;
+    config.barostat.pressure = if expands == accepted { -1e6 } else { 1e6 };
+    let mut gpu = gpu(&config)?;
+
+    let state = |gpu: &Simulation| {
+        let particles = gpu.particles().into_iter().map(|p| {
+            let [x, y, z] = p.position;
+            let [v_x, v_y, v_z] = p.velocity;
+            let [a_x, a_y, a_z] = p.last_acceleration;
+            [x, y, z, v_x, v_y, v_z, a_x, a_y, a_z]
+        });
+        let simulation_box = gpu.simulation_box();
+        let [l_x, l_y, l_z] = simulation_box.lengths;
+        let [xy, xz, yz] = simulation_box.tilt;
+        (particles.collect::<Vec<_>>(), [l_x, l_y, l_z, xy, xz, yz])
+    };
+    // no move in the first frame
+    gpu.step(1);
+    let before = state(&gpu);
+    // the move blocks until it is decided, the substeps are only recorded
+    let mut encoder = gpu.device().create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    gpu.encode_frame(&mut encoder);
+    let after = state(&gpu);
+    Some([before, after])
+}
+
+#[test]
+fn rejected_monte_carlo_move_restores_the_box_and_the_particles() {
+    let Some([before, after]) = first_monte_carlo_move(false) else {
+        return;
+    };
+    assert_eq!(after.1, before.1, "the box after a rejected move");
+    // bit for bit, not within a tolerance
+    let changed = before.0.iter().zip(&after.0).filter(|(a, b)| a != b).count();
+    assert_eq!(changed, 0, "particles changed by a rejected move");
+
+    // the same move with the pressure on its side scales the box
+    let [before, after] = first_monte_carlo_move(true).expect("the adapter is still there");
+    assert_ne!(after.1, before.1, "the box after an accepted move");
+}
+
+#[test]
+fn monte_carlo_barostat_needs_a_thermostat() {
+    let mut config = config();
+    config.barostat = Default::default();
+    config.barostat.kind = BarostatKind::MonteCarlo;
+    assert!(config.validate().is_err());
+    config.thermostat.kind = ThermostatKind::Berendsen;
+    config.validate().expect("with a thermostat the Monte-Carlo barostat is fine");
+    assert_eq!(config.barostat(), Barostat { kind: BarostatKind::MonteCarlo, ..Barostat::off() });
+}
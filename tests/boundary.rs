@@ -0,0 +1,98 @@
+//! Reflecting and open faces on the cpu reference: a particle that crosses a face is
+//! mirrored back or stops outside the box, and no pair interacts through a wall.
+
+use ParticleLife3D::system::boundary::BoundaryMode;
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::cpu::parallel::ParallelBackend;
+use ParticleLife3D::system::cpu::reference::ReferenceBackend;
+use ParticleLife3D::system::particle::Particle;
+
+/// 64 particles on a grid in the middle of a 4 nm box at rest, one substep per frame
+fn config(modes: [BoundaryMode; 3]) -> Config {
+    let mut config = Config::default();
+    config.system.particles = 64;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 0.0;
+    config.integrator.substeps = 1;
+    config.boundary.modes = modes;
+    config
+}
+
+/// the reference with the last particles replaced by `extra`, far from the grid
+fn backend(config: &Config, extra: &[Particle]) -> ReferenceBackend {
+    let mut cpu = ReferenceBackend::new(config).expect("the test config runs on the cpu");
+    let mut particles = cpu.particles().to_vec();
+    let first = particles.len() - extra.len();
+    particles[first..].copy_from_slice(extra);
+    cpu.set_particles(&particles);
+    cpu
+}
+
+fn assert_close(a: [f32; 3], b: [f32; 3], what: &str) {
+    for x in 0..3 {
+        assert!((a[x] - b[x]).abs() < 1e-5, "{}: {:?} instead of {:?}", what, a, b);
+    }
+}
+
+#[test]
+fn reflective_wall_mirrors_the_crossing_particle() {
+    let config = config([BoundaryMode::Reflective; 3]);
+    let dt = config.integrator.dt;
+    // half a step before the +x face at 2 nm
+    let velocity = [4.0, 1.0, -2.0];
+    let particle = Particle::new(0.0, [2.0 - 0.5 * velocity[0] * dt, 1.5, 1.5], velocity);
+    let mut cpu = backend(&config, &[particle]);
+    cpu.step();
+
+    let reflected = cpu.particles().last().unwrap();
+    assert_close(reflected.position, [2.0 - 0.5 * velocity[0] * dt, 1.5 + dt, 1.5 - 2.0 * dt], "position");
+    assert_close(reflected.velocity, [-4.0, 1.0, -2.0], "velocity");
+}
+
+#[test]
+fn pairs_do_not_interact_through_a_wall() {
+    // 0.35 nm apart through the x faces, 3.65 nm apart inside the box
+    let pair = [
+        Particle::new(0.0, [-1.825, -1.5, -1.5], [0.0; 3]),
+        Particle::new(0.0, [1.825, -1.5, -1.5], [0.0; 3]),
+    ];
+    let mut periodic = backend(&config([BoundaryMode::Periodic; 3]), &pair);
+    periodic.step();
+    let accelerations = periodic.particles()[62..].iter().map(|p| p.last_acceleration[0]).collect::<Vec<_>>();
+    assert!(accelerations[0] != 0.0 && accelerations[0] == -accelerations[1], "{:?}", accelerations);
+
+    for mode in [BoundaryMode::Reflective, BoundaryMode::Open] {
+        let mut walls = backend(&config([mode, BoundaryMode::Periodic, BoundaryMode::Periodic]), &pair);
+        walls.step();
+        for particle in &walls.particles()[62..] {
+            assert_close(particle.last_acceleration, [0.0; 3], &format!("{:?} acceleration", mode));
+        }
+    }
+}
+
+#[test]
+fn open_face_absorbs_the_crossing_particle() {
+    let config = config([BoundaryMode::Open; 3]);
+    let dt = config.integrator.dt;
+    let velocity = [4.0, 1.0, -2.0];
+    let leaving = Particle::new(0.0, [2.0 - 0.5 * velocity[0] * dt, 1.5, 1.5], velocity);
+    // about sigma from the face, it would feel the leaving particle
+    let staying = Particle::new(0.0, [1.75, 1.5, 1.5], [0.0; 3]);
+    let mut cpu = backend(&config, &[staying, leaving]);
+    cpu.step();
+    let absorbed = cpu.particles()[63];
+    assert_close(absorbed.position, [2.0 + 0.5 * velocity[0] * dt, 1.5 + dt, 1.5 - 2.0 * dt], "position");
+    assert_close(absorbed.velocity, [0.0; 3], "velocity");
+
+    cpu.step();
+    let particles = cpu.particles();
+    assert_close(particles[63].position, absorbed.position, "position after absorption");
+    assert_close(particles[62].last_acceleration, [0.0; 3], "acceleration next to the absorbed particle");
+}
+
+#[test]
+fn thermal_walls_and_walls_on_the_parallel_backend_are_refused() {
+    assert!(ReferenceBackend::new(&config([BoundaryMode::Thermal; 3])).is_err());
+    assert!(ParallelBackend::new(&config([BoundaryMode::Reflective; 3])).is_err());
+    assert!(ParallelBackend::new(&config([BoundaryMode::Periodic; 3])).is_ok());
+}
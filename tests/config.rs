@@ -0,0 +1,58 @@
+//! The timestep and minimizer sections of the config fill in the defaults of their kind and
+//! refuse values the shaders can not run with, the bins grow with the particle life radius.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::minimizer::{Minimizer, MinimizerKind};
+use ParticleLife3D::system::timestep::Timestep;
+
+#[test]
+fn timestep_encloses_the_integrator_dt() {
+    let mut config = Config::default();
+    config.integrator.dt = 0.002;
+    assert_eq!(config.timestep(), Timestep::fixed(0.002));
+
+    config.timestep.adaptive = true;
+    config.timestep.dt_max = Some(0.001);
+    assert!(config.validate().is_err(), "dt_max below integrator.dt passes");
+    config.timestep.dt_max = Some(0.01);
+    config.validate().expect("the adaptive timestep is valid");
+    let timestep = config.timestep();
+    assert!(timestep.adaptive && (timestep.dt_min - 2e-5).abs() < 1e-9 && timestep.dt_max == 0.01);
+
+    config.timestep.growth = 0.9;
+    assert!(config.validate().is_err(), "a shrinking growth passes");
+}
+
+#[test]
+fn minimizer_takes_the_step_of_its_kind() {
+    let mut config = Config::default();
+    assert_eq!(config.minimizer(), None);
+
+    config.minimizer.kind = Some(MinimizerKind::SteepestDescent);
+    config.validate().expect("steepest descent is valid");
+    assert_eq!(config.minimizer(), Some(Minimizer::steepest_descent(config.minimizer.force_tolerance)));
+
+    config.minimizer.kind = Some(MinimizerKind::Fire);
+    config.minimizer.step = Some(0.02);
+    assert!(config.validate().is_err(), "a step beyond step_max passes");
+    config.minimizer.step_max = Some(0.05);
+    config.validate().expect("the step is within step_max");
+    let minimizer = config.minimizer().unwrap();
+    assert_eq!((minimizer.kind, minimizer.step, minimizer.step_max), (MinimizerKind::Fire, 0.02, 0.05));
+}
+
+#[test]
+fn bins_cover_the_particle_life_radius() {
+    let mut config = Config::default();
+    config.system.box_size = Some(4.0);
+    let cutoff = config.force_field.cutoff;
+    assert_eq!(config.bin_size(), 2.0 * cutoff);
+
+    config.force_field.life_radius = 2.5 * cutoff;
+    config.force_field.verlet_skin = 0.1;
+    assert_eq!(config.bin_size(), 2.5 * cutoff + 0.1);
+    // three bins of 1.69 nm do not fit into 4 nm
+    assert!(config.validate().is_err(), "a box without room for three bins passes");
+    config.system.box_size = Some(6.0);
+    config.validate().expect("three bins fit into 6 nm");
+}
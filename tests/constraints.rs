@@ -0,0 +1,58 @@
+//! SHAKE and RATTLE on the gpu: the constrained bonds of the chains keep their length within
+//! the tolerance and the relative velocities along them vanish. Skips itself when there is no
+//! adapter to run the pipeline on.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::constraints::Constraints;
+use ParticleLife3D::system::simulation::Simulation;
+
+#[test]
+fn constrained_bonds_keep_their_length_and_lose_their_velocity() {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 300.0;
+    config.system.chain_length = 4;
+    config.system.constrain_bonds = true;
+    config.integrator.substeps = 5;
+    config.validate().expect("the test config is valid");
+    let constraints = config.topology().constraints;
+    assert_eq!(constraints.len(), 750);
+    let tolerance = Constraints::default().tolerance;
+    let dt = config.integrator.dt;
+    let mut gpu = match Simulation::builder().config(config).build() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            return;
+        }
+    };
+
+    for frame in 0..20 {
+        gpu.step(1);
+        let stats = gpu.observables().stats;
+        assert!(stats.constraint_error <= tolerance, "frame {}: rms error {}", frame, stats.constraint_error);
+
+        let particles = gpu.particles();
+        let simulation_box = gpu.simulation_box();
+        let (mut largest_along, mut mean_across) = (0.0f32, 0.0f32);
+        for c in &constraints {
+            let (a, b) = (&particles[c.i as usize], &particles[c.j as usize]);
+            let r = simulation_box.minimum_image([0, 1, 2].map(|x| b.position[x] - a.position[x]), [true; 3]);
+            let length = (r[0] * r[0] + r[1] * r[1] + r[2] * r[2]).sqrt();
+            // f32 positions of some nm resolve the length to about 1e-6
+            let deviation = (length - c.length).abs() / c.length;
+            assert!(deviation <= 2.0 * tolerance, "frame {}: {:?} is {} nm long", frame, c, length);
+
+            // RATTLE stops once |r . v| dt is within the tolerance of length^2
+            let v = [0, 1, 2].map(|x| b.velocity[x] - a.velocity[x]);
+            let along = (r[0] * v[0] + r[1] * v[1] + r[2] * v[2]) / length;
+            let speed = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            largest_along = largest_along.max(along.abs());
+            mean_across += speed / constraints.len() as f32;
+        }
+        assert!(largest_along * dt <= 2.0 * tolerance * constraints[0].length, "frame {}: {} nm/ps along a constraint", frame, largest_along);
+        // the pairs still turn around each other
+        assert!(largest_along < 1e-2 * mean_across, "frame {}: {} nm/ps along, {} nm/ps in total", frame, largest_along, mean_across);
+    }
+}
@@ -0,0 +1,72 @@
+//! The cutoff schemes of the pair kernels the cpu backends share with compute.wgsl and the
+//! long range correction of the cpu reference against the analytic LJ tail.
+
+use std::f64::consts::PI;
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::consts::eV_over_mU;
+use ParticleLife3D::system::cpu::kernels::{lennard_jones, lennard_jones_cut, lennard_jones_force};
+use ParticleLife3D::system::cpu::reference::ReferenceBackend;
+use ParticleLife3D::system::cutoff::CutoffScheme;
+use ParticleLife3D::system::params::Params;
+
+/// (force, energy) of the first pair just inside the cutoff, and V and -dV/dr of the plain
+/// potential at the cutoff for scale
+fn at_the_cutoff(scheme: CutoffScheme) -> ((f32, f32), (f32, f32)) {
+    let mut config = Config::default();
+    config.force_field.cutoff_scheme = scheme;
+    let force_field = config.force_field.preset.build();
+    let params = Params::new(&config, &force_field, &config.simulation_box());
+    let pair = force_field.pairs(&config.cutoff())[0];
+    let r_cut = config.force_field.cutoff;
+    let plain = (lennard_jones_force(r_cut, pair.sigma, pair.epsilon), lennard_jones(r_cut, pair.sigma, pair.epsilon));
+    (lennard_jones_cut(&params, r_cut * (1.0 - 1e-5), &pair), plain)
+}
+
+#[test]
+fn truncated_energy_jumps_at_the_cutoff() {
+    let ((_, energy), (_, plain)) = at_the_cutoff(CutoffScheme::Truncated);
+    assert!((energy - plain).abs() < 1e-2 * plain.abs(), "V: {} instead of {}", energy, plain);
+}
+
+#[test]
+fn shifted_energy_vanishes_at_the_cutoff() {
+    let ((force, energy), (plain_force, plain)) = at_the_cutoff(CutoffScheme::Shifted);
+    assert!(energy.abs() < 1e-2 * plain.abs(), "V: {} at the cutoff, unshifted {}", energy, plain);
+    // only the energy is shifted
+    assert!((force - plain_force).abs() < 1e-2 * plain_force.abs(), "F: {} instead of {}", force, plain_force);
+}
+
+#[test]
+fn force_shifted_energy_and_force_vanish_at_the_cutoff() {
+    let ((force, energy), (plain_force, plain)) = at_the_cutoff(CutoffScheme::ForceShifted);
+    assert!(energy.abs() < 1e-2 * plain.abs(), "V: {} at the cutoff, unshifted {}", energy, plain);
+    assert!(force.abs() < 1e-2 * plain_force.abs(), "F: {} at the cutoff, unshifted {}", force, plain_force);
+}
+
+#[test]
+fn switched_energy_and_force_vanish_at_the_cutoff() {
+    let ((force, energy), (plain_force, plain)) = at_the_cutoff(CutoffScheme::Switched);
+    assert!(energy.abs() < 1e-2 * plain.abs(), "V: {} at the cutoff, unswitched {}", energy, plain);
+    assert!(force.abs() < 1e-2 * plain_force.abs(), "F: {} at the cutoff, unswitched {}", force, plain_force);
+}
+
+// E_tail = 8/3 pi rho N epsilon sigma^3 [(sigma / r_c)^9 / 3 - (sigma / r_c)^3] for a single
+// kind of atom, the four types of the default force field are all the same helium
+#[test]
+fn tail_correction_matches_the_analytic_tail() {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    let mut cpu = ReferenceBackend::new(&config).expect("the default force field runs on the cpu");
+    cpu.step();
+
+    let atom = *config.force_field.preset.build().atom(0);
+    let (sigma, epsilon) = (atom.sigma as f64, atom.epsilon as f64);
+    let n = config.system.particles as f64;
+    let density = n / cpu.simulation_box().volume();
+    let sr3 = (sigma / config.force_field.cutoff as f64).powi(3);
+    let tail = 8.0 / 3.0 * PI * density * n * epsilon * sigma.powi(3) * (sr3.powi(3) / 3.0 - sr3) / eV_over_mU as f64;
+    let pe_tail = cpu.stats().PE_tail as f64;
+    assert!(((pe_tail - tail) / tail).abs() < 1e-5, "tail correction {} eV instead of {} eV", pe_tail, tail);
+}
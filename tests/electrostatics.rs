@@ -0,0 +1,42 @@
+//! The cpu Ewald sum against the Madelung energy of rock salt and a plain sum over the
+//! periodic images. tests/backends.rs compares the gpu with the cpu Ewald sum.
+
+use ParticleLife3D::system::electrostatics::{
+    direct_sum_energy, ewald_energy, rock_salt, validate_rock_salt, Ewald, MAX_KVECTORS,
+};
+use ParticleLife3D::system::simulation_box::SimulationBox;
+
+const LATTICE: f32 = 0.564; // in nm, NaCl
+const CUTOFF: f32 = 0.5; // in nm, less than half of two cells
+
+#[test]
+fn ewald_matches_the_madelung_energy() {
+    let simulation_box = SimulationBox::cubic(2.0 * LATTICE);
+    let ewald = Ewald::with_tolerance(CUTOFF, &simulation_box, 1e-6);
+    let error = validate_rock_salt(2, LATTICE, &ewald, CUTOFF);
+    assert!(error < 1e-5, "relative error {}", error);
+}
+
+// the cell has no dipole moment, so the sum over spherical shells converges to the Ewald sum
+#[test]
+fn ewald_matches_the_direct_sum() {
+    let (positions, charges) = rock_salt(2, LATTICE);
+    let simulation_box = SimulationBox::cubic(2.0 * LATTICE);
+    let ewald = Ewald::with_tolerance(CUTOFF, &simulation_box, 1e-6);
+    let energy = ewald_energy(&positions, &charges, &simulation_box, CUTOFF, &ewald).total();
+    let direct = direct_sum_energy(&positions, &charges, &simulation_box, 6);
+    assert!(((direct - energy) / energy).abs() < 1e-5, "ewald: {} mU, direct sum: {} mU", energy, direct);
+}
+
+// the default tolerance takes about 20000 k vectors in this box, alpha has to follow the
+// smaller k space instead of dropping vectors
+#[test]
+fn ewald_fits_the_kvector_cap() {
+    let cells = 8;
+    let cutoff = 0.63775;
+    let simulation_box = SimulationBox::cubic(cells as f32 * LATTICE);
+    let ewald = Ewald::with_tolerance(cutoff, &simulation_box, 1e-4);
+    assert!(ewald.kvectors(&simulation_box).len() <= MAX_KVECTORS);
+    let error = validate_rock_salt(cells, LATTICE, &ewald, cutoff);
+    assert!(error < 1e-2, "relative error {}", error);
+}
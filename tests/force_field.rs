@@ -0,0 +1,43 @@
+//! The pair parameters of `ForceField`: Lorentz–Berthelot mixing of the atom types and the
+//! explicit overrides that replace it for single pairs.
+
+use ParticleLife3D::system::cutoff::{Cutoff, CutoffScheme};
+use ParticleLife3D::system::force_field::ForceField;
+
+fn assert_close(a: f32, b: f32, what: &str) {
+    assert!((a - b).abs() <= 1e-6 * b.abs(), "{}: {} instead of {}", what, a, b);
+}
+
+#[test]
+fn pairs_follow_lorentz_berthelot() {
+    let force_field = ForceField::noble_gases();
+    let n = force_field.type_count();
+    for i in 0..n {
+        for j in 0..n {
+            let (a, b) = (force_field.atom(i), force_field.atom(j));
+            let pair = force_field.pair(i, j);
+            assert_close(pair.sigma, 0.5 * (a.sigma + b.sigma), "sigma");
+            assert_close(pair.epsilon, (a.epsilon * b.epsilon).sqrt(), "epsilon");
+        }
+    }
+}
+
+#[test]
+fn overrides_win_over_mixing() {
+    let mut force_field = ForceField::noble_gases();
+    let n = force_field.type_count();
+    let mixed = force_field.pair(1, 3);
+    force_field.set_override(1, 3, 0.3, 0.5);
+    // both orders and the table the gpu gets, the other pairs keep mixing
+    let pairs = force_field.pairs(&Cutoff::new(CutoffScheme::Truncated, 1.0, 0.9));
+    for pair in [force_field.pair(1, 3), force_field.pair(3, 1), pairs[(n + 3) as usize], pairs[(3 * n + 1) as usize]] {
+        assert_close(pair.sigma, 0.3, "sigma");
+        assert_close(pair.epsilon, 0.5, "epsilon");
+    }
+    let (a, b) = (force_field.atom(1), force_field.atom(2));
+    assert_close(force_field.pair(1, 2).sigma, 0.5 * (a.sigma + b.sigma), "sigma");
+
+    force_field.clear_override(3, 1);
+    assert_eq!(force_field.pair(1, 3).sigma, mixed.sigma);
+    assert_eq!(force_field.pair(1, 3).epsilon, mixed.epsilon);
+}
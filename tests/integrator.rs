@@ -0,0 +1,34 @@
+//! r-RESPA has to end every frame with an outer step, the stats of a frame would hold the
+//! slow energy of an older configuration otherwise.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::integrator::{IntegratorKind, Pass};
+
+fn respa(substeps: u32, respa_steps: u32) -> Config {
+    let mut config = Config::default();
+    config.integrator.kind = IntegratorKind::Respa;
+    config.integrator.substeps = substeps;
+    config.integrator.respa_steps = respa_steps;
+    config
+}
+
+#[test]
+fn every_frame_ends_with_an_outer_step() {
+    let config = respa(8, 4);
+    config.validate().expect("8 substeps are two outer steps");
+    let integrator = config.integrator();
+    for frame in 0..3 {
+        let last = (frame + 1) * config.integrator.substeps - 1;
+        let passes = integrator.passes(last, true, false, false);
+        assert!(passes.contains(&Pass::SlowForce), "frame {} ends with {:?}", frame, passes);
+    }
+}
+
+#[test]
+fn frames_can_not_end_inside_an_outer_step() {
+    assert!(respa(6, 4).validate().is_err());
+    // the other integrators do not care about the inner steps
+    let mut config = respa(6, 4);
+    config.integrator.kind = IntegratorKind::VelocityVerlet;
+    assert!(config.validate().is_ok());
+}
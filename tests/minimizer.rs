@@ -0,0 +1,68 @@
+//! Both minimizers on the gpu relax a Lennard-Jones dimer to the minimum of the pair
+//! potential at 2^(1/6) sigma. Skips itself when there is no adapter to run the pipeline on.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::minimizer::{MinimizerKind, MinimizerStatus};
+use ParticleLife3D::system::simulation::Simulation;
+
+/// the distance of the dimer after the minimization from 1.3 sigma, None without an adapter
+fn relaxed_dimer(kind: MinimizerKind) -> Option<(f32, f32)> {
+    let mut config = Config::default();
+    config.system.particles = 2;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 0.0;
+    config.integrator.substeps = 10;
+    let atom = *config.force_field().expect("the preset loads").atom(0);
+    config.minimizer.kind = Some(kind);
+    // 1e-3 sigma from the minimum, where the curvature is 57 epsilon / sigma^2. closer in, the
+    // energy no longer changes in f32
+    config.minimizer.force_tolerance = 57.0 * atom.epsilon / atom.sigma * 1e-3;
+    // the force decides, unless the energy stops changing altogether
+    config.minimizer.energy_tolerance = 0.0;
+    if kind == MinimizerKind::Fire {
+        // the well of the preset is shallow, its period is some ps and 1 fs barely moves the dimer
+        let period = 2.0 * std::f32::consts::PI * (atom.mass * atom.sigma * atom.sigma / (2.0 * 57.0 * atom.epsilon)).sqrt();
+        config.minimizer.step = Some(period / 100.0);
+        config.minimizer.step_max = Some(period / 20.0);
+    }
+    config.validate().expect("the test config is valid");
+    let mut gpu = match Simulation::builder().config(config).build() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            return None;
+        }
+    };
+    // along a skewed axis, at rest and of the first atom type
+    let axis = [0.6, 0.48, 0.64];
+    let mut particles = gpu.particles();
+    for (particle, distance) in particles.iter_mut().zip([0.0, 1.3 * atom.sigma]) {
+        particle.type_ = 0.0;
+        particle.position = [0, 1, 2].map(|x| 0.1 + distance * axis[x]);
+        particle.velocity = [0.0; 3];
+    }
+    gpu.set_particles(&particles);
+
+    for _ in 0..1000 {
+        gpu.step(1);
+        if !gpu.compute().is_minimizing() {
+            break;
+        }
+    }
+    let state = gpu.compute().minimizer_state().expect("the minimizer ran");
+    assert_eq!(state.status(), MinimizerStatus::ForceConverged, "{:?} after {} steps", kind, state.steps);
+    let [a, b] = [0, 1].map(|i| gpu.particles()[i].position);
+    let d = gpu.simulation_box().minimum_image([0, 1, 2].map(|x| b[x] - a[x]), [true; 3]);
+    Some(((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt(), atom.sigma))
+}
+
+#[test]
+fn minimizers_relax_a_dimer_to_the_minimum() {
+    for kind in [MinimizerKind::SteepestDescent, MinimizerKind::Fire] {
+        let Some((distance, sigma)) = relaxed_dimer(kind) else {
+            return;
+        };
+        let minimum = 2f32.powf(1.0 / 6.0) * sigma;
+        assert!((distance - minimum).abs() < 2e-3 * sigma, "{:?}: {} nm instead of {} nm", kind, distance, minimum);
+    }
+}
@@ -0,0 +1,83 @@
+//! A triclinic box from the config: wrapping and the minimum image have to give the same
+//! answer for every periodic image of a position or a displacement.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::simulation_box::SimulationBox;
+
+fn triclinic() -> Config {
+    let mut config = Config::default();
+    config.system.box_lengths = Some([5.0, 6.0, 7.0]);
+    config.system.box_tilt = [2.0, -1.5, 2.5];
+    config
+}
+
+fn assert_close(a: [f32; 3], b: [f32; 3], what: &str) {
+    for x in 0..3 {
+        assert!((a[x] - b[x]).abs() < 1e-4, "{}: {:?} instead of {:?}", what, a, b);
+    }
+}
+
+/// r + i a + j b + k c
+fn shifted(simulation_box: &SimulationBox, r: [f32; 3], image: [i32; 3]) -> [f32; 3] {
+    let d = simulation_box.from_fractional(image.map(|i| i as f32));
+    [0, 1, 2].map(|x| r[x] + d[x])
+}
+
+#[test]
+fn triclinic_box_round_trips() {
+    let config = triclinic();
+    config.validate().expect("the triclinic box is valid");
+    let simulation_box = config.simulation_box();
+    assert_eq!(simulation_box.lengths, [5.0, 6.0, 7.0]);
+    assert_eq!(simulation_box.tilt, [2.0, -1.5, 2.5]);
+    let half_height = simulation_box.heights().iter().fold(f32::INFINITY, |a, h| a.min(*h)) / 2.0;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..1000 {
+        let s = [0; 3].map(|_| rng.gen_range(0.01..0.99));
+        let r = simulation_box.position(s);
+        assert_close(simulation_box.fractional_position(r), s, "fractional position");
+
+        let image = [0; 3].map(|_| rng.gen_range(-3..=3));
+        let wrapped = simulation_box.wrap(shifted(&simulation_box, r, image), [true; 3]);
+        assert_close(wrapped, r, "wrapped image");
+
+        // shorter than half of the thinnest height, so it is its own minimum image
+        let d = [0; 3].map(|_| rng.gen_range(-1.0..1.0) * half_height / 3f32.sqrt());
+        let far = shifted(&simulation_box, d, image);
+        assert_close(simulation_box.minimum_image(far, [true; 3]), d, "minimum image");
+        // the axes that are not periodic keep their image
+        let open = simulation_box.minimum_image(far, [true, true, false]);
+        assert_close(open, shifted(&simulation_box, d, [0, 0, image[2]]), "minimum image without z");
+    }
+}
+
+#[test]
+fn tilt_beyond_half_a_box_length_is_refused() {
+    let mut config = triclinic();
+    config.system.box_tilt = [2.6, 0.0, 0.0];
+    assert!(config.validate().is_err());
+    config.system.box_tilt = [0.0, 0.0, -3.1];
+    assert!(config.validate().is_err());
+}
+
+// a strong tilt makes the box thinner than its lengths
+#[test]
+fn thin_tilted_box_is_refused() {
+    let mut config = triclinic();
+    config.system.box_lengths = Some([4.0, 5.0, 7.0]);
+    config.system.box_tilt = [0.0; 3];
+    config.validate().expect("the box is still three bins thick");
+    // 4 * 5 / sqrt(5^2 + 2^2) = 3.71 nm along x
+    config.system.box_tilt = [2.0, 0.0, 0.0];
+    assert!(config.validate().is_err(), "{}", config.simulation_box());
+}
+
+#[test]
+fn box_lengths_have_to_be_positive() {
+    let mut config = triclinic();
+    config.system.box_lengths = Some([5.0, -6.0, 7.0]);
+    assert!(config.validate().is_err());
+}
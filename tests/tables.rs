@@ -0,0 +1,87 @@
+//! Pair tables from the config: the tabulated model refuses to run without them, and a
+//! table of the Lennard-Jones potential gives the same energy as the potential itself.
+
+use std::fs;
+
+use ParticleLife3D::system::config::{Config, TableConfig};
+use ParticleLife3D::system::cpu::kernels::{lennard_jones, lennard_jones_force};
+use ParticleLife3D::system::cpu::reference::ReferenceBackend;
+use ParticleLife3D::system::cutoff::CutoffScheme;
+use ParticleLife3D::system::life::ForceModel;
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 100.0;
+    config.integrator.substeps = 1;
+    // the table ends at the cutoff without a shift
+    config.force_field.cutoff_scheme = CutoffScheme::Truncated;
+    config
+}
+
+/// the Lennard-Jones potential of the first atom type up to the cutoff in the "r V F"
+/// format, returns the path
+fn write_table(config: &Config) -> String {
+    let atom = *config.force_field.preset.build().atom(0);
+    let (r_min, r_max, n) = (0.7 * atom.sigma, config.force_field.cutoff, 2000);
+    let mut content = String::from("# r V F\n");
+    for i in 0..n {
+        let r = r_min + (r_max - r_min) * i as f32 / (n - 1) as f32;
+        let (energy, force) = (lennard_jones(r, atom.sigma, atom.epsilon), lennard_jones_force(r, atom.sigma, atom.epsilon));
+        content += &format!("{} {} {}\n", r, energy, force);
+    }
+    let path = std::env::temp_dir().join(format!("{}_lennard_jones.table", std::process::id()));
+    fs::write(&path, content).expect("the temp directory is writable");
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn tabulated_model_needs_a_table() {
+    let mut config = config();
+    config.force_field.model = ForceModel::Tabulated;
+    assert!(config.validate().is_err(), "the tabulated model runs without tables");
+
+    let table = TableConfig {
+        file: "does-not-exist.table".to_string(),
+        ..TableConfig::default()
+    };
+    config.force_field.tables.push(table);
+    assert!(config.validate().is_err(), "a missing table file passes");
+
+    config.force_field.tables[0].file = write_table(&config);
+    config.validate().expect("the table is valid");
+    config.force_field.tables[0].types = [0, 4];
+    assert!(config.validate().is_err(), "a table of an atom type the force field lacks passes");
+    fs::remove_file(&config.force_field.tables[0].file).ok();
+}
+
+// the four types of the default force field are the same helium, so one table serves all pairs
+#[test]
+fn lennard_jones_table_matches_the_potential() {
+    let plain = config();
+    let mut tabulated = config();
+    tabulated.force_field.model = ForceModel::Tabulated;
+    let file = write_table(&tabulated);
+    for i in 0..4 {
+        for j in i..4 {
+            tabulated.force_field.tables.push(TableConfig {
+                types: [i, j],
+                file: file.clone(),
+                ..TableConfig::default()
+            });
+        }
+    }
+    let force_field = tabulated.force_field().expect("the tables load");
+    // the kernels fall back to Lennard-Jones for pairs without table points
+    assert!(force_field.pairs(&tabulated.cutoff()).iter().all(|pair| pair.table_len > 0));
+
+    let mut lennard_jones = ReferenceBackend::new(&plain).expect("the test config runs on the cpu");
+    let mut table = ReferenceBackend::new(&tabulated).expect("the tabulated model runs on the cpu");
+    table.set_particles(lennard_jones.particles());
+    lennard_jones.step();
+    table.step();
+    let (expected, pe) = (lennard_jones.stat().PE, table.stat().PE);
+    assert!(((pe - expected) / expected).abs() < 1e-3, "PE from the table {} mU instead of {} mU", pe, expected);
+    fs::remove_file(file).ok();
+}
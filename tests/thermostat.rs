@@ -0,0 +1,87 @@
+//! The thermostats on the gpu: Berendsen and CSVR bring the system to the target temperature,
+//! Nosé–Hoover conserves its extended energy and the energy Langevin takes out balances the
+//! change of KE + PE. Every test skips itself when there is no adapter to run the pipeline on.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::simulation::{Observables, Simulation};
+use ParticleLife3D::system::thermostat::ThermostatKind;
+
+/// a small, dense system started at 100 K, the thermostat acts every 5 fs
+fn config(kind: ThermostatKind, temperature: f32) -> Config {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 100.0;
+    config.integrator.substeps = 5;
+    config.thermostat.kind = kind;
+    config.thermostat.temperature = temperature;
+    config.thermostat.tau = 0.05;
+    config
+}
+
+/// the observables of every frame, None without an adapter
+fn run(config: &Config, frames: usize) -> Option<Vec<Observables>> {
+    config.validate().expect("the test config is valid");
+    let mut gpu = match Simulation::builder().config(config.clone()).build() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            return None;
+        }
+    };
+    Some(
+        (0..frames)
+            .map(|_| {
+                gpu.step(1);
+                gpu.observables()
+            })
+            .collect(),
+    )
+}
+
+/// in eV, KE + PE + E_thermostat, the tail correction is constant in a fixed box
+fn conserved(observables: &Observables) -> f32 {
+    let stats = &observables.stats;
+    stats.KE + stats.PE + stats.PE_real + stats.PE_recip + stats.PE_bonded + stats.E_thermostat
+}
+
+// 40 frames (4 tau) to get there and 60 to average over, a canonical 1000 particles
+// fluctuate by 2.6% and the potential energy of the melting grid relaxes slower than tau,
+// without a thermostat the system stays around 70 K
+#[test]
+fn berendsen_and_csvr_converge_to_the_target_temperature() {
+    for kind in [ThermostatKind::Berendsen, ThermostatKind::Csvr] {
+        let Some(observables) = run(&config(kind, 200.0), 100) else {
+            return;
+        };
+        let mean = observables[40..].iter().map(|o| o.temperature).sum::<f32>() / 60.0;
+        assert!((mean - 200.0).abs() < 10.0, "{:?}: {} K instead of 200 K", kind, mean);
+    }
+}
+
+/// the change of the conserved energy over the run relative to the energy the thermostat
+/// moved, which has to be large enough to tell a balance from a thermostat that did nothing
+fn relative_drift(kind: ThermostatKind) -> Option<f32> {
+    let observables = run(&config(kind, 150.0), 60)?;
+    let (first, last) = (observables.first().unwrap(), observables.last().unwrap());
+    let work = last.stats.E_thermostat - first.stats.E_thermostat;
+    assert!(work.abs() > 0.2 * first.stats.KE, "{:?} moved only {} eV", kind, work);
+    Some((conserved(last) - conserved(first)) / work.abs())
+}
+
+// the chain is integrated once per frame, which costs some accuracy compared to the substeps
+#[test]
+fn nose_hoover_conserves_the_extended_energy() {
+    let Some(drift) = relative_drift(ThermostatKind::NoseHoover) else {
+        return;
+    };
+    assert!(drift.abs() < 1e-2, "the extended energy drifts by {} of the thermostat work", drift);
+}
+
+#[test]
+fn langevin_energy_balances() {
+    let Some(drift) = relative_drift(ThermostatKind::Langevin) else {
+        return;
+    };
+    assert!(drift.abs() < 1e-3, "KE + PE + E_thermostat drifts by {} of the thermostat work", drift);
+}
@@ -0,0 +1,59 @@
+//! The adaptive timestep on the gpu: dt stays between dt_min and dt_max and no particle
+//! moves further than the largest displacement in one substep. Skips itself when there is
+//! no adapter to run the pipeline on.
+
+use ParticleLife3D::system::config::Config;
+use ParticleLife3D::system::simulation::Simulation;
+
+#[test]
+fn adaptive_timestep_limits_the_displacement() {
+    let mut config = Config::default();
+    config.system.particles = 1000;
+    config.system.box_size = Some(4.0);
+    config.system.temperature = 300.0;
+    // one substep per frame, so every frame shows the move of a single substep
+    config.integrator.substeps = 1;
+    config.timestep.adaptive = true;
+    config.timestep.dt_min = Some(1e-6);
+    // far beyond what the displacement allows, so the limit decides dt
+    config.timestep.dt_max = Some(0.02);
+    let timestep = config.timestep();
+    let sigma = config.force_field().expect("the preset loads").min_sigma();
+    let max_displacement = timestep.displacement * sigma;
+    config.validate().expect("the test config is valid");
+    let mut gpu = match Simulation::builder().config(config).build() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("skipped, no gpu: {}", e);
+            return;
+        }
+    };
+    // a pair at 0.8 sigma starts with a large acceleration
+    let mut particles = gpu.particles();
+    particles[1].position = particles[0].position.map(|x| x + 0.8 * sigma / 3f32.sqrt());
+    gpu.set_particles(&particles);
+
+    let mut dts = Vec::new();
+    for _ in 0..60 {
+        let before = gpu.particles();
+        gpu.step(1);
+        let after = gpu.particles();
+        let largest = before
+            .iter()
+            .zip(&after)
+            .map(|(a, b)| {
+                let d = gpu.simulation_box().minimum_image([0, 1, 2].map(|x| b.position[x] - a.position[x]), [true; 3]);
+                (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+            })
+            .fold(0.0, f32::max);
+        // the first substep runs with integrator.dt, the rest with the dt the shader picked
+        if !dts.is_empty() {
+            assert!(largest <= 1.001 * max_displacement, "a particle moved {} nm in one substep, at most {} nm", largest, max_displacement);
+        }
+        let dt = gpu.observables().stats.dt;
+        assert!(timestep.dt_min <= dt && dt <= timestep.dt_max, "dt {} ps outside [{}, {}]", dt, timestep.dt_min, timestep.dt_max);
+        dts.push(dt);
+    }
+    let longest = dts.iter().fold(0.0f32, |a, dt| a.max(*dt));
+    assert!(longest < timestep.dt_max, "dt grew to dt_max: {:?}", dts);
+}
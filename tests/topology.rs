@@ -0,0 +1,148 @@
+//! The bonded forces of `Topology::forces` against finite differences of its energy. The
+//! shader uses the same functional forms, tests/backends.rs compares the two. An explicit
+//! topology from the config replaces the generated chains.
+
+use std::f32::consts::PI;
+
+use ParticleLife3D::system::config::{AngleTypeConfig, BondTypeConfig, Config};
+use ParticleLife3D::system::simulation_box::SimulationBox;
+use ParticleLife3D::system::topology::{Angle, AngleType, Bond, BondStyle, BondType, DihedralType, Topology};
+
+const H: f32 = 1e-3; // in nm
+
+/// every force component matches the central difference of the energy
+fn assert_gradient(topology: &Topology, positions: &[[f32; 3]]) {
+    let simulation_box = SimulationBox::cubic(10.0);
+    let (forces, _) = topology.forces(positions, &simulation_box);
+    let largest = forces.iter().flatten().fold(1.0f64, |a, f| a.max(f.abs()));
+    let energy = |i: usize, x: usize, shift: f32| {
+        let mut shifted = positions.to_vec();
+        shifted[i][x] += shift;
+        topology.forces(&shifted, &simulation_box).1
+    };
+    for (i, force) in forces.iter().enumerate() {
+        for x in 0..3 {
+            let difference = -(energy(i, x, H) - energy(i, x, -H)) / (2.0 * H as f64);
+            assert!(
+                (difference - force[x]).abs() < 1e-3 * largest,
+                "particle {} axis {}: force {} but the energy changes by {}",
+                i,
+                x,
+                force[x],
+                difference
+            );
+        }
+    }
+}
+
+/// a single bond along a skewed axis
+fn bond_positions(dist: f32) -> Vec<[f32; 3]> {
+    let axis = [0.6, 0.48, 0.64];
+    vec![[0.1, -0.2, 0.3], [0.1 + dist * axis[0], -0.2 + dist * axis[1], 0.3 + dist * axis[2]]]
+}
+
+#[test]
+fn harmonic_bond() {
+    let topology = Topology::linear_chains(2, 2, BondType::harmonic(500.0, 1.0), None, None);
+    for dist in [0.8, 1.0, 1.3] {
+        assert_gradient(&topology, &bond_positions(dist));
+    }
+}
+
+#[test]
+fn fene_bond() {
+    let topology = Topology::linear_chains(2, 2, BondType::fene(30.0, 1.5, 1.0, 1.0), None, None);
+    // inside the repulsive WCA part, beyond it and near the maximum extension
+    for dist in [0.9, 1.05, 1.3, 1.48] {
+        assert_gradient(&topology, &bond_positions(dist));
+    }
+}
+
+// the logarithm is cut off just before the maximum extension, an overstretched bond has to
+// keep an energy that matches its force or it gains energy when it contracts
+#[test]
+fn overstretched_fene_bond() {
+    let topology = Topology::linear_chains(2, 2, BondType::fene(30.0, 1.5, 1.0, 1.0), None, None);
+    for dist in [1.5, 1.6, 2.0] {
+        assert_gradient(&topology, &bond_positions(dist));
+    }
+    let simulation_box = SimulationBox::cubic(10.0);
+    let energies = [1.49, 1.5, 1.6].map(|dist| topology.forces(&bond_positions(dist), &simulation_box).1);
+    assert!(energies[0] < energies[1] && energies[1] < energies[2], "{:?}", energies);
+}
+
+#[test]
+fn angle() {
+    let angle = AngleType { k: 20.0, theta0: 2.0 * PI / 3.0 };
+    let topology = Topology::linear_chains(3, 3, BondType::harmonic(0.0, 1.0), Some(angle), None);
+    let positions = [[0.0, 0.0, 0.0], [1.0, 0.1, -0.1], [1.3, 1.0, 0.2]];
+    assert_gradient(&topology, &positions);
+}
+
+#[test]
+fn dihedral() {
+    let dihedral = DihedralType { k: 5.0, phi0: 0.3, n: 3 };
+    let topology = Topology::linear_chains(4, 4, BondType::harmonic(0.0, 1.0), None, Some(dihedral));
+    // both signs of the dihedral angle
+    for z in [0.7, -0.7] {
+        let positions = [[0.0, 1.0, 0.2], [0.0, 0.0, 0.0], [1.0, 0.1, -0.1], [1.2, 0.6, z]];
+        assert_gradient(&topology, &positions);
+    }
+}
+
+/// every fourth particle is the centre of a star with three arms, bonded to the next three
+fn branched(particles: u32) -> Config {
+    let mut config = Config::default();
+    config.system.particles = particles;
+    let topology = &mut config.topology;
+    topology.bond_types.push(BondTypeConfig { style: BondStyle::Harmonic, k: 5000.0, r0: 0.3, ..Default::default() });
+    topology.angle_types.push(AngleTypeConfig { k: 50.0, theta0: 109.5 });
+    for centre in (0..particles).step_by(4) {
+        for arm in 1..4 {
+            topology.bonds.push([centre, centre + arm, 0]);
+            for other in arm + 1..4 {
+                topology.angles.push([centre + arm, centre, centre + other, 0]);
+            }
+        }
+    }
+    config
+}
+
+#[test]
+fn explicit_topology_replaces_the_chains() {
+    let mut config = branched(8);
+    config.validate().expect("the branched topology is valid");
+    let topology = config.topology();
+    assert_eq!(topology.bond_types, vec![BondType::harmonic(5000.0, 0.3)]);
+    assert!((topology.angle_types[0].theta0 - 109.5f32.to_radians()).abs() < 1e-6);
+    assert_eq!(topology.bonds.len(), 6);
+    assert_eq!(topology.bonds[5], Bond { i: 4, j: 7, type_: 0 });
+    assert_eq!(topology.angles[2], Angle { i: 2, j: 0, k: 3, type_: 0 });
+    assert!(topology.dihedrals.is_empty());
+
+    config.system.constrain_bonds = true;
+    let constrained = config.topology();
+    assert!(constrained.bonds.is_empty());
+    assert!(constrained.constraints.iter().all(|c| c.length == 0.3), "{:?}", constrained.constraints);
+
+    // the generator stays the default
+    let mut chains = Config::default();
+    chains.system.chain_length = 4;
+    assert_eq!(chains.topology().bonds.len(), (chains.system.particles / 4 * 3) as usize);
+}
+
+#[test]
+fn explicit_topology_is_validated() {
+    let broken: [(&str, fn(&mut Config)); 5] = [
+        ("a particle beyond the system", |c| c.topology.bonds.push([7, 8, 0])),
+        ("a term with the same particle twice", |c| c.topology.angles.push([1, 0, 1, 0])),
+        ("a missing type", |c| c.topology.dihedrals.push([0, 1, 2, 3, 0])),
+        ("a FENE bond without sigma", |c| c.topology.bond_types[0].style = BondStyle::Fene),
+        ("generated chains on top", |c| c.system.chain_length = 4),
+    ];
+    for (what, change) in broken {
+        let mut config = branched(8);
+        change(&mut config);
+        assert!(config.validate().is_err(), "{} passes", what);
+    }
+}